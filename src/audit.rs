@@ -0,0 +1,300 @@
+use crate::descriptor::ScriptType;
+use crate::storage;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+// Previous hash of the first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// Audit log of the data directory, set at startup
+static AUDIT_LOG: OnceLock<PathBuf> = OnceLock::new();
+
+// Front end an operation was started from
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FrontEnd {
+    Cli,
+    Web,
+}
+
+impl FrontEnd {
+    // Name written into the log
+    pub fn name(&self) -> &'static str {
+        match self {
+            FrontEnd::Cli => "cli",
+            FrontEnd::Web => "web",
+        }
+    }
+}
+
+// Key operations recorded in the audit log
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Operation {
+    GenerateWallets,
+    GenerateMasterKey,
+    DeriveChildKey,
+    RestoreWallet,
+    DeriveBip85,
+    SplitBackup,
+    SaveWallets,
+    SaveExtendedKey,
+    SaveChildKey,
+    SaveQrCode,
+    RevealWallet,
+    ExportLabels,
+    ExportWatchOnly,
+    ExportWallet,
+    ImportWallets,
+    CreateMultisig,
+    SignKeyRecord,
+}
+
+impl Operation {
+    // Name written into the log
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::GenerateWallets => "generate_wallets",
+            Operation::GenerateMasterKey => "generate_master_key",
+            Operation::DeriveChildKey => "derive_child_key",
+            Operation::RestoreWallet => "restore_wallet",
+            Operation::DeriveBip85 => "derive_bip85",
+            Operation::SplitBackup => "split_backup",
+            Operation::SaveWallets => "save_wallets",
+            Operation::SaveExtendedKey => "save_extended_key",
+            Operation::SaveChildKey => "save_child_key",
+            Operation::SaveQrCode => "save_qr_code",
+            Operation::RevealWallet => "reveal_wallet",
+            Operation::ExportLabels => "export_labels",
+            Operation::ExportWatchOnly => "export_watch_only",
+            Operation::ExportWallet => "export_wallet",
+            Operation::ImportWallets => "import_wallets",
+            Operation::CreateMultisig => "create_multisig",
+            Operation::SignKeyRecord => "sign_key_record",
+        }
+    }
+}
+
+// One line of the audit log, chained to the previous one by its hash
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct AuditEntry {
+    pub seq: u64, // 1 for the first entry
+    pub timestamp: String,
+    pub front_end: String,
+    pub operation: String,
+    pub subjects: Vec<String>, // Addresses, fingerprints or xpubs; never secrets
+    pub prev_hash: String,
+    pub hash: String, // SHA-256 over every other field
+}
+
+impl AuditEntry {
+    // Hash of the entry's contents, including the hash of the previous entry
+    fn compute_hash(&self) -> String {
+        let contents = serde_json::to_string(&(self.seq, &self.timestamp, &self.front_end, &self.operation, &self.subjects, &self.prev_hash))
+            .expect("audit entries always serialize");
+        hex::encode(Sha256::digest(contents.as_bytes()))
+    }
+}
+
+// What the head does not protect against, shown with every verification
+pub const HEAD_NOTE: &str = "audit.head only catches accidental damage: anyone who can write the data directory can cut the log \
+    and rewrite the head to match. Record the latest hash outside the data directory and compare it with the one shown here.";
+
+// Latest entry, kept next to the log so that entries removed from its end are noticed. The head is not
+// authenticated (entries are written while the keystore is locked, so there is no key to sign it with):
+// it detects truncation by accident, such as a partial restore, but not by someone rewriting both files.
+#[derive(Serialize, Deserialize)]
+struct Head {
+    seq: u64,
+    hash: String,
+}
+
+// Outcome of checking the whole chain
+#[derive(PartialEq, Debug)]
+pub enum Verification {
+    Intact { entries: u64, head: String },
+    Broken { line: usize, problem: &'static str },
+}
+
+// Sets the audit log location for the rest of the process
+pub fn init(data_dir: &Path) {
+    let _ = AUDIT_LOG.set(data_dir.join("audit.jsonl"));
+}
+
+// Appends an entry for an operation to the audit log (nothing is logged before `init`)
+pub fn record(front_end: FrontEnd, operation: Operation, subjects: &[&str]) -> Result<(), &'static str> {
+    match AUDIT_LOG.get() {
+        Some(path) => append(path, front_end, operation, subjects).map(|_| ()),
+        None => Ok(()),
+    }
+}
+
+// Subject logged for text that should be an address: the address itself, or the SHA-256 of anything else
+// (a QR code can be made of a private key or a mnemonic, which must never reach the log)
+pub fn address_subject(text: &str) -> String {
+    if ScriptType::from_address(text).is_some() && bitcoin::Address::from_str(text).is_ok() {
+        text.to_string()
+    } else {
+        format!("sha256:{}", hex::encode(Sha256::digest(text.as_bytes())))
+    }
+}
+
+// Checks the audit log of the data directory
+pub fn verify_log() -> Result<Verification, &'static str> {
+    verify(AUDIT_LOG.get().ok_or("Audit log is not initialized")?)
+}
+
+// Appends an entry under the log's lock, then moves the head to it
+fn append(path: &Path, front_end: FrontEnd, operation: Operation, subjects: &[&str]) -> Result<AuditEntry, &'static str> {
+    storage::with_lock(path, || {
+        let (seq, prev_hash) = last_entry(path)?;
+        let mut entry = AuditEntry {
+            seq: seq + 1,
+            timestamp: Utc::now().to_rfc3339(),
+            front_end: front_end.name().to_string(),
+            operation: operation.name().to_string(),
+            subjects: subjects.iter().map(|subject| subject.to_string()).collect(),
+            prev_hash,
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+
+        let line = serde_json::to_string(&entry).map_err(|_| "Failed to serialize audit entry")? + "\n";
+        write_line(path, &line).map_err(|_| "Failed to write the audit log")?;
+        let head = serde_json::to_vec(&Head { seq: entry.seq, hash: entry.hash.clone() }).map_err(|_| "Failed to serialize audit head")?;
+        storage::write_atomic(head_path(path), &head).map_err(|_| "Failed to write the audit head")?;
+        Ok(entry)
+    })
+    .map_err(|_| "Failed to lock the audit log")?
+}
+
+// Sequence number and hash to chain the next entry to: the head, else the last line, else the genesis
+fn last_entry(path: &Path) -> Result<(u64, String), &'static str> {
+    if let Some(head) = fs::read(head_path(path)).ok().and_then(|bytes| serde_json::from_slice::<Head>(&bytes).ok()) {
+        return Ok((head.seq, head.hash));
+    }
+    let log = read_log(path)?;
+    match log.lines().rev().find(|line| !line.trim().is_empty()) {
+        Some(line) => {
+            let entry: AuditEntry = serde_json::from_str(line).map_err(|_| "Audit log is corrupted")?;
+            Ok((entry.seq, entry.hash))
+        }
+        None => Ok((0, GENESIS_HASH.to_string())),
+    }
+}
+
+// Checks every entry's hash and link, then that the log still ends at the recorded head
+fn verify(path: &Path) -> Result<Verification, &'static str> {
+    let log = read_log(path)?;
+    let mut seq = 0;
+    let mut prev_hash = GENESIS_HASH.to_string();
+    for (i, line) in log.lines().enumerate() {
+        let broken = |problem| Ok(Verification::Broken { line: i + 1, problem });
+        let Ok(entry) = serde_json::from_str::<AuditEntry>(line) else {
+            return broken("Entry is not a valid audit record");
+        };
+        if entry.seq != seq + 1 {
+            return broken("Entry is out of sequence (entries were removed or reordered)");
+        }
+        if entry.prev_hash != prev_hash {
+            return broken("Entry does not link to the previous one (entries were removed or edited)");
+        }
+        if entry.compute_hash() != entry.hash {
+            return broken("Entry was edited after it was written");
+        }
+        seq = entry.seq;
+        prev_hash = entry.hash;
+    }
+
+    let head = match fs::read(head_path(path)) {
+        Ok(bytes) => Some(serde_json::from_slice::<Head>(&bytes).map_err(|_| "Audit head is corrupted")?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(_) => return Err("Failed to read the audit head"),
+    };
+    let last_line = log.lines().count();
+    match head {
+        None if seq == 0 => Ok(Verification::Intact { entries: 0, head: prev_hash }),
+        None => Ok(Verification::Broken { line: last_line, problem: "Audit head is missing" }),
+        Some(head) if head.seq > seq => Ok(Verification::Broken { line: last_line, problem: "Log ends before its recorded head (entries were removed from the end)" }),
+        Some(head) if head.seq != seq || head.hash != prev_hash => Ok(Verification::Broken { line: last_line, problem: "Log does not end at its recorded head" }),
+        Some(_) => Ok(Verification::Intact { entries: seq, head: prev_hash }),
+    }
+}
+
+// Reads the whole log (empty if it was never written)
+fn read_log(path: &Path) -> Result<String, &'static str> {
+    match fs::read_to_string(path) {
+        Ok(log) => Ok(log),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(_) => Err("Failed to read the audit log"),
+    }
+}
+
+// Appends a line and syncs it to disk
+fn write_line(path: &Path, line: &str) -> io::Result<()> {
+    let mut file = storage::private_file_options().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+    file.sync_all()
+}
+
+// Head file of a log
+fn head_path(path: &Path) -> PathBuf {
+    path.with_extension("head")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_log(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hd_wallet_audit_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("audit.jsonl")
+    }
+
+    #[test]
+    fn test_chain_detects_edits_and_truncation() {
+        let path = temp_log("chain");
+        assert_eq!(verify(&path), Ok(Verification::Intact { entries: 0, head: GENESIS_HASH.to_string() }));
+
+        append(&path, FrontEnd::Cli, Operation::GenerateWallets, &["1A", "1B"]).unwrap();
+        append(&path, FrontEnd::Web, Operation::SaveWallets, &["1A", "1B"]).unwrap();
+        let last = append(&path, FrontEnd::Cli, Operation::GenerateMasterKey, &["3442193e"]).unwrap();
+        assert_eq!(verify(&path), Ok(Verification::Intact { entries: 3, head: last.hash }));
+        let log = fs::read_to_string(&path).unwrap();
+
+        // Edited subject
+        fs::write(&path, log.replacen("1B", "1C", 1)).unwrap();
+        assert_eq!(verify(&path), Ok(Verification::Broken { line: 1, problem: "Entry was edited after it was written" }));
+
+        // Entry removed from the middle
+        let lines: Vec<&str> = log.lines().collect();
+        fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert!(matches!(verify(&path), Ok(Verification::Broken { line: 2, .. })));
+
+        // Entry removed from the end
+        fs::write(&path, format!("{}\n{}\n", lines[0], lines[1])).unwrap();
+        assert!(matches!(verify(&path), Ok(Verification::Broken { line: 2, problem: "Log ends before its recorded head (entries were removed from the end)" })));
+
+        // Appending after tampering keeps chaining from the recorded head, so the gap stays visible
+        append(&path, FrontEnd::Cli, Operation::DeriveChildKey, &["m/0"]).unwrap();
+        assert!(matches!(verify(&path), Ok(Verification::Broken { line: 3, .. })));
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_address_subject_hides_secrets() {
+        assert_eq!(address_subject("bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"), "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu");
+        assert_eq!(address_subject("1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA"), "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA");
+        for secret in ["KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn", "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about", "1 secret words"] {
+            let subject = address_subject(secret);
+            assert!(subject.starts_with("sha256:") && !subject.contains(secret));
+        }
+    }
+}
@@ -0,0 +1,380 @@
+use crate::config::{Config, StorageKind};
+use crate::keystore::{self, Migration, Session};
+use crate::records::{self, Record, RecordKind};
+use crate::sqlite::SqliteBackend;
+use crate::storage;
+use chrono::Utc;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// Returned when a corrupted data file was backed up instead of being overwritten
+const CORRUPTED_BACKED_UP: &str =
+    "Data file is corrupted. A backup copy (*.corrupt-<time>.bak) was saved next to it and nothing was overwritten";
+
+// Returned when a corrupted data file could not even be backed up
+const CORRUPTED_NOT_BACKED_UP: &str = "Data file is corrupted and could not be backed up. Nothing was overwritten";
+
+// Backend selected at startup, shared by the CLI and the web server
+static BACKEND: OnceLock<Box<dyn Backend>> = OnceLock::new();
+
+// Filter and window of a record query. Records come in creation order and are numbered from 1 in that order
+#[derive(Default)]
+pub struct RecordQuery {
+    pub terms: Vec<QueryTerm>, // Every term must match
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+// One search term. It matches records whose address contains its text or whose creation time starts with it
+// (both ignoring ASCII case), and records of the listed addresses (e.g. found through their labels).
+pub struct QueryTerm {
+    pub text: String,
+    pub addresses: Vec<String>,
+}
+
+impl QueryTerm {
+    fn matches(&self, record: &Value) -> bool {
+        let text = self.text.to_ascii_lowercase();
+        let address = address_of(record).unwrap_or_default();
+        address.to_ascii_lowercase().contains(&text)
+            || generated_at(record).to_ascii_lowercase().starts_with(&text)
+            || self.addresses.iter().any(|listed| listed == address)
+    }
+}
+
+impl RecordQuery {
+    // Runs the query over records loaded in save order (backends without indexes)
+    pub fn apply(&self, mut records: Vec<Value>) -> RecordPage<Value> {
+        records.sort_by(|a, b| generated_at(a).cmp(generated_at(b)));
+        let matching: Vec<(usize, Value)> = records
+            .into_iter()
+            .enumerate()
+            .map(|(i, record)| (i + 1, record))
+            .filter(|(_, record)| self.terms.iter().all(|term| term.matches(record)))
+            .collect();
+        let total = matching.len();
+        RecordPage {
+            records: matching.into_iter().skip(self.offset).take(self.limit.unwrap_or(usize::MAX)).collect(),
+            total,
+        }
+    }
+}
+
+// Records returned by a query with their numbers, and how many records match in total
+pub struct RecordPage<T> {
+    pub records: Vec<(usize, T)>,
+    pub total: usize,
+}
+
+// Persistent storage of wallets, extended keys, child keys and QR codes.
+// Records are exchanged as JSON values; every backend encrypts them with the keys of the unlocked session.
+pub trait Backend: Send + Sync {
+    // Human-readable description, e.g. for the menu banner
+    fn describe(&self) -> String;
+
+    // Where records of a kind are stored (file or database path)
+    fn location(&self, kind: RecordKind) -> String;
+
+    // Whether a keystore password has already been set
+    fn has_password(&self) -> bool;
+
+    // Derives the keys of the stored keystore from its password (any password is accepted before the first save)
+    fn unlock(&self, password: &str) -> Result<Session, &'static str>;
+
+    // Loads the stored records of a kind, as written (see `records::migrate`)
+    fn load(&self, kind: RecordKind, session: &Session) -> Result<Vec<Value>, &'static str>;
+
+    // Number of stored records of a kind
+    fn count(&self, kind: RecordKind, session: &Session) -> Result<usize, &'static str>;
+
+    // Stored records of a kind saved for an address
+    fn find_by_address(&self, kind: RecordKind, session: &Session, address: &str) -> Result<Vec<Value>, &'static str>;
+
+    // One window of the stored records of a kind matching a query
+    fn query(&self, kind: RecordKind, session: &Session, query: &RecordQuery) -> Result<RecordPage<Value>, &'static str>;
+
+    // Appends records of a kind
+    fn append(&self, kind: RecordKind, session: &Session, records: &[Value]) -> Result<(), &'static str>;
+
+    // Encrypts and upgrades stored records of a kind in place, returning how many were rewritten
+    fn upgrade(&self, kind: RecordKind, session: &Session) -> Result<usize, &'static str>;
+
+    // Saves the SVG QR code of an address, returning where it was stored
+    fn save_qr_code(&self, address: &str, svg: &str) -> Result<String, &'static str>;
+
+    // Loads the saved SVG QR code of an address, if there is one
+    fn load_qr_code(&self, address: &str) -> Result<Option<String>, &'static str>;
+}
+
+impl dyn Backend + '_ {
+    // Appends typed records
+    pub fn append_records<T: Record>(&self, session: &Session, records: &[T]) -> Result<(), &'static str> {
+        let values = records
+            .iter()
+            .map(|record| serde_json::to_value(record).map_err(|_| "Failed to serialize record"))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.append(T::KIND, session, &values)
+    }
+
+    // Loads typed records, migrating old ones to the current schema
+    pub fn load_records<T: Record>(&self, session: &Session) -> Result<Vec<T>, &'static str> {
+        self.load(T::KIND, session)?.into_iter().map(typed).collect()
+    }
+
+    // Typed records saved for an address
+    pub fn find_records<T: Record>(&self, session: &Session, address: &str) -> Result<Vec<T>, &'static str> {
+        self.find_by_address(T::KIND, session, address)?.into_iter().map(typed).collect()
+    }
+
+    // One window of typed records matching a query
+    pub fn query_records<T: Record>(&self, session: &Session, query: &RecordQuery) -> Result<RecordPage<T>, &'static str> {
+        let page = self.query(T::KIND, session, query)?;
+        Ok(RecordPage {
+            records: page.records.into_iter().map(|(number, value)| Ok((number, typed(value)?))).collect::<Result<_, &'static str>>()?,
+            total: page.total,
+        })
+    }
+}
+
+// Migrates a stored record to the current schema and reads it as its type
+fn typed<T: Record>(value: Value) -> Result<T, &'static str> {
+    serde_json::from_value(records::migrate(value)?).map_err(|_| keystore::CORRUPTED)
+}
+
+// Address a stored record belongs to, which backends index: its own address, or the reference of an address label
+pub fn address_of(record: &Value) -> Option<&str> {
+    match record.get("address") {
+        Some(address) => address.as_str(),
+        None if record.get("type").and_then(Value::as_str) == Some("addr") => record.get("ref").and_then(Value::as_str),
+        None => None,
+    }
+}
+
+// Creation time of a stored record, which queries sort and filter on
+fn generated_at(record: &Value) -> &str {
+    record.get("generated_at").and_then(Value::as_str).unwrap_or_default()
+}
+
+// Opens the configured backend for the rest of the process
+pub fn init(config: &Config) -> Result<(), &'static str> {
+    let backend: Box<dyn Backend> = match config.storage {
+        StorageKind::Json => Box::new(JsonBackend::new(&config.data_dir)),
+        StorageKind::Sqlite => Box::new(SqliteBackend::open(config.data_dir.join("hd_wallet.sqlite3"))?),
+    };
+    BACKEND.set(backend).map_err(|_| "Storage backend already initialized")
+}
+
+// Backend opened by `init`
+pub fn current() -> &'static dyn Backend {
+    BACKEND.get().expect("Storage backend not initialized").as_ref()
+}
+
+// Stores each record kind in its own keystore file (<data dir>/<kind>/<kind>.json) and QR codes as SVG files
+pub struct JsonBackend {
+    data_dir: PathBuf,
+}
+
+impl JsonBackend {
+    pub fn new(data_dir: impl AsRef<Path>) -> Self {
+        JsonBackend {
+            data_dir: data_dir.as_ref().to_path_buf(),
+        }
+    }
+
+    // Keystore file of a record kind
+    fn path(&self, kind: RecordKind) -> PathBuf {
+        self.data_dir.join(kind.name()).join(format!("{}.json", kind.name()))
+    }
+
+    // SVG file of an address; the address becomes a file name, so only plain address characters are allowed
+    fn qr_code_path(&self, address: &str) -> Result<PathBuf, &'static str> {
+        if address.is_empty() || !address.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err("Address contains characters that cannot be used in a file name");
+        }
+        Ok(self.data_dir.join("qr_codes").join(format!("{}.svg", address)))
+    }
+
+    // Reads the records of a file and checks that each can be migrated, backing the file up otherwise
+    fn read_checked(&self, path: &Path, session: &Session) -> Result<Vec<Value>, &'static str> {
+        let values = keystore::read_records(path, session).map_err(|err| back_up_if_corrupted(path, err))?;
+        for value in &values {
+            records::migrate(value.clone()).map_err(|err| back_up_if_corrupted(path, err))?;
+        }
+        Ok(values)
+    }
+}
+
+impl Backend for JsonBackend {
+    fn describe(&self) -> String {
+        format!("JSON keystore files in {}", self.data_dir.display())
+    }
+
+    fn location(&self, kind: RecordKind) -> String {
+        self.path(kind).display().to_string()
+    }
+
+    fn has_password(&self) -> bool {
+        RecordKind::ALL.iter().any(|&kind| keystore::read_header(self.path(kind)).is_some())
+    }
+
+    fn unlock(&self, password: &str) -> Result<Session, &'static str> {
+        let headers: Vec<_> = RecordKind::ALL.iter().filter_map(|&kind| keystore::read_header(self.path(kind))).collect();
+        Session::open(password, &headers)
+    }
+
+    fn load(&self, kind: RecordKind, session: &Session) -> Result<Vec<Value>, &'static str> {
+        let path = self.path(kind);
+        self.read_checked(&path, session)
+    }
+
+    fn count(&self, kind: RecordKind, session: &Session) -> Result<usize, &'static str> {
+        keystore::count_records(self.path(kind), session)
+    }
+
+    fn find_by_address(&self, kind: RecordKind, session: &Session, address: &str) -> Result<Vec<Value>, &'static str> {
+        let records = self.load(kind, session)?;
+        Ok(records.into_iter().filter(|record| address_of(record) == Some(address)).collect())
+    }
+
+    fn query(&self, kind: RecordKind, session: &Session, query: &RecordQuery) -> Result<RecordPage<Value>, &'static str> {
+        Ok(query.apply(self.load(kind, session)?))
+    }
+
+    // The whole read-modify-write runs under the file lock; files that fail to load are never overwritten
+    fn append(&self, kind: RecordKind, session: &Session, records: &[Value]) -> Result<(), &'static str> {
+        let path = self.path(kind);
+        storage::with_lock(&path, || {
+            let mut values = self.read_checked(&path, session)?;
+            values.extend_from_slice(records);
+            keystore::write_records(&path, session, &values)
+        })
+        .map_err(|_| "Failed to lock data file")?
+    }
+
+    fn upgrade(&self, kind: RecordKind, session: &Session) -> Result<usize, &'static str> {
+        let path = self.path(kind);
+        let encrypted = match keystore::migrate_file(&path, session)? {
+            Migration::Encrypted(count) => count,
+            Migration::Missing | Migration::AlreadyEncrypted => 0,
+        };
+        let upgraded = storage::with_lock(&path, || {
+            let values = self.read_checked(&path, session)?;
+            let outdated = values.iter().filter(|value| records::is_outdated(value)).count();
+            if outdated > 0 {
+                let migrated = values.into_iter().map(records::migrate).collect::<Result<Vec<_>, _>>()?;
+                keystore::write_records(&path, session, &migrated)?;
+            }
+            Ok::<_, &'static str>(outdated)
+        })
+        .map_err(|_| "Failed to lock data file")??;
+        Ok(encrypted.max(upgraded))
+    }
+
+    fn save_qr_code(&self, address: &str, svg: &str) -> Result<String, &'static str> {
+        let path = self.qr_code_path(address)?;
+        storage::write_atomic(&path, svg.as_bytes()).map_err(|_| "Failed to write QR code file")?;
+        Ok(path.display().to_string())
+    }
+
+    fn load_qr_code(&self, address: &str) -> Result<Option<String>, &'static str> {
+        match fs::read_to_string(self.qr_code_path(address)?) {
+            Ok(svg) => Ok(Some(svg)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(_) => Err("Failed to read QR code file"),
+        }
+    }
+}
+
+// Copies a corrupted data file aside so it can be inspected, and explains what happened
+fn back_up_if_corrupted(path: &Path, err: &'static str) -> &'static str {
+    if err != keystore::CORRUPTED {
+        return err;
+    }
+    let backup = format!("{}.corrupt-{}.bak", path.display(), Utc::now().format("%Y%m%dT%H%M%S"));
+    match fs::copy(path, backup) {
+        Ok(_) => CORRUPTED_BACKED_UP,
+        Err(_) => CORRUPTED_NOT_BACKED_UP,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::records::WalletRecord;
+    use serde_json::json;
+
+    // Returns a JSON backend rooted in a fresh temporary directory
+    fn temp_backend(name: &str) -> (PathBuf, Box<dyn Backend>) {
+        let dir = std::env::temp_dir().join(format!("hd_wallet_backend_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("wallets")).unwrap();
+        (dir.clone(), Box::new(JsonBackend::new(dir)))
+    }
+
+    #[test]
+    fn test_plaintext_file_needs_upgrade() {
+        let (dir, backend) = temp_backend("append");
+        let session = backend.unlock("secret").unwrap();
+        let path = dir.join("wallets/wallets.json");
+        let plaintext = json!([{"Mnemonic": "m", "Address": "a", "PublicKey": "p", "PrivateKey": "k", "GeneratedAt": "t"}]).to_string();
+        fs::write(&path, &plaintext).unwrap();
+
+        // Only the upgrade accepts a plaintext file; loads and saves leave it alone
+        assert_eq!(backend.load(RecordKind::Wallet, &session), Err(keystore::NOT_ENCRYPTED));
+        assert_eq!(backend.append_records(&session, &[WalletRecord::new("m2", "a2", "p2", "k2")]), Err(keystore::NOT_ENCRYPTED));
+        assert_eq!(fs::read_to_string(&path).unwrap(), plaintext);
+
+        assert_eq!(backend.upgrade(RecordKind::Wallet, &session), Ok(1));
+        assert_eq!(backend.upgrade(RecordKind::Wallet, &session), Ok(0));
+        backend.append_records(&session, &[WalletRecord::new("m2", "a2", "p2", "k2")]).unwrap();
+        let wallets: Vec<WalletRecord> = backend.load_records(&session).unwrap();
+        assert_eq!(wallets.iter().map(|wallet| wallet.address.as_str()).collect::<Vec<_>>(), ["a", "a2"]);
+        assert!(backend.has_password());
+        assert_eq!(backend.unlock("wrong").err(), Some("Wrong keystore password"));
+    }
+
+    // Concurrent saves (e.g. several browser tabs plus the CLI) must all end up in the file
+    #[test]
+    fn test_concurrent_appends_keep_every_record() {
+        let (_, backend) = temp_backend("concurrent");
+        let session = backend.unlock("secret").unwrap();
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let (backend, session) = (&backend, &session);
+                scope.spawn(move || {
+                    let address = format!("address-{}", i);
+                    backend.append_records(session, &[WalletRecord::new("m", &address, "p", "k")]).unwrap();
+                });
+            }
+        });
+
+        let mut addresses: Vec<String> = backend.load_records::<WalletRecord>(&session).unwrap().into_iter().map(|wallet| wallet.address).collect();
+        addresses.sort();
+        assert_eq!(addresses, (0..8).map(|i| format!("address-{}", i)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_corrupted_file_is_backed_up_not_overwritten() {
+        let (dir, backend) = temp_backend("corrupt");
+        let session = backend.unlock("secret").unwrap();
+        let path = dir.join("wallets/wallets.json");
+        fs::write(&path, "[{\"Mnemonic\": ").unwrap();
+
+        assert_eq!(backend.append_records(&session, &[WalletRecord::new("m", "a", "p", "k")]), Err(CORRUPTED_BACKED_UP));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[{\"Mnemonic\": ");
+        let backups = fs::read_dir(dir.join("wallets")).unwrap().filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().ends_with(".bak")).count();
+        assert_eq!(backups, 1);
+    }
+
+    #[test]
+    fn test_qr_code_file_names_are_checked() {
+        let (dir, backend) = temp_backend("qr");
+        fs::create_dir_all(dir.join("qr_codes")).unwrap();
+        assert!(backend.save_qr_code("1BoatSLRHtKNngkdXEeobR76b53LETtpyT", "<svg/>").is_ok());
+        assert_eq!(backend.load_qr_code("1BoatSLRHtKNngkdXEeobR76b53LETtpyT"), Ok(Some("<svg/>".to_string())));
+        assert_eq!(backend.load_qr_code("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"), Ok(None));
+        assert!(backend.save_qr_code("../../etc/passwd", "<svg/>").is_err());
+    }
+}
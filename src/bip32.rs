@@ -1,14 +1,39 @@
 use hmac::{Hmac, Mac};
 use sha2::Sha512;
-use secp256k1::{Secp256k1, SecretKey, PublicKey};
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
 use num_bigint::BigUint;
 use num_traits::Num;
+use bitcoin::util::base58;
+use bitcoin_hashes::{hash160, Hash};
 use std::convert::TryInto;
+use std::fmt;
+use zeroize::{Zeroize, Zeroizing};
+use crate::hardening;
 
-// Extended private key structure for Bitcoin wallet
+// Offset of hardened child indexes (written as 44' or 44h in paths)
+pub const HARDENED_OFFSET: u32 = 0x80000000;
+
+// Version bytes of mainnet extended private keys (xprv)
+pub const XPRV_VERSION: [u8; 4] = [0x04, 0x88, 0xAD, 0xE4];
+
+// Version bytes of mainnet extended public keys (xpub)
+pub const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xB2, 0x1E];
+
+// Version bytes of SLIP-132 nested segwit keys (yprv/ypub), used by Electrum and Coldcard
+pub const YPRV_VERSION: [u8; 4] = [0x04, 0x9D, 0x78, 0x78];
+pub const YPUB_VERSION: [u8; 4] = [0x04, 0x9D, 0x7C, 0xB2];
+
+// Version bytes of SLIP-132 native segwit keys (zprv/zpub), used by Electrum and Coldcard
+pub const ZPRV_VERSION: [u8; 4] = [0x04, 0xB2, 0x43, 0x0C];
+pub const ZPUB_VERSION: [u8; 4] = [0x04, 0xB2, 0x47, 0x46];
+
+// Extended private key structure for Bitcoin wallet (wiped from memory on drop)
 pub struct ExtendedPrivKey {
-    pub private_key: [u8; 32], // 32-byte private key
-    pub chain_code: [u8; 32],  // 32-byte chain code for key derivation
+    private_key: Box<[u8; 32]>,      // 32-byte private key on its own (locked) heap page, only reachable through expose_private_key
+    pub chain_code: [u8; 32],        // 32-byte chain code for key derivation
+    pub depth: u8,                   // Number of derivations from the master key
+    pub parent_fingerprint: [u8; 4], // Fingerprint of the parent key (zero for a master key)
+    pub child_number: u32,           // Index of this key in its parent
 }
 
 impl ExtendedPrivKey {
@@ -38,10 +63,27 @@ impl ExtendedPrivKey {
             return Err("Invalid private key: out of range");
         }
 
-        Ok(ExtendedPrivKey {
-            private_key,
-            chain_code,
-        })
+        Ok(Self::locked(private_key, chain_code))
+    }
+
+    // Builds a key from a private key and chain code, checking the key range
+    pub fn from_parts(private_key: [u8; 32], chain_code: [u8; 32]) -> Result<Self, &'static str> {
+        if !Self::is_valid_private_key(&private_key) {
+            return Err("Invalid private key: out of range");
+        }
+        Ok(Self::locked(private_key, chain_code))
+    }
+
+    // Exposes the secret private key; callers must not log or persist it by accident
+    pub fn expose_private_key(&self) -> &[u8; 32] {
+        &self.private_key
+    }
+
+    // Moves the private key to the heap and locks it in memory when hardening is on (as a master key)
+    fn locked(private_key: [u8; 32], chain_code: [u8; 32]) -> Self {
+        let private_key = Box::new(private_key);
+        hardening::lock(private_key.as_ptr(), private_key.len());
+        ExtendedPrivKey { private_key, chain_code, depth: 0, parent_fingerprint: [0; 4], child_number: 0 }
     }
 
     // Checks if private key is within valid range
@@ -51,18 +93,15 @@ impl ExtendedPrivKey {
 
     // Derives a child key from the current key using an index
     pub fn derive_child_key(&self, index: u32) -> Result<Self, &'static str> {
-        let mut data = Vec::new();
+        let mut data = Zeroizing::new(Vec::with_capacity(37));
+        let public_key = self.public_key()?;
 
         // Hardened vs non-hardened derivation
         if index >= 0x80000000 {
             data.push(0);
-            data.extend_from_slice(&self.private_key);
+            data.extend_from_slice(&self.private_key[..]);
         } else {
-            let secp = Secp256k1::new();
-            let secret_key = SecretKey::from_slice(&self.private_key)
-                .map_err(|_| "Invalid private key")?;
-            let public_key = PublicKey::from_secret_key(&secp, &secret_key);
-            data.extend_from_slice(&public_key.serialize());
+            data.extend_from_slice(&public_key);
         }
 
         data.extend_from_slice(&index.to_be_bytes());
@@ -80,12 +119,219 @@ impl ExtendedPrivKey {
             &child_key.try_into().map_err(|_| "Invalid child key length")?,
         )?;
 
-        Ok(ExtendedPrivKey {
-            private_key: derived_key,
-            chain_code: child_chain_code.try_into()
+        let mut child = Self::locked(
+            derived_key,
+            child_chain_code.try_into()
                 .map_err(|_| "Invalid chain code length")?,
+        );
+        child.depth = self.depth.checked_add(1).ok_or("Derivation path is too long")?;
+        child.parent_fingerprint = hash160::Hash::hash(&public_key)[..4].try_into().map_err(|_| "Invalid fingerprint length")?;
+        child.child_number = index;
+        Ok(child)
+    }
+
+    // Derives the key at a derivation path, one child index at a time
+    pub fn derive_path(&self, path: &[u32]) -> Result<Self, &'static str> {
+        let mut key = Self::locked(*self.private_key, self.chain_code);
+        (key.depth, key.parent_fingerprint, key.child_number) = (self.depth, self.parent_fingerprint, self.child_number);
+        for &index in path {
+            key = key.derive_child_key(index)?;
+        }
+        Ok(key)
+    }
+
+    // Decodes a Base58Check-encoded extended private key (xprv, or the yprv/zprv used by segwit wallets)
+    pub fn from_base58(encoded: &str) -> Result<Self, &'static str> {
+        let data = Zeroizing::new(base58::from_check(encoded.trim()).map_err(|_| "Invalid Base58Check encoding")?);
+        if data.len() != 78 || ![XPRV_VERSION, YPRV_VERSION, ZPRV_VERSION].iter().any(|version| data[..4] == version[..]) {
+            return Err("Not a mainnet extended private key (xprv)");
+        }
+        if data[45] != 0x00 {
+            return Err("Extended key does not contain a private key");
+        }
+
+        let private_key: [u8; 32] = data[46..78].try_into().map_err(|_| "Invalid private key length")?;
+        if !Self::is_valid_private_key(&private_key) {
+            return Err("Invalid private key: out of range");
+        }
+        let mut key = Self::locked(
+            private_key,
+            data[13..45].try_into().map_err(|_| "Invalid chain code length")?,
+        );
+        key.depth = data[4];
+        key.parent_fingerprint = data[5..9].try_into().map_err(|_| "Invalid fingerprint length")?;
+        key.child_number = u32::from_be_bytes(data[9..13].try_into().map_err(|_| "Invalid child number length")?);
+        Ok(key)
+    }
+
+    // Exposes the key as an xprv, with its depth, parent fingerprint and child number
+    pub fn expose_base58(&self) -> String {
+        let mut data = Zeroizing::new(self.serialization_header(XPRV_VERSION));
+        data.extend_from_slice(&self.chain_code);
+        data.push(0x00);
+        data.extend_from_slice(&self.private_key[..]);
+        base58::check_encode_slice(&data)
+    }
+
+    // Returns the matching xpub, with the key's depth, parent fingerprint and child number
+    pub fn xpub(&self) -> Result<String, &'static str> {
+        let mut data = self.serialization_header(XPUB_VERSION);
+        data.extend_from_slice(&self.chain_code);
+        data.extend_from_slice(&self.public_key()?);
+        Ok(base58::check_encode_slice(&data))
+    }
+
+    // Exposes the key at a path below this key as an extended private key with the given version bytes
+    pub fn expose_base58_at(&self, path: &[u32], version: [u8; 4]) -> Result<String, &'static str> {
+        let key = self.derive_path(path)?;
+        let mut data = Zeroizing::new(key.serialization_header(version));
+        data.extend_from_slice(&key.chain_code);
+        data.push(0x00);
+        data.extend_from_slice(&key.private_key[..]);
+        Ok(base58::check_encode_slice(&data))
+    }
+
+    // Returns the extended public key at a path below this key, with the given version bytes
+    pub fn xpub_at(&self, path: &[u32], version: [u8; 4]) -> Result<String, &'static str> {
+        let key = self.derive_path(path)?;
+        let mut data = key.serialization_header(version);
+        data.extend_from_slice(&key.chain_code);
+        data.extend_from_slice(&key.public_key()?);
+        Ok(base58::check_encode_slice(&data))
+    }
+
+    // Version, depth, parent fingerprint and child number that start the key's serialization
+    fn serialization_header(&self, version: [u8; 4]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(78);
+        data.extend_from_slice(&version);
+        data.push(self.depth);
+        data.extend_from_slice(&self.parent_fingerprint);
+        data.extend_from_slice(&self.child_number.to_be_bytes());
+        data
+    }
+
+    // Returns the key fingerprint (first 4 bytes of HASH160 of the public key), safe to log or show
+    pub fn fingerprint(&self) -> Result<String, &'static str> {
+        Ok(hex::encode(&hash160::Hash::hash(&self.public_key()?)[..4]))
+    }
+
+    // Returns the compressed public key matching the private key
+    pub fn public_key(&self) -> Result<[u8; 33], &'static str> {
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&self.private_key[..])
+            .map_err(|_| "Invalid private key")?;
+        Ok(PublicKey::from_secret_key(&secp, &secret_key).serialize())
+    }
+}
+
+impl Drop for ExtendedPrivKey {
+    fn drop(&mut self) {
+        self.private_key.zeroize();
+        self.chain_code.zeroize();
+        hardening::unlock(self.private_key.as_ptr(), self.private_key.len());
+    }
+}
+
+// Never prints the private key or chain code
+impl fmt::Debug for ExtendedPrivKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ExtendedPrivKey([REDACTED])")
+    }
+}
+
+// Extended public key decoded from its Base58 (xpub) serialization
+#[derive(Clone)]
+pub struct ExtendedPubKey {
+    pub depth: u8,            // Number of derivations from the master key
+    pub child_number: u32,    // Index of this key in its parent
+    pub chain_code: [u8; 32], // 32-byte chain code
+    pub public_key: [u8; 33], // Compressed public key
+}
+
+impl ExtendedPubKey {
+    // Decodes a Base58Check-encoded extended public key
+    pub fn from_base58(encoded: &str) -> Result<Self, &'static str> {
+        let data = base58::from_check(encoded.trim()).map_err(|_| "Invalid Base58Check encoding")?;
+        if data.len() != 78 {
+            return Err("Extended public key must be 78 bytes long");
+        }
+        if data[45] != 0x02 && data[45] != 0x03 {
+            return Err("Extended key does not contain a compressed public key");
+        }
+
+        Ok(ExtendedPubKey {
+            depth: data[4],
+            child_number: u32::from_be_bytes(data[9..13].try_into().map_err(|_| "Invalid child number length")?),
+            chain_code: data[13..45].try_into().map_err(|_| "Invalid chain code length")?,
+            public_key: data[45..78].try_into().map_err(|_| "Invalid public key length")?,
         })
     }
+
+    // Derives a non-hardened child key from the public key alone (CKDpub)
+    pub fn derive_child_key(&self, index: u32) -> Result<Self, &'static str> {
+        if index >= HARDENED_OFFSET {
+            return Err("Hardened derivation needs the private key");
+        }
+        let mut hmac = Hmac::<Sha512>::new_from_slice(&self.chain_code)
+            .map_err(|_| "HMAC initialization failed")?;
+        hmac.update(&self.public_key);
+        hmac.update(&index.to_be_bytes());
+        let result = hmac.finalize().into_bytes();
+        let (tweak, chain_code) = result.split_at(32);
+
+        // Child public key = parent public key + tweak·G
+        let tweak = Scalar::from_be_bytes(tweak.try_into().map_err(|_| "Invalid child key length")?).map_err(|_| "Invalid child key")?;
+        let public_key = PublicKey::from_slice(&self.public_key)
+            .map_err(|_| "Invalid public key")?
+            .add_exp_tweak(&Secp256k1::verification_only(), &tweak)
+            .map_err(|_| "Invalid child key")?;
+        Ok(ExtendedPubKey {
+            depth: self.depth.saturating_add(1),
+            child_number: index,
+            chain_code: chain_code.try_into().map_err(|_| "Invalid chain code length")?,
+            public_key: public_key.serialize(),
+        })
+    }
+
+    // Derives the key at a non-hardened path, one child index at a time
+    pub fn derive_path(&self, path: &[u32]) -> Result<Self, &'static str> {
+        path.iter().try_fold(self.clone(), |key, &index| key.derive_child_key(index))
+    }
+
+    // Checks whether this extended public key belongs to the given private key
+    pub fn matches(&self, key: &ExtendedPrivKey) -> bool {
+        self.chain_code == key.chain_code && key.public_key().map(|public_key| public_key == self.public_key).unwrap_or(false)
+    }
+}
+
+// Re-encodes an extended public key with other version bytes, e.g. a SLIP-132 Zpub as an xpub
+pub fn convert_xpub(encoded: &str, version: [u8; 4]) -> Result<String, &'static str> {
+    ExtendedPubKey::from_base58(encoded)?;
+    let mut data = base58::from_check(encoded.trim()).map_err(|_| "Invalid Base58Check encoding")?;
+    data[..4].copy_from_slice(&version);
+    Ok(base58::check_encode_slice(&data))
+}
+
+// Version bytes and depth of a Base58Check-encoded extended key (depth 0 is a master key)
+pub fn key_header(encoded: &str) -> Result<([u8; 4], u8), &'static str> {
+    let data = Zeroizing::new(base58::from_check(encoded.trim()).map_err(|_| "Invalid Base58Check encoding")?);
+    if data.len() != 78 {
+        return Err("Extended key must be 78 bytes long");
+    }
+    Ok((data[..4].try_into().map_err(|_| "Invalid version length")?, data[4]))
+}
+
+// Writes a derivation path out, e.g. m/44'/0'/0'/0/1
+pub fn format_path(path: &[u32]) -> String {
+    let mut formatted = String::from("m");
+    for &index in path {
+        if index >= HARDENED_OFFSET {
+            formatted.push_str(&format!("/{}'", index - HARDENED_OFFSET));
+        } else {
+            formatted.push_str(&format!("/{}", index));
+        }
+    }
+    formatted
 }
 
 // Adds two scalars (private keys) modulo the curve order
@@ -150,6 +396,85 @@ mod tests {
         assert_ne!(parent_key.chain_code, child_key.chain_code);
     }
 
+    #[test]
+    fn test_derive_path_vector() {
+        // BIP32 test vector 1, chain m/0'/1
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = ExtendedPrivKey::new(&seed).expect("Failed to create key");
+        let key = master.derive_path(&[HARDENED_OFFSET, 1]).expect("Failed to derive path");
+
+        assert_eq!(hex::encode(key.expose_private_key()), "3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368");
+        assert_eq!(hex::encode(key.chain_code), "2a7857631386ba23dacac34180dd1983734e444fdbf774041578e9b6adb37c19");
+    }
+
+    #[test]
+    fn test_xpub_decoding() {
+        // BIP32 test vector 1, chain m
+        let xpub = ExtendedPubKey::from_base58(
+            "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8",
+        ).expect("Failed to decode xpub");
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = ExtendedPrivKey::new(&seed).expect("Failed to create key");
+
+        assert_eq!(xpub.depth, 0);
+        assert!(xpub.matches(&master));
+        assert_eq!(master.xpub().unwrap(), "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8");
+        assert_eq!(master.fingerprint().unwrap(), "3442193e");
+        assert!(!xpub.matches(&master.derive_child_key(0).unwrap()));
+    }
+
+    #[test]
+    fn test_xprv_round_trip() {
+        // BIP32 test vector 1, chain m
+        let xprv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = ExtendedPrivKey::new(&seed).expect("Failed to create key");
+
+        assert_eq!(master.expose_base58(), xprv);
+        assert_eq!(format!("{:?}", master), "ExtendedPrivKey([REDACTED])");
+        assert_eq!(ExtendedPrivKey::from_base58(xprv).unwrap().private_key, master.private_key);
+        assert!(ExtendedPrivKey::from_base58("xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8").is_err());
+    }
+
+    #[test]
+    fn test_serialization_below_master() {
+        // BIP32 test vector 1, chain m/0'/1
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = ExtendedPrivKey::new(&seed).expect("Failed to create key");
+        let path = [HARDENED_OFFSET, 1];
+
+        assert_eq!(master.xpub_at(&path, XPUB_VERSION).unwrap(), "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ");
+        assert_eq!(master.expose_base58_at(&path, XPRV_VERSION).unwrap(), "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs");
+        assert_eq!(master.xpub_at(&[], XPUB_VERSION).unwrap(), master.xpub().unwrap());
+
+        // zprv keys decode to the same key data
+        let zprv = master.expose_base58_at(&path, ZPRV_VERSION).unwrap();
+        assert!(zprv.starts_with("zprv"));
+        assert_eq!(ExtendedPrivKey::from_base58(&zprv).unwrap().private_key, master.derive_path(&path).unwrap().private_key);
+
+        // An imported key below the master keeps its place, so it serializes exactly as it was exported
+        let xprv = "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs";
+        let imported = ExtendedPrivKey::from_base58(xprv).unwrap();
+        assert_eq!((imported.depth, imported.child_number), (2, 1));
+        assert_eq!(imported.expose_base58(), xprv);
+        assert_eq!(imported.xpub().unwrap(), "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ");
+        // BIP32 test vector 1, chain m/0'/1/2'
+        assert_eq!(imported.xpub_at(&[2 + HARDENED_OFFSET], XPUB_VERSION).unwrap(), "xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5");
+    }
+
+    #[test]
+    fn test_public_derivation_matches_private() {
+        // BIP32 test vector 1: m/0'/1/2' has no public parent, but m/0'/1 can be derived from m/0'
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = ExtendedPrivKey::new(&seed).expect("Failed to create key");
+        let account = ExtendedPubKey::from_base58(&master.xpub_at(&[HARDENED_OFFSET], XPUB_VERSION).unwrap()).unwrap();
+
+        let child = account.derive_path(&[1, 7]).unwrap();
+        assert_eq!(child.public_key, master.derive_path(&[HARDENED_OFFSET, 1, 7]).unwrap().public_key().unwrap());
+        assert_eq!((child.depth, child.child_number), (3, 7));
+        assert!(account.derive_child_key(HARDENED_OFFSET).is_err());
+    }
+
     #[test]
     fn test_add_scalars() {
         let a = [
@@ -1,31 +1,49 @@
 use rand::Rng;
 use sha2::{Digest, Sha256, Sha512};
-use std::fs;
 use hmac::Hmac;
 use pbkdf2::pbkdf2;
+use std::fmt;
+use zeroize::{Zeroize, Zeroizing};
+use crate::hardening;
+
+// BIP39 English wordlist, embedded so the binary runs from any directory
+const ENGLISH_WORDLIST: &str = include_str!("english.txt");
+
+// Word counts allowed by BIP39 (12, 15, 18, 21 or 24 words)
+pub const VALID_WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
 
 // Mnemonic structure for generating and storing mnemonic phrases
 pub struct Mnemonic {
-    phrase: String, // Stores the mnemonic phrase
+    phrase: Zeroizing<String>, // Mnemonic phrase, wiped from memory on drop
 }
 
 impl Mnemonic {
     // Generates a mnemonic phrase from random entropy
     pub fn generate(bits: usize) -> Self {
-        if bits % 32 != 0 || bits < 128 || bits > 256 {
+        if !bits.is_multiple_of(32) || !(128..=256).contains(&bits) {
             panic!("Entropy must be a multiple of 32 and between 128 and 256 bits.");
         }
 
         // Generate random entropy
         let mut rng = rand::thread_rng();
-        let entropy: Vec<u8> = (0..bits / 8).map(|_| rng.gen()).collect();
+        let entropy: Zeroizing<Vec<u8>> = Zeroizing::new((0..bits / 8).map(|_| rng.gen()).collect());
+
+        Self::from_entropy(&entropy).expect("Entropy length was validated above")
+    }
+
+    // Builds the mnemonic phrase encoding the given entropy
+    pub fn from_entropy(entropy: &[u8]) -> Result<Self, &'static str> {
+        let bits = entropy.len() * 8;
+        if !bits.is_multiple_of(32) || !(128..=256).contains(&bits) {
+            return Err("Entropy must be a multiple of 32 and between 128 and 256 bits");
+        }
 
         // Calculate checksum for the entropy
-        let checksum = Self::calculate_checksum(&entropy, bits);
+        let checksum = Self::calculate_checksum(entropy, bits);
 
         // Convert entropy and checksum to binary string
-        let mut binary = String::new();
-        for byte in &entropy {
+        let mut binary = Zeroizing::new(String::with_capacity(bits + checksum.len()));
+        for byte in entropy {
             binary.push_str(&format!("{:08b}", byte));
         }
         binary.push_str(&checksum);
@@ -36,27 +54,69 @@ impl Mnemonic {
             .map(|chunk| std::str::from_utf8(chunk).unwrap())
             .collect();
 
-        // Load wordlist and map chunks to words
-        let wordlist = Self::load_wordlist("src/english.txt");
-        let words: Vec<String> = chunks.iter().map(|chunk| {
+        // Load wordlist and map chunks to words, writing them straight into the wiped buffer
+        let wordlist = Self::wordlist();
+        let mut phrase = Zeroizing::new(String::with_capacity(chunks.len() * 9));
+        for (position, chunk) in chunks.iter().enumerate() {
             let index = usize::from_str_radix(chunk, 2).unwrap();
-            wordlist[index].clone()
-        }).collect();
+            if position > 0 {
+                phrase.push(' ');
+            }
+            phrase.push_str(&wordlist[index]);
+        }
+
+        Ok(Self::locked(phrase))
+    }
+
+    // Parses and validates a mnemonic phrase (word count, wordlist and checksum)
+    pub fn from_phrase(phrase: &str) -> Result<Self, &'static str> {
+        let wordlist = Self::wordlist();
+        let words = Zeroizing::new(normalize_phrase(phrase));
+
+        if !VALID_WORD_COUNTS.contains(&words.len()) {
+            return Err("Mnemonic must contain 12, 15, 18, 21 or 24 words");
+        }
+
+        let mut indices = Vec::with_capacity(words.len());
+        for word in words.iter() {
+            match word_index(&wordlist, word) {
+                Some(index) => indices.push(index),
+                None => return Err("Mnemonic contains a word that is not in the BIP39 wordlist"),
+            }
+        }
 
-        Mnemonic {
-            phrase: words.join(" "), // Join words to form mnemonic phrase
+        if !checksum_is_valid(&indices) {
+            return Err("Invalid mnemonic checksum");
         }
+
+        Ok(Self::locked(Zeroizing::new(words.join(" "))))
+    }
+
+    // Wraps a phrase, locking its buffer in memory when hardening is on
+    fn locked(phrase: Zeroizing<String>) -> Self {
+        hardening::lock(phrase.as_ptr(), phrase.capacity());
+        Mnemonic { phrase }
     }
 
-    // Returns the mnemonic phrase as a string
-    pub fn to_string(&self) -> String {
-        self.phrase.clone()
+    // Exposes the secret phrase; callers must not log or persist it by accident
+    pub fn expose_phrase(&self) -> &str {
+        &self.phrase
+    }
+
+    // Returns the entropy encoded by the mnemonic (the checksum is dropped)
+    pub fn to_entropy(&self) -> Zeroizing<Vec<u8>> {
+        let wordlist = Self::wordlist();
+        let indices: Vec<usize> = self
+            .phrase
+            .split_whitespace()
+            .map(|word| word_index(&wordlist, word).expect("mnemonic words are validated on creation"))
+            .collect();
+        Zeroizing::new(indices_to_entropy(&indices).0)
     }
 
-    // Loads the wordlist from a file
-    fn load_wordlist(filepath: &str) -> Vec<String> {
-        let content = fs::read_to_string(filepath).expect("Failed to load wordlist.");
-        content.lines().map(String::from).collect()
+    // Returns the BIP39 English wordlist
+    pub fn wordlist() -> Vec<String> {
+        ENGLISH_WORDLIST.lines().map(String::from).collect()
     }
 
     // Calculates the checksum for the entropy
@@ -68,22 +128,299 @@ impl Mnemonic {
     }
 }
 
+impl Drop for Mnemonic {
+    fn drop(&mut self) {
+        let (ptr, capacity) = (self.phrase.as_ptr(), self.phrase.capacity());
+        self.phrase.zeroize();
+        hardening::unlock(ptr, capacity);
+    }
+}
+
+// Never prints the phrase, only its length
+impl fmt::Debug for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Mnemonic([REDACTED; {} words])", self.phrase.split_whitespace().count())
+    }
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[REDACTED MNEMONIC]")
+    }
+}
+
+// Splits a phrase into lowercase words, ignoring extra whitespace
+pub fn normalize_phrase(phrase: &str) -> Vec<String> {
+    phrase.split_whitespace().map(|word| word.to_lowercase()).collect()
+}
+
+// Returns the index of a word in the (sorted) wordlist
+pub fn word_index(wordlist: &[String], word: &str) -> Option<usize> {
+    wordlist.binary_search_by(|probe| probe.as_str().cmp(word)).ok()
+}
+
+// Packs 11-bit word indices into entropy bytes and the trailing checksum bits
+fn indices_to_entropy(indices: &[usize]) -> (Vec<u8>, u8) {
+    let total_bits = indices.len() * 11;
+    let checksum_bits = total_bits / 33;
+    let entropy_bits = total_bits - checksum_bits;
+
+    let mut entropy = vec![0u8; entropy_bits / 8];
+    let mut checksum = 0u8;
+    for bit in 0..total_bits {
+        let index = indices[bit / 11];
+        let value = (index >> (10 - bit % 11)) & 1;
+        if bit < entropy_bits {
+            entropy[bit / 8] |= (value as u8) << (7 - bit % 8);
+        } else {
+            checksum = (checksum << 1) | value as u8;
+        }
+    }
+    (entropy, checksum)
+}
+
+// Checks whether a sequence of word indices carries a valid BIP39 checksum
+pub fn checksum_is_valid(indices: &[usize]) -> bool {
+    if !VALID_WORD_COUNTS.contains(&indices.len()) {
+        return false;
+    }
+    let (entropy, checksum) = indices_to_entropy(indices);
+    let checksum_bits = indices.len() / 3;
+    let hash = Sha256::digest(&entropy);
+    hash[0] >> (8 - checksum_bits) == checksum
+}
+
+// Outcome of matching a typed word against the wordlist
+#[derive(Debug, PartialEq)]
+pub enum WordMatch {
+    Exact(String),            // The word is in the wordlist
+    Completed(String),        // The word is an unambiguous prefix of a wordlist entry
+    Suggestions(Vec<String>), // The word is unknown; closest candidates, best first
+}
+
+// Matches a typed word, completing unique prefixes and suggesting fixes for typos
+pub fn match_word(wordlist: &[String], typed: &str) -> WordMatch {
+    let typed = typed.trim().to_lowercase();
+
+    if word_index(wordlist, &typed).is_some() {
+        return WordMatch::Exact(typed);
+    }
+
+    let completions = complete_prefix(wordlist, &typed);
+    if completions.len() == 1 {
+        return WordMatch::Completed(completions[0].clone());
+    }
+
+    // BIP39 words are unique in their first four letters, so a longer word
+    // with a known four-letter prefix is most likely a misspelled ending
+    if typed.chars().count() > 4 {
+        let prefix: String = typed.chars().take(4).collect();
+        let by_prefix = complete_prefix(wordlist, &prefix);
+        if by_prefix.len() == 1 {
+            let mut suggestions = by_prefix;
+            for word in suggest_corrections(wordlist, &typed, 2) {
+                if !suggestions.contains(&word) {
+                    suggestions.push(word);
+                }
+            }
+            return WordMatch::Suggestions(suggestions);
+        }
+    }
+
+    if completions.len() > 1 {
+        return WordMatch::Suggestions(completions);
+    }
+
+    WordMatch::Suggestions(suggest_corrections(wordlist, &typed, 2))
+}
+
+// Returns every wordlist entry starting with the given prefix
+pub fn complete_prefix(wordlist: &[String], prefix: &str) -> Vec<String> {
+    if prefix.is_empty() {
+        return Vec::new();
+    }
+    let start = wordlist.partition_point(|word| word.as_str() < prefix);
+    wordlist[start..]
+        .iter()
+        .take_while(|word| word.starts_with(prefix))
+        .cloned()
+        .collect()
+}
+
+// Returns wordlist entries within `max_distance` edits of the word, closest first
+pub fn suggest_corrections(wordlist: &[String], word: &str, max_distance: usize) -> Vec<String> {
+    let mut candidates: Vec<(usize, &String)> = wordlist
+        .iter()
+        .map(|candidate| (levenshtein(word, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    candidates.sort();
+    candidates.into_iter().map(|(_, candidate)| candidate.clone()).collect()
+}
+
+// Computes the Levenshtein edit distance between two words
+fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+// Finds phrases obtained by swapping two adjacent words that pass the checksum
+pub fn find_adjacent_swaps(wordlist: &[String], words: &[String]) -> Vec<String> {
+    let indices: Option<Vec<usize>> = words.iter().map(|word| word_index(wordlist, word)).collect();
+    let mut indices = match indices {
+        Some(indices) => indices,
+        None => return Vec::new(),
+    };
+
+    let mut phrases = Vec::new();
+    for i in 0..indices.len().saturating_sub(1) {
+        if indices[i] == indices[i + 1] {
+            continue;
+        }
+        indices.swap(i, i + 1);
+        if checksum_is_valid(&indices) {
+            let mut swapped = words.to_vec();
+            swapped.swap(i, i + 1);
+            phrases.push(swapped.join(" "));
+        }
+        indices.swap(i, i + 1);
+    }
+    phrases
+}
+
 // Seed structure for deriving a seed from a mnemonic phrase
 pub struct Seed {
-    data: Vec<u8>, // Stores the seed bytes
+    data: Zeroizing<Vec<u8>>, // Seed bytes, wiped from memory on drop
 }
 
 impl Seed {
     // Derives a seed from a mnemonic phrase and passphrase using PBKDF2
     pub fn new(mnemonic: &str, passphrase: &str) -> Self {
-        let salt = format!("mnemonic{}", passphrase); // Salt for PBKDF2
-        let mut seed = vec![0u8; 64]; // 64-byte seed
-        pbkdf2::<Hmac<Sha512>>(mnemonic.as_bytes(), salt.as_bytes(), 2048, &mut seed); // PBKDF2 with HMAC-SHA512
-        Seed { data: seed }
+        let salt = Zeroizing::new(format!("mnemonic{}", passphrase)); // Salt for PBKDF2
+        let mut seed = Self::locked(Zeroizing::new(vec![0u8; 64])); // 64-byte seed, locked before it is filled
+        pbkdf2::<Hmac<Sha512>>(mnemonic.as_bytes(), salt.as_bytes(), 2048, &mut seed.data); // PBKDF2 with HMAC-SHA512
+        seed
     }
 
-    // Returns the seed as a byte slice
-    pub fn as_bytes(&self) -> &[u8] {
+    // Wraps raw seed bytes, such as a recovered SLIP-39 master secret
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        Self::locked(Zeroizing::new(data))
+    }
+
+    // Wraps seed bytes, locking their buffer in memory when hardening is on
+    fn locked(data: Zeroizing<Vec<u8>>) -> Self {
+        hardening::lock(data.as_ptr(), data.capacity());
+        Seed { data }
+    }
+
+    // Exposes the secret seed bytes; callers must not log or persist them by accident
+    pub fn expose_bytes(&self) -> &[u8] {
         &self.data
     }
-}
\ No newline at end of file
+}
+
+impl Drop for Seed {
+    fn drop(&mut self) {
+        let (ptr, capacity) = (self.data.as_ptr(), self.data.capacity());
+        self.data.zeroize();
+        hardening::unlock(ptr, capacity);
+    }
+}
+
+// Never prints the seed, only its length
+impl fmt::Debug for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Seed([REDACTED; {} bytes])", self.data.len())
+    }
+}
+
+impl fmt::Display for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[REDACTED SEED]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VECTOR_PHRASE: &str = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+
+    // Tests the BIP39 test vector for 0x7f7f... entropy
+    #[test]
+    fn test_from_entropy_vector() {
+        let mnemonic = Mnemonic::from_entropy(&[0x7f; 16]).expect("Valid entropy");
+        assert_eq!(mnemonic.expose_phrase(), VECTOR_PHRASE);
+    }
+
+    // Tests that formatting a mnemonic or seed never reveals the secret
+    #[test]
+    fn test_secrets_are_redacted() {
+        let mnemonic = Mnemonic::from_phrase(VECTOR_PHRASE).unwrap();
+        let seed = Seed::new(VECTOR_PHRASE, "");
+        for formatted in [format!("{:?}", mnemonic), mnemonic.to_string(), format!("{:?}", seed), seed.to_string()] {
+            assert!(formatted.contains("REDACTED"));
+            assert!(!formatted.contains("legal") && !formatted.contains(&hex::encode(&seed.expose_bytes()[..4])));
+        }
+    }
+
+    // Tests the BIP39 seed test vector with the "TREZOR" passphrase
+    #[test]
+    fn test_seed_vector() {
+        let seed = Seed::new(VECTOR_PHRASE, "TREZOR");
+        assert_eq!(
+            hex::encode(seed.expose_bytes()),
+            "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6fa457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607"
+        );
+    }
+
+    // Tests that phrases with a bad checksum or unknown words are rejected
+    #[test]
+    fn test_from_phrase_validation() {
+        assert!(Mnemonic::from_phrase(VECTOR_PHRASE).is_ok());
+        assert!(Mnemonic::from_phrase(&VECTOR_PHRASE.replace("yellow", "year")).is_err());
+        assert!(Mnemonic::from_phrase(&VECTOR_PHRASE.replace("yellow", "yelow")).is_err());
+        assert!(Mnemonic::from_phrase("legal winner thank").is_err());
+    }
+
+    // Tests prefix completion and typo suggestions
+    #[test]
+    fn test_match_word() {
+        let wordlist = Mnemonic::wordlist();
+        assert_eq!(match_word(&wordlist, "legal"), WordMatch::Exact("legal".to_string()));
+        assert_eq!(match_word(&wordlist, "sausa"), WordMatch::Completed("sausage".to_string()));
+        assert_eq!(match_word(&wordlist, "aban"), WordMatch::Completed("abandon".to_string()));
+        match match_word(&wordlist, "winnre") {
+            WordMatch::Suggestions(words) => assert_eq!(words[0], "winner"),
+            other => panic!("Unexpected match: {:?}", other),
+        }
+        match match_word(&wordlist, "thnak") {
+            WordMatch::Suggestions(words) => assert!(words.contains(&"thank".to_string())),
+            other => panic!("Unexpected match: {:?}", other),
+        }
+    }
+
+    // Tests that swapping two adjacent words back is detected
+    #[test]
+    fn test_find_adjacent_swaps() {
+        let wordlist = Mnemonic::wordlist();
+        let mut words = normalize_phrase(VECTOR_PHRASE);
+        words.swap(3, 4);
+        let candidates = find_adjacent_swaps(&wordlist, &words);
+        assert!(candidates.contains(&VECTOR_PHRASE.to_string()));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::bip32::{format_path, ExtendedPrivKey, HARDENED_OFFSET};
+use crate::bip39::{Mnemonic, Seed};
+use base64::Engine;
+use bitcoin::util::base58;
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+
+// Purpose index of all BIP85 derivations (m/83696968')
+const BIP85_PURPOSE: u32 = 83696968;
+
+// HMAC key turning a derived private key into entropy
+const ENTROPY_HMAC_KEY: &[u8] = b"bip-entropy-from-k";
+
+// Language index of the English BIP39 wordlist
+const ENGLISH_LANGUAGE: u32 = 0;
+
+// Alphabet of the base85 password application (RFC 1924)
+const BASE85_ALPHABET: &[u8; 85] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+// BIP85 applications and their parameters
+#[derive(Clone, Copy, PartialEq)]
+pub enum Application {
+    Mnemonic { words: usize },        // 39': BIP39 mnemonic of 12, 18 or 24 words
+    Wif,                              // 2': private key in Wallet Import Format
+    Xprv,                             // 32': extended private key
+    Hex { bytes: usize },             // 128169': 16 to 64 raw bytes
+    PasswordBase64 { length: usize }, // 707764': base64 password of 20 to 86 characters
+    PasswordBase85 { length: usize }, // 707785': base85 password of 10 to 80 characters
+}
+
+impl Application {
+    // Builds an application from its number and its length parameter (words, bytes or characters)
+    pub fn from_number(number: u32, length: usize) -> Result<Self, &'static str> {
+        let application = match number {
+            39 => Application::Mnemonic { words: length },
+            2 => Application::Wif,
+            32 => Application::Xprv,
+            128169 => Application::Hex { bytes: length },
+            707764 => Application::PasswordBase64 { length },
+            707785 => Application::PasswordBase85 { length },
+            _ => return Err("Unsupported BIP85 application number"),
+        };
+        application.validate()?;
+        Ok(application)
+    }
+
+    // Checks the length parameter against the range allowed by BIP85
+    fn validate(&self) -> Result<(), &'static str> {
+        match *self {
+            Application::Mnemonic { words } if ![12, 18, 24].contains(&words) => Err("Mnemonics must have 12, 18 or 24 words"),
+            Application::Hex { bytes } if !(16..=64).contains(&bytes) => Err("Hex entropy must be between 16 and 64 bytes"),
+            Application::PasswordBase64 { length } if !(20..=86).contains(&length) => {
+                Err("Base64 passwords must be between 20 and 86 characters")
+            }
+            Application::PasswordBase85 { length } if !(10..=80).contains(&length) => {
+                Err("Base85 passwords must be between 10 and 80 characters")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Derivation path below m/83696968' (all indices hardened)
+    pub fn path(&self, index: u32) -> Vec<u32> {
+        let path = match *self {
+            Application::Mnemonic { words } => vec![39, ENGLISH_LANGUAGE, words as u32, index],
+            Application::Wif => vec![2, index],
+            Application::Xprv => vec![32, index],
+            Application::Hex { bytes } => vec![128169, bytes as u32, index],
+            Application::PasswordBase64 { length } => vec![707764, length as u32, index],
+            Application::PasswordBase85 { length } => vec![707785, length as u32, index],
+        };
+        std::iter::once(BIP85_PURPOSE).chain(path).map(|i| i + HARDENED_OFFSET).collect()
+    }
+
+    // Derivation path written out, e.g. m/83696968'/39'/0'/12'/0'
+    pub fn path_string(&self, index: u32) -> String {
+        format_path(&self.path(index))
+    }
+
+    // Derives the application output for a child index
+    pub fn derive(&self, master: &ExtendedPrivKey, index: u32) -> Result<String, &'static str> {
+        self.validate()?;
+        if index >= HARDENED_OFFSET {
+            return Err("Index must be below 2^31");
+        }
+
+        let entropy = derive_entropy(master, &self.path(index))?;
+        let output = match *self {
+            Application::Mnemonic { words } => Mnemonic::from_entropy(&entropy[..words * 4 / 3])?.expose_phrase().to_string(),
+            Application::Wif => {
+                let mut data = vec![0x80];
+                data.extend_from_slice(&entropy[..32]);
+                data.push(0x01); // Compressed public key
+                base58::check_encode_slice(&data)
+            }
+            Application::Xprv => {
+                let key = ExtendedPrivKey::from_parts(
+                    entropy[32..].try_into().map_err(|_| "Invalid private key length")?,
+                    entropy[..32].try_into().map_err(|_| "Invalid chain code length")?,
+                )?;
+                key.expose_base58()
+            }
+            Application::Hex { bytes } => hex::encode(&entropy[..bytes]),
+            Application::PasswordBase64 { length } => {
+                base64::engine::general_purpose::STANDARD.encode(entropy)[..length].to_string()
+            }
+            Application::PasswordBase85 { length } => base85_encode(&entropy)[..length].to_string(),
+        };
+        Ok(output)
+    }
+}
+
+// Derives the 64 bytes of entropy for a full derivation path
+pub fn derive_entropy(master: &ExtendedPrivKey, path: &[u32]) -> Result<[u8; 64], &'static str> {
+    let key = master.derive_path(path)?;
+    let mut hmac = Hmac::<Sha512>::new_from_slice(ENTROPY_HMAC_KEY).map_err(|_| "HMAC initialization failed")?;
+    hmac.update(key.expose_private_key());
+    Ok(hmac.finalize().into_bytes().into())
+}
+
+// Builds the master key from an xprv, a hex seed or a mnemonic (with its passphrase)
+pub fn master_key_from_input(input: &str, passphrase: &str) -> Result<ExtendedPrivKey, &'static str> {
+    let input = input.trim();
+    if input.starts_with("xprv") {
+        return ExtendedPrivKey::from_base58(input);
+    }
+    if let Ok(seed) = hex::decode(input) {
+        return ExtendedPrivKey::new(&seed);
+    }
+    let mnemonic = Mnemonic::from_phrase(input)?;
+    ExtendedPrivKey::new(Seed::new(mnemonic.expose_phrase(), passphrase).expose_bytes())
+}
+
+// Encodes bytes with the RFC 1924 base85 alphabet (4 bytes become 5 characters)
+fn base85_encode(data: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in data.chunks(4) {
+        let mut padded = [0u8; 4];
+        padded[..chunk.len()].copy_from_slice(chunk);
+        let mut value = u32::from_be_bytes(padded);
+        let mut digits = [0u8; 5];
+        for digit in digits.iter_mut().rev() {
+            *digit = BASE85_ALPHABET[(value % 85) as usize];
+            value /= 85;
+        }
+        encoded.extend(digits[..chunk.len() + 1].iter().map(|&digit| digit as char));
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Master key of the BIP85 test vectors
+    const MASTER: &str = "xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb";
+
+    fn master() -> ExtendedPrivKey {
+        ExtendedPrivKey::from_base58(MASTER).unwrap()
+    }
+
+    #[test]
+    fn test_entropy_vectors() {
+        let entropy = derive_entropy(&master(), &[BIP85_PURPOSE + HARDENED_OFFSET, HARDENED_OFFSET, HARDENED_OFFSET]).unwrap();
+        assert_eq!(
+            hex::encode(entropy),
+            "efecfbccffea313214232d29e71563d941229afb4338c21f9517c41aaa0d16f00b83d2a09ef747e7a64e8e2bd5a14869e693da66ce94ac2da570ab7ee48618f7"
+        );
+        let entropy = derive_entropy(&master(), &[BIP85_PURPOSE + HARDENED_OFFSET, HARDENED_OFFSET, 1 + HARDENED_OFFSET]).unwrap();
+        assert_eq!(
+            hex::encode(entropy),
+            "70c6e3e8ebee8dc4c0dbba66076819bb8c09672527c4277ca8729532ad711872218f826919f6b67218adde99018a6df9095ab2b58d803b5b93ec9802085a690e"
+        );
+    }
+
+    #[test]
+    fn test_mnemonic_vectors() {
+        let derive = |words| Application::from_number(39, words).unwrap().derive(&master(), 0).unwrap();
+        assert_eq!(derive(12), "girl mad pet galaxy egg matter matrix prison refuse sense ordinary nose");
+        assert_eq!(
+            derive(18),
+            "near account window bike charge season chef number sketch tomorrow excuse sniff circle vital hockey outdoor supply token"
+        );
+        assert_eq!(
+            derive(24),
+            "puppy ocean match cereal symbol another shed magic wrap hammer bulb intact gadget divorce twin tonight reason outdoor destroy simple truth cigar social volcano"
+        );
+    }
+
+    #[test]
+    fn test_key_vectors() {
+        assert_eq!(Application::Wif.derive(&master(), 0).unwrap(), "Kzyv4uF39d4Jrw2W7UryTHwZr1zQVNk4dAFyqE6BuMrMh1Za7uhp");
+        assert_eq!(
+            Application::Xprv.derive(&master(), 0).unwrap(),
+            "xprv9s21ZrQH143K2srSbCSg4m4kLvPMzcWydgmKEnMmoZUurYuBuYG46c6P71UGXMzmriLzCCBvKQWBUv3vPB3m1SATMhp3uEjXHJ42jFg7myX"
+        );
+        assert_eq!(
+            Application::from_number(128169, 64).unwrap().derive(&master(), 0).unwrap(),
+            "492db4698cf3b73a5a24998aa3e9d7fa96275d85724a91e71aa2d645442f878555d078fd1f1f67e368976f04137b1f7a0d19232136ca50c44614af72b5582a5c"
+        );
+    }
+
+    #[test]
+    fn test_password_vectors() {
+        assert_eq!(Application::from_number(707764, 21).unwrap().derive(&master(), 0).unwrap(), "dKLoepugzdVJvdL56ogNV");
+        assert_eq!(Application::from_number(707785, 12).unwrap().derive(&master(), 0).unwrap(), "_s`{TW89)i4`");
+        assert!(Application::from_number(707764, 19).is_err());
+    }
+}
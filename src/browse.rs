@@ -0,0 +1,148 @@
+use crate::backend::{Backend, QueryTerm, RecordQuery};
+use crate::keystore::Session;
+use crate::labels::Labels;
+use crate::records::{LabelRecord, WalletRecord};
+
+// Number of wallets listed per page in the CLI and on the web
+pub const PAGE_SIZE: usize = 10;
+
+// Placeholder shown instead of a secret until it is explicitly revealed
+pub const HIDDEN: &str = "•••••••• (hidden)";
+
+// A saved wallet with its number (from 1, in creation order) and current label
+pub struct Entry {
+    pub id: usize,
+    pub wallet: WalletRecord,
+    pub label: Option<LabelRecord>,
+}
+
+// One page of search results
+pub struct Page {
+    pub entries: Vec<Entry>,
+    pub number: usize, // 1-based page number
+    pub pages: usize,  // At least 1, even without results
+    pub total: usize,  // Number of matching wallets
+}
+
+// Turns a search text into backend query terms. A term matches part of the address, label or note
+// (case-insensitive) or the start of the save date ("2024-05", "2024-05-17"). Labels are only stored
+// encrypted, so label and note matches are resolved to their addresses here.
+pub fn terms(labels: &Labels, query: &str) -> Vec<QueryTerm> {
+    query
+        .split_whitespace()
+        .map(|term| {
+            let text = term.to_lowercase();
+            let addresses = labels
+                .iter()
+                .filter(|label| label.label_type == "addr")
+                .filter(|label| label.label.to_lowercase().contains(&text) || label.note.as_deref().unwrap_or_default().to_lowercase().contains(&text))
+                .map(|label| label.reference.clone())
+                .collect();
+            QueryTerm { text, addresses }
+        })
+        .collect()
+}
+
+// Finds one page of saved wallets matching a search text, clamping the page number to the available pages.
+// Only the wallets of the page are decrypted; labels are all read only when there is text to search for.
+pub fn search(backend: &dyn Backend, session: &Session, query: &str, number: usize) -> Result<Page, &'static str> {
+    let labels = if query.trim().is_empty() { None } else { Some(Labels::from_records(backend.load_records(session)?)) };
+    let mut request = RecordQuery {
+        terms: labels.as_ref().map(|labels| terms(labels, query)).unwrap_or_default(),
+        offset: (number.max(1) - 1) * PAGE_SIZE,
+        limit: Some(PAGE_SIZE),
+    };
+    let mut results = backend.query_records::<WalletRecord>(session, &request)?;
+    let pages = results.total.div_ceil(PAGE_SIZE).max(1);
+    let number = number.clamp(1, pages);
+    if (number - 1) * PAGE_SIZE != request.offset {
+        request.offset = (number - 1) * PAGE_SIZE;
+        results = backend.query_records(session, &request)?;
+    }
+
+    let entries = results
+        .records
+        .into_iter()
+        .map(|(id, wallet)| {
+            let label = match &labels {
+                Some(labels) => labels.address(&wallet.address).cloned(),
+                None => label(backend, session, &wallet.address)?,
+            };
+            Ok(Entry { id, wallet, label })
+        })
+        .collect::<Result<_, &'static str>>()?;
+    Ok(Page { entries, number, pages, total: results.total })
+}
+
+// Saved wallet with a number, if there is one
+pub fn wallet(backend: &dyn Backend, session: &Session, id: usize) -> Result<Option<Entry>, &'static str> {
+    let Some(offset) = id.checked_sub(1) else {
+        return Ok(None);
+    };
+    let request = RecordQuery { offset, limit: Some(1), ..RecordQuery::default() };
+    let Some((id, wallet)) = backend.query_records::<WalletRecord>(session, &request)?.records.pop() else {
+        return Ok(None);
+    };
+    let label = label(backend, session, &wallet.address)?;
+    Ok(Some(Entry { id, wallet, label }))
+}
+
+// Current label of one address, read through the backend's address lookup
+fn label(backend: &dyn Backend, session: &Session, address: &str) -> Result<Option<LabelRecord>, &'static str> {
+    Ok(Labels::from_records(backend.find_records(session, address)?).address(address).cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::JsonBackend;
+    use crate::labels::new_label;
+    use crate::sqlite::SqliteBackend;
+
+    // Both backends must number, filter and page the same way
+    #[test]
+    fn test_search_and_paginate() {
+        let dir = std::env::temp_dir().join(format!("hd_wallet_browse_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for kind in ["wallets", "labels"] {
+            std::fs::create_dir_all(dir.join("json").join(kind)).unwrap();
+        }
+        let backends: [Box<dyn Backend>; 2] = [Box::new(JsonBackend::new(dir.join("json"))), Box::new(SqliteBackend::open(dir.join("hd_wallet.sqlite3")).unwrap())];
+
+        // Saved out of creation order: numbers follow the save date, then the save order
+        let wallets: Vec<WalletRecord> = (0..25)
+            .map(|i| WalletRecord {
+                generated_at: format!("2024-0{}-01T00:00:00+00:00", 1 + i % 3),
+                ..WalletRecord::new("m", &format!("1Address{}", i), "p", "k")
+            })
+            .collect();
+        for backend in &backends {
+            let session = backend.unlock("secret").unwrap();
+            backend.append_records(&session, &wallets).unwrap();
+            backend.append_records(&session, &[new_label("addr", "1Address7", "Customer 42", Some("Paid in full")).unwrap()]).unwrap();
+            backend.append_records(&session, &[new_label("xpub", "1Address8", "Customer 43", None).unwrap()]).unwrap();
+
+            let ids = |query: &str| -> Vec<usize> {
+                search(backend.as_ref(), &session, query, 1).unwrap().entries.iter().map(|entry| entry.id).collect()
+            };
+            assert_eq!(search(backend.as_ref(), &session, "", 1).unwrap().total, 25);
+            assert_eq!(ids("1address2"), [8, 9, 17, 18, 24, 25]);
+            assert_eq!(ids("address2 2024-03"), [18, 24, 25]);
+            assert_eq!(ids("customer"), [12]);
+            assert_eq!(ids("paid 2024-02"), [12]);
+            assert!(ids("_").is_empty());
+            assert!(ids("2025").is_empty());
+
+            let last = search(backend.as_ref(), &session, "", 9).unwrap();
+            assert_eq!((last.number, last.pages, last.total), (3, 3, 25));
+            assert_eq!(last.entries.iter().map(|entry| entry.id).collect::<Vec<_>>(), [21, 22, 23, 24, 25]);
+            assert_eq!(search(backend.as_ref(), &session, "nothing", 1).unwrap().pages, 1);
+
+            let labelled = wallet(backend.as_ref(), &session, 12).unwrap().unwrap();
+            assert_eq!(labelled.wallet.address, "1Address7");
+            assert_eq!(labelled.label.map(|label| label.label), Some("Customer 42".to_string()));
+            assert!(wallet(backend.as_ref(), &session, 26).unwrap().is_none());
+            assert!(wallet(backend.as_ref(), &session, 0).unwrap().is_none());
+        }
+    }
+}
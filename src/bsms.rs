@@ -0,0 +1,354 @@
+use crate::backend;
+use crate::bip32::{ExtendedPrivKey, ExtendedPubKey, XPUB_VERSION};
+use crate::descriptor::{self, Descriptor, DescriptorKey, MultisigScript};
+use crate::keystore::Session;
+use crate::multisig::{Cosigner, Multisig};
+use crate::records::ExtendedKeyRecord;
+use aes::cipher::{KeyIvInit, StreamCipher};
+use base64::Engine;
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+use bitcoin::util::misc::{signed_msg_hash, MessageSignature};
+use hmac::{Hmac, Mac};
+use pbkdf2::pbkdf2;
+use rand::Rng;
+use sha2::{Digest, Sha256, Sha512};
+use zeroize::Zeroizing;
+
+// First line of every BSMS record
+const VERSION: &str = "BSMS 1.0";
+
+// Token of sessions without encryption
+pub const NO_TOKEN: &str = "00";
+
+// Path restrictions of descriptor records: the receiving and change branches
+const PATH_RESTRICTIONS: &str = "/0/*,/1/*";
+
+// Longest key record description
+const MAX_DESCRIPTION_LENGTH: usize = 80;
+
+// AES-256 in counter mode with a 128-bit big-endian counter, as BIP129 uses it
+type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
+
+// Encryption modes of a BIP129 session
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Encryption {
+    None,     // Records are exchanged in the clear
+    Standard, // One 64-bit token shared by every signer
+    Extended, // One 128-bit token per signer
+}
+
+impl Encryption {
+    // Every mode, in menu order
+    pub const ALL: [Encryption; 3] = [Encryption::None, Encryption::Standard, Encryption::Extended];
+
+    // Name used in forms
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encryption::None => "none",
+            Encryption::Standard => "standard",
+            Encryption::Extended => "extended",
+        }
+    }
+
+    // Description shown in menus
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Encryption::None => "NO_ENCRYPTION (records in the clear)",
+            Encryption::Standard => "STANDARD (one 64-bit token shared by every signer)",
+            Encryption::Extended => "EXTENDED (one 128-bit token per signer)",
+        }
+    }
+
+    // Mode of a name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Encryption::ALL.into_iter().find(|encryption| encryption.name() == name)
+    }
+
+    // Hex tokens of a new session, handed to the signers by the coordinator
+    pub fn new_tokens(&self, signers: usize) -> Vec<String> {
+        let mut rng = rand::thread_rng();
+        match self {
+            Encryption::None => vec![NO_TOKEN.to_string()],
+            Encryption::Standard => vec![hex::encode(rng.gen::<[u8; 8]>())],
+            Encryption::Extended => (0..signers).map(|_| hex::encode(rng.gen::<[u8; 16]>())).collect(),
+        }
+    }
+}
+
+// Encryption key of a token: PBKDF2-HMAC-SHA512 of "No SPOF", salted with the token bytes, 2048 rounds
+fn encryption_key(token: &str) -> Result<Zeroizing<[u8; 32]>, &'static str> {
+    let salt = hex::decode(token).ok().filter(|salt| salt.len() == 8 || salt.len() == 16).ok_or("Token must be 16 or 32 hex characters (or 00 without encryption)")?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    pbkdf2::<Hmac<Sha512>>(b"No SPOF", &salt, 2048, &mut key[..]);
+    Ok(key)
+}
+
+// MAC of a record: HMAC-SHA256 keyed with SHA256 of the encryption key, over the hex token followed by the record
+fn mac(key: &[u8; 32], token: &str, record: &[u8]) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&Sha256::digest(key)).expect("HMAC accepts any key length");
+    mac.update(token.as_bytes());
+    mac.update(record);
+    mac
+}
+
+// Encrypts a record for a token: hex of the MAC followed by the AES-256-CTR ciphertext, whose IV is the MAC's first 16 bytes
+// (records of sessions without encryption are exchanged as they are)
+pub fn encrypt(token: &str, record: &str) -> Result<String, &'static str> {
+    if token == NO_TOKEN {
+        return Ok(record.to_string());
+    }
+    let key = encryption_key(token)?;
+    let mac = mac(&key, token, record.as_bytes()).finalize().into_bytes();
+    let mut data = record.as_bytes().to_vec();
+    Aes256Ctr::new(key.as_ref().into(), mac[..16].into()).apply_keystream(&mut data);
+    Ok(hex::encode([&mac[..], &data].concat()))
+}
+
+// Decrypts a record with a token, checking its MAC
+pub fn decrypt(token: &str, data: &str) -> Result<String, &'static str> {
+    if token == NO_TOKEN {
+        return Ok(data.trim().to_string());
+    }
+    let key = encryption_key(token)?;
+    let data = hex::decode(data.trim()).ok().filter(|data| data.len() > 32).ok_or("Encrypted record is not hex-encoded")?;
+    let (expected, ciphertext) = data.split_at(32);
+    let mut record = ciphertext.to_vec();
+    Aes256Ctr::new(key.as_ref().into(), expected[..16].into()).apply_keystream(&mut record);
+    mac(&key, token, &record).verify_slice(expected).map_err(|_| "Record does not decrypt with this token (wrong token or altered record)")?;
+    String::from_utf8(record).map_err(|_| "Decrypted record is not text")
+}
+
+// Lines of a record, which must start with the BSMS version
+fn record_lines(record: &str, count: usize) -> Result<Vec<&str>, &'static str> {
+    let lines: Vec<&str> = record.trim().lines().map(str::trim_end).collect();
+    if lines.first() != Some(&VERSION) {
+        return Err("Not a BSMS 1.0 record");
+    }
+    if lines.len() != count {
+        return Err("BSMS record has the wrong number of lines");
+    }
+    Ok(lines)
+}
+
+// Signer's key record: its account key, signed with that key's private key
+pub struct KeyRecord {
+    pub token: String,
+    pub cosigner: Cosigner,
+    pub description: String,
+}
+
+impl KeyRecord {
+    // Signed key record of the BIP48 account of a master key held here (round 1, signer)
+    pub fn sign(master: &ExtendedPrivKey, script: MultisigScript, account: u32, token: &str, description: &str) -> Result<String, &'static str> {
+        if token != NO_TOKEN {
+            encryption_key(token)?;
+        }
+        // Records are read back with trailing whitespace dropped, so the description is signed without surrounding whitespace
+        let description = description.trim();
+        if description.chars().count() > MAX_DESCRIPTION_LENGTH || description.contains(['\n', '\r']) {
+            return Err("Description must be one line of at most 80 characters");
+        }
+        let cosigner = Cosigner::local(master, script, account)?;
+        let body = format!("{}\n{}\n{}\n{}", VERSION, token, cosigner.key(), description);
+        let account_key = master.derive_path(&cosigner.path)?;
+        let secret = SecretKey::from_slice(account_key.expose_private_key()).map_err(|_| "Invalid account private key")?;
+        let signature = Secp256k1::signing_only().sign_ecdsa_recoverable(&Message::from(signed_msg_hash(&body)), &secret);
+        let signature = base64::engine::general_purpose::STANDARD.encode(MessageSignature::new(signature, true).serialize());
+        Ok(format!("{}\n{}", body, signature))
+    }
+
+    // Parses a key record and checks its signature against its key (round 2, coordinator)
+    pub fn parse(record: &str) -> Result<Self, &'static str> {
+        let lines = record_lines(record, 5)?;
+        let cosigner = Cosigner::parse(lines[2])?;
+        let signature = base64::engine::general_purpose::STANDARD
+            .decode(lines[4])
+            .ok()
+            .and_then(|signature| MessageSignature::from_slice(&signature).ok())
+            .ok_or("Key record signature is not a base64 message signature")?;
+        let signer = signature.recover_pubkey(&Secp256k1::verification_only(), signed_msg_hash(&lines[..4].join("\n")));
+        let account_key = ExtendedPubKey::from_base58(&cosigner.xpub)?.public_key;
+        if !matches!(signer, Ok(key) if key.compressed && key.inner.serialize() == account_key) {
+            return Err("Key record signature does not match its key");
+        }
+        Ok(KeyRecord { token: lines[1].to_string(), cosigner, description: lines[3].to_string() })
+    }
+}
+
+// Wallet a coordinator built from key records, with the descriptor record encrypted for each token
+pub struct Setup {
+    pub multisig: Multisig,
+    pub descriptions: Vec<String>, // Signer descriptions, in cosigner order
+    pub records: Vec<(String, String)>, // Token and descriptor record to hand to its signers
+}
+
+// Checks the key records of a session and builds its wallet (round 2, coordinator)
+pub fn coordinate(name: &str, threshold: usize, script: MultisigScript, tokens: &[String], records: &[String]) -> Result<Setup, &'static str> {
+    let mut used = vec![false; tokens.len()];
+    let mut cosigners = Vec::new();
+    let mut descriptions = Vec::new();
+    for record in records {
+        // Each record must decrypt with one of the session tokens and name that token
+        let (index, key_record) = tokens
+            .iter()
+            .enumerate()
+            .find_map(|(i, token)| decrypt(token, record).ok().map(|plain| (i, KeyRecord::parse(&plain))))
+            .ok_or("A key record does not decrypt with any token of this session")?;
+        let key_record = key_record?;
+        if key_record.token != tokens[index] {
+            return Err("A key record was made for another session token");
+        }
+        if tokens.len() > 1 && used[index] {
+            return Err("Two key records use the same signer token");
+        }
+        used[index] = true;
+        cosigners.push(key_record.cosigner);
+        descriptions.push(key_record.description);
+    }
+    if tokens.len() > 1 && used.contains(&false) {
+        return Err("Every signer token needs its key record");
+    }
+
+    let multisig = Multisig::new(name, threshold, script, cosigners)?;
+    let record = descriptor_record(&multisig)?;
+    let records = tokens.iter().map(|token| Ok((token.clone(), encrypt(token, &record)?))).collect::<Result<Vec<_>, &'static str>>()?;
+    Ok(Setup { multisig, descriptions, records })
+}
+
+// Descriptor record of a wallet: its descriptor template, path restrictions and first receiving address
+pub fn descriptor_record(multisig: &Multisig) -> Result<String, &'static str> {
+    let template = descriptor::with_checksum(&multisig.descriptor(false).to_string().replace("/0/*", "/**"))?;
+    let address = multisig.addresses(false, 0, 1)?.remove(0).address;
+    Ok(format!("{}\n{}\n{}\n{}", VERSION, template, PATH_RESTRICTIONS, address))
+}
+
+// Checks a descriptor record against a master key held here: the wallet must include its key and derive the stated
+// first address (round 2, signer)
+pub fn check_descriptor_record(record: &str, token: &str, master: &ExtendedPrivKey, name: &str) -> Result<Multisig, &'static str> {
+    let record = decrypt(token, record)?;
+    let lines = record_lines(&record, 4)?;
+    if lines[2] != PATH_RESTRICTIONS {
+        return Err("Only /0/*,/1/* path restrictions are supported");
+    }
+    let template = descriptor::strip_checksum(lines[1])?;
+    let Descriptor::Multi { script, threshold, keys, sorted: true } = Descriptor::parse(&template.replace("/**", "/0/*"))? else {
+        return Err("Descriptor record is not a sortedmulti wallet");
+    };
+
+    // Cosigner keys without their /0/* steps; the one matching this master key is marked as local
+    let mut cosigners = keys
+        .into_iter()
+        .map(|key| Cosigner::parse(&DescriptorKey { path: Vec::new(), wildcard: false, ..key }.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let fingerprint = master.fingerprint()?;
+    let local = cosigners
+        .iter_mut()
+        .find(|cosigner| cosigner.fingerprint == fingerprint && master.xpub_at(&cosigner.path, XPUB_VERSION).ok().as_ref() == Some(&cosigner.xpub))
+        .ok_or("The descriptor record does not include this signer's key")?;
+    local.local = true;
+
+    let multisig = Multisig::new(name, threshold, script, cosigners)?;
+    if multisig.addresses(false, 0, 1)?[0].address != lines[3] {
+        return Err("First address of the descriptor record does not match its descriptor");
+    }
+    Ok(multisig)
+}
+
+// Records pasted together: multi-line records are separated by empty lines, encrypted records sit one per line
+pub fn split_records(text: &str) -> Vec<String> {
+    let text = text.replace("\r\n", "\n");
+    text.split("\n\n")
+        .map(str::trim)
+        .filter(|block| !block.is_empty())
+        .flat_map(|block| match block.starts_with(VERSION) {
+            true => vec![block.to_string()],
+            false => block.lines().map(|line| line.trim().to_string()).filter(|line| !line.is_empty()).collect(),
+        })
+        .collect()
+}
+
+// Saved master keys that can act as signers, in the order they were saved (imported account keys cannot
+// derive a BIP48 account)
+pub fn signer_keys(session: &Session) -> Result<Vec<ExtendedPrivKey>, &'static str> {
+    backend::current()
+        .load_records::<ExtendedKeyRecord>(session)?
+        .iter()
+        .filter(|record| record.origin.is_none())
+        .map(ExtendedKeyRecord::key)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypted_session() {
+        let masters: Vec<ExtendedPrivKey> = (1u8..=3).map(|i| ExtendedPrivKey::new(&[i; 32]).unwrap()).collect();
+        for encryption in Encryption::ALL {
+            // Round 1: the coordinator hands out tokens and every signer returns a signed, encrypted key record
+            let tokens = encryption.new_tokens(masters.len());
+            let records: Vec<String> = masters
+                .iter()
+                .enumerate()
+                .map(|(i, master)| {
+                    let token = &tokens[i % tokens.len()];
+                    encrypt(token, &KeyRecord::sign(master, MultisigScript::Wsh, 0, token, &format!("Signer {}", i + 1)).unwrap()).unwrap()
+                })
+                .collect();
+
+            // Round 2: the coordinator checks the records, and every signer checks the descriptor record
+            let setup = coordinate("Vault", 2, MultisigScript::Wsh, &tokens, &records).unwrap();
+            assert_eq!(setup.records.len(), tokens.len());
+            assert_eq!(setup.descriptions, ["Signer 1", "Signer 2", "Signer 3"]);
+            for (i, master) in masters.iter().enumerate() {
+                let (token, record) = &setup.records[i % setup.records.len()];
+                let checked = check_descriptor_record(record, token, master, "Vault").unwrap();
+                assert_eq!(checked.descriptor(true).with_checksum(), setup.multisig.descriptor(true).with_checksum());
+                assert_eq!(checked.cosigners.iter().filter(|cosigner| cosigner.local).count(), 1);
+            }
+        }
+
+        // Altered records and signatures are refused
+        let token = Encryption::Standard.new_tokens(1).remove(0);
+        let record = KeyRecord::sign(&masters[0], MultisigScript::Wsh, 0, &token, "Signer").unwrap();
+        let mut encrypted = encrypt(&token, &record).unwrap().into_bytes();
+        encrypted[70] = if encrypted[70] == b'0' { b'1' } else { b'0' };
+        assert!(decrypt(&token, std::str::from_utf8(&encrypted).unwrap()).is_err());
+        assert_eq!(KeyRecord::parse(&record.replace("Signer", "Mallory")).err(), Some("Key record signature does not match its key"));
+        assert!(check_descriptor_record(&descriptor_record(&coordinate("Vault", 1, MultisigScript::Wsh, &[NO_TOKEN.to_string()], &[
+            KeyRecord::sign(&masters[1], MultisigScript::Wsh, 0, NO_TOKEN, "").unwrap(),
+            KeyRecord::sign(&masters[2], MultisigScript::Wsh, 0, NO_TOKEN, "").unwrap(),
+        ]).unwrap().multisig).unwrap(), NO_TOKEN, &masters[0], "Vault").is_err());
+    }
+    #[test]
+    fn test_reference_vectors() {
+        // Key record of the BIP32 test vector 1 master, checked against an independent BIP137 message signature check
+        let master = ExtendedPrivKey::new(&hex::decode("000102030405060708090a0b0c0d0e0f").unwrap()).unwrap();
+        let key = "[3442193e/48h/0h/0h/2h]xpub6E64WfdQwBGz85XhbZryr9gUGUPBgoSu5WV6tJWpzAvgAmpVpdPHkT3XYm9R5J6MeWzvLQoz4q845taC9Q28XutbptxAmg7q8QPkjvTL4oi";
+        let standard = "a54044308ceac9b7";
+        let record = KeyRecord::sign(&master, MultisigScript::Wsh, 0, standard, " Signer 1 ").unwrap();
+        assert_eq!(record, format!("BSMS 1.0\n{}\n{}\nSigner 1\nHyhoeSEFkzht1lwi6mZkGRXYQ8+itilp+y6noehGo3KQT5g+CEeuewAOm19PaQpK/PcgW4T8Nb9RYWK0GFowRJA=", standard, key));
+        assert_eq!(KeyRecord::parse(&record).unwrap().description, "Signer 1");
+        assert!(KeyRecord::sign(&master, MultisigScript::Wsh, 0, NO_TOKEN, &"é".repeat(80)).is_ok());
+        assert!(KeyRecord::sign(&master, MultisigScript::Wsh, 0, NO_TOKEN, &"é".repeat(81)).is_err());
+
+        // Encryption key, MAC (whose first 16 bytes are the IV) and AES-256-CTR ciphertext of a STANDARD session,
+        // checked against PBKDF2, HMAC and AES-CTR from an independent library
+        assert_eq!(hex::encode(encryption_key(standard).unwrap()), "7673ffd9efd70336a5442eda0b31457f7b6cdf7b42fe17f274434df55efa9839");
+        let encrypted = encrypt(standard, &record).unwrap();
+        assert_eq!(&encrypted[..64], "a5b08f91ead42b17f63a8cac924ef2756e0fb149556316d0e89d738fa446191e");
+        assert_eq!(&encrypted[64..], "7d3cfea08aa8ef5fc6eb2868f5e910f1e9e99ae29da99e278a1e3f00a78d92e60f771d0d7a89da21993dc0b4d242843065ae9e41333e1c28b1dda13247e3a3bdde7c7e0ceecf4153120a42eb7c03ec610e77ba6740521d29de4da7f38cf2824bc00795b4dc9fa26e8842bfe42532b51a7972524fe3b148a66271072dcfe8e69dbf4d63b36d2f824bff5406dbfc72b210e660beba607b21661b26b8c7e48bb52a44c5e142863cdd750888b271cf867af41e72211fb285ebc56a8c632dd41af3415d78d991e3e7085745c08018bd3d3de202a0310245f999b9c19f0f5b8453d9a6d2325a4addbda4aa50618c2d19b882ff5268ae1c664f35bc199f91ec1c2dd6e8d052");
+        assert_eq!(decrypt(standard, &encrypted).unwrap(), record);
+
+        // The same for an EXTENDED session token
+        let extended = "00112233445566778899aabbccddeeff";
+        let record = KeyRecord::sign(&master, MultisigScript::Wsh, 0, extended, "Signer 1").unwrap();
+        assert_eq!(record, format!("BSMS 1.0\n{}\n{}\nSigner 1\nICeX3gyHTdxrnaJ8+TtrUrkWyS4FnqzWSYrzbcqofNC+VEf03yhw7JZvPNnoLkaT+LaVFIyjh4cIEwuHFEBHgQ4=", extended, key));
+        assert_eq!(hex::encode(encryption_key(extended).unwrap()), "0ddad86d5e541947dbe4c3ca38a8a7c0023abbd36ce846e877caf066045c97df");
+        let encrypted = encrypt(extended, &record).unwrap();
+        assert_eq!(&encrypted[..64], "a2a5483d768586a009fac02caf631c544943de3a20d1b5ff005e747c7127b3a9");
+        assert_eq!(&encrypted[64..], "3ba06fb91ddbab0d2fc4d0e24ad45142f93ec575a97b742a5b7c173e8d0fd31d35fa66d521c5fda267ab87ecdcdcba94c0e2db827d791401d036bdaa35e50c6e56340cab2fadbc019f117565fe81d125faf5d2e631f041311ac5be7854efa9b6fc8f8c3eace1ebc6eeca5c7eb309e078443729c8a2790028767b1b829e068fce411fac848ce4d8520c366f3fddd7adeb5f9204490da34fa35f69273a43db57a482a6ecf400cd422c814ccb3ac88ba5fec3187419beb1ef7cbecfbb7b6ce0c5c9a8714a7f5219e323e8de0f866889d0c74abde54dc540bc07f49e83e70e8e10a16881cf561d1c7fa48f642975b925a16eeb3ac278c59fb20a90de0e21faa7617ed9ce4bcfa9e4565d3bc17684ba6b2114df45");
+        assert_eq!(decrypt(extended, &encrypted).unwrap(), record);
+    }
+}
@@ -0,0 +1,422 @@
+use rand::Rng;
+use std::fmt;
+
+// Bech32 alphabet; the position of a character is its value in GF(32)
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+// Human-readable part of every codex32 string
+const HRP: &str = "ms";
+
+// Share index reserved for the unshared secret
+const SECRET_INDEX: char = 's';
+
+// Indices handed out to shares, in order ('s' is reserved for the secret)
+const SHARE_INDICES: &str = "acdefghjklmnpqrtuvwxyz023456789";
+
+// Header characters: threshold, 4-character identifier and share index
+const HEADER_LENGTH: usize = 6;
+
+// Seeds longer than this use the long checksum
+const MAX_SHORT_SEED_BYTES: usize = 50;
+
+// BCH generator polynomials and target residues of the short and long checksums
+const SHORT_GENERATOR: &str = "em3gqeeelmcss";
+const SHORT_TARGET: &str = "secretshare32";
+const LONG_GENERATOR: &str = "02e6fe4xh4x9kyh";
+const LONG_TARGET: &str = "secretshare32ex";
+
+// Discrete logarithms of the GF(32) elements (index 0 is unused)
+const GF32_LOG: [u8; 32] = [
+    0, 0, 1, 14, 2, 28, 15, 22, 3, 5, 29, 26, 16, 7, 23, 11,
+    4, 25, 6, 10, 30, 13, 27, 21, 17, 18, 8, 19, 24, 9, 12, 20,
+];
+
+// Powers of the GF(32) generator
+const GF32_EXP: [u8; 31] = [
+    1, 2, 4, 8, 16, 9, 18, 13, 26, 29, 19, 15, 30, 21, 3, 6,
+    12, 24, 25, 27, 31, 23, 7, 14, 28, 17, 11, 22, 5, 10, 20,
+];
+
+// A validated codex32 string (BIP93), either the secret itself or one of its shares
+#[derive(Clone, PartialEq)]
+pub struct Codex32String {
+    values: Vec<u8>, // GF(32) values of everything after "ms1", checksum included
+}
+
+impl Codex32String {
+    // Parses a codex32 string, checking its length, case, header and BCH checksum
+    pub fn from_string(s: &str) -> Result<Self, &'static str> {
+        let s = s.trim();
+        if !(48..=93).contains(&s.len()) && !(125..=127).contains(&s.len()) {
+            return Err("Invalid codex32 string length");
+        }
+        if s.chars().any(|c| c.is_ascii_lowercase()) && s.chars().any(|c| c.is_ascii_uppercase()) {
+            return Err("Codex32 strings must not mix upper and lower case");
+        }
+
+        let s = s.to_ascii_lowercase();
+        let (hrp, data) = s.rsplit_once('1').ok_or("Missing codex32 separator")?;
+        if hrp != HRP {
+            return Err("Codex32 strings must start with 'ms1'");
+        }
+        let values = data.chars().map(char_value).collect::<Result<Vec<u8>, _>>()?;
+
+        let long = s.len() > 93;
+        if checksum_residue(long, &values) != field_values(if long { LONG_TARGET } else { SHORT_TARGET }) {
+            return Err("Invalid codex32 checksum");
+        }
+
+        let codex32 = Codex32String { values };
+        codex32.validate()?;
+        Ok(codex32)
+    }
+
+    // Encodes seed bytes as a codex32 string with the given header
+    pub fn from_seed(threshold: u8, identifier: &str, share_index: char, seed: &[u8]) -> Result<Self, &'static str> {
+        if threshold == 1 || threshold > 9 {
+            return Err("Threshold must be 0 or between 2 and 9");
+        }
+        if identifier.len() != 4 {
+            return Err("Identifier must be 4 characters long");
+        }
+
+        let mut values = vec![char_value(char::from(b'0' + threshold))?];
+        for c in identifier.to_ascii_lowercase().chars() {
+            values.push(char_value(c)?);
+        }
+        values.push(char_value(share_index.to_ascii_lowercase())?);
+        values.extend(bytes_to_values(seed));
+
+        // The checksum is the residue left after appending the target
+        let long = seed.len() > MAX_SHORT_SEED_BYTES;
+        let target = field_values(if long { LONG_TARGET } else { SHORT_TARGET });
+        let mut input = values.clone();
+        input.extend(&target);
+        values.extend(checksum_residue(long, &input));
+
+        let codex32 = Codex32String { values };
+        codex32.validate()?;
+        Ok(codex32)
+    }
+
+    // Checks the header and that the payload padding is short enough
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.values.len() < HEADER_LENGTH + self.checksum_length() {
+            return Err("Codex32 string is too short");
+        }
+        let threshold = CHARSET[self.values[0] as usize];
+        if threshold != b'0' && !(b'2'..=b'9').contains(&threshold) {
+            return Err("Invalid codex32 threshold");
+        }
+        if threshold == b'0' && self.share_index() != SECRET_INDEX {
+            return Err("Share index must be 's' when the threshold is 0");
+        }
+        if (self.payload().len() * 5) % 8 > 4 {
+            return Err("Invalid codex32 payload length");
+        }
+        Ok(())
+    }
+
+    // Number of checksum characters
+    fn checksum_length(&self) -> usize {
+        if HRP.len() + 1 + self.values.len() > 93 { LONG_TARGET.len() } else { SHORT_TARGET.len() }
+    }
+
+    // Payload values between the header and the checksum
+    fn payload(&self) -> &[u8] {
+        &self.values[HEADER_LENGTH..self.values.len() - self.checksum_length()]
+    }
+
+    // Number of shares needed to recover the secret (0 when the secret is not shared)
+    pub fn threshold(&self) -> u8 {
+        CHARSET[self.values[0] as usize] - b'0'
+    }
+
+    // Identifier shared by all the shares of one secret
+    pub fn identifier(&self) -> String {
+        self.values[1..5].iter().map(|&value| CHARSET[value as usize] as char).collect()
+    }
+
+    // Index of this share ('s' for the secret itself)
+    pub fn share_index(&self) -> char {
+        CHARSET[self.values[5] as usize] as char
+    }
+
+    // Decodes the payload back to bytes, dropping the padding bits
+    pub fn seed(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut accumulator: u32 = 0;
+        let mut bits = 0;
+        for &value in self.payload() {
+            accumulator = (accumulator << 5) | value as u32;
+            bits += 5;
+            if bits >= 8 {
+                bits -= 8;
+                bytes.push((accumulator >> bits) as u8);
+                accumulator &= (1 << bits) - 1;
+            }
+        }
+        bytes
+    }
+}
+
+impl fmt::Display for Codex32String {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}1", HRP)?;
+        for &value in &self.values {
+            write!(f, "{}", CHARSET[value as usize] as char)?;
+        }
+        Ok(())
+    }
+}
+
+// Splits a seed into `share_count` shares, any `threshold` of which recover it
+pub fn split_seed(seed: &[u8], threshold: u8, share_count: u8, identifier: &str) -> Result<Vec<Codex32String>, &'static str> {
+    if !(16..=64).contains(&seed.len()) {
+        return Err("Seed length must be between 16 and 64 bytes");
+    }
+    if threshold == 1 {
+        if share_count != 1 {
+            return Err("A threshold of 1 only allows a single share");
+        }
+        return Ok(vec![Codex32String::from_seed(0, identifier, SECRET_INDEX, seed)?]);
+    }
+    if !(2..=9).contains(&threshold) {
+        return Err("Threshold must be between 1 and 9");
+    }
+    if share_count < threshold || share_count as usize > SHARE_INDICES.len() {
+        return Err("Share count must be between the threshold and 31");
+    }
+
+    // The secret and threshold - 1 random shares fix the polynomial, the rest are interpolated
+    let mut rng = rand::thread_rng();
+    let mut base = vec![Codex32String::from_seed(threshold, identifier, SECRET_INDEX, seed)?];
+    for index in SHARE_INDICES.chars().take(threshold as usize - 1) {
+        let random: Vec<u8> = (0..seed.len()).map(|_| rng.gen()).collect();
+        base.push(Codex32String::from_seed(threshold, identifier, index, &random)?);
+    }
+
+    let mut shares: Vec<Codex32String> = base[1..].to_vec();
+    for index in SHARE_INDICES.chars().skip(threshold as usize - 1).take((share_count - threshold + 1) as usize) {
+        shares.push(interpolate_at(&base, index)?);
+    }
+    Ok(shares)
+}
+
+// Generates a random 4-character identifier
+pub fn random_identifier() -> String {
+    let mut rng = rand::thread_rng();
+    (0..4).map(|_| CHARSET[rng.gen_range(0..32)] as char).collect()
+}
+
+// Recovers the secret seed from a set of shares (or from the unshared secret itself)
+pub fn recover_seed(shares: &[Codex32String]) -> Result<Vec<u8>, &'static str> {
+    Ok(interpolate_at(shares, SECRET_INDEX)?.seed())
+}
+
+// Interpolates the shares to derive the share at the given index
+pub fn interpolate_at(shares: &[Codex32String], index: char) -> Result<Codex32String, &'static str> {
+    let first = shares.first().ok_or("No shares provided")?;
+    if (shares.len() as u8) < first.threshold() {
+        return Err("Not enough shares to reach the threshold");
+    }
+    for share in shares {
+        if share.values.len() != first.values.len() {
+            return Err("Shares have different lengths");
+        }
+        if share.threshold() != first.threshold() {
+            return Err("Shares have different thresholds");
+        }
+        if share.identifier() != first.identifier() {
+            return Err("Shares have different identifiers");
+        }
+    }
+
+    // An input share at the requested index is returned as is
+    let target = char_value(index)?;
+    if let Some(share) = shares.iter().find(|share| share.values[5] == target) {
+        return Ok(share.clone());
+    }
+
+    // Lagrange interpolation of each character over GF(32); addition is XOR
+    let indices: Vec<u8> = shares.iter().map(|share| share.values[5]).collect();
+    let product = indices.iter().fold(1, |product, &x| gf_mul(product, x ^ target));
+    let mut values = vec![0u8; first.values.len()];
+    for (i, share) in shares.iter().enumerate() {
+        let mut denominator = 1;
+        for (j, &x) in indices.iter().enumerate() {
+            if i == j {
+                denominator = gf_mul(denominator, x ^ target);
+            } else if x == indices[i] {
+                return Err("Shares must have distinct indices");
+            } else {
+                denominator = gf_mul(denominator, x ^ indices[i]);
+            }
+        }
+        let weight = gf_div(product, denominator);
+        for (value, &share_value) in values.iter_mut().zip(&share.values) {
+            *value ^= gf_mul(weight, share_value);
+        }
+    }
+
+    // Inconsistent shares interpolate to a string whose checksum no longer holds
+    let result = Codex32String { values };
+    Codex32String::from_string(&result.to_string()).map_err(|_| "Shares are inconsistent and cannot be combined")
+}
+
+// Computes the BCH checksum residue of "ms" followed by the given values
+fn checksum_residue(long: bool, values: &[u8]) -> Vec<u8> {
+    let generator = field_values(if long { LONG_GENERATOR } else { SHORT_GENERATOR });
+    let mut residue = vec![0u8; generator.len()];
+    *residue.last_mut().unwrap() = 1;
+
+    let mut input = |value: u8| {
+        let top = residue.remove(0);
+        residue.push(value);
+        for (coefficient, &g) in residue.iter_mut().zip(&generator) {
+            *coefficient ^= gf_mul(g, top);
+        }
+    };
+
+    // The human-readable part is expanded as in bech32
+    for byte in HRP.bytes() {
+        input(byte >> 5);
+    }
+    input(0);
+    for byte in HRP.bytes() {
+        input(byte & 0x1f);
+    }
+    for &value in values {
+        input(value);
+    }
+    residue
+}
+
+// Converts bytes to 5-bit values, padding the last one with zero bits
+fn bytes_to_values(bytes: &[u8]) -> Vec<u8> {
+    let mut values = Vec::new();
+    let mut accumulator: u32 = 0;
+    let mut bits = 0;
+    for &byte in bytes {
+        accumulator = (accumulator << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            values.push(((accumulator >> bits) & 0x1f) as u8);
+        }
+        accumulator &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        values.push(((accumulator << (5 - bits)) & 0x1f) as u8);
+    }
+    values
+}
+
+// Returns the GF(32) value of a lowercase bech32 character
+fn char_value(c: char) -> Result<u8, &'static str> {
+    CHARSET
+        .iter()
+        .position(|&charset_char| charset_char as char == c)
+        .map(|position| position as u8)
+        .ok_or("Invalid bech32 character")
+}
+
+// Converts a constant bech32 string to its GF(32) values
+fn field_values(s: &str) -> Vec<u8> {
+    s.chars().map(|c| char_value(c).unwrap()).collect()
+}
+
+// Multiplication in GF(32)
+fn gf_mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    GF32_EXP[(GF32_LOG[a as usize] as usize + GF32_LOG[b as usize] as usize) % 31]
+}
+
+// Division in GF(32); the divisor must not be zero
+fn gf_div(a: u8, b: u8) -> u8 {
+    if a == 0 {
+        return 0;
+    }
+    GF32_EXP[(31 + GF32_LOG[a as usize] as usize - GF32_LOG[b as usize] as usize) % 31]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_all(strings: &[&str]) -> Vec<Codex32String> {
+        strings.iter().map(|s| Codex32String::from_string(s).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_vector_1() {
+        let secret = Codex32String::from_string("ms10testsxxxxxxxxxxxxxxxxxxxxxxxxxx4nzvca9cmczlw").unwrap();
+        assert_eq!(secret.threshold(), 0);
+        assert_eq!(secret.identifier(), "test");
+        assert_eq!(hex::encode(recover_seed(&[secret]).unwrap()), "318c6318c6318c6318c6318c6318c631");
+    }
+
+    #[test]
+    fn test_vector_2() {
+        let shares = parse_all(&[
+            "MS12NAMEA320ZYXWVUTSRQPNMLKJHGFEDCAXRPP870HKKQRM",
+            "MS12NAMECACDEFGHJKLMNPQRSTUVWXYZ023FTR2GDZMPY6PN",
+        ]);
+        assert_eq!(
+            interpolate_at(&shares, 'd').unwrap().to_string(),
+            "ms12namedll4f8jlh4e5vdvuldlfxu2jhdnlsm97xvenrxeg"
+        );
+        let secret = interpolate_at(&shares, 's').unwrap();
+        assert_eq!(secret.to_string(), "ms12names6xqguzttxkeqnjsjzv4jv3nz5k3kwgsphuh6evw");
+        assert_eq!(hex::encode(secret.seed()), "d1808e096b35b209ca12132b264662a5");
+    }
+
+    #[test]
+    fn test_vector_3() {
+        let shares = parse_all(&[
+            "ms13cashsllhdmn9m42vcsamx24zrxgs3qqjzqud4m0d6nln",
+            "ms13casha320zyxwvutsrqpnmlkjhgfedca2a8d0zehn8a0t",
+            "ms13cashcacdefghjklmnpqrstuvwxyz023949xq35my48dr",
+        ]);
+        assert_eq!(interpolate_at(&shares, 'd').unwrap().to_string(), "ms13cashd0wsedstcdcts64cd7wvy4m90lm28w4ffupqs7rm");
+        assert_eq!(interpolate_at(&shares, 'e').unwrap().to_string(), "ms13casheekgpemxzshcrmqhaydlp6yhms3ws7320xyxsar9");
+        assert_eq!(interpolate_at(&shares, 'f').unwrap().to_string(), "ms13cashf8jh6sdrkpyrsp5ut94pj8ktehhw2hfvyrj48704");
+    }
+
+    #[test]
+    fn test_vector_4_and_5() {
+        let seed = hex::decode("ffeeddccbbaa99887766554433221100ffeeddccbbaa99887766554433221100").unwrap();
+        let secret = Codex32String::from_seed(0, "leet", 's', &seed).unwrap();
+        assert_eq!(
+            secret.to_string(),
+            "ms10leetsllhdmn9m42vcsamx24zrxgs3qrl7ahwvhw4fnzrhve25gvezzyqqtum9pgv99ycma"
+        );
+
+        let long = Codex32String::from_string(
+            "MS100C8VSM32ZXFGUHPCHTLUPZRY9X8GF2TVDW0S3JN54KHCE6MUA7LQPZYGSFJD6AN074RXVCEMLH8WU3TK925ACDEFGHJKLMNPQRSTUVWXY06FHPV80UNDVARHRAK",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(long.seed()),
+            "dc5423251cb87175ff8110c8531d0952d8d73e1194e95b5f19d6f9df7c01111104c9baecdfea8cccc677fb9ddc8aec5553b86e528bcadfdcc201c17c638c47e9"
+        );
+    }
+
+    #[test]
+    fn test_invalid_strings() {
+        assert!(Codex32String::from_string("ms10testsxxxxxxxxxxxxxxxxxxxxxxxxxxxxmazxdp4sx5q").is_err());
+        assert!(Codex32String::from_string("ms10TESTsxxxxxxxxxxxxxxxxxxxxxxxxxx4nzvca9cmczlw").is_err());
+        assert!(Codex32String::from_string("ms10testxxxxxxxxxxxxxxxxxxxxxxxxxxxx3wq9mzgrwag9").is_err());
+    }
+
+    #[test]
+    fn test_split_and_recover() {
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let shares = split_seed(&seed, 3, 5, "cash").unwrap();
+        assert_eq!(shares.len(), 5);
+        assert_eq!(recover_seed(&[shares[4].clone(), shares[0].clone(), shares[2].clone()]).unwrap(), seed);
+        assert!(recover_seed(&shares[..2]).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use std::path::PathBuf;
+
+// Environment variable selecting the storage backend when --storage is not given
+const STORAGE_ENV: &str = "HD_WALLET_STORAGE";
+
+// Environment variable selecting the data directory when --data-dir is not given
+const DATA_DIR_ENV: &str = "HD_WALLET_DATA_DIR";
+
+// Environment variable turning on hardened mode when --hardened is not given ("1" or "true")
+const HARDENED_ENV: &str = "HD_WALLET_HARDENED";
+
+// Name of the data directory under the XDG data home
+const APP_NAME: &str = "hd_wallet";
+
+// Usage line shown for invalid arguments
+const USAGE: &str = "Usage: hd_wallet [--data-dir DIR] [--storage json|sqlite] [--hardened]";
+
+// Storage backends that can be selected
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StorageKind {
+    Json,   // One keystore file per record kind (default)
+    Sqlite, // Embedded SQLite database, for large numbers of records
+}
+
+impl StorageKind {
+    // Parses a backend name
+    pub fn parse(name: &str) -> Result<Self, &'static str> {
+        match name.trim().to_lowercase().as_str() {
+            "json" => Ok(StorageKind::Json),
+            "sqlite" => Ok(StorageKind::Sqlite),
+            _ => Err("Unknown storage backend (expected json or sqlite)"),
+        }
+    }
+}
+
+// Runtime configuration of the program
+pub struct Config {
+    pub data_dir: PathBuf,     // Root directory of all saved data
+    pub storage: StorageKind, // Backend storing wallets, keys and QR codes
+    pub hardened: bool,       // Disable core dumps and lock secrets in memory (see `hardening`)
+}
+
+impl Config {
+    // Reads the configuration from the command line, falling back to the environment
+    pub fn load() -> Result<Self, &'static str> {
+        Self::from_sources(std::env::args().skip(1), |name| std::env::var(name).ok())
+    }
+
+    // Builds the configuration from arguments and an environment lookup (flags win over variables)
+    pub fn from_sources(args: impl IntoIterator<Item = String>, env: impl Fn(&str) -> Option<String>) -> Result<Self, &'static str> {
+        let mut storage = None;
+        let mut data_dir = None;
+        let mut hardened = false;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--hardened" {
+                hardened = true;
+                continue;
+            }
+            let (flag, value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), value.to_string()),
+                None if arg.starts_with("--") => (arg, args.next().ok_or(USAGE)?),
+                None => return Err(USAGE),
+            };
+            match flag.as_str() {
+                "--storage" => storage = Some(StorageKind::parse(&value)?),
+                "--data-dir" if !value.is_empty() => data_dir = Some(PathBuf::from(value)),
+                _ => return Err(USAGE),
+            }
+        }
+        let storage = match storage {
+            Some(storage) => storage,
+            None => non_empty(&env, STORAGE_ENV).map(|name| StorageKind::parse(&name)).transpose()?.unwrap_or(StorageKind::Json),
+        };
+        let data_dir = match data_dir.or_else(|| non_empty(&env, DATA_DIR_ENV).map(PathBuf::from)) {
+            Some(data_dir) => data_dir,
+            None => default_data_dir(&env).ok_or("Cannot determine the data directory; set --data-dir or HD_WALLET_DATA_DIR")?,
+        };
+
+        let hardened = hardened || non_empty(&env, HARDENED_ENV).is_some_and(|value| value == "1" || value.eq_ignore_ascii_case("true"));
+
+        Ok(Config { data_dir, storage, hardened })
+    }
+}
+
+// XDG default: $XDG_DATA_HOME/hd_wallet, or ~/.local/share/hd_wallet
+fn default_data_dir(env: &impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    let data_home = non_empty(env, "XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| non_empty(env, "HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+    Some(data_home.join(APP_NAME))
+}
+
+// Value of an environment variable, ignoring empty ones
+fn non_empty(env: &impl Fn(&str) -> Option<String>, name: &str) -> Option<String> {
+    env(name).filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(args: &[&str], storage_env: Option<&str>) -> Result<Config, &'static str> {
+        config_with_env(args, &[(STORAGE_ENV, storage_env.unwrap_or_default()), ("HOME", "/home/satoshi")])
+    }
+
+    fn config_with_env(args: &[&str], vars: &[(&str, &str)]) -> Result<Config, &'static str> {
+        Config::from_sources(args.iter().map(|arg| arg.to_string()), |name| {
+            vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn test_storage_selection() {
+        assert_eq!(config(&[], None).unwrap().storage, StorageKind::Json);
+        assert_eq!(config(&[], Some("SQLite")).unwrap().storage, StorageKind::Sqlite);
+        assert_eq!(config(&["--storage", "json"], Some("sqlite")).unwrap().storage, StorageKind::Json);
+        assert_eq!(config(&["--storage=sqlite"], None).unwrap().storage, StorageKind::Sqlite);
+        assert!(config(&["--storage"], None).is_err());
+        assert!(config(&["--storage", "csv"], None).is_err());
+        assert!(config(&["--verbose"], None).is_err());
+    }
+
+    #[test]
+    fn test_data_dir_selection() {
+        let home = [("HOME", "/home/satoshi")];
+        assert_eq!(config_with_env(&[], &home).unwrap().data_dir, PathBuf::from("/home/satoshi/.local/share/hd_wallet"));
+        assert_eq!(
+            config_with_env(&[], &[("HOME", "/home/satoshi"), ("XDG_DATA_HOME", "/xdg")]).unwrap().data_dir,
+            PathBuf::from("/xdg/hd_wallet")
+        );
+        assert_eq!(
+            config_with_env(&[], &[("HOME", "/home/satoshi"), (DATA_DIR_ENV, "/srv/wallets")]).unwrap().data_dir,
+            PathBuf::from("/srv/wallets")
+        );
+        assert_eq!(
+            config_with_env(&["--data-dir", "vault", "--storage=sqlite"], &[(DATA_DIR_ENV, "/srv/wallets")]).unwrap().data_dir,
+            PathBuf::from("vault")
+        );
+        assert_eq!(config_with_env(&["--data-dir=vault"], &[]).unwrap().data_dir, PathBuf::from("vault"));
+        assert!(config_with_env(&[], &[]).is_err());
+        assert!(config_with_env(&["--data-dir="], &home).is_err());
+    }
+
+    #[test]
+    fn test_hardened_mode_is_opt_in() {
+        let home = ("HOME", "/home/satoshi");
+        assert!(!config_with_env(&[], &[home]).unwrap().hardened);
+        assert!(config_with_env(&["--hardened", "--storage", "sqlite"], &[home]).unwrap().hardened);
+        assert!(config_with_env(&[], &[home, (HARDENED_ENV, "1")]).unwrap().hardened);
+        assert!(!config_with_env(&[], &[home, (HARDENED_ENV, "0")]).unwrap().hardened);
+    }
+}
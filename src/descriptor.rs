@@ -0,0 +1,639 @@
+use crate::bip32::{ExtendedPrivKey, ExtendedPubKey, HARDENED_OFFSET, XPUB_VERSION};
+use bitcoin::blockdata::opcodes;
+use bitcoin::blockdata::script::Builder;
+use bitcoin::secp256k1::{Secp256k1, XOnlyPublicKey};
+use bitcoin::{Address, Network, PublicKey};
+use std::fmt;
+
+// Characters allowed in descriptors, in the order the BIP380 checksum assigns them
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+
+// Characters of the checksum itself (the bech32 alphabet)
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+// Most addresses derived from a descriptor at once
+pub const MAX_RANGE: u32 = 1000;
+
+// Account shown for generated wallets and exported for BIP39 wallets (BIP84, m/84'/0'/0')
+pub const DEFAULT_ACCOUNT: [u32; 3] = [84 + HARDENED_OFFSET, HARDENED_OFFSET, HARDENED_OFFSET];
+
+// Script types of single-key wallets
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScriptType {
+    Pkh,    // Legacy P2PKH
+    ShWpkh, // Nested segwit P2SH-P2WPKH
+    Wpkh,   // Native segwit P2WPKH
+    Tr,     // Taproot P2TR, key path only
+}
+
+impl ScriptType {
+    // Every script type, oldest first
+    pub const ALL: [ScriptType; 4] = [ScriptType::Pkh, ScriptType::ShWpkh, ScriptType::Wpkh, ScriptType::Tr];
+
+    // Address type name, as used by Electrum and Coldcard (e.g. p2wpkh)
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScriptType::Pkh => "p2pkh",
+            ScriptType::ShWpkh => "p2sh-p2wpkh",
+            ScriptType::Wpkh => "p2wpkh",
+            ScriptType::Tr => "p2tr",
+        }
+    }
+
+    // Script type of an address type name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|script_type| script_type.name() == name)
+    }
+
+    // Script type of a mainnet address, from its prefix
+    pub fn from_address(address: &str) -> Option<Self> {
+        match address.chars().next()? {
+            '1' => Some(ScriptType::Pkh),
+            '3' => Some(ScriptType::ShWpkh),
+            _ if address.to_lowercase().starts_with("bc1q") => Some(ScriptType::Wpkh),
+            _ if address.to_lowercase().starts_with("bc1p") => Some(ScriptType::Tr),
+            _ => None,
+        }
+    }
+
+    // Script type of a single-key descriptor, from its outer functions
+    pub fn from_descriptor(descriptor: &str) -> Option<Self> {
+        let descriptor = descriptor.trim();
+        if descriptor.starts_with("sh(wpkh(") {
+            Some(ScriptType::ShWpkh)
+        } else if descriptor.starts_with("wpkh(") {
+            Some(ScriptType::Wpkh)
+        } else if descriptor.starts_with("pkh(") {
+            Some(ScriptType::Pkh)
+        } else if descriptor.starts_with("tr(") {
+            Some(ScriptType::Tr)
+        } else {
+            None
+        }
+    }
+
+    // Wraps a key expression into a descriptor of this script type (without checksum)
+    pub fn descriptor(&self, key: &str) -> String {
+        match self {
+            ScriptType::Pkh => format!("pkh({})", key),
+            ScriptType::ShWpkh => format!("sh(wpkh({}))", key),
+            ScriptType::Wpkh => format!("wpkh({})", key),
+            ScriptType::Tr => format!("tr({})", key),
+        }
+    }
+
+    // Mainnet address of a compressed public key
+    pub fn address(&self, public_key: &[u8]) -> Result<String, &'static str> {
+        self.build_address(public_key).map(|address| address.to_string())
+    }
+
+    // Mainnet address of a compressed public key (taproot uses its x coordinate as the internal key)
+    fn build_address(&self, public_key: &[u8]) -> Result<Address, &'static str> {
+        let compressed = PublicKey::from_slice(public_key).ok().filter(|key| key.compressed).ok_or("Invalid compressed public key")?;
+        match self {
+            ScriptType::Pkh => Ok(Address::p2pkh(&compressed, Network::Bitcoin)),
+            ScriptType::ShWpkh => Address::p2shwpkh(&compressed, Network::Bitcoin).map_err(|_| "Failed to build segwit address"),
+            ScriptType::Wpkh => Address::p2wpkh(&compressed, Network::Bitcoin).map_err(|_| "Failed to build segwit address"),
+            ScriptType::Tr => taproot_address(&public_key[1..]),
+        }
+    }
+}
+
+// Script wrappers of multisig descriptors
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MultisigScript {
+    Sh,    // Legacy P2SH
+    Wsh,   // Native segwit P2WSH
+    ShWsh, // Nested segwit P2SH-P2WSH
+}
+
+impl MultisigScript {
+    // Address type name, e.g. p2wsh
+    pub fn name(&self) -> &'static str {
+        match self {
+            MultisigScript::Sh => "p2sh",
+            MultisigScript::Wsh => "p2wsh",
+            MultisigScript::ShWsh => "p2sh-p2wsh",
+        }
+    }
+
+    // Largest number of keys the script can hold (P2SH scripts are limited to 520 bytes)
+    fn max_keys(&self) -> usize {
+        match self {
+            MultisigScript::Sh => 15,
+            MultisigScript::Wsh | MultisigScript::ShWsh => 20,
+        }
+    }
+}
+
+// Master key fingerprint and path that a descriptor key was derived from
+#[derive(Clone, PartialEq, Debug)]
+pub struct KeyOrigin {
+    pub fingerprint: String, // 8 lowercase hex characters
+    pub path: Vec<u32>,
+}
+
+impl KeyOrigin {
+    // Parses a key origin, e.g. [d34db33f/84h/0h/0h]
+    pub fn parse(text: &str) -> Result<Self, &'static str> {
+        let text = text.trim().strip_prefix('[').and_then(|text| text.strip_suffix(']')).ok_or("Key origin must be written as [fingerprint/path]")?;
+        let mut steps = text.split('/');
+        let fingerprint = steps.next().unwrap_or_default().to_lowercase();
+        if fingerprint.len() != 8 || !fingerprint.chars().all(|ch| ch.is_ascii_hexdigit()) {
+            return Err("Key origin fingerprint must be 8 hex characters");
+        }
+        Ok(KeyOrigin { fingerprint, path: steps.map(parse_step).collect::<Result<_, _>>()? })
+    }
+}
+
+impl fmt::Display for KeyOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}{}]", self.fingerprint, path_text(&self.path))
+    }
+}
+
+// Public key material of a key expression
+#[derive(Clone)]
+pub enum Key {
+    Xpub(String, ExtendedPubKey), // Kept as written, since an xpub's parent fingerprint is not decoded
+    Public([u8; 33]),             // Compressed public key
+    XOnly([u8; 32]),              // Taproot x-only public key
+}
+
+// Key expression of a descriptor, e.g. [d34db33f/84h/0h/0h]xpub.../0/*
+#[derive(Clone)]
+pub struct DescriptorKey {
+    pub origin: Option<KeyOrigin>,
+    pub key: Key,
+    pub path: Vec<u32>, // Unhardened steps below an xpub
+    pub wildcard: bool, // Whether the path ends with /*
+}
+
+impl DescriptorKey {
+    // Parses a key expression; private keys and hardened steps below an xpub are refused
+    pub fn parse(text: &str) -> Result<Self, &'static str> {
+        let text = text.trim();
+        let (origin, rest) = match text.starts_with('[') {
+            true => {
+                let end = text.find(']').ok_or("Key origin is missing its closing ']'")?;
+                (Some(KeyOrigin::parse(&text[..=end])?), &text[end + 1..])
+            }
+            false => (None, text),
+        };
+
+        let mut steps = rest.split('/');
+        let encoded = steps.next().unwrap_or_default();
+        let mut steps: Vec<&str> = steps.collect();
+        let wildcard = match steps.last() {
+            Some(&"*") => {
+                steps.pop();
+                true
+            }
+            Some(&"*'") | Some(&"*h") => return Err("Hardened wildcards need the private key"),
+            _ => false,
+        };
+        let path = steps.into_iter().map(parse_step).collect::<Result<Vec<u32>, _>>()?;
+
+        let key = if encoded.starts_with("xprv") || encoded.starts_with("tprv") || encoded.len() == 51 || encoded.len() == 52 {
+            return Err("Descriptors with private keys are not supported; use the xpub");
+        } else if encoded.starts_with("xpub") {
+            Key::Xpub(encoded.to_string(), ExtendedPubKey::from_base58(encoded)?)
+        } else if encoded.len() == 66 {
+            let key = hex::decode(encoded).ok().and_then(|bytes| <[u8; 33]>::try_from(bytes).ok()).ok_or("Invalid hex public key")?;
+            PublicKey::from_slice(&key).map_err(|_| "Invalid hex public key")?;
+            Key::Public(key)
+        } else if encoded.len() == 64 {
+            let key = hex::decode(encoded).ok().and_then(|bytes| <[u8; 32]>::try_from(bytes).ok()).ok_or("Invalid x-only public key")?;
+            XOnlyPublicKey::from_slice(&key).map_err(|_| "Invalid x-only public key")?;
+            Key::XOnly(key)
+        } else {
+            return Err("Unsupported key (expected a mainnet xpub or a hex public key)");
+        };
+
+        match key {
+            Key::Xpub(..) if path.iter().any(|&step| step >= HARDENED_OFFSET) => Err("Hardened derivation below an xpub needs the private key"),
+            Key::Xpub(..) => Ok(DescriptorKey { origin, key, path, wildcard }),
+            _ if !path.is_empty() || wildcard => Err("Only xpubs can have derivation steps"),
+            _ => Ok(DescriptorKey { origin, key, path, wildcard }),
+        }
+    }
+
+    // Compressed public key at an index of the wildcard (the index is ignored without one)
+    fn public_key(&self, index: u32) -> Result<[u8; 33], &'static str> {
+        match &self.key {
+            Key::Xpub(_, xpub) => Ok(xpub.derive_path(&self.steps(index))?.public_key),
+            Key::Public(key) => Ok(*key),
+            Key::XOnly(_) => Err("X-only keys are only allowed in tr()"),
+        }
+    }
+
+    // Taproot internal key at an index of the wildcard
+    fn x_only(&self, index: u32) -> Result<[u8; 32], &'static str> {
+        match &self.key {
+            Key::XOnly(key) => Ok(*key),
+            _ => self.public_key(index).map(|key| key[1..].try_into().expect("compressed keys are 33 bytes")),
+        }
+    }
+
+    // Steps below the key, with the wildcard replaced by an index
+    fn steps(&self, index: u32) -> Vec<u32> {
+        let mut steps = self.path.clone();
+        if self.wildcard {
+            steps.push(index);
+        }
+        steps
+    }
+
+    // Derivation path of the key at an index, from its origin fingerprint when known (e.g. d34db33f/84h/0h/0h/0/5)
+    fn full_path(&self, index: u32) -> String {
+        match &self.origin {
+            Some(origin) => format!("{}{}", origin.fingerprint, path_text(&[&origin.path[..], &self.steps(index)].concat())),
+            None => format!("m{}", path_text(&self.steps(index))),
+        }
+    }
+}
+
+impl fmt::Display for DescriptorKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(origin) = &self.origin {
+            write!(f, "{}", origin)?;
+        }
+        match &self.key {
+            Key::Xpub(encoded, _) => write!(f, "{}", encoded)?,
+            Key::Public(key) => write!(f, "{}", hex::encode(key))?,
+            Key::XOnly(key) => write!(f, "{}", hex::encode(key))?,
+        }
+        write!(f, "{}", path_text(&self.path))?;
+        if self.wildcard {
+            write!(f, "/*")?;
+        }
+        Ok(())
+    }
+}
+
+// Output descriptor (BIP380-386) of a single-key or multisig wallet
+#[derive(Clone)]
+pub enum Descriptor {
+    Single(ScriptType, DescriptorKey),
+    Multi {
+        script: MultisigScript,
+        threshold: usize,
+        keys: Vec<DescriptorKey>,
+        sorted: bool, // sortedmulti: keys are sorted at each index, so their order does not matter
+    },
+}
+
+// Address derived from a descriptor at one index
+pub struct Derived {
+    pub index: u32,
+    pub address: String,
+    pub script_pubkey: String, // Hex-encoded output script
+    pub paths: Vec<String>,    // Derivation path of each key, in descriptor order
+}
+
+impl Descriptor {
+    // Parses a descriptor, checking its checksum when present
+    pub fn parse(text: &str) -> Result<Self, &'static str> {
+        let text = strip_checksum(text)?;
+        let (name, inner) = split_call(text)?;
+        match name {
+            "pkh" => Ok(Descriptor::Single(ScriptType::Pkh, single_key(inner)?)),
+            "wpkh" => Ok(Descriptor::Single(ScriptType::Wpkh, single_key(inner)?)),
+            "tr" if inner.contains(',') => Err("Taproot script trees are not supported"),
+            "tr" => Ok(Descriptor::Single(ScriptType::Tr, DescriptorKey::parse(inner)?)),
+            "wsh" => Self::parse_multi(MultisigScript::Wsh, inner),
+            "sh" => match split_call(inner)? {
+                ("wpkh", key) => Ok(Descriptor::Single(ScriptType::ShWpkh, single_key(key)?)),
+                ("wsh", multi) => Self::parse_multi(MultisigScript::ShWsh, multi),
+                _ => Self::parse_multi(MultisigScript::Sh, inner),
+            },
+            _ => Err("Unsupported descriptor (expected pkh, wpkh, sh(wpkh), tr, or multi / sortedmulti inside sh, wsh or sh(wsh))"),
+        }
+    }
+
+    // Parses multi(k,KEY,...) or sortedmulti(k,KEY,...) inside its script wrapper
+    fn parse_multi(script: MultisigScript, text: &str) -> Result<Self, &'static str> {
+        let (name, arguments) = split_call(text)?;
+        let sorted = match name {
+            "multi" => false,
+            "sortedmulti" => true,
+            _ => return Err("Only multi and sortedmulti are supported inside sh and wsh"),
+        };
+        let mut arguments = arguments.split(',');
+        let threshold: usize = arguments.next().unwrap_or_default().trim().parse().map_err(|_| "Multisig threshold must be a number")?;
+        let keys = arguments.map(single_key).collect::<Result<Vec<_>, _>>()?;
+        if keys.len() > script.max_keys() {
+            return Err("Too many keys for this multisig script (at most 15 in sh, 20 in wsh)");
+        }
+        if threshold == 0 || threshold > keys.len() {
+            return Err("Multisig threshold must be between 1 and the number of keys");
+        }
+        Ok(Descriptor::Multi { script, threshold, keys, sorted })
+    }
+
+    // Receive or change descriptor of an account below a master key, e.g. wpkh([fp/84h/0h/0h]xpub.../0/*)
+    pub fn account(master: &ExtendedPrivKey, script_type: ScriptType, path: &[u32], change: bool) -> Result<Self, &'static str> {
+        let xpub = master.xpub_at(path, XPUB_VERSION)?;
+        let key = DescriptorKey {
+            origin: Some(KeyOrigin { fingerprint: master.fingerprint()?, path: path.to_vec() }),
+            key: Key::Xpub(xpub.clone(), ExtendedPubKey::from_base58(&xpub)?),
+            path: vec![change as u32],
+            wildcard: true,
+        };
+        Ok(Descriptor::Single(script_type, key))
+    }
+
+    // Keys of the descriptor, in order
+    pub fn keys(&self) -> &[DescriptorKey] {
+        match self {
+            Descriptor::Single(_, key) => std::slice::from_ref(key),
+            Descriptor::Multi { keys, .. } => keys,
+        }
+    }
+
+    // Whether the descriptor has a wildcard, and so describes a range of addresses
+    pub fn is_ranged(&self) -> bool {
+        self.keys().iter().any(|key| key.wildcard)
+    }
+
+    // Address type name, e.g. p2wpkh or p2wsh
+    pub fn address_type(&self) -> &'static str {
+        match self {
+            Descriptor::Single(script_type, _) => script_type.name(),
+            Descriptor::Multi { script, .. } => script.name(),
+        }
+    }
+
+    // The descriptor followed by its checksum
+    pub fn with_checksum(&self) -> String {
+        with_checksum(&self.to_string()).expect("printed descriptors only use descriptor characters")
+    }
+
+    // Address, output script and key paths at an index of the wildcard
+    pub fn derive(&self, index: u32) -> Result<Derived, &'static str> {
+        let address = match self {
+            Descriptor::Single(ScriptType::Tr, key) => taproot_address(&key.x_only(index)?)?,
+            Descriptor::Single(script_type, key) => script_type.build_address(&key.public_key(index)?)?,
+            Descriptor::Multi { script, threshold, keys, sorted } => {
+                let mut public_keys = keys.iter().map(|key| key.public_key(index)).collect::<Result<Vec<_>, _>>()?;
+                if *sorted {
+                    public_keys.sort();
+                }
+                let mut builder = Builder::new().push_int(*threshold as i64);
+                for public_key in &public_keys {
+                    builder = builder.push_slice(public_key);
+                }
+                let witness_script = builder.push_int(public_keys.len() as i64).push_opcode(opcodes::all::OP_CHECKMULTISIG).into_script();
+                match script {
+                    MultisigScript::Sh => Address::p2sh(&witness_script, Network::Bitcoin).map_err(|_| "Multisig script is too large for P2SH")?,
+                    MultisigScript::Wsh => Address::p2wsh(&witness_script, Network::Bitcoin),
+                    MultisigScript::ShWsh => Address::p2shwsh(&witness_script, Network::Bitcoin),
+                }
+            }
+        };
+        Ok(Derived {
+            index,
+            script_pubkey: hex::encode(address.script_pubkey().as_bytes()),
+            address: address.to_string(),
+            paths: self.keys().iter().map(|key| key.full_path(index)).collect(),
+        })
+    }
+
+    // Addresses at `count` indexes from `start` (a descriptor without a wildcard has a single address)
+    pub fn derive_range(&self, start: u32, count: u32) -> Result<Vec<Derived>, &'static str> {
+        if !self.is_ranged() {
+            return Ok(vec![self.derive(0)?]);
+        }
+        if count > MAX_RANGE {
+            return Err("At most 1000 addresses can be derived at once");
+        }
+        let end = start.checked_add(count).filter(|&end| end <= HARDENED_OFFSET).ok_or("Address range goes past the last unhardened index")?;
+        (start..end).map(|index| self.derive(index)).collect()
+    }
+}
+
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Descriptor::Single(script_type, key) => write!(f, "{}", script_type.descriptor(&key.to_string())),
+            Descriptor::Multi { script, threshold, keys, sorted } => {
+                let keys = keys.iter().map(ToString::to_string).collect::<Vec<_>>().join(",");
+                let multi = format!("{}({},{})", if *sorted { "sortedmulti" } else { "multi" }, threshold, keys);
+                match script {
+                    MultisigScript::Sh => write!(f, "sh({})", multi),
+                    MultisigScript::Wsh => write!(f, "wsh({})", multi),
+                    MultisigScript::ShWsh => write!(f, "sh(wsh({}))", multi),
+                }
+            }
+        }
+    }
+}
+
+// Descriptors of a generated wallet's seed: its master key, whose P2PKH address is the one saved, and its
+// default BIP84 account with the first receiving addresses
+pub struct AccountSummary {
+    pub master: String,         // Descriptor of the saved master-key address, e.g. pkh([73c5da0a]03...)#checksum
+    pub receive: String,        // Receive descriptor of the BIP84 account, with its checksum
+    pub change: String,         // Change descriptor of the BIP84 account, with its checksum
+    pub addresses: Vec<String>, // First receiving addresses of the BIP84 account
+}
+
+// Master-key descriptor of a seed, plus the receive and change descriptors of its default account with its first
+// `count` receiving addresses
+pub fn default_account(seed: &[u8], count: u32) -> Result<AccountSummary, &'static str> {
+    let master = ExtendedPrivKey::new(seed)?;
+    let origin = KeyOrigin { fingerprint: master.fingerprint()?, path: Vec::new() };
+    let key = DescriptorKey { origin: Some(origin), key: Key::Public(master.public_key()?), path: Vec::new(), wildcard: false };
+    let receive = Descriptor::account(&master, ScriptType::Wpkh, &DEFAULT_ACCOUNT, false)?;
+    let change = Descriptor::account(&master, ScriptType::Wpkh, &DEFAULT_ACCOUNT, true)?;
+    Ok(AccountSummary {
+        master: Descriptor::Single(ScriptType::Pkh, key).with_checksum(),
+        addresses: receive.derive_range(0, count)?.into_iter().map(|derived| derived.address).collect(),
+        receive: receive.with_checksum(),
+        change: change.with_checksum(),
+    })
+}
+
+// BIP380 checksum of a descriptor (without its '#')
+pub fn checksum(descriptor: &str) -> Result<String, &'static str> {
+    let mut c = 1u64;
+    let mut class = 0u64;
+    let mut class_count = 0;
+    for ch in descriptor.chars() {
+        let position = INPUT_CHARSET.find(ch).ok_or("Descriptor contains a character that is not allowed")? as u64;
+        c = polymod(c, position & 31);
+        class = class * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = polymod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = polymod(c, class);
+    }
+    for _ in 0..8 {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+    Ok((0..8).map(|j| CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char).collect())
+}
+
+// Appends the checksum to a descriptor, e.g. raw(deadbeef)#89f8spxm
+pub fn with_checksum(descriptor: &str) -> Result<String, &'static str> {
+    Ok(format!("{}#{}", descriptor, checksum(descriptor)?))
+}
+
+// Removes the checksum from a descriptor, checking it when present
+pub fn strip_checksum(text: &str) -> Result<&str, &'static str> {
+    let text = text.trim();
+    match text.split_once('#') {
+        None => Ok(text),
+        Some((descriptor, sum)) if checksum(descriptor)? == sum => Ok(descriptor),
+        Some(_) => Err("Descriptor checksum does not match"),
+    }
+}
+
+// Bare keys of a descriptor (xpubs, xprvs, WIF or hex keys), without key origins or derivation steps
+pub fn keys(descriptor: &str) -> Vec<&str> {
+    let mut keys = Vec::new();
+    let mut start = 0;
+    for (i, ch) in descriptor.char_indices() {
+        match ch {
+            // Text before '(' names a function, not a key
+            '(' => start = i + 1,
+            ')' | ',' => {
+                push_key(&mut keys, &descriptor[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    push_key(&mut keys, &descriptor[start..]);
+    keys
+}
+
+// Adds a key expression's key, skipping empty text and multisig thresholds
+fn push_key<'a>(keys: &mut Vec<&'a str>, expression: &'a str) {
+    let expression = expression.trim();
+    let key = match expression.split_once(']') {
+        Some((_, key)) => key,
+        None => expression,
+    };
+    let key = key.split('/').next().unwrap_or_default();
+    if !key.is_empty() && !key.chars().all(|ch| ch.is_ascii_digit()) {
+        keys.push(key);
+    }
+}
+
+// Splits name(arguments) into its name and arguments
+fn split_call(text: &str) -> Result<(&str, &str), &'static str> {
+    let text = text.trim();
+    let (name, rest) = text.split_once('(').ok_or("Descriptor function is missing its '('")?;
+    let arguments = rest.strip_suffix(')').ok_or("Descriptor function is missing its closing ')'")?;
+    Ok((name.trim(), arguments))
+}
+
+// Parses the key of a non-taproot script, which must be a compressed key
+fn single_key(text: &str) -> Result<DescriptorKey, &'static str> {
+    let key = DescriptorKey::parse(text)?;
+    if let Key::XOnly(_) = key.key {
+        return Err("X-only keys are only allowed in tr()");
+    }
+    Ok(key)
+}
+
+// Parses one derivation step, hardened with ' or h
+fn parse_step(step: &str) -> Result<u32, &'static str> {
+    let (number, hardened) = match step.strip_suffix(['\'', 'h', 'H']) {
+        Some(number) => (number, true),
+        None => (step, false),
+    };
+    let index: u32 = number.parse().map_err(|_| "Invalid derivation step")?;
+    if index >= HARDENED_OFFSET {
+        return Err("Derivation step is out of range");
+    }
+    Ok(if hardened { index + HARDENED_OFFSET } else { index })
+}
+
+// Steps of a path written the descriptor way, e.g. /84h/0h/0h/0
+pub fn path_text(path: &[u32]) -> String {
+    path.iter()
+        .map(|&step| if step >= HARDENED_OFFSET { format!("/{}h", step - HARDENED_OFFSET) } else { format!("/{}", step) })
+        .collect()
+}
+
+// Key-path-only taproot address of an x-only internal key (BIP86 tweak)
+fn taproot_address(x_only: &[u8]) -> Result<Address, &'static str> {
+    let internal_key = XOnlyPublicKey::from_slice(x_only).map_err(|_| "Invalid x-only public key")?;
+    Ok(Address::p2tr(&Secp256k1::verification_only(), internal_key, None, Network::Bitcoin))
+}
+
+// One step of the BIP380 checksum's BCH code
+fn polymod(c: u64, value: u64) -> u64 {
+    let c0 = c >> 35;
+    let mut c = ((c & 0x7_ffff_ffff) << 5) ^ value;
+    for (bit, generator) in [0xf5dee51989, 0xa9fdca3312, 0x1bab10e32d, 0x3706b1677a, 0x644d626ffd].into_iter().enumerate() {
+        if c0 >> bit & 1 == 1 {
+            c ^= generator;
+        }
+    }
+    c
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum_and_keys() {
+        // BIP380 test vectors
+        assert_eq!(with_checksum("raw(deadbeef)").unwrap(), "raw(deadbeef)#89f8spxm");
+        assert_eq!(strip_checksum("raw(deadbeef)#89f8spxm"), Ok("raw(deadbeef)"));
+        assert_eq!(strip_checksum("raw(deadbeef)#89f8spxn"), Err("Descriptor checksum does not match"));
+        assert_eq!(strip_checksum("raw(deadbeef)"), Ok("raw(deadbeef)"));
+        assert!(checksum("raw(deadbeef)é").is_err());
+
+        let descriptor = "sh(wpkh([d34db33f/49'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/0/*))";
+        assert_eq!(ScriptType::from_descriptor(descriptor), Some(ScriptType::ShWpkh));
+        assert_eq!(keys(descriptor), ["xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL"]);
+        assert_eq!(keys("multi(1,022f8bde4d1a07209355b4a7250a5c5128e88b84bddc619ab7cba8d569b240efe4,025cbdf0646e5db4eaa398f365f2ea7a0e3d419b7e0330e39ce92bddedcac4f9bc)").len(), 2);
+    }
+
+    #[test]
+    fn test_descriptor_addresses() {
+        // BIP381 and BIP383 vectors
+        assert_eq!(Descriptor::parse("pkh(02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)").unwrap().derive(0).unwrap().address, "1cMh228HTCiwS8ZsaakH8A8wze1JR5ZsP");
+        let multi = "sh(multi(2,022f01e5e15cca351daff3843fb70f3c2f0a1bdd05e5af888a67784ef3e10a2a01,03acd484e2f0c7f65309ad178a9f559abde09796974c57e714c35f110dfc27ccbe))";
+        let derived = Descriptor::parse(multi).unwrap().derive_range(0, 5).unwrap();
+        assert_eq!(derived.len(), 1);
+        assert_eq!(derived[0].address, "3GtEB3yg3r5de2cDJG48SkQwxfxJumKQdN");
+        assert_eq!(derived[0].paths, ["m", "m"]);
+        let sorted = multi.replace("multi(2,022f01e5e15cca351daff3843fb70f3c2f0a1bdd05e5af888a67784ef3e10a2a01,03acd484e2f0c7f65309ad178a9f559abde09796974c57e714c35f110dfc27ccbe)", "sortedmulti(2,03acd484e2f0c7f65309ad178a9f559abde09796974c57e714c35f110dfc27ccbe,022f01e5e15cca351daff3843fb70f3c2f0a1bdd05e5af888a67784ef3e10a2a01)");
+        assert_eq!(Descriptor::parse(&sorted).unwrap().derive(0).unwrap().address, "3GtEB3yg3r5de2cDJG48SkQwxfxJumKQdN");
+        assert_eq!(Descriptor::parse("wsh(multi(3,022f01e5e15cca351daff3843fb70f3c2f0a1bdd05e5af888a67784ef3e10a2a01,03acd484e2f0c7f65309ad178a9f559abde09796974c57e714c35f110dfc27ccbe))").err(), Some("Multisig threshold must be between 1 and the number of keys"));
+
+        // BIP86 vector: first receiving address of the "abandon ... about" mnemonic
+        let seed = crate::bip39::Seed::new("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about", "");
+        let master = ExtendedPrivKey::new(seed.expose_bytes()).unwrap();
+        let tr = Descriptor::account(&master, ScriptType::Tr, &[86 + HARDENED_OFFSET, HARDENED_OFFSET, HARDENED_OFFSET], false).unwrap();
+        let derived = tr.derive(0).unwrap();
+        assert_eq!(derived.address, "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr");
+        assert_eq!(derived.script_pubkey, "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c");
+        assert_eq!(derived.paths, ["73c5da0a/86h/0h/0h/0/0"]);
+
+        // Printing keeps the key origin and path, so a parsed descriptor prints back identically
+        let printed = tr.with_checksum();
+        assert!(printed.starts_with("tr([73c5da0a/86h/0h/0h]xpub"));
+        assert_eq!(Descriptor::parse(&printed).unwrap().with_checksum(), printed);
+        assert_eq!(Descriptor::parse(&tr.to_string().replace("/0/*", "/0h/*")).err(), Some("Hardened derivation below an xpub needs the private key"));
+
+        // The master-key descriptor of a generated wallet derives the address that is saved for it
+        let account = default_account(seed.expose_bytes(), 1).unwrap();
+        let wallet = crate::wallet::Wallet::from_seed(seed.expose_bytes()).unwrap();
+        assert!(account.master.starts_with("pkh([73c5da0a]"));
+        assert_eq!(Descriptor::parse(&account.master).unwrap().derive(0).unwrap().address, wallet.get_address());
+        assert_eq!(account.addresses, ["bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"]);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::bip32::{ExtendedPrivKey, HARDENED_OFFSET};
+use crate::bip39::{self, Mnemonic, Seed};
+use bitcoin::{Address, Network, PublicKey};
+use hmac::{Hmac, Mac};
+use pbkdf2::pbkdf2;
+use rand::Rng;
+use sha2::Sha512;
+
+// Number of words of newly generated Electrum seeds (132 bits)
+const SEED_WORDS: usize = 12;
+
+// HMAC key used to compute the seed version
+const VERSION_HMAC_KEY: &[u8] = b"Seed version";
+
+// PBKDF2 salt prefix of Electrum seeds (the passphrase is appended)
+const SALT_PREFIX: &str = "electrum";
+
+// Electrum v2 seed types, identified by the hex prefix of their version hash
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SeedType {
+    Standard, // "01": P2PKH addresses at m/0/i and m/1/i
+    Segwit,   // "100": P2WPKH addresses at m/0'/0/i and m/0'/1/i
+}
+
+impl SeedType {
+    // Hex prefix of the version hash
+    fn prefix(&self) -> &'static str {
+        match self {
+            SeedType::Standard => "01",
+            SeedType::Segwit => "100",
+        }
+    }
+
+    // Human-readable name of the seed type
+    pub fn name(&self) -> &'static str {
+        match self {
+            SeedType::Standard => "standard",
+            SeedType::Segwit => "segwit",
+        }
+    }
+}
+
+// Kind of wallet a phrase can be restored as
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PhraseType {
+    Bip39,              // Valid BIP39 mnemonic only
+    Electrum(SeedType), // Valid Electrum seed only
+    Both(SeedType),     // Valid as both; the user has to choose
+    Unknown,            // Neither
+}
+
+// Normalizes a phrase or passphrase the way Electrum does (lowercase, single spaces)
+pub fn normalize(text: &str) -> String {
+    text.to_lowercase().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Returns the Electrum seed type of a phrase, if its version hash matches one
+pub fn seed_type(phrase: &str) -> Option<SeedType> {
+    let mut hmac = Hmac::<Sha512>::new_from_slice(VERSION_HMAC_KEY).ok()?;
+    hmac.update(normalize(phrase).as_bytes());
+    let version = hex::encode(hmac.finalize().into_bytes());
+    [SeedType::Standard, SeedType::Segwit]
+        .into_iter()
+        .find(|seed_type| version.starts_with(seed_type.prefix()))
+}
+
+// Detects whether a phrase is a BIP39 mnemonic, an Electrum seed, or both
+pub fn detect_phrase_type(phrase: &str) -> PhraseType {
+    let is_bip39 = Mnemonic::from_phrase(phrase).is_ok();
+    match (is_bip39, seed_type(phrase)) {
+        (true, None) => PhraseType::Bip39,
+        (false, Some(seed_type)) => PhraseType::Electrum(seed_type),
+        (true, Some(seed_type)) => PhraseType::Both(seed_type),
+        (false, None) => PhraseType::Unknown,
+    }
+}
+
+// Generates a new Electrum seed of the given type from the BIP39 English wordlist
+pub fn generate(target: SeedType) -> String {
+    let wordlist = Mnemonic::wordlist();
+    let mut rng = rand::thread_rng();
+    loop {
+        let indices: Vec<usize> = (0..SEED_WORDS).map(|_| rng.gen_range(0..wordlist.len())).collect();
+        let phrase = indices.iter().map(|&index| wordlist[index].as_str()).collect::<Vec<_>>().join(" ");
+
+        // Like Electrum, never hand out a phrase that is also a valid BIP39 mnemonic
+        if seed_type(&phrase) == Some(target) && !bip39::checksum_is_valid(&indices) {
+            return phrase;
+        }
+    }
+}
+
+// Derives the BIP32 seed of an Electrum phrase (PBKDF2-HMAC-SHA512 with the "electrum" salt)
+pub fn to_seed(phrase: &str, passphrase: &str) -> Seed {
+    let salt = format!("{}{}", SALT_PREFIX, normalize(passphrase));
+    let mut seed = vec![0u8; 64];
+    pbkdf2::<Hmac<Sha512>>(normalize(phrase).as_bytes(), salt.as_bytes(), 2048, &mut seed);
+    Seed::from_bytes(seed)
+}
+
+// Derivation path of a receiving (or change) address
+pub fn derivation_path(seed_type: SeedType, change: bool, index: u32) -> Vec<u32> {
+    let branch = [change as u32, index];
+    match seed_type {
+        SeedType::Standard => branch.to_vec(),
+        SeedType::Segwit => [&[HARDENED_OFFSET][..], &branch].concat(),
+    }
+}
+
+// Derives the first `count` receiving (or change) addresses of an Electrum wallet
+pub fn addresses(seed_type: SeedType, master: &ExtendedPrivKey, change: bool, count: u32) -> Result<Vec<String>, &'static str> {
+    (0..count)
+        .map(|index| {
+            let key = master.derive_path(&derivation_path(seed_type, change, index))?;
+            address(seed_type, &key.public_key()?)
+        })
+        .collect()
+}
+
+// Address of a compressed public key in an Electrum wallet of the given type
+pub fn address(seed_type: SeedType, public_key: &[u8]) -> Result<String, &'static str> {
+    let public_key = PublicKey::from_slice(public_key).map_err(|_| "Invalid public key")?;
+    match seed_type {
+        SeedType::Standard => Ok(Address::p2pkh(&public_key, Network::Bitcoin).to_string()),
+        SeedType::Segwit => Address::p2wpkh(&public_key, Network::Bitcoin)
+            .map(|address| address.to_string())
+            .map_err(|_| "Failed to build segwit address"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segwit_seed_vectors() {
+        // From Electrum's mnemonic tests
+        let phrase = "wild father tree among universe such mobile favorite target dynamic credit identify";
+        assert_eq!(seed_type(phrase), Some(SeedType::Segwit));
+        assert_eq!(
+            hex::encode(to_seed(phrase, "").expose_bytes()),
+            "aac2a6302e48577ab4b46f23dbae0774e2e62c796f797d0a1b5faeb528301e3064342dafb79069e7c4c6b8c38ae11d7a973bec0d4f70626f8cc5184a8d0b0756"
+        );
+        assert_eq!(
+            hex::encode(to_seed(phrase, "Did you ever hear the tragedy of Darth Plagueis the Wise?").expose_bytes()),
+            "4aa29f2aeb0127efb55138ab9e7be83b36750358751906f86c662b21a1ea1370f949e6d1a12fa56d3d93cadda93038c76ac8118597364e46f5156fde6183c82f"
+        );
+    }
+
+    #[test]
+    fn test_wallet_addresses() {
+        // From Electrum's wallet tests
+        let phrase = "cycle rocket west magnet parrot shuffle foot correct salt library feed song";
+        assert_eq!(detect_phrase_type(phrase), PhraseType::Electrum(SeedType::Standard));
+        let master = ExtendedPrivKey::new(to_seed(phrase, "").expose_bytes()).unwrap();
+        assert_eq!(addresses(SeedType::Standard, &master, false, 1).unwrap(), ["1NNkttn1YvVGdqBW4PR6zvc3Zx3H5owKRf"]);
+        assert_eq!(addresses(SeedType::Standard, &master, true, 1).unwrap(), ["1KSezYMhAJMWqFbVFB2JshYg69UpmEXR4D"]);
+
+        let phrase = "bitter grass shiver impose acquire brush forget axis eager alone wine silver";
+        assert_eq!(detect_phrase_type(phrase), PhraseType::Electrum(SeedType::Segwit));
+        let master = ExtendedPrivKey::new(to_seed(phrase, "").expose_bytes()).unwrap();
+        assert_eq!(addresses(SeedType::Segwit, &master, false, 1).unwrap(), ["bc1q3g5tmkmlvxryhh843v4dz026avatc0zzr6h3af"]);
+        assert_eq!(addresses(SeedType::Segwit, &master, true, 1).unwrap(), ["bc1qdy94n2q5qcp0kg7v9yzwe6wvfkhnvyzje7nx2p"]);
+    }
+
+    #[test]
+    fn test_generate_and_detect() {
+        let phrase = generate(SeedType::Segwit);
+        assert_eq!(phrase.split_whitespace().count(), SEED_WORDS);
+        assert_eq!(detect_phrase_type(&phrase), PhraseType::Electrum(SeedType::Segwit));
+        assert_eq!(
+            detect_phrase_type("legal winner thank year wave sausage worth useful legal winner thank yellow"),
+            PhraseType::Bip39
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+// Whether secret buffers should be locked in memory (set once hardening is applied)
+static HARDENED: AtomicBool = AtomicBool::new(false);
+
+// Set when locking a secret buffer failed (e.g. RLIMIT_MEMLOCK reached)
+static LOCK_FAILED: AtomicBool = AtomicBool::new(false);
+
+// Number of live secret buffers on each locked page, so a page is only unlocked when the last one is dropped
+static LOCKED_PAGES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+// Menu banner line when hardened mode was not requested
+pub const OFF: &str = "🔓 Hardened mode: off (start with --hardened to keep secrets out of core dumps and swap)";
+
+// Outcome of the process hardening steps
+#[derive(Clone, Copy, Debug)]
+pub struct HardeningStatus {
+    pub core_dumps_disabled: bool, // RLIMIT_CORE set to zero
+    pub not_dumpable: bool,        // PR_SET_DUMPABLE turned off (no ptrace or /proc/<pid>/mem by other users)
+    pub memory_locked: bool,       // Secret buffers are mlocked and kept out of swap
+}
+
+impl HardeningStatus {
+    // Whether every hardening step succeeded
+    pub fn is_complete(&self) -> bool {
+        self.core_dumps_disabled && self.not_dumpable && self.memory_locked
+    }
+
+    // One-line summary for the menu banner
+    pub fn describe(&self) -> String {
+        let mark = |ok: bool| if ok { "on" } else { "FAILED" };
+        format!(
+            "{} Hardened mode: no core dumps {}, not dumpable {}, memory locking {}",
+            if self.is_complete() { "🔒" } else { "⚠️" },
+            mark(self.core_dumps_disabled),
+            mark(self.not_dumpable),
+            mark(self.memory_locked)
+        )
+    }
+}
+
+// Disables core dumps and ptrace access, and turns on locking of secret buffers
+pub fn harden_process() -> HardeningStatus {
+    let core_dumps_disabled = disable_core_dumps();
+    let not_dumpable = disable_dumpable();
+
+    // Probe that at least one page can be locked before relying on it
+    let probe = [0u8; 1];
+    let memory_locked = mlock_page(page_start(probe.as_ptr() as usize)) && {
+        munlock_page(page_start(probe.as_ptr() as usize));
+        true
+    };
+    HARDENED.store(memory_locked, Ordering::SeqCst);
+    LOCK_FAILED.store(!memory_locked, Ordering::SeqCst);
+
+    HardeningStatus {
+        core_dumps_disabled,
+        not_dumpable,
+        memory_locked,
+    }
+}
+
+// Current status, including memory locking failures that happened after startup
+pub fn status(initial: &HardeningStatus) -> HardeningStatus {
+    HardeningStatus {
+        memory_locked: initial.memory_locked && !LOCK_FAILED.load(Ordering::SeqCst),
+        ..*initial
+    }
+}
+
+// Locks the pages holding a secret buffer so they are never written to swap
+pub fn lock(ptr: *const u8, len: usize) {
+    if len == 0 || !HARDENED.load(Ordering::SeqCst) {
+        return;
+    }
+    let mut pages = LOCKED_PAGES.lock().unwrap();
+    for page in pages_of(ptr as usize, len) {
+        let count = pages.entry(page).or_insert(0);
+        if *count == 0 && !mlock_page(page) {
+            LOCK_FAILED.store(true, Ordering::SeqCst);
+        }
+        *count += 1;
+    }
+}
+
+// Releases the pages locked for a secret buffer (call after wiping it)
+pub fn unlock(ptr: *const u8, len: usize) {
+    if len == 0 || !HARDENED.load(Ordering::SeqCst) {
+        return;
+    }
+    let mut pages = LOCKED_PAGES.lock().unwrap();
+    for page in pages_of(ptr as usize, len) {
+        if let Some(count) = pages.get_mut(&page) {
+            *count -= 1;
+            if *count == 0 {
+                pages.remove(&page);
+                munlock_page(page);
+            }
+        }
+    }
+}
+
+// Start addresses of the pages covering a memory range
+fn pages_of(start: usize, len: usize) -> impl Iterator<Item = usize> {
+    let size = page_size();
+    (page_start(start)..start + len).step_by(size)
+}
+
+// Start address of the page containing an address
+fn page_start(address: usize) -> usize {
+    address - address % page_size()
+}
+
+#[cfg(unix)]
+fn page_size() -> usize {
+    // SAFETY: sysconf has no preconditions
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as usize,
+        _ => 4096,
+    }
+}
+
+#[cfg(unix)]
+fn mlock_page(page: usize) -> bool {
+    // SAFETY: the page belongs to a live allocation of this process
+    unsafe { libc::mlock(page as *const libc::c_void, page_size()) == 0 }
+}
+
+#[cfg(unix)]
+fn munlock_page(page: usize) {
+    // SAFETY: unlocking never invalidates memory
+    unsafe {
+        libc::munlock(page as *const libc::c_void, page_size());
+    }
+}
+
+#[cfg(unix)]
+fn disable_core_dumps() -> bool {
+    let limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: setrlimit only reads the struct passed by reference
+    unsafe { libc::setrlimit(libc::RLIMIT_CORE, &limit) == 0 }
+}
+
+#[cfg(target_os = "linux")]
+fn disable_dumpable() -> bool {
+    // SAFETY: PR_SET_DUMPABLE takes a plain integer argument
+    unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0, 0, 0, 0) == 0 }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn disable_dumpable() -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+fn page_size() -> usize {
+    4096
+}
+
+#[cfg(not(unix))]
+fn mlock_page(_page: usize) -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+fn munlock_page(_page: usize) {}
+
+#[cfg(not(unix))]
+fn disable_core_dumps() -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+fn disable_dumpable() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pages_of_range() {
+        let size = page_size();
+        assert_eq!(pages_of(size + 10, 20).collect::<Vec<_>>(), [size]);
+        assert_eq!(pages_of(2 * size - 1, 2).collect::<Vec<_>>(), [size, 2 * size]);
+    }
+}
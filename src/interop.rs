@@ -0,0 +1,698 @@
+use crate::backend;
+use crate::bip32::{self, format_path, ExtendedPrivKey, HARDENED_OFFSET, XPRV_VERSION, XPUB_VERSION, YPRV_VERSION, YPUB_VERSION, ZPRV_VERSION, ZPUB_VERSION};
+use crate::descriptor::{self, Descriptor, KeyOrigin, ScriptType};
+use crate::electrum::{self, SeedType};
+use crate::keystore::Session;
+use crate::labels;
+use crate::records::{DescriptorRecord, ExtendedKeyRecord, LabelRecord, WalletKind, WalletRecord};
+use crate::verify;
+use crate::wallet::Wallet;
+use bitcoin::{Network, PrivateKey};
+use serde_json::{json, Value};
+use zeroize::Zeroizing;
+
+// Seed version written into Electrum wallet files (Electrum upgrades older files when it opens them)
+const ELECTRUM_SEED_VERSION: u64 = 17;
+
+// Last index of the ranged descriptors handed to Bitcoin Core
+const CORE_RANGE_END: u32 = 999;
+
+// Wallet file formats of other wallet software
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    Electrum, // Electrum wallet file (unencrypted JSON)
+    Sparrow,  // Sparrow / Specter wallet export (label, birth height and descriptor)
+    Coldcard, // Coldcard generic JSON (BIP44, BIP49 and BIP84 account xpubs)
+    Core,     // Bitcoin Core `importdescriptors` request
+    CoreDump, // Bitcoin Core `dumpwallet` text file (import only)
+}
+
+impl Format {
+    // Every format, in menu order
+    pub const ALL: [Format; 5] = [Format::Electrum, Format::Sparrow, Format::Coldcard, Format::Core, Format::CoreDump];
+
+    // Name used in URLs and file names
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Electrum => "electrum",
+            Format::Sparrow => "sparrow",
+            Format::Coldcard => "coldcard",
+            Format::Core => "core",
+            Format::CoreDump => "dumpwallet",
+        }
+    }
+
+    // Name shown in menus
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Format::Electrum => "Electrum wallet file",
+            Format::Sparrow => "Sparrow / Specter wallet export",
+            Format::Coldcard => "Coldcard generic JSON",
+            Format::Core => "Bitcoin Core importdescriptors",
+            Format::CoreDump => "Bitcoin Core dumpwallet",
+        }
+    }
+
+    // Format of a name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.name() == name)
+    }
+
+    // Whether saved wallets can be written in this format
+    pub fn can_export(&self) -> bool {
+        *self != Format::CoreDump
+    }
+
+    // Whether exports hold private keys (the other formats are watch-only)
+    pub fn has_secrets(&self) -> bool {
+        matches!(self, Format::Electrum | Format::Core)
+    }
+}
+
+// Wallets, keys, watch-only descriptors and labels read from another wallet's file, not saved yet
+#[derive(Default)]
+pub struct Imported {
+    pub wallets: Vec<WalletRecord>,
+    pub extended_keys: Vec<ExtendedKeyRecord>,
+    pub descriptors: Vec<DescriptorRecord>,
+    pub labels: Vec<LabelRecord>,
+}
+
+impl Imported {
+    // Addresses of the imported wallets and descriptors and fingerprints of the imported keys, for the audit log
+    pub fn audit_subjects(&self) -> Vec<String> {
+        let fingerprints = self.extended_keys.iter().filter_map(|key| key.key().and_then(|key| key.fingerprint()).ok());
+        let addresses = self.wallets.iter().map(|wallet| wallet.address.clone()).chain(self.descriptors.iter().map(|descriptor| descriptor.address.clone()));
+        let mut subjects: Vec<String> = addresses.chain(fingerprints).collect();
+        subjects.dedup();
+        subjects
+    }
+}
+
+// Numbers of records saved by an import
+pub struct Summary {
+    pub wallets: usize,
+    pub extended_keys: usize,
+    pub descriptors: usize,
+    pub labels: usize,
+    pub duplicates: usize, // Wallets, keys and descriptors that were already saved
+}
+
+// HD account of a saved wallet: its Electrum account, or the BIP84 account of a BIP39 mnemonic
+struct Account<'a> {
+    master: &'a ExtendedPrivKey,
+    path: Vec<u32>,
+    script_type: ScriptType,
+}
+
+impl Account<'_> {
+    // Key origin, e.g. [73c5da0a/84h/0h/0h]
+    fn origin(&self) -> Result<String, &'static str> {
+        Ok(format!("[{}{}]", self.master.fingerprint()?, descriptor::path_text(&self.path)))
+    }
+
+    // Account xpub with the version bytes of its script type (zpub, ypub or xpub)
+    fn xpub(&self) -> Result<String, &'static str> {
+        self.master.xpub_at(&self.path, slip132_versions(self.script_type).1)
+    }
+
+    // Receive or change descriptor with its checksum, holding the account xprv when `private` is set
+    fn descriptor(&self, change: bool, private: bool) -> Result<String, &'static str> {
+        if !private {
+            return Ok(Descriptor::account(self.master, self.script_type, &self.path, change)?.with_checksum());
+        }
+        let xprv = Zeroizing::new(self.master.expose_base58_at(&self.path, XPRV_VERSION)?);
+        let key = Zeroizing::new(format!("{}{}/{}/*", self.origin()?, xprv.as_str(), change as u8));
+        descriptor::with_checksum(&self.script_type.descriptor(&key))
+    }
+
+    // First receiving address of the account
+    fn first_address(&self) -> Result<String, &'static str> {
+        Descriptor::account(self.master, self.script_type, &self.path, false)?.derive(0).map(|derived| derived.address)
+    }
+}
+
+// Writes a saved wallet in another wallet's format (Electrum and Core exports hold its private keys)
+pub fn export(format: Format, wallet: &WalletRecord, label: Option<&str>) -> Result<String, &'static str> {
+    let label = label.unwrap_or(&wallet.address);
+    let export = match format {
+        Format::Electrum => electrum_wallet(wallet, label)?,
+        Format::Sparrow => sparrow_wallet(wallet, label)?,
+        Format::Coldcard => coldcard_wallet(wallet)?,
+        Format::Core => core_descriptors(wallet, label)?,
+        Format::CoreDump => return Err("Bitcoin Core dumpwallet files can only be imported"),
+    };
+    Ok(serde_json::to_string_pretty(&export).expect("wallet exports always serialize") + "\n")
+}
+
+// Reads another wallet's file into records, without saving them
+pub fn import(format: Format, text: &str) -> Result<Imported, &'static str> {
+    let mut imported = Imported::default();
+    match format {
+        Format::Electrum => read_electrum(&mut imported, &parse_json(text)?)?,
+        Format::Sparrow => read_sparrow(&mut imported, &parse_json(text)?)?,
+        Format::Coldcard => read_coldcard(&mut imported, &parse_json(text)?)?,
+        Format::Core => read_core(&mut imported, &parse_json(text)?)?,
+        Format::CoreDump => read_dumpwallet(&mut imported, text)?,
+    }
+    if imported.wallets.is_empty() && imported.extended_keys.is_empty() && imported.descriptors.is_empty() && imported.labels.is_empty() {
+        return Err("No keys, xpubs or labels found in the file");
+    }
+    Ok(imported)
+}
+
+// Saves imported records, skipping wallets, keys and descriptors that are already saved
+pub fn save(imported: Imported, session: &Session) -> Result<Summary, &'static str> {
+    let saved_wallets = backend::current().load_records::<WalletRecord>(session)?;
+    let saved_keys = backend::current().load_records::<ExtendedKeyRecord>(session)?;
+    let saved_descriptors = backend::current().load_records::<DescriptorRecord>(session)?;
+    let total = imported.wallets.len() + imported.extended_keys.len() + imported.descriptors.len();
+
+    let mut wallets: Vec<WalletRecord> = Vec::new();
+    for wallet in imported.wallets {
+        if !saved_wallets.iter().chain(&wallets).any(|saved| saved.address == wallet.address) {
+            wallets.push(wallet);
+        }
+    }
+    let mut extended_keys: Vec<ExtendedKeyRecord> = Vec::new();
+    for key in imported.extended_keys {
+        if !saved_keys.iter().chain(&extended_keys).any(|saved| saved.private_key.eq_ignore_ascii_case(&key.private_key) && saved.chain_code.eq_ignore_ascii_case(&key.chain_code)) {
+            extended_keys.push(key);
+        }
+    }
+    let mut descriptors: Vec<DescriptorRecord> = Vec::new();
+    for descriptor in imported.descriptors {
+        if !saved_descriptors.iter().chain(&descriptors).any(|saved| saved.descriptor == descriptor.descriptor) {
+            descriptors.push(descriptor);
+        }
+    }
+
+    backend::current().append_records(session, &wallets)?;
+    backend::current().append_records(session, &extended_keys)?;
+    backend::current().append_records(session, &descriptors)?;
+    backend::current().append_records(session, &imported.labels)?;
+    Ok(Summary {
+        duplicates: total - wallets.len() - extended_keys.len() - descriptors.len(),
+        wallets: wallets.len(),
+        extended_keys: extended_keys.len(),
+        descriptors: descriptors.len(),
+        labels: imported.labels.len(),
+    })
+}
+
+// Electrum wallet file: an HD keystore (with the seed of Electrum wallets), or the imported key itself
+fn electrum_wallet(wallet: &WalletRecord, label: &str) -> Result<Value, &'static str> {
+    let kind = wallet.kind()?;
+    let (keystore, wallet_type) = match kind {
+        WalletKind::Imported(script_type) => {
+            let public_key = &wallet.public_key;
+            let private_key = Zeroizing::new(match script_type {
+                ScriptType::Pkh => wif(&wallet.private_key)?.to_string(),
+                _ => format!("{}:{}", electrum_script_name(script_type), wif(&wallet.private_key)?.as_str()),
+            });
+            (json!({ "type": "imported", "keypairs": { public_key: private_key.as_str() } }), "imported")
+        }
+        _ => {
+            let master = master(wallet)?;
+            let account = account(&master, kind);
+            let (xprv_version, xpub_version) = slip132_versions(account.script_type);
+            let mut keystore = json!({
+                "type": "bip32",
+                "xprv": Zeroizing::new(master.expose_base58_at(&account.path, xprv_version)?).as_str(),
+                "xpub": master.xpub_at(&account.path, xpub_version)?,
+                "root_fingerprint": master.fingerprint()?,
+                "derivation": format_path(&account.path),
+            });
+            if let WalletKind::Electrum(seed_type) = kind {
+                keystore["seed"] = json!(wallet.mnemonic);
+                keystore["seed_type"] = json!(seed_type.name());
+            }
+            (keystore, "standard")
+        }
+    };
+
+    let mut file = json!({
+        "keystore": keystore,
+        "wallet_type": wallet_type,
+        "seed_version": ELECTRUM_SEED_VERSION,
+        "use_encryption": false,
+        "labels": { &wallet.address: label },
+    });
+    if let WalletKind::Imported(script_type) = kind {
+        file["addresses"] = json!({ &wallet.address: { "type": electrum_script_name(script_type), "pubkey": wallet.public_key } });
+    }
+    Ok(file)
+}
+
+// Sparrow / Specter wallet export of the wallet's HD account (watch-only)
+fn sparrow_wallet(wallet: &WalletRecord, label: &str) -> Result<Value, &'static str> {
+    let kind = wallet.kind()?;
+    if let WalletKind::Imported(_) = kind {
+        return Err("Imported keys have no account xpub; export them as an Electrum wallet or Bitcoin Core descriptors");
+    }
+    let master = master(wallet)?;
+    Ok(json!({
+        "label": label,
+        "blockheight": 0,
+        "descriptor": account(&master, kind).descriptor(false, false)?,
+    }))
+}
+
+// Coldcard generic JSON: the master xpub and the BIP44, BIP49 and BIP84 account xpubs (watch-only)
+fn coldcard_wallet(wallet: &WalletRecord) -> Result<Value, &'static str> {
+    if let WalletKind::Imported(_) = wallet.kind()? {
+        return Err("Imported keys have no master key to export account xpubs from");
+    }
+    let master = master(wallet)?;
+    let xfp = master.fingerprint()?.to_uppercase();
+    let mut export = json!({ "chain": "BTC", "xfp": xfp, "account": 0, "xpub": master.xpub()? });
+    for (purpose, script_type) in [(44, ScriptType::Pkh), (49, ScriptType::ShWpkh), (84, ScriptType::Wpkh)] {
+        let account = Account { master: &master, path: vec![purpose + HARDENED_OFFSET, HARDENED_OFFSET, HARDENED_OFFSET], script_type };
+        let mut entry = json!({
+            "name": script_type.name(),
+            "xfp": xfp,
+            "deriv": format_path(&account.path),
+            "xpub": master.xpub_at(&account.path, XPUB_VERSION)?,
+            "desc": account.descriptor(false, false)?,
+            "first": account.first_address()?,
+        });
+        if script_type != ScriptType::Pkh {
+            entry["_pub"] = json!(account.xpub()?);
+        }
+        export[format!("bip{}", purpose)] = entry;
+    }
+    Ok(export)
+}
+
+// Bitcoin Core `importdescriptors` request: the account's receive and change descriptors with its xprv,
+// plus the saved key itself when the account does not cover it (BIP39 master keys and imported keys)
+fn core_descriptors(wallet: &WalletRecord, label: &str) -> Result<Value, &'static str> {
+    let kind = wallet.kind()?;
+    let mut requests = Vec::new();
+    if !matches!(kind, WalletKind::Imported(_)) {
+        let master = master(wallet)?;
+        let account = account(&master, kind);
+        for change in [false, true] {
+            // Timestamp 0 rescans the whole chain: a restored wallet may be older than its record
+            requests.push(json!({
+                "desc": Zeroizing::new(account.descriptor(change, true)?).as_str(),
+                "timestamp": 0,
+                "active": true,
+                "internal": change,
+                "range": [0, CORE_RANGE_END],
+            }));
+        }
+    }
+    if !matches!(kind, WalletKind::Electrum(_)) {
+        let descriptor = Zeroizing::new(kind.script_type().descriptor(&wif(&wallet.private_key)?));
+        requests.push(json!({ "desc": Zeroizing::new(descriptor::with_checksum(&descriptor)?).as_str(), "timestamp": 0, "label": label }));
+    }
+    Ok(Value::Array(requests))
+}
+
+// Electrum wallet file: its seed or keys, and its address labels
+fn read_electrum(imported: &mut Imported, file: &Value) -> Result<(), &'static str> {
+    if file["use_encryption"].as_bool() == Some(true) {
+        return Err("Electrum wallet file is encrypted; remove its password in Electrum first");
+    }
+    let keystore = file.get("keystore").ok_or("Not an Electrum standard wallet file (no keystore)")?;
+    match keystore["type"].as_str() {
+        Some("bip32") => {
+            if let Some(seed) = keystore["seed"].as_str() {
+                let seed_type = electrum::seed_type(seed).ok_or("Keystore seed is not an Electrum v2 seed")?;
+                imported.wallets.push(electrum_seed_wallet(seed, keystore["passphrase"].as_str().unwrap_or_default(), seed_type)?);
+            } else if let Some(xprv) = keystore["xprv"].as_str() {
+                push_xprv(imported, xprv, electrum_origin(keystore)?, slip132_script_type(bip32::key_header(xprv)?.0))?;
+            } else if let Some(xpub) = keystore["xpub"].as_str() {
+                let script_type = slip132_script_type(bip32::key_header(xpub)?.0).ok_or("Unsupported Electrum xpub version")?;
+                let origin = electrum_origin(keystore)?.map(|origin| origin.to_string()).unwrap_or_default();
+                let key = format!("{}{}/0/*", origin, bip32::convert_xpub(xpub, XPUB_VERSION)?);
+                imported.descriptors.push(DescriptorRecord::new("Electrum watch-only wallet", &Descriptor::parse(&script_type.descriptor(&key))?)?);
+            }
+        }
+        Some("imported") => {
+            for (_, serialized) in keystore["keypairs"].as_object().ok_or("Imported keystore has no key pairs")? {
+                let serialized = serialized.as_str().ok_or("Imported keystore has no key pairs")?;
+                let (script_type, wif) = match serialized.split_once(':') {
+                    Some((name, wif)) => (electrum_script_type(name).ok_or("Unsupported Electrum script type")?, wif),
+                    None => (ScriptType::Pkh, serialized),
+                };
+                imported.wallets.push(imported_wallet(wif, script_type)?);
+            }
+        }
+        Some("hardware") => return Err("Electrum hardware wallet files hold no keys; import the device's export instead"),
+        _ => return Err("Unsupported Electrum keystore type"),
+    }
+
+    for (reference, label) in file["labels"].as_object().into_iter().flatten() {
+        let label_type = if reference.len() == 64 && reference.chars().all(|ch| ch.is_ascii_hexdigit()) { "tx" } else { "addr" };
+        if let Some(label) = label.as_str() {
+            imported.labels.push(labels::new_label(label_type, reference, label, None)?);
+        }
+    }
+    Ok(())
+}
+
+// Sparrow / Specter wallet export
+fn read_sparrow(imported: &mut Imported, export: &Value) -> Result<(), &'static str> {
+    let descriptor = export["descriptor"].as_str().ok_or("Not a Sparrow / Specter wallet export (no descriptor)")?;
+    read_descriptor(imported, descriptor, export["label"].as_str().unwrap_or("Sparrow / Specter wallet"))
+}
+
+// Coldcard generic JSON: every single-key account becomes a watch-only descriptor; multisig cosigner
+// xpubs (BIP45, BIP48) are left out, since they only make a wallet together with the other cosigners
+fn read_coldcard(imported: &mut Imported, export: &Value) -> Result<(), &'static str> {
+    let xfp = export["xfp"].as_str().ok_or("Not a Coldcard generic JSON export (no xfp)")?;
+    for account in export.as_object().into_iter().flat_map(|accounts| accounts.values()) {
+        let (Some(xpub), Some(deriv)) = (account["xpub"].as_str(), account["deriv"].as_str()) else {
+            continue;
+        };
+        let Some(script_type) = account["name"].as_str().and_then(ScriptType::from_name) else {
+            continue;
+        };
+        let key = format!("[{}{}]{}/0/*", xfp, deriv.trim_start_matches('m'), bip32::convert_xpub(xpub, XPUB_VERSION)?);
+        let descriptor = Descriptor::parse(&script_type.descriptor(&key))?;
+        if account["first"].as_str().is_some_and(|first| descriptor.derive(0).map(|derived| derived.address).as_deref() != Ok(first)) {
+            return Err("A Coldcard account's first address does not match its xpub");
+        }
+        imported.descriptors.push(DescriptorRecord::new(&format!("Coldcard {} {}", xfp, deriv), &descriptor)?);
+    }
+    Ok(())
+}
+
+// Bitcoin Core `importdescriptors` request (an array of descriptors, or a single one)
+fn read_core(imported: &mut Imported, request: &Value) -> Result<(), &'static str> {
+    let requests = match request {
+        Value::Array(requests) => requests.iter().collect(),
+        request => vec![request],
+    };
+    for request in requests {
+        let descriptor = request["desc"].as_str().ok_or("Not a Bitcoin Core importdescriptors request (no desc)")?;
+        read_descriptor(imported, descriptor, request["label"].as_str().unwrap_or("Bitcoin Core descriptor"))?;
+    }
+    Ok(())
+}
+
+// Bitcoin Core `dumpwallet` file: the HD master key and every key with its address type and label
+fn read_dumpwallet(imported: &mut Imported, text: &str) -> Result<(), &'static str> {
+    for line in text.lines().map(str::trim) {
+        if let Some(xprv) = line.strip_prefix("# extended private masterkey:") {
+            push_xprv(imported, xprv, None, None)?;
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // <WIF> <time> [label=...|change=1|reserve=1|hdseed=1|script=1 ...] # addr=<address>,...
+        let (fields, comment) = line.split_once('#').unwrap_or((line, ""));
+        let mut fields = fields.split_whitespace();
+        let wif = fields.next().unwrap_or_default();
+        let flags: Vec<&str> = fields.skip(1).collect();
+        if flags.iter().any(|flag| flag.starts_with("script=") || flag.starts_with("hdseed=") || flag.starts_with("inactivehdseed=")) {
+            continue;
+        }
+        let address = comment.trim().strip_prefix("addr=").and_then(|addresses| addresses.split(',').next());
+        let script_type = address.and_then(ScriptType::from_address).unwrap_or(ScriptType::Pkh);
+        let wallet = imported_wallet(wif, script_type)?;
+        if let Some(label) = flags.iter().find_map(|flag| flag.strip_prefix("label=")).map(decode_dump_string).filter(|label| !label.is_empty()) {
+            imported.labels.push(labels::new_label("addr", &wallet.address, &label, None)?);
+        }
+        imported.wallets.push(wallet);
+    }
+    Ok(())
+}
+
+// Keys of a descriptor: xprvs become extended keys and WIF keys wallets; a descriptor of public keys is saved as watch-only
+fn read_descriptor(imported: &mut Imported, descriptor: &str, label: &str) -> Result<(), &'static str> {
+    let descriptor = descriptor::strip_checksum(descriptor)?;
+    let keys = descriptor::keys(descriptor);
+    let is_private = |key: &&str| ExtendedPrivKey::from_base58(key).is_ok() || PrivateKey::from_wif(key).is_ok();
+    if !keys.iter().any(is_private) {
+        imported.descriptors.push(DescriptorRecord::new(label, &Descriptor::parse(descriptor)?)?);
+        return Ok(());
+    }
+    if !keys.iter().all(is_private) {
+        return Err("Descriptors that mix private and public keys are not supported; import the private keys and the watch-only descriptor separately");
+    }
+    for key in keys {
+        if ExtendedPrivKey::from_base58(key).is_ok() {
+            push_xprv(imported, key, key_origin(descriptor, key)?, ScriptType::from_descriptor(descriptor))?;
+        } else {
+            let script_type = ScriptType::from_descriptor(descriptor).ok_or("WIF keys can only be imported from pkh, sh(wpkh) or wpkh descriptors")?;
+            imported.wallets.push(imported_wallet(key, script_type)?);
+        }
+    }
+    Ok(())
+}
+
+// Adds an xprv: a master key on its own, an account key with the origin and address type it is used with
+fn push_xprv(imported: &mut Imported, xprv: &str, origin: Option<KeyOrigin>, script_type: Option<ScriptType>) -> Result<(), &'static str> {
+    let key = ExtendedPrivKey::from_base58(xprv)?;
+    let record = match origin.filter(|origin| !origin.path.is_empty()) {
+        None if key.depth == 0 => ExtendedKeyRecord::new(&key),
+        None => return Err("Account xprv has no key origin ([fingerprint/path]), so its derivation path would be lost; import the seed or the master xprv instead"),
+        Some(origin) if origin.path.len() != usize::from(key.depth) => return Err("Key origin path does not match the depth of the xprv"),
+        Some(origin) if origin.path.last() != Some(&key.child_number) => return Err("Key origin path does not end at the child number of the xprv"),
+        Some(origin) => {
+            let script_type = script_type.ok_or("Account xprvs can only be imported from pkh, sh(wpkh), wpkh or tr descriptors")?;
+            ExtendedKeyRecord::account(&key, &origin, script_type)
+        }
+    };
+    imported.extended_keys.push(record);
+    Ok(())
+}
+
+// Key origin written before a key of a descriptor, e.g. [73c5da0a/84h/0h/0h]
+fn key_origin(descriptor: &str, key: &str) -> Result<Option<KeyOrigin>, &'static str> {
+    let Some(start) = descriptor.find(key) else {
+        return Ok(None);
+    };
+    match descriptor[..start].strip_suffix(']').and_then(|before| before.rfind('[')) {
+        Some(open) => KeyOrigin::parse(&descriptor[open..start]).map(Some),
+        None => Ok(None),
+    }
+}
+
+// Key origin of an Electrum keystore, when the file records its root fingerprint and derivation
+fn electrum_origin(keystore: &Value) -> Result<Option<KeyOrigin>, &'static str> {
+    match (keystore["root_fingerprint"].as_str(), keystore["derivation"].as_str()) {
+        (Some(fingerprint), Some(derivation)) => KeyOrigin::parse(&format!("[{}{}]", fingerprint, derivation.trim_start_matches('m'))).map(Some),
+        _ => Ok(None),
+    }
+}
+
+// Wallet record of an Electrum seed, saved like the restore flow does (first receiving key)
+fn electrum_seed_wallet(phrase: &str, passphrase: &str, seed_type: SeedType) -> Result<WalletRecord, &'static str> {
+    let master = ExtendedPrivKey::new(electrum::to_seed(phrase, passphrase).expose_bytes())?;
+    let path = electrum::derivation_path(seed_type, false, 0);
+    let key = Wallet::from_extended_key(&master.derive_path(&path)?)?;
+    let address = electrum::addresses(seed_type, &master, false, 1)?.remove(0);
+    Ok(WalletRecord {
+        seed_type: WalletKind::Electrum(seed_type).seed_type(),
+        derivation_path: Some(format_path(&path)),
+        ..WalletRecord::new(&electrum::normalize(phrase), &address, key.get_public_key(), key.expose_private_key())
+    })
+}
+
+// Wallet record of a single WIF key, with the address of its script type and no mnemonic
+fn imported_wallet(wif: &str, script_type: ScriptType) -> Result<WalletRecord, &'static str> {
+    let key = PrivateKey::from_wif(wif.trim()).map_err(|_| "Invalid WIF private key")?;
+    if key.network != Network::Bitcoin {
+        return Err("WIF key is not a mainnet key");
+    }
+    if !key.compressed {
+        return Err("Uncompressed WIF keys are not supported");
+    }
+    let wallet = Wallet::from_private_key(&Zeroizing::new(key.inner.secret_bytes())[..])?;
+    let public_key = hex::decode(wallet.get_public_key()).map_err(|_| "Invalid public key")?;
+    Ok(WalletRecord {
+        seed_type: WalletKind::Imported(script_type).seed_type(),
+        ..WalletRecord::new("", &script_type.address(&public_key)?, wallet.get_public_key(), wallet.expose_private_key())
+    })
+}
+
+// Master key of a saved wallet, re-derived from its mnemonic and checked against the saved key
+fn master(wallet: &WalletRecord) -> Result<ExtendedPrivKey, &'static str> {
+    let (master, path) = verify::rederive(wallet)?;
+    if !verify::derives_private_key(&master, &path, &wallet.private_key) {
+        return Err("The mnemonic does not derive the saved key (restored with a passphrase?), so its account cannot be exported");
+    }
+    Ok(master)
+}
+
+// HD account of a wallet kind below its master key
+fn account(master: &ExtendedPrivKey, kind: WalletKind) -> Account<'_> {
+    match kind {
+        WalletKind::Electrum(seed_type) => {
+            let path = electrum::derivation_path(seed_type, false, 0);
+            Account { master, path: path[..path.len() - 2].to_vec(), script_type: kind.script_type() }
+        }
+        _ => Account { master, path: descriptor::DEFAULT_ACCOUNT.to_vec(), script_type: ScriptType::Wpkh },
+    }
+}
+
+// Extended private and public key version bytes of a script type (SLIP-132), as Electrum expects them
+fn slip132_versions(script_type: ScriptType) -> ([u8; 4], [u8; 4]) {
+    match script_type {
+        ScriptType::Pkh | ScriptType::Tr => (XPRV_VERSION, XPUB_VERSION),
+        ScriptType::ShWpkh => (YPRV_VERSION, YPUB_VERSION),
+        ScriptType::Wpkh => (ZPRV_VERSION, ZPUB_VERSION),
+    }
+}
+
+// Script type of SLIP-132 version bytes (xprv and xpub are P2PKH)
+fn slip132_script_type(version: [u8; 4]) -> Option<ScriptType> {
+    ScriptType::ALL.into_iter().find(|&script_type| {
+        let (private, public) = slip132_versions(script_type);
+        version == private || version == public
+    })
+}
+
+// Electrum's name of a script type
+fn electrum_script_name(script_type: ScriptType) -> &'static str {
+    match script_type {
+        ScriptType::ShWpkh => "p2wpkh-p2sh",
+        script_type => script_type.name(),
+    }
+}
+
+// Script type of an Electrum script type name
+fn electrum_script_type(name: &str) -> Option<ScriptType> {
+    ScriptType::ALL.into_iter().find(|script_type| electrum_script_name(*script_type) == name)
+}
+
+// WIF encoding of a stored hex-encoded private key
+fn wif(private_key: &str) -> Result<Zeroizing<String>, &'static str> {
+    let bytes = Zeroizing::new(hex::decode(private_key).map_err(|_| "Private key is not hex-encoded")?);
+    let key = PrivateKey::from_slice(&bytes, Network::Bitcoin).map_err(|_| "Private key is not a valid secp256k1 key")?;
+    Ok(Zeroizing::new(key.to_wif()))
+}
+
+// Decodes the %XX escapes Bitcoin Core writes into dumpwallet labels
+fn decode_dump_string(text: &str) -> String {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match tail.get(..2).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()) {
+            Some(decoded) if byte == b'%' => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// Parses a JSON wallet file
+fn parse_json(text: &str) -> Result<Value, &'static str> {
+    serde_json::from_str(text).map_err(|_| "File is not valid JSON")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bip39::Seed;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn bip39_wallet() -> WalletRecord {
+        let wallet = Wallet::from_seed(Seed::new(MNEMONIC, "").expose_bytes()).unwrap();
+        WalletRecord::new(MNEMONIC, wallet.get_address(), wallet.get_public_key(), wallet.expose_private_key())
+    }
+
+    #[test]
+    fn test_exports_match_reference_wallets() {
+        let wallet = bip39_wallet();
+
+        // BIP84 test vector account of the all-"abandon" mnemonic
+        let coldcard: Value = serde_json::from_str(&export(Format::Coldcard, &wallet, None).unwrap()).unwrap();
+        assert_eq!(coldcard["xfp"], "73C5DA0A");
+        assert_eq!(coldcard["bip84"]["_pub"], "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs");
+        assert_eq!(coldcard["bip84"]["first"], "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu");
+        assert_eq!(coldcard["bip44"]["first"], "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA");
+        assert!(!coldcard.to_string().contains(&wallet.private_key));
+
+        let sparrow: Value = serde_json::from_str(&export(Format::Sparrow, &wallet, Some("Savings")).unwrap()).unwrap();
+        let descriptor = sparrow["descriptor"].as_str().unwrap();
+        assert!(descriptor.starts_with("wpkh([73c5da0a/84h/0h/0h]xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)#"));
+        assert!(descriptor::strip_checksum(descriptor).is_ok());
+
+        // Core gets the account with its xprv, plus the saved master key that the account does not cover
+        let core: Value = serde_json::from_str(&export(Format::Core, &wallet, None).unwrap()).unwrap();
+        assert_eq!(core.as_array().unwrap().len(), 3);
+        assert_eq!(core[1]["internal"], true);
+        assert!(core[0]["desc"].as_str().unwrap().starts_with("wpkh([73c5da0a/84h/0h/0h]xprv"));
+        assert!(core[2]["desc"].as_str().unwrap().starts_with("pkh(L") || core[2]["desc"].as_str().unwrap().starts_with("pkh(K"));
+
+        // Reading the exports back gives the same keys
+        let imported = import(Format::Core, &export(Format::Core, &wallet, None).unwrap()).unwrap();
+        assert_eq!(imported.wallets[0].address, wallet.address);
+        assert_eq!(imported.wallets[0].private_key, wallet.private_key);
+        assert_eq!(imported.wallets[0].kind(), Ok(WalletKind::Imported(ScriptType::Pkh)));
+        assert_eq!(imported.extended_keys.len(), 2);
+        let (origin, script_type) = imported.extended_keys[0].account_origin().unwrap().unwrap();
+        assert_eq!((origin.to_string().as_str(), script_type), ("[73c5da0a/84h/0h/0h]", ScriptType::Wpkh));
+        let mut electrum: Value = serde_json::from_str(&export(Format::Electrum, &wallet, None).unwrap()).unwrap();
+        assert!(electrum["keystore"]["xprv"].as_str().unwrap().starts_with("zprv"));
+        let key = import(Format::Electrum, &electrum.to_string()).unwrap().extended_keys.remove(0);
+        assert_eq!(key.private_key, imported.extended_keys[0].private_key);
+        assert_eq!(key.origin, imported.extended_keys[0].origin);
+        assert_eq!(key.script_type.as_deref(), Some("p2wpkh"));
+
+        // An account xprv without its key origin would lose its path, so it is refused
+        let xprv = core[0]["desc"].as_str().unwrap().split(']').nth(1).unwrap().split('/').next().unwrap();
+        assert!(import(Format::Core, &json!({ "desc": format!("wpkh({}/0/*)", xprv) }).to_string()).is_err());
+
+        // Watch-only exports come back as descriptors with the same first address
+        let sparrow = import(Format::Sparrow, &export(Format::Sparrow, &wallet, Some("Savings")).unwrap()).unwrap();
+        assert_eq!(sparrow.descriptors[0].address, "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu");
+        assert_eq!(sparrow.descriptors[0].label, "Savings");
+        let coldcard = import(Format::Coldcard, &coldcard.to_string()).unwrap();
+        let addresses: Vec<&str> = coldcard.descriptors.iter().map(|descriptor| descriptor.address.as_str()).collect();
+        assert_eq!(addresses.len(), 3);
+        assert!(addresses.contains(&"bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu") && addresses.contains(&"1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA"));
+        electrum["keystore"].as_object_mut().unwrap().remove("xprv");
+        let watch_only = import(Format::Electrum, &electrum.to_string()).unwrap();
+        assert_eq!(watch_only.descriptors[0].address, "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu");
+        assert!(watch_only.descriptors[0].descriptor.starts_with("wpkh([73c5da0a/84h/0h/0h]xpub6CatWdiZiodm"));
+
+        // Electrum seeds round-trip with their seed, imported keys with their script type
+        let phrase = electrum::generate(SeedType::Segwit);
+        let electrum_wallet = electrum_seed_wallet(&phrase, "", SeedType::Segwit).unwrap();
+        let file = export(Format::Electrum, &electrum_wallet, Some("Cold storage")).unwrap();
+        let imported = import(Format::Electrum, &file).unwrap();
+        assert_eq!(imported.wallets[0].address, electrum_wallet.address);
+        assert_eq!(imported.labels[0].label, "Cold storage");
+        let segwit_key = imported_wallet(&wif(&electrum_wallet.private_key).unwrap(), ScriptType::Wpkh).unwrap();
+        assert_eq!(segwit_key.address, electrum_wallet.address);
+        let file = export(Format::Electrum, &segwit_key, None).unwrap();
+        assert!(file.contains("\"p2wpkh:"));
+        assert_eq!(import(Format::Electrum, &file).unwrap().wallets[0].address, electrum_wallet.address);
+    }
+
+    #[test]
+    fn test_dumpwallet_import() {
+        let dump = "\
+# Wallet dump created by Bitcoin Core v25.0.0
+# extended private masterkey: xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi
+
+KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn 2024-01-01T00:00:00Z hdseed=1 # addr=bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4
+KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn 2024-01-01T00:00:00Z label=Rent%20%25 # addr=bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4
+00 2024-01-01T00:00:00Z script=1 # addr=3Cz8A4MfyZkz2qdUD1XhJJR2UB3C7cpFQX
+
+# End of dump
+";
+        let imported = import(Format::CoreDump, dump).unwrap();
+        assert_eq!(imported.extended_keys.len(), 1);
+        assert_eq!(imported.wallets.len(), 1);
+        assert_eq!(imported.wallets[0].address, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+        assert!(imported.wallets[0].mnemonic.is_empty());
+        assert_eq!(imported.labels[0].label, "Rent %");
+        assert!(import(Format::CoreDump, "# nothing here\n").is_err());
+    }
+}
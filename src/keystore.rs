@@ -0,0 +1,492 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::backend;
+use crate::storage;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use zeroize::Zeroizing;
+
+// Format marker and current version of keystore files
+const KEYSTORE_FORMAT: &str = "hd_wallet-keystore";
+const KEYSTORE_VERSION: u32 = 1;
+
+// Algorithms of keystores
+const KDF_ARGON2ID: &str = "argon2id";
+const CIPHER_XCHACHA20_POLY1305: &str = "xchacha20poly1305";
+
+// Argon2id cost of new keystores (64 MiB, 3 passes, 1 lane)
+const ARGON2_MEMORY_KIB: u32 = 65536;
+const ARGON2_ITERATIONS: u32 = 3;
+const ARGON2_PARALLELISM: u32 = 1;
+
+// Plaintext of the check block, decrypted to verify a password before touching any record
+const CHECK_PLAINTEXT: &[u8] = b"hd_wallet keystore check";
+
+// Error returned for data files that cannot be parsed (callers back them up before giving up)
+pub const CORRUPTED: &str = "Data file is corrupted";
+
+// Error returned for plaintext data files outside of the migration, which is the only place they are trusted
+pub const NOT_ENCRYPTED: &str = "Data file is not encrypted. Encrypt it first with menu option 13 (keystore migration)";
+
+// Keys of the unlocked keystores, shared by the CLI and the web interface
+static SESSION: Mutex<Option<Arc<Session>>> = Mutex::new(None);
+
+// Key derivation parameters stored in the keystore header
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct KdfParams {
+    algorithm: String,
+    salt: String, // Hex
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+// One encrypted JSON record with its own random nonce
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EncryptedRecord {
+    pub nonce: String,      // Hex, 24 bytes
+    pub ciphertext: String, // Base64, includes the Poly1305 tag
+}
+
+// Versioned keystore header: key derivation, cipher and password check block
+#[derive(Serialize, Deserialize, Clone)]
+pub struct KeystoreHeader {
+    format: String,
+    version: u32,
+    kdf: KdfParams,
+    cipher: String,
+    check: EncryptedRecord,
+}
+
+// On-disk keystore file: header, encrypted record count and the encrypted records
+#[derive(Serialize, Deserialize)]
+struct KeystoreFile {
+    #[serde(flatten)]
+    header: KeystoreHeader,
+    count: EncryptedRecord, // Number of records, so records removed from the end are noticed
+    records: Vec<EncryptedRecord>,
+}
+
+// Key derived from the keystore password, with the header fields its ciphertexts are bound to
+pub struct KeystoreKey {
+    key: Zeroizing<[u8; 32]>,
+    kdf: KdfParams,
+    binding: String, // Format, version, cipher and KDF parameters with the salt
+}
+
+impl KeystoreKey {
+    // Associated data of the ciphertext in a slot ("check", "wallets.json/record/3", "wallets/17"): binding the header
+    // and the slot detects records reordered within a file or moved between files
+    fn associated_data(&self, slot: &str) -> Vec<u8> {
+        format!("{}/{}", self.binding, slot).into_bytes()
+    }
+}
+
+// Result of migrating one plaintext data file
+#[derive(Debug, PartialEq)]
+pub enum Migration {
+    Missing,          // No file to migrate
+    AlreadyEncrypted, // File is already a keystore
+    Encrypted(usize), // Plaintext file encrypted, with its number of records
+}
+
+// Contents of a data file on disk
+enum StoredFile {
+    Missing,
+    Plaintext(Vec<Value>),
+    Keystore(Box<KeystoreFile>),
+}
+
+// Keys derived from the password when the keystores were unlocked. The password itself is not kept, and
+// Argon2id runs once per salt and session instead of on every load and save.
+pub struct Session {
+    keys: Vec<KeystoreKey>, // One per salt in use; the first also encrypts new keystores
+}
+
+impl Session {
+    // Derives the keys of existing keystore headers from a password (a new salt when there are none yet)
+    pub fn open(password: &str, headers: &[KeystoreHeader]) -> Result<Self, &'static str> {
+        if password.is_empty() {
+            return Err("The keystore password cannot be empty");
+        }
+        let mut session = Session { keys: Vec::new() };
+        for header in headers {
+            if session.keys.iter().any(|key| key.kdf == header.kdf) {
+                session.key(header)?;
+            } else {
+                session.keys.push(open_header(header, password)?);
+            }
+        }
+        if session.keys.is_empty() {
+            session.keys.push(new_key(password)?);
+        }
+        Ok(session)
+    }
+
+    // Key of a keystore header, checked against its check block
+    pub fn key(&self, header: &KeystoreHeader) -> Result<&KeystoreKey, &'static str> {
+        check_header(header)?;
+        let key = self
+            .keys
+            .iter()
+            .find(|key| key.kdf == header.kdf)
+            .ok_or("Keystore was created by another program since it was unlocked; unlock it again")?;
+        match decrypt(key, CHECK_SLOT, &header.check) {
+            Ok(check) if check.as_slice() == CHECK_PLAINTEXT => Ok(key),
+            _ => Err("Wrong keystore password"),
+        }
+    }
+
+    // Header of a new keystore, with the key it was made for
+    pub fn new_header(&self) -> Result<(KeystoreHeader, &KeystoreKey), &'static str> {
+        let key = &self.keys[0];
+        let header = KeystoreHeader {
+            format: KEYSTORE_FORMAT.to_string(),
+            version: KEYSTORE_VERSION,
+            kdf: key.kdf.clone(),
+            cipher: CIPHER_XCHACHA20_POLY1305.to_string(),
+            check: encrypt(key, CHECK_SLOT, CHECK_PLAINTEXT)?,
+        };
+        Ok((header, key))
+    }
+}
+
+// Unlocks the keystores for this session, deriving their keys through the storage backend
+pub fn unlock(password: &str) -> Result<(), &'static str> {
+    let session = backend::current().unlock(password)?;
+    *SESSION.lock().unwrap() = Some(Arc::new(session));
+    Ok(())
+}
+
+// Keys of the current session, if the keystores are unlocked
+pub fn session() -> Option<Arc<Session>> {
+    SESSION.lock().unwrap().clone()
+}
+
+// Header of a data file, if it is a readable keystore (other files report their problem when they are used)
+pub fn read_header(path: impl AsRef<Path>) -> Option<KeystoreHeader> {
+    match load(path) {
+        Ok(StoredFile::Keystore(keystore)) => Some(keystore.header),
+        _ => None,
+    }
+}
+
+// Reads all records of a keystore file; plaintext files are refused until they are migrated
+pub fn read_records(path: impl AsRef<Path>, session: &Session) -> Result<Vec<Value>, &'static str> {
+    let path = path.as_ref();
+    match load(path)? {
+        StoredFile::Missing => Ok(Vec::new()),
+        StoredFile::Plaintext(_) => Err(NOT_ENCRYPTED),
+        StoredFile::Keystore(keystore) => {
+            let key = session.key(&keystore.header)?;
+            if unseal(key, &count_slot(path), &keystore.count)? != keystore.records.len() {
+                return Err("Keystore file has lost or gained records since it was written");
+            }
+            keystore.records.iter().enumerate().map(|(i, record)| unseal(key, &record_slot(path, i), record)).collect()
+        }
+    }
+}
+
+// Number of records in a keystore file, read from its encrypted count without decrypting the records
+pub fn count_records(path: impl AsRef<Path>, session: &Session) -> Result<usize, &'static str> {
+    let path = path.as_ref();
+    match load(path)? {
+        StoredFile::Missing => Ok(0),
+        StoredFile::Plaintext(_) => Err(NOT_ENCRYPTED),
+        StoredFile::Keystore(keystore) => {
+            let key = session.key(&keystore.header)?;
+            unseal(key, &count_slot(path), &keystore.count)?.as_u64().map(|count| count as usize).ok_or(CORRUPTED)
+        }
+    }
+}
+
+// Replaces all records of a data file, keeping the header of an existing keystore (callers hold the file lock)
+pub fn write_records(path: impl AsRef<Path>, session: &Session, records: &[Value]) -> Result<(), &'static str> {
+    let path = path.as_ref();
+    let keystore = match load(path)? {
+        StoredFile::Keystore(keystore) => seal_file(path, keystore.header.clone(), session.key(&keystore.header)?, records)?,
+        StoredFile::Missing => {
+            let (header, key) = session.new_header()?;
+            seal_file(path, header, key, records)?
+        }
+        StoredFile::Plaintext(_) => return Err(NOT_ENCRYPTED),
+    };
+    write(path, &keystore)
+}
+
+// Encrypts an existing plaintext data file in place
+pub fn migrate_file(path: impl AsRef<Path>, session: &Session) -> Result<Migration, &'static str> {
+    let path = path.as_ref();
+    storage::with_lock(path, || match load(path)? {
+        StoredFile::Missing => Ok(Migration::Missing),
+        StoredFile::Keystore(_) => Ok(Migration::AlreadyEncrypted),
+        StoredFile::Plaintext(records) => {
+            let (header, key) = session.new_header()?;
+            write(path, &seal_file(path, header, key, &records)?)?;
+            Ok(Migration::Encrypted(records.len()))
+        }
+    })
+    .map_err(|_| "Failed to lock data file")?
+}
+
+// Reads a data file, refusing anything that is neither a keystore nor a plaintext record list
+fn load(path: impl AsRef<Path>) -> Result<StoredFile, &'static str> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(StoredFile::Missing),
+        Err(_) => return Err("Failed to read data file"),
+    };
+    if content.trim().is_empty() {
+        return Ok(StoredFile::Missing);
+    }
+    let value: Value = serde_json::from_str(&content).map_err(|_| CORRUPTED)?;
+    if value.is_array() {
+        return serde_json::from_value(value).map(StoredFile::Plaintext).map_err(|_| CORRUPTED);
+    }
+    if value["format"] != KEYSTORE_FORMAT {
+        return Err(CORRUPTED); // Neither a keystore nor a record list
+    }
+    if value["version"] != KEYSTORE_VERSION {
+        return Err("Unsupported keystore version");
+    }
+    serde_json::from_value(value).map(|keystore| StoredFile::Keystore(Box::new(keystore))).map_err(|_| CORRUPTED)
+}
+
+// Writes a keystore file atomically
+fn write(path: &Path, keystore: &KeystoreFile) -> Result<(), &'static str> {
+    let json = serde_json::to_string_pretty(keystore).map_err(|_| "Failed to serialize keystore")?;
+    storage::write_atomic(path, json.as_bytes()).map_err(|_| "Failed to write keystore file")
+}
+
+// Derives the key of new keystores from a password and a fresh salt
+fn new_key(password: &str) -> Result<KeystoreKey, &'static str> {
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let kdf = KdfParams {
+        algorithm: KDF_ARGON2ID.to_string(),
+        salt: hex::encode(salt),
+        memory_kib: ARGON2_MEMORY_KIB,
+        iterations: ARGON2_ITERATIONS,
+        parallelism: ARGON2_PARALLELISM,
+    };
+    derive_key(password, kdf)
+}
+
+// Derives the key of a keystore and checks it against the check block
+fn open_header(header: &KeystoreHeader, password: &str) -> Result<KeystoreKey, &'static str> {
+    check_header(header)?;
+    let key = derive_key(password, header.kdf.clone())?;
+    match decrypt(&key, CHECK_SLOT, &header.check) {
+        Ok(check) if check.as_slice() == CHECK_PLAINTEXT => Ok(key),
+        _ => Err("Wrong keystore password"),
+    }
+}
+
+// Refuses headers of other formats, versions or ciphers
+fn check_header(header: &KeystoreHeader) -> Result<(), &'static str> {
+    if header.format != KEYSTORE_FORMAT || header.version != KEYSTORE_VERSION {
+        return Err("Unsupported keystore version");
+    }
+    if header.cipher != CIPHER_XCHACHA20_POLY1305 {
+        return Err("Unsupported keystore cipher");
+    }
+    Ok(())
+}
+
+// Encrypts one JSON record for its slot
+pub fn seal(key: &KeystoreKey, slot: &str, record: &Value) -> Result<EncryptedRecord, &'static str> {
+    let plaintext = Zeroizing::new(serde_json::to_vec(record).map_err(|_| "Failed to serialize record")?);
+    encrypt(key, slot, &plaintext)
+}
+
+// Decrypts one JSON record from its slot
+pub fn unseal(key: &KeystoreKey, slot: &str, record: &EncryptedRecord) -> Result<Value, &'static str> {
+    let plaintext = decrypt(key, slot, record)?;
+    serde_json::from_slice(&plaintext).map_err(|_| CORRUPTED)
+}
+
+// Encrypts a list of records, each bound to its file and position, with their count
+fn seal_file(path: &Path, header: KeystoreHeader, key: &KeystoreKey, records: &[Value]) -> Result<KeystoreFile, &'static str> {
+    Ok(KeystoreFile {
+        header,
+        count: seal(key, &count_slot(path), &Value::from(records.len()))?,
+        records: records.iter().enumerate().map(|(i, record)| seal(key, &record_slot(path, i), record)).collect::<Result<_, _>>()?,
+    })
+}
+
+// Slot of the check block
+const CHECK_SLOT: &str = "check";
+
+// Slot of the record count of a keystore file ("wallets.json/count"); files of one session share their key,
+// so the file name keeps counts and records from being moved between them
+fn count_slot(path: &Path) -> String {
+    format!("{}/count", file_name(path))
+}
+
+// Slot of a record in a keystore file ("wallets.json/record/3")
+fn record_slot(path: &Path, index: usize) -> String {
+    format!("{}/record/{}", file_name(path), index)
+}
+
+// File name part of a keystore path
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+// Stretches the password into a 256-bit key with Argon2id
+fn derive_key(password: &str, kdf: KdfParams) -> Result<KeystoreKey, &'static str> {
+    if kdf.algorithm != KDF_ARGON2ID {
+        return Err("Unsupported keystore KDF");
+    }
+    let salt = hex::decode(&kdf.salt).map_err(|_| "Invalid keystore salt")?;
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32)).map_err(|_| "Invalid keystore KDF parameters")?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), &salt, key.as_mut())
+        .map_err(|_| "Keystore key derivation failed")?;
+    let binding = format!(
+        "{}/{}/{}/{}/{}/{}/{}/{}",
+        KEYSTORE_FORMAT, KEYSTORE_VERSION, CIPHER_XCHACHA20_POLY1305, kdf.algorithm, kdf.salt, kdf.memory_kib, kdf.iterations, kdf.parallelism
+    );
+    Ok(KeystoreKey { key, kdf, binding })
+}
+
+// Encrypts one record under a fresh random nonce, authenticating the header and the record's slot
+fn encrypt(key: &KeystoreKey, slot: &str, plaintext: &[u8]) -> Result<EncryptedRecord, &'static str> {
+    let mut nonce = [0u8; 24];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let ciphertext = XChaCha20Poly1305::new(key.key.as_ref().into())
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg: plaintext, aad: &key.associated_data(slot) })
+        .map_err(|_| "Encryption failed")?;
+    Ok(EncryptedRecord {
+        nonce: hex::encode(nonce),
+        ciphertext: base64::engine::general_purpose::STANDARD.encode(ciphertext),
+    })
+}
+
+// Decrypts and authenticates one record
+fn decrypt(key: &KeystoreKey, slot: &str, record: &EncryptedRecord) -> Result<Zeroizing<Vec<u8>>, &'static str> {
+    let nonce = hex::decode(&record.nonce).ok().filter(|nonce| nonce.len() == 24).ok_or("Invalid record nonce")?;
+    let ciphertext = base64::engine::general_purpose::STANDARD
+        .decode(&record.ciphertext)
+        .map_err(|_| "Invalid record ciphertext")?;
+    XChaCha20Poly1305::new(key.key.as_ref().into())
+        .decrypt(XNonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: &key.associated_data(slot) })
+        .map(Zeroizing::new)
+        .map_err(|_| "Keystore record failed authentication")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Returns a fresh file path in the temporary directory
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("hd_wallet_keystore_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("wallets.json");
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    // Keys of a data directory, as unlocked with a password
+    fn session(password: &str, path: &Path) -> Result<Session, &'static str> {
+        Session::open(password, read_header(path).as_slice())
+    }
+
+    #[test]
+    fn test_write_and_read_records() {
+        let path = temp_file("write");
+        let records = [json!({"Mnemonic": "abandon ability"}), json!({"Address": "1BoatSLRHtKNngkdXEeobR76b53LETtpyT"})];
+        let keys = session("correct horse", &path).unwrap();
+        write_records(&path, &keys, &records[..1]).unwrap();
+        let salt = |path: &std::path::PathBuf| serde_json::from_str::<Value>(&fs::read_to_string(path).unwrap()).unwrap()["kdf"]["salt"].clone();
+        let first_salt = salt(&path);
+        write_records(&path, &keys, &records).unwrap();
+        assert_eq!(salt(&path), first_salt);
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("abandon") && !content.contains("1BoatSLR"));
+        assert_eq!(read_records(&path, &session("correct horse", &path).unwrap()).unwrap(), records);
+        assert_eq!(count_records(&path, &keys), Ok(2));
+
+        // New files of a session reuse its key, so the password is stretched once per session
+        let other = path.with_file_name("keys.json");
+        let _ = fs::remove_file(&other);
+        write_records(&other, &keys, &records).unwrap();
+        assert_eq!(salt(&other), first_salt);
+        assert_eq!(keys.keys.len(), 1);
+
+        assert_eq!(session("wrong horse", &path).err(), Some("Wrong keystore password"));
+        assert!(Session::open("", &[]).is_err());
+    }
+
+    #[test]
+    fn test_migrate_plaintext_file() {
+        let path = temp_file("migrate");
+        let keys = session("secret", &path).unwrap();
+        assert_eq!(migrate_file(&path, &keys), Ok(Migration::Missing));
+
+        let records = vec![json!({"PrivateKey": "00ff"}), json!({"PrivateKey": "ff00"})];
+        fs::write(&path, serde_json::to_string_pretty(&records).unwrap()).unwrap();
+
+        // Outside of the migration a plaintext file is never trusted, nor silently encrypted
+        assert_eq!(read_records(&path, &keys), Err(NOT_ENCRYPTED));
+        assert_eq!(write_records(&path, &keys, &records), Err(NOT_ENCRYPTED));
+        assert_eq!(migrate_file(&path, &keys), Ok(Migration::Encrypted(2)));
+        assert_eq!(migrate_file(&path, &keys), Ok(Migration::AlreadyEncrypted));
+        assert_eq!(read_records(&path, &keys).unwrap(), records);
+    }
+
+    #[test]
+    fn test_tampered_record_is_rejected() {
+        let path = temp_file("tamper");
+        let keys = session("secret", &path).unwrap();
+        write_records(&path, &keys, &[json!({"PrivateKey": "00ff"})]).unwrap();
+
+        let mut keystore: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let ciphertext = keystore["records"][0]["ciphertext"].as_str().unwrap().to_string();
+        let flipped = if ciphertext.starts_with('A') { "B" } else { "A" };
+        keystore["records"][0]["ciphertext"] = json!(format!("{}{}", flipped, &ciphertext[1..]));
+        fs::write(&path, keystore.to_string()).unwrap();
+
+        assert_eq!(read_records(&path, &keys), Err("Keystore record failed authentication"));
+
+        // Records swapped within a file, or moved from another file of the same or another data directory, are rejected too
+        let records = [json!({"PrivateKey": "00ff"}), json!({"PrivateKey": "ff00"})];
+        write_records(&path, &keys, &records).unwrap();
+        let mut keystore: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        keystore["records"].as_array_mut().unwrap().swap(0, 1);
+        fs::write(&path, keystore.to_string()).unwrap();
+        assert_eq!(read_records(&path, &keys), Err("Keystore record failed authentication"));
+
+        let sibling = path.with_file_name("keys.json");
+        let elsewhere = temp_file("tamper_other");
+        let _ = fs::remove_file(&sibling);
+        for (other, other_keys) in [(&sibling, &keys), (&elsewhere, &session("secret", &elsewhere).unwrap())] {
+            write_records(&path, &keys, &records).unwrap();
+            write_records(other, other_keys, &records).unwrap();
+            let mut keystore: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+            let foreign: Value = serde_json::from_str(&fs::read_to_string(other).unwrap()).unwrap();
+            keystore["records"][0] = foreign["records"][0].clone();
+            fs::write(&path, keystore.to_string()).unwrap();
+            assert_eq!(read_records(&path, &keys), Err("Keystore record failed authentication"));
+        }
+
+        // Records cut from the end, down to none at all, are noticed through the encrypted count
+        for keep in [1, 0] {
+            write_records(&path, &keys, &records).unwrap();
+            let mut keystore: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+            keystore["records"].as_array_mut().unwrap().truncate(keep);
+            fs::write(&path, keystore.to_string()).unwrap();
+            assert_eq!(read_records(&path, &keys), Err("Keystore file has lost or gained records since it was written"));
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use crate::backend;
+use crate::keystore::Session;
+use crate::records::LabelRecord;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Reference types defined by BIP329
+pub const BIP329_TYPES: [&str; 6] = ["tx", "addr", "pubkey", "input", "output", "xpub"];
+
+// Longest label BIP329 asks wallets to keep; longer ones are truncated
+const MAX_LABEL_CHARS: usize = 255;
+
+// One line of a BIP329 label export
+#[derive(Serialize, Deserialize)]
+struct Bip329Label {
+    #[serde(rename = "type")]
+    label_type: String,
+    #[serde(rename = "ref")]
+    reference: String,
+    #[serde(default)]
+    label: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    origin: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    spendable: Option<bool>,
+}
+
+// Current label of every reference, keyed by (type, reference)
+pub struct Labels(BTreeMap<(String, String), LabelRecord>);
+
+impl Labels {
+    // Replays saved label records in order, so later ones replace earlier ones and empty ones remove them
+    pub fn from_records(records: Vec<LabelRecord>) -> Self {
+        let mut labels = BTreeMap::new();
+        for record in records {
+            let key = (record.label_type.clone(), record.reference.clone());
+            if record.label.is_empty() && record.note.is_none() {
+                labels.remove(&key);
+            } else {
+                labels.insert(key, record);
+            }
+        }
+        Labels(labels)
+    }
+
+    // Current label of a reference
+    pub fn get(&self, label_type: &str, reference: &str) -> Option<&LabelRecord> {
+        self.0.get(&(label_type.to_string(), reference.to_string()))
+    }
+
+    // Current label of an address
+    pub fn address(&self, address: &str) -> Option<&LabelRecord> {
+        self.get("addr", address)
+    }
+
+    // Every current label, sorted by type and reference
+    pub fn iter(&self) -> impl Iterator<Item = &LabelRecord> {
+        self.0.values()
+    }
+
+    // Writes the labels as BIP329 JSON Lines (notes stay local; references with only a note are skipped)
+    pub fn to_bip329(&self) -> String {
+        self.iter()
+            .filter(|record| !record.label.is_empty())
+            .map(|record| {
+                let line = Bip329Label {
+                    label_type: record.label_type.clone(),
+                    reference: record.reference.clone(),
+                    label: record.label.clone(),
+                    origin: record.origin.clone(),
+                    spendable: record.spendable,
+                };
+                serde_json::to_string(&line).expect("labels always serialize") + "\n"
+            })
+            .collect()
+    }
+}
+
+// Loads the current labels from the storage backend
+pub fn load(session: &Session) -> Result<Labels, &'static str> {
+    Ok(Labels::from_records(backend::current().load_records(session)?))
+}
+
+// Saves labels imported from BIP329 JSON Lines, keeping the local notes of relabelled references
+pub fn import_bip329(session: &Session, text: &str) -> Result<usize, &'static str> {
+    let current = load(session)?;
+    let imported: Vec<LabelRecord> = parse_bip329(text)?
+        .into_iter()
+        .map(|record| LabelRecord {
+            note: current.get(&record.label_type, &record.reference).and_then(|existing| existing.note.clone()),
+            ..record
+        })
+        .collect();
+    backend::current().append_records(session, &imported)?;
+    Ok(imported.len())
+}
+
+// Builds a label record after checking its type and reference, truncating overlong labels
+pub fn new_label(label_type: &str, reference: &str, label: &str, note: Option<&str>) -> Result<LabelRecord, &'static str> {
+    if !BIP329_TYPES.contains(&label_type) {
+        return Err("Unknown label type (expected tx, addr, pubkey, input, output or xpub)");
+    }
+    if reference.trim().is_empty() {
+        return Err("The labelled reference cannot be empty");
+    }
+    let label: String = label.trim().chars().take(MAX_LABEL_CHARS).collect();
+    Ok(LabelRecord::new(label_type, reference.trim(), &label, note.map(str::trim)))
+}
+
+// Parses BIP329 JSON Lines (as exported by Sparrow and other wallets); blank lines are ignored
+pub fn parse_bip329(text: &str) -> Result<Vec<LabelRecord>, &'static str> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let line: Bip329Label = serde_json::from_str(line).map_err(|_| "Invalid BIP329 label line")?;
+            Ok(LabelRecord {
+                origin: line.origin,
+                spendable: line.spendable,
+                ..new_label(&line.label_type, &line.reference, &line.label, None)?
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Lines in the style of a Sparrow export
+    const SPARROW_EXPORT: &str = r#"{"type":"xpub","ref":"xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8","label":"Cold storage"}
+{"type":"addr","ref":"bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c","label":"Customer 42"}
+
+{"type":"output","ref":"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:0","label":"Change","spendable":false}
+"#;
+
+    #[test]
+    fn test_bip329_round_trip() {
+        let records = parse_bip329(SPARROW_EXPORT).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2].spendable, Some(false));
+
+        let labels = Labels::from_records(records);
+        assert_eq!(labels.address("bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c").unwrap().label, "Customer 42");
+        let exported = labels.to_bip329();
+        let reparsed = Labels::from_records(parse_bip329(&exported).unwrap());
+        assert_eq!(reparsed.to_bip329(), exported);
+        assert_eq!(exported.lines().count(), 3);
+
+        assert!(parse_bip329(r#"{"type":"wallet","ref":"x","label":"y"}"#).is_err());
+        assert!(parse_bip329("not json").is_err());
+    }
+
+    #[test]
+    fn test_latest_label_wins() {
+        let labels = Labels::from_records(vec![
+            new_label("addr", "1A", "first", None).unwrap(),
+            new_label("addr", "1B", "other", Some("vip")).unwrap(),
+            new_label("addr", "1A", "second", None).unwrap(),
+            new_label("addr", "1B", "", None).unwrap(),
+        ]);
+        assert_eq!(labels.address("1A").unwrap().label, "second");
+        assert!(labels.address("1B").is_none());
+        assert_eq!(labels.iter().count(), 1);
+        assert_eq!(new_label("addr", "1A", &"x".repeat(300), None).unwrap().label.len(), MAX_LABEL_CHARS);
+    }
+}
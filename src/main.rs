@@ -2,26 +2,75 @@ mod wallet;
 mod bip39;
 mod bip32;
 mod web;
+mod recovery;
+mod slip39;
+mod codex32;
+mod seedxor;
+mod bip85;
+mod electrum;
+mod hardening;
+mod keystore;
+mod records;
+mod storage;
+mod backend;
+mod sqlite;
+mod config;
+mod browse;
+mod labels;
+mod verify;
+mod audit;
+mod watch_only;
+mod descriptor;
+mod interop;
+mod multisig;
+mod bsms;
 
 use std::io::{self, Write};
 use std::thread;
 use std::fmt;
+use std::path::Path;
 use wallet::Wallet;
-use bip39::{Mnemonic, Seed};
-use bip32::ExtendedPrivKey;
-use hex;
-use serde_json::{json, Value};
-use chrono::Utc;
+use bip39::{Mnemonic, Seed, WordMatch};
+use bip32::{format_path, ExtendedPrivKey};
 use qrcode::QrCode;
 use web::start_server;
+use electrum::PhraseType;
+use audit::Operation;
+use interop::Format;
+use descriptor::{Derived, Descriptor};
+use multisig::{Cosigner, Multisig};
+use records::{ChildKeyRecord, DescriptorRecord, ExtendedKeyRecord, LabelRecord, Record, RecordKind, WalletRecord};
+use config::Config;
+use recovery::{Checkpoint, MutationRules, Progress, RecoveryTarget};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use keystore::Session;
+use zeroize::Zeroizing;
 
 // Main function to run the Bitcoin Wallet Generator
 fn main() {
     // Initialize the async runtime for the web interface
     let rt = tokio::runtime::Runtime::new().unwrap();
 
+    // Read the data directory, storage backend and hardened mode from flags, environment or defaults
+    let config = Config::load().unwrap_or_else(|err| {
+        eprintln!("❌ {}", err);
+        std::process::exit(2);
+    });
+
+    // In hardened mode, keep secrets out of core dumps and swap for the whole session (CLI and web)
+    let hardening_status = config.hardened.then(hardening::harden_process);
+
     // Create necessary directories for storing data
-    create_directories().expect("Failed to create directories");
+    create_directories(&config.data_dir).expect("Failed to create directories");
+    warn_about_data_permissions(&config.data_dir);
+
+    // Open the storage backend selected with --storage or HD_WALLET_STORAGE
+    if let Err(err) = backend::init(&config) {
+        eprintln!("❌ {}", err);
+        std::process::exit(1);
+    }
+    audit::init(&config.data_dir);
 
     // Main loop for the CLI interface
     loop {
@@ -29,12 +78,35 @@ fn main() {
         println!("\n=============================");
         println!("   Bitcoin Wallet Generator  ");
         println!("=============================");
+        match &hardening_status {
+            Some(status) => println!("{}", hardening::status(status).describe()),
+            None => println!("{}", hardening::OFF),
+        }
+        println!("💾 Storage: {}", backend::current().describe());
         println!("1. Generate wallets");
         println!("2. Generate extended private key");
         println!("3. Derive child key");
         println!("4. Generate QR code for a wallet address");
-        println!("5. Use Bitcoin Wallet Generator on web interface");
-        println!("6. Exit");
+        println!("5. Restore wallet from mnemonic");
+        println!("6. Recover mnemonic with missing or wrong words");
+        println!("7. Recover BIP39 passphrase");
+        println!("8. SLIP-39 Shamir backup (split / combine)");
+        println!("9. Codex32 backup (split / recover)");
+        println!("10. SeedXOR backup (split / combine)");
+        println!("11. BIP85 child keys, mnemonics and passwords");
+        println!("12. Electrum seed (generate / restore)");
+        println!("13. Encrypt existing wallet files (keystore migration)");
+        println!("14. Browse saved wallets");
+        println!("15. Labels (add, list, BIP329 import / export)");
+        println!("16. Verify saved wallets and keys");
+        println!("17. Verify audit log");
+        println!("18. Export watch-only wallet list (JSON / CSV)");
+        println!("19. Wallet files (Electrum, Sparrow, Coldcard, Bitcoin Core import / export)");
+        println!("20. Descriptor addresses (import a descriptor, derive addresses)");
+        println!("21. Multisig wallets (create, addresses, Coldcard / Sparrow config)");
+        println!("22. BSMS multisig setup (BIP129 coordinator / signer)");
+        println!("23. Use Bitcoin Wallet Generator on web interface");
+        println!("24. Exit");
         println!("=============================");
 
         // Prompt the user to select an option
@@ -58,11 +130,29 @@ fn main() {
             2 => generate_extended_priv_key(), // Generate extended private key
             3 => derive_child_key(), // Derive child key
             4 => generate_qr_code_for_address(), // Generate QR code for a wallet address
-            5 => {
+            5 => restore_wallet(), // Restore a wallet from a mnemonic phrase
+            6 => recover_mnemonic(), // Recover missing or wrong mnemonic words
+            7 => recover_passphrase(&config.data_dir), // Brute-force a forgotten BIP39 passphrase
+            8 => shamir_backup(), // Split or combine SLIP-39 shares
+            9 => codex32_backup(), // Split or recover codex32 shares
+            10 => seed_xor_backup(), // Split or combine SeedXOR parts
+            11 => derive_bip85(), // Derive BIP85 child entropy
+            12 => electrum_seed(), // Generate or restore an Electrum seed
+            13 => migrate_keystores(), // Encrypt plaintext data files
+            14 => browse_wallets(), // List, search and inspect saved wallets
+            15 => manage_labels(&config.data_dir), // Label addresses and xpubs, BIP329 import/export
+            16 => verify_records(), // Re-derive saved records and report mismatches
+            17 => verify_audit_log(), // Check the audit log hash chain
+            18 => export_watch_only(&config.data_dir), // Addresses, public keys and xpubs without secrets
+            19 => wallet_files(&config.data_dir), // Export to or import from other wallet software
+            20 => descriptor_addresses(), // Derive addresses of a pasted descriptor
+            21 => multisig_wallets(&config.data_dir), // M-of-N sortedmulti wallets
+            22 => bsms_setup(), // BIP129 multisig setup between a coordinator and signers
+            23 => {
                 println!("\nStarting web interface...");
                 rt.block_on(start_server()); // Start the web interface
             }
-            6 => {
+            24 => {
                 println!("\n✅ Exiting... Thank you for using Bitcoin Wallet Generator!");
                 break; // Exit the program
             }
@@ -71,15 +161,50 @@ fn main() {
     }
 }
 
-// Function to create necessary directories for storing data
-fn create_directories() -> Result<(), std::io::Error> {
-    let directories = ["data/wallets", "data/extended_keys", "data/child_keys", "data/qr_codes"];
-    for dir in directories.iter() {
-        std::fs::create_dir_all(dir)?; // Create each directory if it doesn't exist
+// Function to create necessary directories for storing data, accessible to the owner only
+fn create_directories(data_dir: &Path) -> Result<(), std::io::Error> {
+    storage::create_private_dir(data_dir)?;
+    let directories = RecordKind::ALL.iter().map(RecordKind::name).chain(["qr_codes", "recovery"]);
+    for dir in directories {
+        storage::create_private_dir(data_dir.join(dir))?; // Create each directory if it doesn't exist
     }
     Ok(())
 }
 
+// Warns when saved data can be read by other users, and points at data left by older versions in ./data
+fn warn_about_data_permissions(data_dir: &Path) {
+    let exposed = storage::world_readable(data_dir);
+    if let Some(first) = exposed.first() {
+        println!("⚠️ {} file(s) in {} are readable by other users, e.g. {}", exposed.len(), data_dir.display(), first.display());
+        println!("   Restrict them with: chmod -R go-rwx {}", data_dir.display());
+    }
+
+    let legacy = Path::new("data");
+    if legacy.is_dir() && std::fs::canonicalize(legacy).ok() != std::fs::canonicalize(data_dir).ok() {
+        println!("ℹ️ Found a ./data directory from an older version; run with --data-dir data to keep using it.");
+    }
+}
+
+// Prints a prompt and returns the trimmed line entered by the user
+fn prompt_input(message: &str) -> String {
+    print!("{}", message);
+    io::stdout().flush().unwrap();
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).expect("Failed to read input");
+    input.trim().to_string()
+}
+
+// Prints a prompt and returns the line exactly as typed (without the newline), for passphrases
+fn prompt_line(message: &str) -> String {
+    print!("{}", message);
+    io::stdout().flush().unwrap();
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).expect("Failed to read input");
+    input.trim_end_matches(['\n', '\r']).to_string()
+}
+
 // Function to generate multiple wallets
 fn generate_wallets() {
     // Prompt the user to enter the number of wallets to generate
@@ -103,44 +228,52 @@ fn generate_wallets() {
     for i in 0..count {
         let handle = thread::spawn(move || {
             let mnemonic = Mnemonic::generate(128); // Generate a 12-word mnemonic
-            let seed = Seed::new(&mnemonic.to_string(), ""); // Derive seed from mnemonic
-            let wallet = Wallet::from_seed(seed.as_bytes()).unwrap(); // Create wallet from seed
+            let seed = Seed::new(mnemonic.expose_phrase(), ""); // Derive seed from mnemonic
+            let wallet = Wallet::from_seed(seed.expose_bytes()).unwrap(); // Create wallet from seed
+            let account = descriptor::default_account(seed.expose_bytes(), 3)?; // Master-key and BIP84 account descriptors
 
-            (
+            Ok((
                 i,
-                mnemonic.to_string(),
+                mnemonic.expose_phrase().to_string(),
                 wallet.get_address().to_string(),
                 wallet.get_public_key().to_string(),
-                wallet.get_private_key().to_string(),
-            )
+                wallet.expose_private_key().to_string(),
+                account,
+            ))
         });
         handles.push(handle);
     }
 
-    let mut wallets: Vec<Value> = Vec::new();
+    // Collect results from threads, so that nothing is shown when one of them failed
+    let generated = match handles.into_iter().map(|handle| handle.join().expect("Thread panicked")).collect::<Result<Vec<_>, &'static str>>() {
+        Ok(generated) => generated,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+
+    let mut wallets: Vec<WalletRecord> = Vec::new();
     let mut addresses: Vec<String> = Vec::new();
 
-    // Collect results from threads and display wallet details
-    for handle in handles {
-        let (index, mnemonic, address, public_key, private_key) = handle.join().expect("Thread panicked");
+    // Display wallet details
+    for (index, mnemonic, address, public_key, private_key, account) in generated {
         println!("\n🚀 Wallet #{}:", index + 1);
         println!("  Mnemonic     : {}", mnemonic);
-        println!("  Address      : {}", address);
+        println!("  Address      : {} (master key, P2PKH; the address that is saved)", address);
         println!("  Public Key   : {}", public_key);
         println!("  Private Key  : {}", private_key);
-
-        // Create JSON object for the wallet
-        let wallet_json = json!({
-            "Mnemonic": mnemonic,
-            "Address": address,
-            "PublicKey": public_key,
-            "PrivateKey": private_key,
-            "GeneratedAt": Utc::now().to_rfc3339(),
-        });
-
-        wallets.push(wallet_json);
+        println!("  Descriptor   : {}", account.master);
+        println!("  BIP84 account m/84'/0'/0' of the same mnemonic (a different address, for wallet software):");
+        println!("  Receive      : {}", account.receive);
+        println!("  Change       : {}", account.change);
+        println!("  Receiving    : {}", account.addresses.join(", "));
+
+        // Create the record for the wallet
+        wallets.push(WalletRecord::new(&mnemonic, &address, &public_key, &private_key));
         addresses.push(address);
     }
+    audit(Operation::GenerateWallets, &addresses.iter().map(String::as_str).collect::<Vec<_>>());
 
     // Prompt user to generate QR codes for wallets
     println!("\n📷 Which wallets would you like to generate QR codes for?");
@@ -154,8 +287,7 @@ fn generate_wallets() {
         "all" => {
             // Generate QR codes for all wallets
             for (i, address) in addresses.iter().enumerate() {
-                let file_name = format!("data/qr_codes/{}.svg", address);
-                if let Err(err) = generate_qr_code(address, &file_name) {
+                if let Err(err) = generate_qr_code(address) {
                     println!("\n❌ Failed to generate QR code for Wallet #{}: {}", i + 1, err);
                 }
             }
@@ -172,8 +304,7 @@ fn generate_wallets() {
 
             for &index in &indexes {
                 if index > 0 && index <= addresses.len() {
-                    let file_name = format!("data/qr_codes/{}.svg", addresses[index - 1]);
-                    if let Err(err) = generate_qr_code(&addresses[index - 1], &file_name) {
+                    if let Err(err) = generate_qr_code(&addresses[index - 1]) {
                         println!("\n❌ Failed to generate QR code for Wallet #{}: {}", index, err);
                     }
                 } else {
@@ -201,260 +332,1903 @@ fn generate_wallets() {
     }
 }
 
-// Function to save generated wallets to a JSON file
-fn save_wallets_to_file(wallets: &[Value]) -> Result<(), std::io::Error> {
-    let file_path = "data/wallets/wallets.json";
+// Function to restore a wallet from a mnemonic phrase, correcting typos interactively
+fn restore_wallet() {
+    let wordlist = Mnemonic::wordlist();
 
-    // Load existing wallets or create a new vector
-    let mut existing_wallets: Vec<Value> = if let Ok(file) = std::fs::read_to_string(file_path) {
-        serde_json::from_str(&file).unwrap_or_else(|_| Vec::new())
-    } else {
-        Vec::new()
-    };
-    existing_wallets.extend_from_slice(wallets);
+    let phrase = prompt_input("\n📝 Enter your mnemonic phrase (words may be shortened to their first 4 letters): ");
+
+    // Electrum seeds are not BIP39 mnemonics and are restored differently
+    match electrum::detect_phrase_type(&phrase) {
+        PhraseType::Electrum(seed_type) => {
+            println!("\n🔎 This is an Electrum {} seed, not a BIP39 mnemonic.", seed_type.name());
+            restore_electrum_seed(&phrase, seed_type);
+            return;
+        }
+        PhraseType::Both(seed_type) => {
+            println!("\n🔎 This phrase is valid both as a BIP39 mnemonic and as an Electrum {} seed.", seed_type.name());
+            if prompt_input("   Restore it as an Electrum seed? (y/N): ").eq_ignore_ascii_case("y") {
+                restore_electrum_seed(&phrase, seed_type);
+                return;
+            }
+        }
+        PhraseType::Bip39 | PhraseType::Unknown => {}
+    }
 
-    // Write updated wallets to the file
-    let json_data = serde_json::to_string_pretty(&existing_wallets)?;
-    std::fs::write(file_path, json_data)?;
+    let typed_words = bip39::normalize_phrase(&phrase);
+    if !bip39::VALID_WORD_COUNTS.contains(&typed_words.len()) {
+        println!("\n❌ Invalid mnemonic. It must contain 12, 15, 18, 21 or 24 words.");
+        return;
+    }
 
-    Ok(())
-}
+    // Resolve each word against the wordlist, asking the user when it is ambiguous
+    let mut words = Vec::with_capacity(typed_words.len());
+    for (position, typed) in typed_words.iter().enumerate() {
+        let mut current = typed.clone();
+        loop {
+            match bip39::match_word(&wordlist, &current) {
+                WordMatch::Exact(word) => {
+                    words.push(word);
+                    break;
+                }
+                WordMatch::Completed(word) => {
+                    println!("  🔤 Word #{}: '{}' completed to '{}'", position + 1, current, word);
+                    words.push(word);
+                    break;
+                }
+                WordMatch::Suggestions(suggestions) => {
+                    if suggestions.is_empty() {
+                        println!("\n❌ Word #{} '{}' is not in the BIP39 wordlist.", position + 1, current);
+                    } else {
+                        println!("\n❓ Word #{} '{}' is not in the BIP39 wordlist. Did you mean:", position + 1, current);
+                        for (i, suggestion) in suggestions.iter().take(9).enumerate() {
+                            println!("  {}. {}", i + 1, suggestion);
+                        }
+                    }
+                    let answer = prompt_input("   Select a number or type the correct word: ");
+                    current = match answer.parse::<usize>() {
+                        Ok(choice) if choice > 0 && choice <= suggestions.len().min(9) => suggestions[choice - 1].clone(),
+                        _ => answer,
+                    };
+                }
+            }
+        }
+    }
 
-// Function to save data to a file in JSON format
-fn save_to_file(data: &Value, file_name: &str) -> Result<(), std::io::Error> {
-    let mut existing_data: Vec<Value> = if let Ok(file_content) = std::fs::read_to_string(file_name) {
-        match serde_json::from_str(&file_content) {
-            Ok(parsed) => parsed,
-            Err(_) => {
-                println!("\n⚠️ Le fichier JSON existant est corrompu. Il sera réinitialisé.");
-                Vec::new()
+    // Validate the checksum, looking for two swapped neighbouring words if it fails
+    let mnemonic = match Mnemonic::from_phrase(&words.join(" ")) {
+        Ok(mnemonic) => mnemonic,
+        Err(err) => {
+            let candidates = bip39::find_adjacent_swaps(&wordlist, &words);
+            if candidates.is_empty() {
+                println!("\n❌ Error: {}", err);
+                return;
+            }
+
+            println!("\n⚠️ {}. Swapping two adjacent words gives a valid phrase:", err);
+            for (i, candidate) in candidates.iter().enumerate() {
+                println!("  {}. {}", i + 1, candidate);
+            }
+            let answer = prompt_input("   Select the phrase to use (or press Enter to cancel): ");
+            match answer.parse::<usize>() {
+                Ok(choice) if choice > 0 && choice <= candidates.len() => {
+                    Mnemonic::from_phrase(&candidates[choice - 1]).expect("Candidate passed the checksum")
+                }
+                _ => {
+                    println!("\n📝 Restore cancelled.");
+                    return;
+                }
             }
         }
-    } else {
-        Vec::new()
     };
 
-    existing_data.push(data.clone());
-
-    let json_data = serde_json::to_string_pretty(&existing_data)?;
-    std::fs::write(file_name, json_data)?;
+    let passphrase = prompt_line("\n🔐 Enter the BIP39 passphrase (leave empty for none): ");
+    let seed = Seed::new(mnemonic.expose_phrase(), &passphrase);
+    let wallet = match Wallet::from_seed(seed.expose_bytes()) {
+        Ok(wallet) => wallet,
+        Err(err) => {
+            println!("\n❌ Error: {}", err);
+            return;
+        }
+    };
 
-    Ok(())
+    audit(Operation::RestoreWallet, &[wallet.get_address()]);
+    println!("\n✅ Wallet restored:");
+    println!("  Mnemonic     : {}", mnemonic.expose_phrase());
+    println!("  Address      : {}", wallet.get_address());
+    println!("  Public Key   : {}", wallet.get_public_key());
+    println!("  Private Key  : {}", wallet.expose_private_key());
+
+    let save_choice = prompt_input("\n💾 Do you want to save this wallet to a file? (y/n): ");
+    if save_choice.eq_ignore_ascii_case("y") {
+        let record = WalletRecord::new(
+            mnemonic.expose_phrase(),
+            wallet.get_address(),
+            wallet.get_public_key(),
+            wallet.expose_private_key(),
+        );
+        if let Err(err) = save_wallets_to_file(&[record]) {
+            println!("\n❌ Failed to save wallet: {}", err);
+        } else {
+            println!("\n✅ Wallet saved successfully!");
+        }
+    }
 }
 
-// Function to generate an extended private key from a seed
-fn generate_extended_priv_key() {
-    // Prompt the user to enter a seed (hex-encoded)
-    print!("\n🔑 Enter a seed (hex-encoded): ");
-    io::stdout().flush().unwrap();
+// Function to recover a mnemonic with unknown ('?') or mistyped words
+fn recover_mnemonic() {
+    let wordlist = Mnemonic::wordlist();
 
-    let mut seed_hex = String::new();
-    io::stdin().read_line(&mut seed_hex).expect("Failed to read input");
-    let seed = match hex::decode(seed_hex.trim()) {
-        Ok(bytes) => bytes, // Decode the hex-encoded seed
-        Err(_) => {
-            println!("\n❌ Invalid seed format. Please enter a valid hex string.");
+    let phrase = prompt_input("\n📝 Enter the mnemonic, using '?' for each missing word: ");
+    let template = match recovery::parse_template(&wordlist, &phrase) {
+        Ok(template) => template,
+        Err(err) => {
+            println!("\n❌ Error: {}", err);
             return;
         }
     };
 
-    // Generate the extended private key from the seed
-    match ExtendedPrivKey::new(&seed) {
-        Ok(ext_key) => {
-            println!("\n✅ Extended Private Key generated:");
-            println!("  🔒 Private Key: {}", hex::encode(ext_key.private_key));
-            println!("  🔗 Chain Code: {}", hex::encode(ext_key.chain_code));
-
-            // Create JSON object for the extended private key
-            let ext_key_json = json!({
-                "PrivateKey": hex::encode(ext_key.private_key),
-                "GeneratedAt": Utc::now().to_rfc3339(),
-                "ChainCode": hex::encode(ext_key.chain_code),
-            });
+    let unknown = template.iter().filter(|index| index.is_none()).count();
+    if unknown > recovery::MAX_UNKNOWN_WORDS {
+        println!("\n❌ At most {} unknown words can be recovered.", recovery::MAX_UNKNOWN_WORDS);
+        return;
+    }
 
-            // Prompt user to save the extended private key
-            print!("\n💾 Do you want to save this extended private key to a file? (y/n): ");
-            io::stdout().flush().unwrap();
+    let target_input = prompt_input("\n🎯 Enter a known legacy address or xpub (leave empty to list all valid phrases): ");
+    let target = match RecoveryTarget::parse(&target_input) {
+        Ok(target) => target,
+        Err(err) => {
+            println!("\n❌ Error: {}", err);
+            return;
+        }
+    };
+    let passphrase = prompt_line("\n🔐 Enter the BIP39 passphrase (leave empty for none): ");
 
-            let mut save_choice = String::new();
-            io::stdin().read_line(&mut save_choice).expect("Failed to read input");
+    // Words that are all known but fail to validate point to a single wrong word
+    let progress = if unknown > 0 {
+        println!("\n🔍 Searching {} unknown word(s)...", unknown);
+        Progress::new(recovery::missing_words_space(&template))
+    } else {
+        println!("\n🔍 No '?' given; looking for a single wrong word...");
+        Progress::new(recovery::wrong_word_space(template.len()))
+    };
 
-            if save_choice.trim().eq_ignore_ascii_case("y") {
-                if let Err(err) = save_to_file(&ext_key_json, "data/extended_keys/extended_keys.json") {
-                    println!("\n❌ Failed to save extended private key: {}", err);
-                } else {
-                    println!("\n✅ Extended private key saved successfully!");
-                }
-            }
+    let done = AtomicBool::new(false);
+    let result = thread::scope(|scope| {
+        scope.spawn(|| progress.report_until(&done));
+        let result = if unknown > 0 {
+            recovery::recover_missing_words(&wordlist, &template, target.as_ref(), &passphrase, &progress)
+        } else {
+            let indices: Vec<usize> = template.iter().map(|index| index.unwrap()).collect();
+            recovery::recover_wrong_word(&wordlist, &indices, target.as_ref(), &passphrase, &progress)
+        };
+        done.store(true, Ordering::Relaxed);
+        result
+    });
+
+    println!(
+        "\n📊 {} candidates checked in {:.1}s, {} passed the checksum.",
+        progress.checked.load(Ordering::Relaxed),
+        progress.started.elapsed().as_secs_f64(),
+        result.valid_checksums
+    );
+
+    if result.matches.is_empty() {
+        println!("\n❌ No matching mnemonic found.");
+    } else if target.is_some() {
+        for phrase in &result.matches {
+            println!("\n✅ Matching mnemonic found: {}", phrase);
+        }
+    } else {
+        println!("\n📝 Phrases with a valid checksum (provide an address or xpub to narrow them down):");
+        for phrase in &result.matches {
+            println!("  {}", phrase);
+        }
+        if result.valid_checksums > result.matches.len() as u64 {
+            println!("  ... and {} more", result.valid_checksums - result.matches.len() as u64);
         }
-        Err(e) => println!("\n❌ Error: {}", e), // Handle errors during key generation
     }
 }
 
-// Function to derive a child key from a parent private key and chain code
-fn derive_child_key() {
-    // Prompt the user to enter the parent private key (hex-encoded)
-    print!("\n🔑 Enter a parent private key (hex-encoded, 64 characters): ");
-    io::stdout().flush().unwrap();
+// Function to recover a forgotten BIP39 passphrase by testing mutated guesses
+fn recover_passphrase(data_dir: &Path) {
+    let checkpoint_path = data_dir.join("recovery").join("passphrase_checkpoint.json");
 
-    let mut parent_key_hex = String::new();
-    io::stdin().read_line(&mut parent_key_hex).expect("Failed to read input");
-    let parent_key = match hex::decode(parent_key_hex.trim()) {
-        Ok(bytes) if bytes.len() == 32 => {
-            let mut array = [0u8; 32];
-            array.copy_from_slice(&bytes);
-            array
-        }
-        _ => {
-            println!("\n❌ Invalid private key. Must be a 64-character hex string.");
+    let phrase = prompt_input("\n📝 Enter the mnemonic phrase: ");
+    let mnemonic = match Mnemonic::from_phrase(&phrase) {
+        Ok(mnemonic) => mnemonic,
+        Err(err) => {
+            println!("\n❌ Error: {}", err);
             return;
         }
     };
 
-    // Prompt the user to enter the chain code (hex-encoded)
-    print!("\n🔗 Enter a chain code (hex-encoded, 64 characters): ");
-    io::stdout().flush().unwrap();
-
-    let mut chain_code_hex = String::new();
-    io::stdin().read_line(&mut chain_code_hex).expect("Failed to read input");
-    let chain_code = match hex::decode(chain_code_hex.trim()) {
-        Ok(bytes) if bytes.len() == 32 => {
-            let mut array = [0u8; 32];
-            array.copy_from_slice(&bytes);
-            array
+    let target_input = prompt_input("\n🎯 Enter a known legacy address or xpub of the wallet: ");
+    let target = match RecoveryTarget::parse(&target_input) {
+        Ok(Some(target)) => target,
+        Ok(None) => {
+            println!("\n❌ A known address or xpub is required to recognise the right passphrase.");
+            return;
         }
-        _ => {
-            println!("\n❌ Invalid chain code. Must be a 64-character hex string.");
+        Err(err) => {
+            println!("\n❌ Error: {}", err);
             return;
         }
     };
 
-    // Prompt the user to enter the index for the child key
-    print!("\n🔢 Enter an index for the child key (e.g., 0, 1, ...): ");
-    io::stdout().flush().unwrap();
+    // Base guesses come from a single template or from a file with one guess per line
+    let source = prompt_line("\n🔐 Enter your best passphrase guess, or @path to a file with one guess per line: ");
+    let bases: Vec<String> = match source.strip_prefix('@') {
+        Some(path) => match std::fs::read_to_string(path.trim()) {
+            Ok(content) => content.lines().map(String::from).collect(),
+            Err(err) => {
+                println!("\n❌ Failed to read {}: {}", path.trim(), err);
+                return;
+            }
+        },
+        None => vec![source],
+    };
 
-    let mut index_input = String::new();
-    io::stdin().read_line(&mut index_input).expect("Failed to read input");
-    let index: u32 = match index_input.trim().parse() {
-        Ok(num) => num,
-        Err(_) => {
-            println!("\n❌ Invalid index. Please enter a valid number.");
+    let rules_input = prompt_input("\n🧬 Mutation rules (case,space,typo,subst) [case,space,typo]: ");
+    let rules = match MutationRules::parse(if rules_input.is_empty() { "case,space,typo" } else { &rules_input }) {
+        Ok(rules) => rules,
+        Err(err) => {
+            println!("\n❌ Error: {}", err);
             return;
         }
     };
 
-    // Create the parent extended private key
-    let parent_ext_key = ExtendedPrivKey {
-        private_key: parent_key,
-        chain_code,
-    };
+    let candidates = recovery::expand_passphrases(&bases, &rules);
+    let job_id = recovery::passphrase_job_id(mnemonic.expose_phrase(), &target_input, &candidates);
+    println!("\n🔢 {} candidate passphrases generated with rules [{}].", candidates.len(), rules.describe());
+
+    // Offer to resume a previous run of the same search
+    let mut start = 0;
+    if let Ok(content) = std::fs::read_to_string(&checkpoint_path) {
+        if let Ok(checkpoint) = serde_json::from_str::<Checkpoint>(&content) {
+            if checkpoint.job_id == job_id && checkpoint.next_index < candidates.len() as u64 {
+                let answer = prompt_input(&format!("\n⏯️ Resume from candidate {}? (y/n): ", checkpoint.next_index));
+                if answer.eq_ignore_ascii_case("y") {
+                    start = checkpoint.next_index;
+                }
+            }
+        }
+    }
 
-    // Derive the child key
-    match parent_ext_key.derive_child_key(index) {
-        Ok(child_key) => {
-            println!("\n✅ Child Key derived:");
-            println!("  🔒 Private Key: {}", hex::encode(child_key.private_key));
-            println!("  🔗 Chain Code: {}", hex::encode(child_key.chain_code));
+    let progress = Progress::resumed(candidates.len() as u64, start);
+    let done = AtomicBool::new(false);
+    let found = thread::scope(|scope| {
+        scope.spawn(|| progress.report_until(&done));
+        let found = recovery::recover_passphrase(mnemonic.expose_phrase(), &candidates, &target, &progress, |next_index| {
+            let checkpoint = Checkpoint { job_id: job_id.clone(), next_index };
+            if let Err(err) = storage::write_atomic(&checkpoint_path, serde_json::to_string_pretty(&checkpoint).unwrap().as_bytes()) {
+                println!("\n⚠️ Failed to save checkpoint: {}", err);
+            }
+        });
+        done.store(true, Ordering::Relaxed);
+        found
+    });
+
+    println!(
+        "\n📊 {} passphrases tested in {:.1}s ({:.1} passphrases/s).",
+        progress.checked.load(Ordering::Relaxed) - start,
+        progress.started.elapsed().as_secs_f64(),
+        progress.rate()
+    );
+
+    match found {
+        Some(passphrase) => {
+            let _ = std::fs::remove_file(&checkpoint_path);
+            println!("\n✅ Passphrase found: {:?}", passphrase);
+        }
+        None => println!("\n❌ No candidate matched. Try other guesses or more mutation rules."),
+    }
+}
 
-            // Create JSON object for the child key
-            let child_key_json = json!({
-                "PrivateKey": hex::encode(child_key.private_key),
-                "ChainCode": hex::encode(child_key.chain_code),
-                "Index": index,
-                "DerivationPath": format!("m/44'/0'/0'/0/{}", index),
-                "GeneratedAt": Utc::now().to_rfc3339(),
-            });
+// Function to split a master secret into SLIP-39 shares or combine shares back
+fn shamir_backup() {
+    println!("\n1. Split a master secret into shares");
+    println!("2. Combine shares to recover a master secret");
+    match prompt_input("Please select an option: ").as_str() {
+        "1" => split_shamir_shares(),
+        "2" => combine_shamir_shares(),
+        _ => println!("\n❌ Invalid option."),
+    }
+}
 
-            // Prompt user to save the child key
-            print!("\n💾 Do you want to save this child key to a file? (y/n): ");
-            io::stdout().flush().unwrap();
+// Function to create SLIP-39 share groups for a new or existing master secret
+fn split_shamir_shares() {
+    let secret_input = prompt_input("\n🔑 Enter a master secret (hex), or leave empty to generate a new one: ");
+    let master_secret = if secret_input.is_empty() {
+        let bits: usize = match prompt_input("🔢 Strength in bits (128 or 256) [128]: ").as_str() {
+            "" | "128" => 128,
+            "256" => 256,
+            _ => {
+                println!("\n❌ Invalid strength. Please enter 128 or 256.");
+                return;
+            }
+        };
+        let mut rng = rand::thread_rng();
+        (0..bits / 8).map(|_| rand::Rng::gen::<u8>(&mut rng)).collect()
+    } else {
+        match hex::decode(&secret_input) {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                println!("\n❌ Invalid master secret. Please enter a valid hex string.");
+                return;
+            }
+        }
+    };
 
-            let mut save_choice = String::new();
-            io::stdin().read_line(&mut save_choice).expect("Failed to read input");
+    // Groups are written as member thresholds, e.g. "1-of-1,2-of-3,3-of-5"
+    let groups_input = prompt_input("\n👥 Groups (e.g. '3-of-5' or '1-of-1,2-of-3,3-of-5'): ");
+    let groups: Option<Vec<(u8, u8)>> = groups_input
+        .split(',')
+        .map(|group| {
+            let (threshold, count) = group.trim().split_once("-of-")?;
+            Some((threshold.trim().parse().ok()?, count.trim().parse().ok()?))
+        })
+        .collect();
+    let groups = match groups {
+        Some(groups) => groups,
+        None => {
+            println!("\n❌ Invalid groups. Use the form 'T-of-N', separated by commas.");
+            return;
+        }
+    };
 
-            if save_choice.trim().eq_ignore_ascii_case("y") {
-                if let Err(err) = save_to_file(&child_key_json, "data/child_keys/child_keys.json") {
-                    println!("\n❌ Failed to save child key: {}", err);
-                } else {
-                    println!("\n✅ Child key saved successfully!");
-                }
+    let group_threshold: u8 = if groups.len() == 1 {
+        1
+    } else {
+        match prompt_input(&format!("🔢 How many of the {} groups are needed to recover? ", groups.len())).parse() {
+            Ok(threshold) => threshold,
+            Err(_) => {
+                println!("\n❌ Invalid group threshold.");
+                return;
             }
         }
-        Err(e) => println!("\n❌ Error: {}", e), // Handle errors during key derivation
+    };
+
+    let passphrase = prompt_line("\n🔐 Enter a passphrase to encrypt the master secret (leave empty for none): ");
+    let extendable = !prompt_input("🧩 Allow extending this backup with more shares later? (Y/n): ").eq_ignore_ascii_case("n");
+
+    let share_groups = match slip39::split_master_secret(&master_secret, &passphrase, group_threshold, &groups, extendable, 1) {
+        Ok(share_groups) => share_groups,
+        Err(err) => {
+            println!("\n❌ Error: {}", err);
+            return;
+        }
+    };
+
+    println!("\n✅ SLIP-39 shares generated ({} of {} groups required):", group_threshold, groups.len());
+    for (group_index, (shares, (threshold, count))) in share_groups.iter().zip(&groups).enumerate() {
+        println!("\n👥 Group #{} ({} of {} shares required):", group_index + 1, threshold, count);
+        for (member_index, share) in shares.iter().enumerate() {
+            println!("  Share #{}: {}", member_index + 1, share);
+        }
     }
-}
 
-// Custom error type for QR code generation
-#[derive(Debug)]
-enum MyError {
-    QrError(qrcode::types::QrError),
-    IoError(std::io::Error),
+    let seed = Seed::from_bytes(master_secret);
+    match Wallet::from_seed(seed.expose_bytes()) {
+        Ok(wallet) => {
+            audit(Operation::SplitBackup, &["slip39", wallet.get_address()]);
+            println!("\n🔑 Master secret : {}", hex::encode(seed.expose_bytes()));
+            println!("🏠 Wallet address: {}", wallet.get_address());
+        }
+        Err(err) => println!("\n❌ Error: {}", err),
+    }
 }
 
-// Implement Display for MyError to provide error messages
-impl fmt::Display for MyError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            MyError::QrError(e) => write!(f, "QR Error: {}", e),
-            MyError::IoError(e) => write!(f, "IO Error: {}", e),
+// Function to recover a master secret from SLIP-39 shares
+fn combine_shamir_shares() {
+    println!("\n📝 Enter the share mnemonics, one per line, and an empty line when done:");
+    let mut mnemonics = Vec::new();
+    loop {
+        let share = prompt_input(&format!("  Share #{}: ", mnemonics.len() + 1));
+        if share.is_empty() {
+            break;
         }
+        mnemonics.push(share);
     }
-}
 
-// Implement Error for MyError to integrate with Rust's error handling
-impl std::error::Error for MyError {}
+    let passphrase = prompt_line("\n🔐 Enter the passphrase (leave empty for none): ");
+    let master_secret = match slip39::combine_mnemonics(&mnemonics, &passphrase) {
+        Ok(master_secret) => master_secret,
+        Err(err) => {
+            println!("\n❌ Error: {}", err);
+            return;
+        }
+    };
 
-// Convert qrcode::types::QrError to MyError
-impl From<qrcode::types::QrError> for MyError {
-    fn from(err: qrcode::types::QrError) -> MyError {
-        MyError::QrError(err)
+    let seed = Seed::from_bytes(master_secret);
+    match Wallet::from_seed(seed.expose_bytes()) {
+        Ok(wallet) => {
+            println!("\n✅ Master secret recovered:");
+            println!("  Master Secret : {}", hex::encode(seed.expose_bytes()));
+            println!("  Address       : {}", wallet.get_address());
+            println!("  Public Key    : {}", wallet.get_public_key());
+            println!("  Private Key   : {}", wallet.expose_private_key());
+        }
+        Err(err) => println!("\n❌ Error: {}", err),
     }
 }
 
-// Convert std::io::Error to MyError
-impl From<std::io::Error> for MyError {
-    fn from(err: std::io::Error) -> MyError {
-        MyError::IoError(err)
+// Function to split a master seed into codex32 shares or recover it from shares
+fn codex32_backup() {
+    println!("\n1. Split a master seed into codex32 shares");
+    println!("2. Recover a master seed from codex32 shares");
+    match prompt_input("Please select an option: ").as_str() {
+        "1" => split_codex32_shares(),
+        "2" => recover_codex32_seed(),
+        _ => println!("\n❌ Invalid option."),
     }
 }
 
-// Function to generate a QR code and save it to a file
-fn generate_qr_code(data: &str, file_name: &str) -> Result<(), MyError> {
-    let code = QrCode::new(data)?; // Generate the QR code
+// Function to create codex32 shares for a new or existing master seed
+fn split_codex32_shares() {
+    let seed_input = prompt_input("\n🔑 Enter a master seed (hex), or leave empty to generate a new one: ");
+    let seed: Vec<u8> = if seed_input.is_empty() {
+        let mut rng = rand::thread_rng();
+        (0..16).map(|_| rand::Rng::gen::<u8>(&mut rng)).collect()
+    } else {
+        match hex::decode(&seed_input) {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                println!("\n❌ Invalid master seed. Please enter a valid hex string.");
+                return;
+            }
+        }
+    };
 
-    let image = code
-        .render::<qrcode::render::svg::Color>()
-        .min_dimensions(200, 200)
-        .build();
+    let threshold: u8 = match prompt_input("\n🔢 How many shares are needed to recover (1-9)? ").parse() {
+        Ok(threshold) => threshold,
+        Err(_) => {
+            println!("\n❌ Invalid threshold.");
+            return;
+        }
+    };
+    let share_count: u8 = if threshold == 1 {
+        1
+    } else {
+        match prompt_input("🔢 How many shares should be created? ").parse() {
+            Ok(count) => count,
+            Err(_) => {
+                println!("\n❌ Invalid share count.");
+                return;
+            }
+        }
+    };
 
-    std::fs::write(file_name, image)?; // Save the QR code as an SVG file
+    let identifier = match prompt_input("🏷️  Identifier (4 bech32 characters, leave empty for a random one): ") {
+        identifier if identifier.is_empty() => codex32::random_identifier(),
+        identifier => identifier,
+    };
 
-    println!("✅ QR Code saved as {}", file_name);
+    let shares = match codex32::split_seed(&seed, threshold, share_count, &identifier) {
+        Ok(shares) => shares,
+        Err(err) => {
+            println!("\n❌ Error: {}", err);
+            return;
+        }
+    };
 
-    Ok(())
+    println!("\n✅ Codex32 shares generated ({} of {} required):", threshold, shares.len());
+    for share in &shares {
+        println!("  Share {}: {}", share.share_index(), share);
+    }
+    if let Ok(wallet) = Wallet::from_seed(&seed) {
+        audit(Operation::SplitBackup, &["codex32", wallet.get_address()]);
+    }
+    show_codex32_seed(&seed);
 }
 
-// Function to generate a QR code for a wallet address
-fn generate_qr_code_for_address() {
-    // Prompt the user to enter the wallet address
-    print!("\n🔑 Enter the wallet address to generate the QR code: ");
-    io::stdout().flush().unwrap();
-
-    let mut wallet_address = String::new();
-    io::stdin().read_line(&mut wallet_address).expect("Failed to read input");
-    let wallet_address = wallet_address.trim();
+// Function to recover a master seed from codex32 shares
+fn recover_codex32_seed() {
+    println!("\n📝 Enter the codex32 strings, one per line, and an empty line when done:");
+    let mut shares = Vec::new();
+    loop {
+        let input = prompt_input(&format!("  Share #{}: ", shares.len() + 1));
+        if input.is_empty() {
+            break;
+        }
+        match codex32::Codex32String::from_string(&input) {
+            Ok(share) => shares.push(share),
+            Err(err) => println!("  ❌ {}. Please enter it again.", err),
+        }
+    }
+
+    match codex32::recover_seed(&shares) {
+        Ok(seed) => show_codex32_seed(&seed),
+        Err(err) => println!("\n❌ Error: {}", err),
+    }
+}
+
+// Displays the master key and first address derived from a codex32 master seed
+fn show_codex32_seed(seed: &[u8]) {
+    let master_key = match ExtendedPrivKey::new(seed) {
+        Ok(master_key) => master_key,
+        Err(err) => {
+            println!("\n❌ Error: {}", err);
+            return;
+        }
+    };
+    match Wallet::from_extended_key(&master_key) {
+        Ok(wallet) => {
+            println!("\n🔑 Master seed   : {}", hex::encode(seed));
+            println!("🔒 Private Key   : {}", hex::encode(master_key.expose_private_key()));
+            println!("🔗 Chain Code    : {}", hex::encode(master_key.chain_code));
+            println!("🏠 Wallet address: {}", wallet.get_address());
+        }
+        Err(err) => println!("\n❌ Error: {}", err),
+    }
+}
+
+// Function to split a mnemonic into SeedXOR parts or combine parts back
+fn seed_xor_backup() {
+    println!("\n1. Split a mnemonic into SeedXOR parts");
+    println!("2. Combine SeedXOR parts into the original mnemonic");
+    match prompt_input("Please select an option: ").as_str() {
+        "1" => split_seed_xor(),
+        "2" => combine_seed_xor(),
+        _ => println!("\n❌ Invalid option."),
+    }
+}
+
+// Function to split a mnemonic into parts whose entropies XOR to the original
+fn split_seed_xor() {
+    let mnemonic = match Mnemonic::from_phrase(&prompt_input("\n📝 Enter the mnemonic phrase to split: ")) {
+        Ok(mnemonic) => mnemonic,
+        Err(err) => {
+            println!("\n❌ Error: {}", err);
+            return;
+        }
+    };
+    let part_count: usize = match prompt_input(&format!("🔢 Number of parts (2-{}) [2]: ", seedxor::MAX_PARTS)).as_str() {
+        "" => 2,
+        input => match input.parse() {
+            Ok(count) => count,
+            Err(_) => {
+                println!("\n❌ Invalid number of parts.");
+                return;
+            }
+        },
+    };
+
+    match seedxor::split_mnemonic(&mnemonic, part_count) {
+        Ok(parts) => {
+            if let Ok(wallet) = Wallet::from_seed(Seed::new(mnemonic.expose_phrase(), "").expose_bytes()) {
+                audit(Operation::SplitBackup, &["seedxor", wallet.get_address()]);
+            }
+            println!("\n✅ SeedXOR parts generated (all {} are needed to recover):", parts.len());
+            for (index, part) in parts.iter().enumerate() {
+                println!("  Part #{}: {}", index + 1, part.expose_phrase());
+            }
+            println!("\n⚠️  Each part is also a valid wallet on its own; keep them in separate places.");
+        }
+        Err(err) => println!("\n❌ Error: {}", err),
+    }
+}
+
+// Function to XOR SeedXOR parts back into the original mnemonic
+fn combine_seed_xor() {
+    println!("\n📝 Enter the SeedXOR parts, one per line, and an empty line when done:");
+    let mut parts = Vec::new();
+    loop {
+        let input = prompt_input(&format!("  Part #{}: ", parts.len() + 1));
+        if input.is_empty() {
+            break;
+        }
+        match Mnemonic::from_phrase(&input) {
+            Ok(part) => parts.push(part),
+            Err(err) => println!("  ❌ {}. Please enter it again.", err),
+        }
+    }
+
+    let mnemonic = match seedxor::combine_mnemonics(&parts) {
+        Ok(mnemonic) => mnemonic,
+        Err(err) => {
+            println!("\n❌ Error: {}", err);
+            return;
+        }
+    };
+    let seed = Seed::new(mnemonic.expose_phrase(), "");
+    match Wallet::from_seed(seed.expose_bytes()) {
+        Ok(wallet) => {
+            println!("\n✅ Mnemonic recovered:");
+            println!("  Mnemonic : {}", mnemonic.expose_phrase());
+            println!("  Address  : {}", wallet.get_address());
+        }
+        Err(err) => println!("\n❌ Error: {}", err),
+    }
+}
+
+// Function to derive BIP85 child mnemonics, keys and passwords from a master key
+fn derive_bip85() {
+    let input = prompt_input("\n🔑 Enter the master xprv, seed (hex) or mnemonic: ");
+    let passphrase = if input.contains(char::is_whitespace) {
+        prompt_line("🔐 Enter the mnemonic passphrase (leave empty for none): ")
+    } else {
+        String::new()
+    };
+    let master = match bip85::master_key_from_input(&input, &passphrase) {
+        Ok(master) => master,
+        Err(err) => {
+            println!("\n❌ Error: {}", err);
+            return;
+        }
+    };
+
+    println!("\n1. BIP39 mnemonic (39')");
+    println!("2. WIF private key (2')");
+    println!("3. Extended private key (32')");
+    println!("4. Hex entropy (128169')");
+    println!("5. Base64 password (707764')");
+    println!("6. Base85 password (707785')");
+    let (number, length_prompt, default_length) = match prompt_input("Please select an application: ").as_str() {
+        "1" => (39, Some("🔢 Number of words (12, 18 or 24) [24]: "), 24),
+        "2" => (2, None, 0),
+        "3" => (32, None, 0),
+        "4" => (128169, Some("🔢 Number of bytes (16-64) [32]: "), 32),
+        "5" => (707764, Some("🔢 Password length (20-86) [21]: "), 21),
+        "6" => (707785, Some("🔢 Password length (10-80) [12]: "), 12),
+        _ => {
+            println!("\n❌ Invalid option.");
+            return;
+        }
+    };
+    let length = match length_prompt.map(prompt_input) {
+        None => default_length,
+        Some(input) if input.is_empty() => default_length,
+        Some(input) => match input.parse() {
+            Ok(length) => length,
+            Err(_) => {
+                println!("\n❌ Invalid length.");
+                return;
+            }
+        },
+    };
+    let index: u32 = match prompt_input("🔢 Child index [0]: ").as_str() {
+        "" => 0,
+        input => match input.parse() {
+            Ok(index) => index,
+            Err(_) => {
+                println!("\n❌ Invalid index.");
+                return;
+            }
+        },
+    };
+
+    let application = match bip85::Application::from_number(number, length) {
+        Ok(application) => application,
+        Err(err) => {
+            println!("\n❌ Error: {}", err);
+            return;
+        }
+    };
+    match application.derive(&master, index) {
+        Ok(output) => {
+            if let Ok(fingerprint) = master.fingerprint() {
+                audit(Operation::DeriveBip85, &[&fingerprint, &application.path_string(index)]);
+            }
+            println!("\n✅ BIP85 child derived:");
+            println!("  Path   : {}", application.path_string(index));
+            println!("  Result : {}", output);
+        }
+        Err(err) => println!("\n❌ Error: {}", err),
+    }
+}
+
+// Function to generate a new Electrum seed or restore an existing one
+fn electrum_seed() {
+    println!("\n1. Generate a new Electrum seed");
+    println!("2. Restore an Electrum seed");
+    match prompt_input("Please select an option: ").as_str() {
+        "1" => {
+            let seed_type = match prompt_input("\n🧩 Seed type (1: segwit, 2: standard) [1]: ").as_str() {
+                "" | "1" => electrum::SeedType::Segwit,
+                "2" => electrum::SeedType::Standard,
+                _ => {
+                    println!("\n❌ Invalid seed type.");
+                    return;
+                }
+            };
+            let phrase = electrum::generate(seed_type);
+            println!("\n✅ Electrum {} seed generated:", seed_type.name());
+            println!("  Seed : {}", phrase);
+            restore_electrum_seed(&phrase, seed_type);
+        }
+        "2" => {
+            let phrase = prompt_input("\n📝 Enter your Electrum seed: ");
+            match electrum::seed_type(&phrase) {
+                Some(seed_type) => restore_electrum_seed(&phrase, seed_type),
+                None => println!("\n❌ This is not a valid Electrum standard or segwit seed."),
+            }
+        }
+        _ => println!("\n❌ Invalid option."),
+    }
+}
+
+// Function to restore an Electrum wallet and show its first addresses
+fn restore_electrum_seed(phrase: &str, seed_type: electrum::SeedType) {
+    let passphrase = prompt_line("\n🔐 Enter the seed extension (passphrase, leave empty for none): ");
+    let seed = electrum::to_seed(phrase, &passphrase);
+    let master = match ExtendedPrivKey::new(seed.expose_bytes()) {
+        Ok(master) => master,
+        Err(err) => {
+            println!("\n❌ Error: {}", err);
+            return;
+        }
+    };
+    let (receiving, change) = match (
+        electrum::addresses(seed_type, &master, false, 5),
+        electrum::addresses(seed_type, &master, true, 1),
+    ) {
+        (Ok(receiving), Ok(change)) => (receiving, change),
+        (Err(err), _) | (_, Err(err)) => {
+            println!("\n❌ Error: {}", err);
+            return;
+        }
+    };
+
+    audit(Operation::RestoreWallet, &[&receiving[0]]);
+    println!("\n✅ Electrum {} wallet restored:", seed_type.name());
+    for (index, address) in receiving.iter().enumerate() {
+        println!("  Receiving {} : {}", format_path(&electrum::derivation_path(seed_type, false, index as u32)), address);
+    }
+    println!("  Change {}    : {}", format_path(&electrum::derivation_path(seed_type, true, 0)), change[0]);
+
+    let save_choice = prompt_input("\n💾 Do you want to save this wallet to a file? (y/n): ");
+    if save_choice.eq_ignore_ascii_case("y") {
+        let path = electrum::derivation_path(seed_type, false, 0);
+        let key = match master.derive_path(&path).and_then(|key| Wallet::from_extended_key(&key)) {
+            Ok(key) => key,
+            Err(err) => {
+                println!("\n❌ Error: {}", err);
+                return;
+            }
+        };
+        let record = WalletRecord {
+            seed_type: Some(format!("electrum-{}", seed_type.name())),
+            derivation_path: Some(format_path(&path)),
+            ..WalletRecord::new(&electrum::normalize(phrase), &receiving[0], key.get_public_key(), key.expose_private_key())
+        };
+        if let Err(err) = save_wallets_to_file(&[record]) {
+            println!("\n❌ Failed to save wallet: {}", err);
+        } else {
+            println!("\n✅ Wallet saved successfully!");
+        }
+    }
+}
+
+// Function to save generated wallets to the encrypted wallet keystore, with an optional label
+fn save_wallets_to_file(wallets: &[WalletRecord]) -> Result<(), &'static str> {
+    let session = keystore_session()?;
+    let addresses: Vec<&str> = wallets.iter().map(|wallet| wallet.address.as_str()).collect();
+    let labels = prompt_labels("addr", &addresses)?;
+    backend::current().append_records(&session, wallets)?;
+    audit(Operation::SaveWallets, &addresses);
+    backend::current().append_records(&session, &labels)
+}
+
+// Asks for an optional label and note applied to every given reference
+fn prompt_labels(label_type: &str, references: &[&str]) -> Result<Vec<LabelRecord>, &'static str> {
+    let label = prompt_input("🏷️ Label (optional, press Enter to skip): ");
+    if label.is_empty() {
+        return Ok(Vec::new());
+    }
+    let note = prompt_input("📝 Note (optional): ");
+    references.iter().map(|reference| labels::new_label(label_type, reference, &label, Some(&note))).collect()
+}
+
+// Function to save a record to the encrypted keystore file of its kind
+fn save_to_file<T: Record>(record: &T) -> Result<(), &'static str> {
+    let session = keystore_session()?;
+    backend::current().append_records(&session, std::slice::from_ref(record))
+}
+
+// Returns the unlocked keystore session, prompting for the password the first time
+fn keystore_session() -> Result<Arc<Session>, &'static str> {
+    if let Some(session) = keystore::session() {
+        return Ok(session);
+    }
+
+    let password = Zeroizing::new(prompt_line("\n🔐 Enter the keystore password: "));
+    if !backend::current().has_password() {
+        // No keystore yet: this password protects all saved secrets from now on
+        let confirmation = Zeroizing::new(prompt_line("🔐 Confirm the new keystore password: "));
+        if *confirmation != *password {
+            return Err("Passwords do not match");
+        }
+    }
+    keystore::unlock(&password)?;
+    println!("🔓 Keystore unlocked for this session.");
+    keystore::session().ok_or("Keystore is locked")
+}
+
+// Encrypts plaintext wallet, extended key and child key files in place and upgrades their records
+fn migrate_keystores() {
+    let session = match keystore_session() {
+        Ok(session) => session,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+
+    let backend = backend::current();
+    for kind in RecordKind::ALL {
+        let location = backend.location(kind);
+        match backend.upgrade(kind, &session) {
+            Ok(0) => println!("🔒 {}: encrypted and up to date", location),
+            Ok(count) => println!("✅ {}: {} record(s) encrypted or upgraded to schema version {}", location, count, records::SCHEMA_VERSION),
+            Err(err) => println!("❌ {}: {}", location, err),
+        }
+    }
+}
+
+// Lists saved wallets page by page, filtered by address, label or date, and shows one in detail
+fn browse_wallets() {
+    let session = match keystore_session() {
+        Ok(session) => session,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+
+    let query = prompt_input("\n🔎 Search by address, label or date (e.g. 2024-05-17), or press Enter to list all: ");
+    let mut page_number = 1;
+    loop {
+        // Each page is read again, so a changed label shows up right away
+        let page = match browse::search(backend::current(), &session, &query, page_number) {
+            Ok(page) => page,
+            Err(err) => {
+                println!("\n❌ {}", err);
+                return;
+            }
+        };
+        if page.total == 0 {
+            println!("\n📭 No saved wallets match.");
+            return;
+        }
+        println!("\n📒 Saved wallets (page {} of {}, {} match(es)):", page.number, page.pages, page.total);
+        for entry in &page.entries {
+            let label = entry.label.as_ref().map(|label| label.label.as_str()).unwrap_or_default();
+            println!("  #{:<6} {:<44} {}  {}", entry.id, entry.wallet.address, entry.wallet.generated_at, label);
+        }
+
+        let command = prompt_input("\n➡️ Enter a wallet number to inspect, 'n' for next page, 'p' for previous page, or 'q' to go back: ");
+        match command.to_lowercase().as_str() {
+            "n" => page_number = page.number + 1,
+            "p" => page_number = page.number.saturating_sub(1),
+            "q" | "" => return,
+            number => match number.parse::<usize>().map_err(|_| "Enter a wallet number").and_then(|id| browse::wallet(backend::current(), &session, id)) {
+                Ok(Some(entry)) => show_wallet(&entry, &session),
+                Ok(None) => println!("\n❌ No saved wallet #{}.", number),
+                Err(err) => println!("\n❌ {}", err),
+            },
+        }
+    }
+}
+
+// Shows one saved wallet, revealing its secrets and QR code only on request
+fn show_wallet(entry: &browse::Entry, session: &Session) {
+    let (id, wallet, label) = (entry.id, &entry.wallet, entry.label.as_ref());
+    println!("\n🗂️ Wallet #{}", id);
+    println!("  Address      : {}", wallet.address);
+    if let Some(label) = label {
+        println!("  Label        : {}", label.label);
+        if let Some(note) = &label.note {
+            println!("  Note         : {}", note);
+        }
+    }
+    println!("  Public Key   : {}", wallet.public_key);
+    if let Some(path) = &wallet.derivation_path {
+        println!("  Path         : {}", path);
+    }
+    if let Some(seed_type) = &wallet.seed_type {
+        println!("  Seed Type    : {}", seed_type);
+    }
+    println!("  Saved At     : {}", wallet.generated_at);
+    println!("  Mnemonic     : {}", browse::HIDDEN);
+    println!("  Private Key  : {}", browse::HIDDEN);
+    match backend::current().load_qr_code(&wallet.address) {
+        Ok(Some(_)) => println!("  QR Code      : saved"),
+        Ok(None) => println!("  QR Code      : not saved (option 4 saves one)"),
+        Err(err) => println!("  QR Code      : {}", err),
+    }
+
+    let answer = prompt_input("\n🔐 Type 'reveal' to show the mnemonic and private key, 'qr' to print the QR code, 'label' to change the label, or press Enter to go back: ");
+    match answer.to_lowercase().as_str() {
+        "reveal" => {
+            audit(Operation::RevealWallet, &[&wallet.address]);
+            println!("\n  Mnemonic     : {}", wallet.mnemonic);
+            println!("  Private Key  : {}", wallet.private_key);
+        }
+        "qr" => match QrCode::new(&wallet.address) {
+            Ok(code) => println!("\n{}", code.render::<char>().dark_color('█').light_color(' ').module_dimensions(2, 1).build()),
+            Err(err) => println!("\n❌ QR Error: {}", err),
+        },
+        "label" => {
+            let text = prompt_input("🏷️ New label (empty removes it): ");
+            let note = prompt_input("📝 Note (optional): ");
+            match labels::new_label("addr", &wallet.address, &text, Some(&note)).and_then(|label| backend::current().append_records(session, &[label])) {
+                Ok(()) => println!("\n✅ Label saved."),
+                Err(err) => println!("\n❌ {}", err),
+            }
+        }
+        _ => (),
+    }
+}
+
+// Adds, lists, imports and exports labels of addresses, public keys and xpubs
+fn manage_labels(data_dir: &Path) {
+    let session = match keystore_session() {
+        Ok(session) => session,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+
+    println!("\n🏷️ Labels:");
+    println!("  1. Add or change a label");
+    println!("  2. List labels");
+    println!("  3. Import BIP329 labels (e.g. from Sparrow)");
+    println!("  4. Export BIP329 labels");
+    match prompt_input("Select an option: ").as_str() {
+        "1" => {
+            let label_type = prompt_input("🔖 Type (addr, pubkey, xpub, tx, input, output) [addr]: ");
+            let label_type = if label_type.is_empty() { "addr".to_string() } else { label_type.to_lowercase() };
+            let reference = prompt_input("🔑 Address, public key, xpub or other reference: ");
+            let text = prompt_input("🏷️ Label (empty removes it): ");
+            let note = prompt_input("📝 Note (optional): ");
+            match labels::new_label(&label_type, &reference, &text, Some(&note)).and_then(|label| backend::current().append_records(&session, &[label])) {
+                Ok(()) => println!("\n✅ Label saved."),
+                Err(err) => println!("\n❌ {}", err),
+            }
+        }
+        "2" => match labels::load(&session) {
+            Ok(labels) => {
+                println!();
+                for label in labels.iter() {
+                    let note = label.note.as_deref().map(|note| format!(" ({})", note)).unwrap_or_default();
+                    println!("  {:<6} {} → {}{}", label.label_type, label.reference, label.label, note);
+                }
+                println!("\n📒 {} label(s).", labels.iter().count());
+            }
+            Err(err) => println!("\n❌ {}", err),
+        },
+        "3" => {
+            let path = prompt_input("📂 Path of the BIP329 file (JSON Lines): ");
+            match std::fs::read_to_string(&path) {
+                Ok(text) => match labels::import_bip329(&session, &text) {
+                    Ok(count) => println!("\n✅ {} label(s) imported.", count),
+                    Err(err) => println!("\n❌ {}", err),
+                },
+                Err(err) => println!("\n❌ Failed to read {}: {}", path, err),
+            }
+        }
+        "4" => {
+            let default_path = data_dir.join("labels.jsonl");
+            let path = prompt_input(&format!("📂 Export to [{}]: ", default_path.display()));
+            let path = if path.is_empty() { default_path } else { path.into() };
+            match labels::load(&session) {
+                Ok(labels) => match storage::write_atomic(&path, labels.to_bip329().as_bytes()) {
+                    Ok(()) => {
+                        audit(Operation::ExportLabels, &[&path.display().to_string()]);
+                        println!("\n✅ Labels exported to {}", path.display());
+                    }
+                    Err(err) => println!("\n❌ Failed to write {}: {}", path.display(), err),
+                },
+                Err(err) => println!("\n❌ {}", err),
+            }
+        }
+        _ => println!("\n❌ Invalid option."),
+    }
+}
+
+// Function to generate an extended private key from a seed
+fn generate_extended_priv_key() {
+    // Prompt the user to enter a seed (hex-encoded)
+    print!("\n🔑 Enter a seed (hex-encoded): ");
+    io::stdout().flush().unwrap();
+
+    let mut seed_hex = String::new();
+    io::stdin().read_line(&mut seed_hex).expect("Failed to read input");
+    let seed = match hex::decode(seed_hex.trim()) {
+        Ok(bytes) => bytes, // Decode the hex-encoded seed
+        Err(_) => {
+            println!("\n❌ Invalid seed format. Please enter a valid hex string.");
+            return;
+        }
+    };
+
+    // Generate the extended private key from the seed
+    match ExtendedPrivKey::new(&seed) {
+        Ok(ext_key) => {
+            let fingerprint = ext_key.fingerprint().unwrap_or_default();
+            audit(Operation::GenerateMasterKey, &[&fingerprint]);
+            println!("\n✅ Extended Private Key generated:");
+            println!("  🔒 Private Key: {}", hex::encode(ext_key.expose_private_key()));
+            println!("  🔗 Chain Code: {}", hex::encode(ext_key.chain_code));
+
+            // Create the record for the extended private key
+            let ext_key_record = ExtendedKeyRecord::new(&ext_key);
+
+            // Prompt user to save the extended private key
+            print!("\n💾 Do you want to save this extended private key to a file? (y/n): ");
+            io::stdout().flush().unwrap();
+
+            let mut save_choice = String::new();
+            io::stdin().read_line(&mut save_choice).expect("Failed to read input");
+
+            if save_choice.trim().eq_ignore_ascii_case("y") {
+                if let Err(err) = save_to_file(&ext_key_record).and_then(|_| save_xpub_label(&ext_key)) {
+                    println!("\n❌ Failed to save extended private key: {}", err);
+                } else {
+                    audit(Operation::SaveExtendedKey, &[&fingerprint]);
+                    println!("\n✅ Extended private key saved successfully!");
+                }
+            }
+        }
+        Err(e) => println!("\n❌ Error: {}", e), // Handle errors during key generation
+    }
+}
+
+// Offers to label the xpub of a saved extended key
+fn save_xpub_label(ext_key: &ExtendedPrivKey) -> Result<(), &'static str> {
+    let xpub = ext_key.xpub()?;
+    println!("  🔓 xpub: {}", xpub);
+    let labels = prompt_labels("xpub", &[&xpub])?;
+    let session = keystore_session()?;
+    backend::current().append_records(&session, &labels)
+}
+
+// Function to re-derive every saved wallet and key and report records whose fields disagree
+fn verify_records() {
+    let session = match keystore_session() {
+        Ok(session) => session,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+    let report = match verify::run(&session) {
+        Ok(report) => report,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+
+    for finding in &report.findings {
+        println!("\n⚠️ {} #{} ({}):", finding.kind.name(), finding.number, finding.reference);
+        for problem in &finding.problems {
+            println!("  - {}", problem);
+        }
+    }
+    if report.findings.is_empty() {
+        println!("\n✅ All {} saved record(s) match the keys they were derived from.", report.checked);
+    } else {
+        println!("\n❌ {} of {} saved record(s) do not match. Restore them from your backup before relying on them.", report.findings.len(), report.checked);
+    }
+}
+
+// Function to check that the audit log was neither edited nor truncated
+fn verify_audit_log() {
+    match audit::verify_log() {
+        Ok(audit::Verification::Intact { entries, head }) => {
+            println!("\n✅ Audit log intact: {} entries, latest hash {}", entries, head);
+            println!("   ⚠️ {}", audit::HEAD_NOTE);
+        }
+        Ok(audit::Verification::Broken { line, problem }) => println!("\n❌ Audit log broken at line {}: {}", line, problem),
+        Err(err) => println!("\n❌ {}", err),
+    }
+}
+
+// Function to export saved wallets and keys without their mnemonics and private keys
+fn export_watch_only(data_dir: &Path) {
+    let session = match keystore_session() {
+        Ok(session) => session,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+    let (entries, skipped) = match watch_only::collect(&session) {
+        Ok(collected) => collected,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+
+    let (extension, contents) = match prompt_input("\n📄 Format (1: JSON, 2: CSV) [1]: ").as_str() {
+        "" | "1" => ("json", watch_only::to_json(&entries)),
+        "2" => ("csv", watch_only::to_csv(&entries)),
+        _ => {
+            println!("\n❌ Invalid format.");
+            return;
+        }
+    };
+    let default_path = data_dir.join(format!("watch_only.{}", extension));
+    let path = prompt_input(&format!("📂 Export to [{}]: ", default_path.display()));
+    let path = if path.is_empty() { default_path } else { path.into() };
+    match storage::write_atomic(&path, contents.as_bytes()) {
+        Ok(()) => {
+            audit(Operation::ExportWatchOnly, &[&path.display().to_string()]);
+            println!("\n✅ {} watch-only entries exported to {}", entries.len(), path.display());
+            if skipped > 0 {
+                println!("⚠️ {} record(s) with invalid keys were left out; run option 16 to see them.", skipped);
+            }
+        }
+        Err(err) => println!("\n❌ Failed to write {}: {}", path.display(), err),
+    }
+}
+
+// Exports a saved wallet to, or imports keys from, the wallet files of other wallet software
+fn wallet_files(data_dir: &Path) {
+    let session = match keystore_session() {
+        Ok(session) => session,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+
+    println!("\n🔁 Wallet files:");
+    println!("  1. Export a saved wallet");
+    println!("  2. Import a wallet file");
+    let export = match prompt_input("Select an option: ").as_str() {
+        "1" => true,
+        "2" => false,
+        _ => {
+            println!("\n❌ Invalid option.");
+            return;
+        }
+    };
+    let formats: Vec<Format> = Format::ALL.into_iter().filter(|format| !export || format.can_export()).collect();
+    for (i, format) in formats.iter().enumerate() {
+        println!("  {}. {}", i + 1, format.describe());
+    }
+    let format = match prompt_input("📄 Format: ").parse::<usize>().ok().and_then(|number| formats.get(number.wrapping_sub(1))) {
+        Some(format) => *format,
+        None => {
+            println!("\n❌ Invalid format.");
+            return;
+        }
+    };
+    if export {
+        export_wallet_file(data_dir, format, &session);
+    } else {
+        import_wallet_file(format, &session);
+    }
+}
+
+// Writes one saved wallet in another wallet's format
+fn export_wallet_file(data_dir: &Path, format: Format, session: &Session) {
+    let (wallets, labels) = match backend::current().load_records::<WalletRecord>(session).and_then(|wallets| Ok((wallets, labels::load(session)?))) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+    let number = prompt_input("🔢 Wallet number (as listed by option 14): ");
+    let Some(wallet) = number.parse::<usize>().ok().and_then(|number| wallets.get(number.wrapping_sub(1))) else {
+        println!("\n❌ No saved wallet #{}.", number);
+        return;
+    };
+    let label = labels.address(&wallet.address).map(|label| label.label.as_str());
+    let contents = match interop::export(format, wallet, label) {
+        Ok(contents) => Zeroizing::new(contents),
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+    if format.has_secrets() {
+        println!("\n⚠️ This file holds the wallet's private keys: keep it as safe as the mnemonic.");
+        if !prompt_input("Continue? (y/n): ").eq_ignore_ascii_case("y") {
+            return;
+        }
+    }
+
+    let default_path = data_dir.join(format!("wallet-{}-{}.json", number, format.name()));
+    let path = prompt_input(&format!("📂 Export to [{}]: ", default_path.display()));
+    let path = if path.is_empty() { default_path } else { path.into() };
+    match storage::write_atomic(&path, contents.as_bytes()) {
+        Ok(()) => {
+            audit(Operation::ExportWallet, &[format.name(), &wallet.address]);
+            println!("\n✅ Wallet #{} exported as {} to {}", number, format.describe(), path.display());
+        }
+        Err(err) => println!("\n❌ Failed to write {}: {}", path.display(), err),
+    }
+}
+
+// Reads another wallet's file and saves the wallets, keys and labels it holds
+fn import_wallet_file(format: Format, session: &Session) {
+    let path = prompt_input("📂 Path of the file to import: ");
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => Zeroizing::new(text),
+        Err(err) => {
+            println!("\n❌ Failed to read {}: {}", path, err);
+            return;
+        }
+    };
+    let imported = match interop::import(format, &text) {
+        Ok(imported) => imported,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+    let subjects = imported.audit_subjects();
+    match interop::save(imported, session) {
+        Ok(summary) => {
+            let subjects: Vec<&str> = [format.name()].into_iter().chain(subjects.iter().map(String::as_str)).collect();
+            audit(Operation::ImportWallets, &subjects);
+            println!(
+                "\n✅ Imported {} wallet(s), {} extended key(s), {} watch-only descriptor(s) and {} label(s); {} already saved.",
+                summary.wallets, summary.extended_keys, summary.descriptors, summary.labels, summary.duplicates
+            );
+        }
+        Err(err) => println!("\n❌ {}", err),
+    }
+}
+
+// Derives addresses, scripts and key paths of a pasted or saved watch-only descriptor, and QR codes for any of them
+fn descriptor_addresses() {
+    let text = match prompt_input("\n🧾 Paste a descriptor (checksum optional), or press Enter to pick a saved watch-only descriptor: ") {
+        text if text.is_empty() => match select_saved_descriptor() {
+            Some(text) => text,
+            None => return,
+        },
+        text => text,
+    };
+    let descriptor = match Descriptor::parse(&text) {
+        Ok(descriptor) => descriptor,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+    println!("\n✅ Valid {} descriptor: {}", descriptor.address_type(), descriptor.with_checksum());
+
+    let derived = if descriptor.is_ranged() {
+        let start = match prompt_input("🔢 First index (default 0): ").as_str() {
+            "" => Some(0),
+            start => start.parse::<u32>().ok(),
+        };
+        let count = match prompt_input("🔢 Number of addresses (default 10): ").as_str() {
+            "" => Some(10),
+            count => count.parse::<u32>().ok().filter(|&count| count > 0),
+        };
+        match (start, count) {
+            (Some(start), Some(count)) => descriptor.derive_range(start, count),
+            _ => Err("Invalid index or number of addresses"),
+        }
+    } else {
+        descriptor.derive_range(0, 1)
+    };
+    let derived = match derived {
+        Ok(derived) => derived,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+    print_derived(&derived);
+    prompt_qr_codes(&derived);
+}
+
+// Lists the watch-only descriptors saved by wallet file imports and returns the chosen one
+fn select_saved_descriptor() -> Option<String> {
+    let mut descriptors = match keystore_session().and_then(|session| backend::current().load_records::<DescriptorRecord>(&session)) {
+        Ok(descriptors) if descriptors.is_empty() => {
+            println!("\n📭 No watch-only descriptors saved yet; import an xpub or descriptor with menu option 19.");
+            return None;
+        }
+        Ok(descriptors) => descriptors,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return None;
+        }
+    };
+    for (i, record) in descriptors.iter().enumerate() {
+        println!("  {}. {} ({})", i + 1, record.label, record.address);
+    }
+    let number = prompt_input("🔢 Descriptor number: ");
+    match number.parse::<usize>().ok().filter(|&number| number >= 1 && number <= descriptors.len()) {
+        Some(number) => Some(descriptors.swap_remove(number - 1).descriptor),
+        None => {
+            println!("\n❌ No saved descriptor #{}.", number);
+            None
+        }
+    }
+}
+
+// Prints derived addresses with their output scripts and key paths
+fn print_derived(derived: &[Derived]) {
+    for address in derived {
+        println!("\n📍 #{} {}", address.index, address.address);
+        println!("  Script       : {}", address.script_pubkey);
+        println!("  Path(s)      : {}", address.paths.join(", "));
+    }
+}
+
+// Prompts for the derived addresses to generate QR codes for, by derivation index
+fn prompt_qr_codes(derived: &[Derived]) {
+    println!("\n📷 Which addresses would you like to generate QR codes for?");
+    let choice = prompt_input("   Enter 'all', 'none', or a comma-separated list of indexes (e.g., 0,2): ").to_lowercase();
+    let selected: Vec<&str> = match choice.as_str() {
+        "all" => derived.iter().map(|address| address.address.as_str()).collect(),
+        "none" | "" => Vec::new(),
+        _ => choice
+            .split(',')
+            .filter_map(|index| {
+                let found = index.trim().parse::<u32>().ok().and_then(|index| derived.iter().find(|address| address.index == index));
+                if found.is_none() {
+                    println!("\n❌ Invalid index: {}. Skipping...", index.trim());
+                }
+                found.map(|address| address.address.as_str())
+            })
+            .collect(),
+    };
+    for address in selected {
+        if let Err(err) = generate_qr_code(address) {
+            println!("\n❌ Failed to generate QR code for {}: {}", address, err);
+        }
+    }
+}
+
+// Creates multisig wallets, lists their addresses and exports their coordinator config
+fn multisig_wallets(data_dir: &Path) {
+    let session = match keystore_session() {
+        Ok(session) => session,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+
+    println!("\n🔐 Multisig wallets:");
+    println!("  1. Create a multisig wallet");
+    println!("  2. Show addresses of a saved multisig wallet");
+    println!("  3. Export a coordinator config (Coldcard / Sparrow)");
+    match prompt_input("Select an option: ").as_str() {
+        "1" => create_multisig(&session),
+        "2" => show_multisig_addresses(&session),
+        "3" => export_multisig_config(data_dir, &session),
+        _ => println!("\n❌ Invalid option."),
+    }
+}
+
+// Builds a wallet from keys generated here and pasted cosigner keys, then saves it
+fn create_multisig(session: &Session) {
+    let name = prompt_input("🏷️ Wallet name (up to 20 characters): ");
+    println!("  1. P2WSH (native segwit, m/48'/0'/account'/2')");
+    println!("  2. P2SH-P2WSH (nested segwit, m/48'/0'/account'/1')");
+    let script = match prompt_input("📜 Script type: ").as_str() {
+        "1" => multisig::SCRIPTS[0],
+        "2" => multisig::SCRIPTS[1],
+        _ => {
+            println!("\n❌ Invalid script type.");
+            return;
+        }
+    };
+    let account = match prompt_input("🔢 BIP48 account number (default 0): ").as_str() {
+        "" => Some(0),
+        account => account.parse::<u32>().ok(),
+    };
+    let local = match prompt_input("🔑 Number of keys to generate here (default 1): ").as_str() {
+        "" => Some(1),
+        local => local.parse::<usize>().ok().filter(|&local| local <= multisig::MAX_COSIGNERS),
+    };
+    let (Some(account), Some(local)) = (account, local) else {
+        println!("\n❌ Invalid account or number of keys.");
+        return;
+    };
+
+    // Keys generated here, each with a mnemonic to back up
+    let mut mnemonics = Vec::new();
+    let mut masters = Vec::new();
+    let mut cosigners = Vec::new();
+    for _ in 0..local {
+        match multisig::generate_cosigner(script, account) {
+            Ok((mnemonic, master, cosigner)) => {
+                mnemonics.push(mnemonic);
+                masters.push(master);
+                cosigners.push(cosigner);
+            }
+            Err(err) => {
+                println!("\n❌ {}", err);
+                return;
+            }
+        }
+    }
+
+    // Keys of the other cosigners, pasted one per line
+    println!("📋 Paste the other cosigners' keys, one per line ([fingerprint/48h/0h/0h/2h]xpub...), then an empty line:");
+    loop {
+        let line = prompt_input("");
+        if line.is_empty() {
+            break;
+        }
+        match Cosigner::parse(&line) {
+            Ok(cosigner) => cosigners.push(cosigner),
+            Err(err) => println!("❌ {} (key skipped)", err),
+        }
+    }
+    let threshold = prompt_input(&format!("✍️ Signatures required (1 to {}): ", cosigners.len())).parse::<usize>().unwrap_or(0);
+    let multisig = match Multisig::new(&name, threshold, script, cosigners) {
+        Ok(multisig) => multisig,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+
+    println!("\n✅ {} {} wallet \"{}\":", multisig.policy(), script.name(), multisig.name);
+    println!("  Receive      : {}", multisig.descriptor(false).with_checksum());
+    println!("  Change       : {}", multisig.descriptor(true).with_checksum());
+    match multisig.addresses(false, 0, 3) {
+        Ok(derived) => println!("  Receiving    : {}", derived.iter().map(|address| address.address.as_str()).collect::<Vec<_>>().join(", ")),
+        Err(err) => println!("\n❌ {}", err),
+    }
+    for (mnemonic, cosigner) in mnemonics.iter().zip(&multisig.cosigners) {
+        println!("\n🔑 Key {} generated here; write down its mnemonic:", cosigner.fingerprint);
+        println!("  {}", mnemonic.expose_phrase());
+    }
+
+    if !prompt_input("\n💾 Save this wallet and the keys generated here? (y/n): ").eq_ignore_ascii_case("y") {
+        println!("\n📝 Multisig wallet was not saved.");
+        return;
+    }
+    match multisig::save(&multisig, &masters.iter().collect::<Vec<_>>(), session) {
+        Ok(()) => {
+            let subjects: Vec<&str> = [multisig.name.as_str()].into_iter().chain(multisig.cosigners.iter().map(|cosigner| cosigner.fingerprint.as_str())).collect();
+            audit(Operation::CreateMultisig, &subjects);
+            println!("\n✅ Multisig wallet saved.");
+        }
+        Err(err) => println!("\n❌ Failed to save the multisig wallet: {}", err),
+    }
+}
+
+// Lists saved multisig wallets and returns the one the user picks, with its number
+fn select_multisig(session: &Session) -> Option<(usize, Multisig)> {
+    let saved = match multisig::load(session) {
+        Ok(saved) => saved,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return None;
+        }
+    };
+    for number in &saved.malformed {
+        println!("\n⚠️ Multisig wallet #{} could not be read and is skipped; menu option 16 shows why.", number);
+    }
+    if saved.wallets.is_empty() {
+        println!("\n📭 No multisig wallets saved yet.");
+        return None;
+    }
+    for (number, wallet) in &saved.wallets {
+        println!("  {}. {} ({} {})", number, wallet.name, wallet.policy(), wallet.script.name());
+    }
+    let number = prompt_input("🔢 Multisig wallet number: ");
+    match number.parse::<usize>().ok().and_then(|number| saved.wallets.into_iter().find(|(saved, _)| *saved == number)) {
+        Some(wallet) => Some(wallet),
+        None => {
+            println!("\n❌ No saved multisig wallet #{}.", number);
+            None
+        }
+    }
+}
+
+// Derives receiving or change addresses of a saved multisig wallet
+fn show_multisig_addresses(session: &Session) {
+    let Some((_, multisig)) = select_multisig(session) else {
+        return;
+    };
+    let change = prompt_input("🔁 Change addresses? (y/n): ").eq_ignore_ascii_case("y");
+    let start = match prompt_input("🔢 First index (default 0): ").as_str() {
+        "" => Some(0),
+        start => start.parse::<u32>().ok(),
+    };
+    let derived = match start {
+        Some(start) => multisig.addresses(change, start, 10),
+        None => Err("Invalid index"),
+    };
+    match derived {
+        Ok(derived) => {
+            println!("\n🧾 {}", multisig.descriptor(change).with_checksum());
+            print_derived(&derived);
+            prompt_qr_codes(&derived);
+        }
+        Err(err) => println!("\n❌ {}", err),
+    }
+}
+
+// Writes the Coldcard multisig setup file of a saved wallet, which Sparrow imports too
+fn export_multisig_config(data_dir: &Path, session: &Session) {
+    let Some((number, multisig)) = select_multisig(session) else {
+        return;
+    };
+    let default_path = data_dir.join(format!("multisig-{}-coldcard.txt", number));
+    let path = prompt_input(&format!("📂 Export to [{}]: ", default_path.display()));
+    let path = if path.is_empty() { default_path } else { path.into() };
+    match storage::write_atomic(&path, multisig.coordinator_config().as_bytes()) {
+        Ok(()) => {
+            audit(Operation::ExportWallet, &["coldcard-multisig", &multisig.name]);
+            println!("\n✅ Multisig wallet #{} exported to {}", number, path.display());
+        }
+        Err(err) => println!("\n❌ Failed to write {}: {}", path.display(), err),
+    }
+}
+
+// Runs either side of a BIP129 (BSMS) multisig setup
+fn bsms_setup() {
+    let session = match keystore_session() {
+        Ok(session) => session,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+
+    println!("\n🤝 BSMS multisig setup:");
+    println!("  1. Coordinator: start a session (tokens for the signers)");
+    println!("  2. Coordinator: combine key records into a descriptor record");
+    println!("  3. Signer: make a key record from a saved extended key");
+    println!("  4. Signer: check a descriptor record");
+    match prompt_input("Select an option: ").as_str() {
+        "1" => start_bsms_session(),
+        "2" => combine_key_records(&session),
+        "3" => sign_key_record(&session),
+        "4" => check_descriptor_record(&session),
+        _ => println!("\n❌ Invalid option."),
+    }
+}
+
+// Picks the session encryption and prints the tokens to hand to the signers
+fn start_bsms_session() {
+    for (i, encryption) in bsms::Encryption::ALL.iter().enumerate() {
+        println!("  {}. {}", i + 1, encryption.describe());
+    }
+    let encryption = prompt_input("🔒 Encryption: ").parse::<usize>().ok().and_then(|number| bsms::Encryption::ALL.get(number.wrapping_sub(1)).copied());
+    let signers = prompt_input(&format!("👥 Number of signers (2 to {}): ", multisig::MAX_COSIGNERS)).parse::<usize>().ok().filter(|signers| (2..=multisig::MAX_COSIGNERS).contains(signers));
+    let (Some(encryption), Some(signers)) = (encryption, signers) else {
+        println!("\n❌ Invalid encryption or number of signers.");
+        return;
+    };
+    let tokens = encryption.new_tokens(signers);
+    println!("\n✅ Session tokens ({}); keep them, they are needed to combine the key records:", encryption.name());
+    for (i, token) in tokens.iter().enumerate() {
+        match encryption {
+            bsms::Encryption::Extended => println!("  Signer {:>2}: {}", i + 1, token),
+            _ => println!("  All signers: {}", token),
+        }
+    }
+}
+
+// Reads a pasted BSMS record, ended by an empty line
+fn prompt_record(prompt: &str) -> String {
+    println!("{}", prompt);
+    let mut lines = Vec::new();
+    loop {
+        let line = prompt_line("");
+        if line.trim().is_empty() {
+            break;
+        }
+        lines.push(line.trim_end().to_string());
+    }
+    lines.join("\n")
+}
+
+// Prompts for a BIP48 script type and account
+fn prompt_bip48_account() -> Option<(descriptor::MultisigScript, u32)> {
+    println!("  1. P2WSH (native segwit, m/48'/0'/account'/2')");
+    println!("  2. P2SH-P2WSH (nested segwit, m/48'/0'/account'/1')");
+    let script = match prompt_input("📜 Script type: ").as_str() {
+        "1" => multisig::SCRIPTS[0],
+        "2" => multisig::SCRIPTS[1],
+        _ => return None,
+    };
+    let account = match prompt_input("🔢 BIP48 account number (default 0): ").as_str() {
+        "" => Some(0),
+        account => account.parse::<u32>().ok(),
+    };
+    account.map(|account| (script, account))
+}
+
+// Checks the signers' key records and prints the descriptor record for each token
+fn combine_key_records(session: &Session) {
+    let name = prompt_input("🏷️ Wallet name (up to 20 characters): ");
+    let Some((script, _)) = prompt_bip48_account() else {
+        println!("\n❌ Invalid script type or account.");
+        return;
+    };
+    let tokens: Vec<String> = prompt_record("🎟️ Session tokens, one per line (00 without encryption), then an empty line:").lines().map(str::to_string).collect();
+    let mut records = Vec::new();
+    loop {
+        let record = prompt_record(&format!("📋 Key record {} (empty to finish), then an empty line:", records.len() + 1));
+        if record.is_empty() {
+            break;
+        }
+        records.push(record);
+    }
+    let threshold = prompt_input(&format!("✍️ Signatures required (1 to {}): ", records.len())).parse::<usize>().unwrap_or(0);
+    let setup = match bsms::coordinate(&name, threshold, script, &tokens, &records) {
+        Ok(setup) => setup,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+
+    let multisig = &setup.multisig;
+    println!("\n✅ {} {} wallet \"{}\":", multisig.policy(), script.name(), multisig.name);
+    for (cosigner, description) in multisig.cosigners.iter().zip(&setup.descriptions) {
+        println!("  Signer       : {} {}", cosigner.fingerprint, description);
+    }
+    println!("  Receive      : {}", multisig.descriptor(false).with_checksum());
+    for (token, record) in &setup.records {
+        println!("\n📨 Descriptor record for token {}:", token);
+        println!("{}", record);
+    }
+    save_bsms_wallet(multisig, session);
+}
+
+// Signs the BIP48 account key of a saved extended key for a session
+fn sign_key_record(session: &Session) {
+    let Some(master) = select_signer_key(session) else {
+        return;
+    };
+    let Some((script, account)) = prompt_bip48_account() else {
+        println!("\n❌ Invalid script type or account.");
+        return;
+    };
+    let token = prompt_input("🎟️ Session token from the coordinator (00 without encryption): ");
+    let description = prompt_input("📝 Description (up to 80 characters): ");
+    let record = bsms::KeyRecord::sign(&master, script, account, &token, &description).and_then(|record| bsms::encrypt(&token, &record));
+    match record {
+        Ok(record) => {
+            audit(Operation::SignKeyRecord, &[&master.fingerprint().unwrap_or_default()]);
+            println!("\n📨 Key record for the coordinator:\n{}", record);
+        }
+        Err(err) => println!("\n❌ {}", err),
+    }
+}
+
+// Checks that a descriptor record includes a saved key and derives the address it states
+fn check_descriptor_record(session: &Session) {
+    let Some(master) = select_signer_key(session) else {
+        return;
+    };
+    let token = prompt_input("🎟️ Session token (00 without encryption): ");
+    let record = prompt_record("📋 Descriptor record, then an empty line:");
+    let name = prompt_input("🏷️ Wallet name (up to 20 characters): ");
+    let multisig = match bsms::check_descriptor_record(&record, &token, &master, &name) {
+        Ok(multisig) => multisig,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return;
+        }
+    };
+
+    println!("\n✅ Descriptor record checked: {} {} wallet including this key.", multisig.policy(), multisig.script.name());
+    println!("  Receive      : {}", multisig.descriptor(false).with_checksum());
+    match multisig.addresses(false, 0, 1) {
+        Ok(derived) => println!("  First address: {} (compare it with the coordinator and the other signers)", derived[0].address),
+        Err(err) => println!("\n❌ {}", err),
+    }
+    save_bsms_wallet(&multisig, session);
+}
+
+// Lists saved extended keys and returns the one the user picks
+fn select_signer_key(session: &Session) -> Option<ExtendedPrivKey> {
+    let mut keys = match bsms::signer_keys(session) {
+        Ok(keys) if keys.is_empty() => {
+            println!("\n📭 No extended keys saved yet.");
+            return None;
+        }
+        Ok(keys) => keys,
+        Err(err) => {
+            println!("\n❌ {}", err);
+            return None;
+        }
+    };
+    for (i, key) in keys.iter().enumerate() {
+        println!("  {}. {}", i + 1, key.fingerprint().unwrap_or_default());
+    }
+    let number = prompt_input("🔢 Extended key number: ");
+    match number.parse::<usize>().ok().filter(|&number| number >= 1 && number <= keys.len()) {
+        Some(number) => Some(keys.swap_remove(number - 1)),
+        None => {
+            println!("\n❌ No saved extended key #{}.", number);
+            None
+        }
+    }
+}
+
+// Offers to save a wallet set up over BSMS; its keys are already saved or held by other signers
+fn save_bsms_wallet(multisig: &Multisig, session: &Session) {
+    if !prompt_input("\n💾 Save this multisig wallet? (y/n): ").eq_ignore_ascii_case("y") {
+        println!("\n📝 Multisig wallet was not saved.");
+        return;
+    }
+    match multisig::save(multisig, &[], session) {
+        Ok(()) => {
+            let subjects: Vec<&str> = [multisig.name.as_str()].into_iter().chain(multisig.cosigners.iter().map(|cosigner| cosigner.fingerprint.as_str())).collect();
+            audit(Operation::CreateMultisig, &subjects);
+            println!("\n✅ Multisig wallet saved.");
+        }
+        Err(err) => println!("\n❌ Failed to save the multisig wallet: {}", err),
+    }
+}
+
+// Records a key operation started from the CLI, warning instead of failing the operation
+fn audit(operation: Operation, subjects: &[&str]) {
+    if let Err(err) = audit::record(audit::FrontEnd::Cli, operation, subjects) {
+        println!("\n⚠️ {}", err);
+    }
+}
+
+// Function to derive a child key from a parent private key and chain code
+fn derive_child_key() {
+    // Prompt the user to enter the parent private key (hex-encoded)
+    print!("\n🔑 Enter a parent private key (hex-encoded, 64 characters): ");
+    io::stdout().flush().unwrap();
+
+    let mut parent_key_hex = String::new();
+    io::stdin().read_line(&mut parent_key_hex).expect("Failed to read input");
+    let parent_key = match hex::decode(parent_key_hex.trim()) {
+        Ok(bytes) if bytes.len() == 32 => {
+            let mut array = [0u8; 32];
+            array.copy_from_slice(&bytes);
+            array
+        }
+        _ => {
+            println!("\n❌ Invalid private key. Must be a 64-character hex string.");
+            return;
+        }
+    };
+
+    // Prompt the user to enter the chain code (hex-encoded)
+    print!("\n🔗 Enter a chain code (hex-encoded, 64 characters): ");
+    io::stdout().flush().unwrap();
+
+    let mut chain_code_hex = String::new();
+    io::stdin().read_line(&mut chain_code_hex).expect("Failed to read input");
+    let chain_code = match hex::decode(chain_code_hex.trim()) {
+        Ok(bytes) if bytes.len() == 32 => {
+            let mut array = [0u8; 32];
+            array.copy_from_slice(&bytes);
+            array
+        }
+        _ => {
+            println!("\n❌ Invalid chain code. Must be a 64-character hex string.");
+            return;
+        }
+    };
+
+    // Prompt the user to enter the index for the child key
+    print!("\n🔢 Enter an index for the child key (e.g., 0, 1, ...): ");
+    io::stdout().flush().unwrap();
+
+    let mut index_input = String::new();
+    io::stdin().read_line(&mut index_input).expect("Failed to read input");
+    let index: u32 = match index_input.trim().parse() {
+        Ok(num) => num,
+        Err(_) => {
+            println!("\n❌ Invalid index. Please enter a valid number.");
+            return;
+        }
+    };
+
+    // Create the parent extended private key
+    let parent_ext_key = match ExtendedPrivKey::from_parts(parent_key, chain_code) {
+        Ok(key) => key,
+        Err(e) => {
+            println!("\n❌ Error: {}", e);
+            return;
+        }
+    };
+
+    // Derive the child key
+    match parent_ext_key.derive_child_key(index) {
+        Ok(child_key) => {
+            let fingerprint = child_key.fingerprint().unwrap_or_default();
+            audit(Operation::DeriveChildKey, &[&parent_ext_key.fingerprint().unwrap_or_default(), &fingerprint]);
+            println!("\n✅ Child Key derived:");
+            println!("  🔒 Private Key: {}", hex::encode(child_key.expose_private_key()));
+            println!("  🔗 Chain Code: {}", hex::encode(child_key.chain_code));
+
+            // Create the record for the child key
+            let child_key_record = ChildKeyRecord::new(&child_key, index);
+
+            // Prompt user to save the child key
+            print!("\n💾 Do you want to save this child key to a file? (y/n): ");
+            io::stdout().flush().unwrap();
+
+            let mut save_choice = String::new();
+            io::stdin().read_line(&mut save_choice).expect("Failed to read input");
+
+            if save_choice.trim().eq_ignore_ascii_case("y") {
+                if let Err(err) = save_to_file(&child_key_record) {
+                    println!("\n❌ Failed to save child key: {}", err);
+                } else {
+                    audit(Operation::SaveChildKey, &[&fingerprint]);
+                    println!("\n✅ Child key saved successfully!");
+                }
+            }
+        }
+        Err(e) => println!("\n❌ Error: {}", e), // Handle errors during key derivation
+    }
+}
+
+// Custom error type for QR code generation
+#[derive(Debug)]
+enum MyError {
+    QrError(qrcode::types::QrError),
+    Storage(&'static str),
+}
+
+// Implement Display for MyError to provide error messages
+impl fmt::Display for MyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MyError::QrError(e) => write!(f, "QR Error: {}", e),
+            MyError::Storage(e) => write!(f, "Storage Error: {}", e),
+        }
+    }
+}
+
+// Implement Error for MyError to integrate with Rust's error handling
+impl std::error::Error for MyError {}
+
+// Convert qrcode::types::QrError to MyError
+impl From<qrcode::types::QrError> for MyError {
+    fn from(err: qrcode::types::QrError) -> MyError {
+        MyError::QrError(err)
+    }
+}
+
+// Function to generate a QR code and save it with the storage backend, returning where it was stored
+fn generate_qr_code(data: &str) -> Result<String, MyError> {
+    let code = QrCode::new(data)?; // Generate the QR code
+
+    let image = code
+        .render::<qrcode::render::svg::Color>()
+        .min_dimensions(200, 200)
+        .build();
+
+    let location = backend::current().save_qr_code(data, &image).map_err(MyError::Storage)?; // Save the SVG QR code
+    audit(Operation::SaveQrCode, &[&audit::address_subject(data)]);
+
+    println!("✅ QR Code saved as {}", location);
+
+    Ok(location)
+}
+
+// Function to generate a QR code for a wallet address
+fn generate_qr_code_for_address() {
+    // Prompt the user to enter the wallet address
+    print!("\n🔑 Enter the wallet address to generate the QR code: ");
+    io::stdout().flush().unwrap();
+
+    let mut wallet_address = String::new();
+    io::stdin().read_line(&mut wallet_address).expect("Failed to read input");
+    let wallet_address = wallet_address.trim();
 
     if wallet_address.is_empty() {
         println!("\n❌ Invalid input. The wallet address cannot be empty.");
         return;
     }
 
-    // Generate the QR code and save it to a file
-    let file_name = format!("data/qr_codes/{}.svg", wallet_address);
-
-    if let Err(err) = generate_qr_code(wallet_address, &file_name) {
-        println!("\n❌ Failed to generate QR code: {}", err);
-    } else {
-        println!("\n✅ QR code generated successfully: {}", file_name);
+    // Generate the QR code and save it with the storage backend
+    match generate_qr_code(wallet_address) {
+        Ok(location) => println!("\n✅ QR code generated successfully: {}", location),
+        Err(err) => println!("\n❌ Failed to generate QR code: {}", err),
     }
 }
\ No newline at end of file
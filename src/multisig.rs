@@ -0,0 +1,322 @@
+use crate::backend;
+use crate::bip32::{convert_xpub, format_path, ExtendedPrivKey, ExtendedPubKey, HARDENED_OFFSET, XPUB_VERSION};
+use crate::bip39::{Mnemonic, Seed};
+use crate::descriptor::{Derived, Descriptor, DescriptorKey, Key, KeyOrigin, MultisigScript};
+use crate::keystore::Session;
+use crate::records::{self, CosignerRecord, ExtendedKeyRecord, MultisigRecord, Record, SCHEMA_VERSION};
+use chrono::Utc;
+use serde_json::Value;
+
+// Most cosigners of a wallet (Coldcard's limit, well inside the P2WSH one)
+pub const MAX_COSIGNERS: usize = 15;
+
+// Longest wallet name Coldcard accepts
+const MAX_NAME_LENGTH: usize = 20;
+
+// Multisig script types this wallet creates
+pub const SCRIPTS: [MultisigScript; 2] = [MultisigScript::Wsh, MultisigScript::ShWsh];
+
+// BIP48 account path of a multisig script, m/48'/0'/account'/script'
+pub fn bip48_path(script: MultisigScript, account: u32) -> Result<Vec<u32>, &'static str> {
+    let script_index = match script {
+        MultisigScript::ShWsh => 1,
+        MultisigScript::Wsh => 2,
+        MultisigScript::Sh => return Err("Only P2WSH and P2SH-P2WSH multisig wallets are supported"),
+    };
+    if account >= HARDENED_OFFSET {
+        return Err("Account number is out of range");
+    }
+    Ok(vec![48 + HARDENED_OFFSET, HARDENED_OFFSET, account + HARDENED_OFFSET, script_index + HARDENED_OFFSET])
+}
+
+// Script type of a name, e.g. p2wsh
+pub fn script_from_name(name: &str) -> Option<MultisigScript> {
+    SCRIPTS.into_iter().find(|script| script.name() == name)
+}
+
+// Account key of one cosigner
+#[derive(Clone)]
+pub struct Cosigner {
+    pub fingerprint: String, // Master key fingerprint, 8 lowercase hex characters
+    pub path: Vec<u32>,      // Account path below the master key
+    pub xpub: String,
+    pub local: bool, // Whether its master key was generated here
+}
+
+impl Cosigner {
+    // Account key of a master key held here
+    pub fn local(master: &ExtendedPrivKey, script: MultisigScript, account: u32) -> Result<Self, &'static str> {
+        let path = bip48_path(script, account)?;
+        Ok(Cosigner { fingerprint: master.fingerprint()?, xpub: master.xpub_at(&path, XPUB_VERSION)?, path, local: true })
+    }
+
+    // Parses a pasted key with its origin, e.g. [d34db33f/48h/0h/0h/2h]xpub... (SLIP-132 Ypub and Zpub keys are accepted)
+    pub fn parse(text: &str) -> Result<Self, &'static str> {
+        let text = text.trim();
+        let (origin, encoded) = text.split_once(']').ok_or("Cosigner keys need their key origin, e.g. [d34db33f/48h/0h/0h/2h]xpub...")?;
+        let encoded = if encoded.starts_with("xpub") { encoded.to_string() } else { convert_xpub(encoded, XPUB_VERSION)? };
+        let key = DescriptorKey::parse(&format!("{}]{}", origin, encoded))?;
+        match key {
+            DescriptorKey { origin: Some(KeyOrigin { fingerprint, path }), key: Key::Xpub(xpub, _), path: steps, wildcard: false } if steps.is_empty() => {
+                Ok(Cosigner { fingerprint, path, xpub, local: false })
+            }
+            _ => Err("Cosigner keys must be an account xpub with its key origin and no derivation steps"),
+        }
+    }
+
+    // Key expression of the cosigner, e.g. [d34db33f/48h/0h/0h/2h]xpub...
+    pub fn key(&self) -> String {
+        self.descriptor_key(None).to_string()
+    }
+
+    // Descriptor key of the receive (`Some(false)`) or change (`Some(true)`) branch, or of the account itself
+    fn descriptor_key(&self, change: Option<bool>) -> DescriptorKey {
+        DescriptorKey {
+            origin: Some(KeyOrigin { fingerprint: self.fingerprint.clone(), path: self.path.clone() }),
+            key: Key::Xpub(self.xpub.clone(), ExtendedPubKey::from_base58(&self.xpub).expect("cosigner xpubs are checked when created")),
+            path: change.map(|change| vec![change as u32]).unwrap_or_default(),
+            wildcard: change.is_some(),
+        }
+    }
+}
+
+// M-of-N sortedmulti wallet
+#[derive(Clone)]
+pub struct Multisig {
+    pub name: String,
+    pub threshold: usize,
+    pub script: MultisigScript,
+    pub cosigners: Vec<Cosigner>,
+}
+
+impl Multisig {
+    // Checks the name, policy and keys of a new wallet
+    pub fn new(name: &str, threshold: usize, script: MultisigScript, cosigners: Vec<Cosigner>) -> Result<Self, &'static str> {
+        let name = name.trim();
+        if name.is_empty() || name.len() > MAX_NAME_LENGTH || !name.chars().all(|ch| ch.is_ascii_graphic() || ch == ' ') {
+            return Err("Wallet name must be 1 to 20 printable ASCII characters");
+        }
+        if !SCRIPTS.contains(&script) {
+            return Err("Only P2WSH and P2SH-P2WSH multisig wallets are supported");
+        }
+        if cosigners.len() < 2 || cosigners.len() > MAX_COSIGNERS {
+            return Err("A multisig wallet needs 2 to 15 cosigners");
+        }
+        if threshold == 0 || threshold > cosigners.len() {
+            return Err("Multisig threshold must be between 1 and the number of keys");
+        }
+        if cosigners.iter().enumerate().any(|(i, cosigner)| cosigners[..i].iter().any(|other| other.xpub == cosigner.xpub)) {
+            return Err("The same xpub is used by two cosigners");
+        }
+        Ok(Multisig { name: name.to_string(), threshold, script, cosigners })
+    }
+
+    // Policy, e.g. 2 of 3
+    pub fn policy(&self) -> String {
+        format!("{} of {}", self.threshold, self.cosigners.len())
+    }
+
+    // Receive or change descriptor, e.g. wsh(sortedmulti(2,[fp/48h/0h/0h/2h]xpub.../0/*,...))
+    pub fn descriptor(&self, change: bool) -> Descriptor {
+        Descriptor::Multi {
+            script: self.script,
+            threshold: self.threshold,
+            keys: self.cosigners.iter().map(|cosigner| cosigner.descriptor_key(Some(change))).collect(),
+            sorted: true,
+        }
+    }
+
+    // Receiving or change addresses at `count` indexes from `start`
+    pub fn addresses(&self, change: bool, start: u32, count: u32) -> Result<Vec<Derived>, &'static str> {
+        self.descriptor(change).derive_range(start, count)
+    }
+
+    // Coldcard multisig setup file, which Sparrow and other coordinators import too
+    pub fn coordinator_config(&self) -> String {
+        let mut config = format!(
+            "# Coldcard Multisig setup file (exported by Bitcoin Wallet Generator)\n#\nName: {}\nPolicy: {}\n",
+            self.name,
+            self.policy()
+        );
+        // One derivation line covers every key when they share their path; otherwise each key gets its own
+        let shared_path = self.cosigners.iter().all(|cosigner| cosigner.path == self.cosigners[0].path);
+        if shared_path {
+            config.push_str(&format!("Derivation: {}\n", format_path(&self.cosigners[0].path)));
+        }
+        config.push_str(&format!("Format: {}\n\n", self.script.name().to_uppercase()));
+        for cosigner in &self.cosigners {
+            if !shared_path {
+                config.push_str(&format!("Derivation: {}\n", format_path(&cosigner.path)));
+            }
+            config.push_str(&format!("{}: {}\n", cosigner.fingerprint.to_uppercase(), cosigner.xpub));
+        }
+        config
+    }
+
+    // Record saved for the wallet
+    pub fn to_record(&self) -> Result<MultisigRecord, &'static str> {
+        Ok(MultisigRecord {
+            schema_version: SCHEMA_VERSION,
+            name: self.name.clone(),
+            threshold: self.threshold,
+            script_type: self.script.name().to_string(),
+            cosigners: self.cosigners.iter().map(|cosigner| CosignerRecord { key: cosigner.key(), local: cosigner.local }).collect(),
+            address: self.descriptor(false).derive(0)?.address,
+            generated_at: Utc::now().to_rfc3339(),
+        })
+    }
+
+    // Wallet of a saved record
+    pub fn from_record(record: &MultisigRecord) -> Result<Self, &'static str> {
+        let script = script_from_name(&record.script_type).ok_or("Unknown multisig script type")?;
+        let cosigners = record
+            .cosigners
+            .iter()
+            .map(|cosigner| Cosigner::parse(&cosigner.key).map(|key| Cosigner { local: cosigner.local, ..key }))
+            .collect::<Result<Vec<_>, _>>()?;
+        Multisig::new(&record.name, record.threshold, script, cosigners)
+    }
+}
+
+// New cosigner key held here: a 24-word mnemonic to back up, its master key and its account key
+pub fn generate_cosigner(script: MultisigScript, account: u32) -> Result<(Mnemonic, ExtendedPrivKey, Cosigner), &'static str> {
+    let mnemonic = Mnemonic::generate(256);
+    let master = ExtendedPrivKey::new(Seed::new(mnemonic.expose_phrase(), "").expose_bytes())?;
+    let cosigner = Cosigner::local(&master, script, account)?;
+    Ok((mnemonic, master, cosigner))
+}
+
+// Saves a new wallet together with the master keys of its local cosigners
+pub fn save(multisig: &Multisig, masters: &[&ExtendedPrivKey], session: &Session) -> Result<(), &'static str> {
+    let backend = backend::current();
+    backend.append_records(session, &masters.iter().map(|master| ExtendedKeyRecord::new(master)).collect::<Vec<_>>())?;
+    backend.append_records(session, &[multisig.to_record()?])
+}
+
+// Saved multisig wallets with their 1-based record numbers, in the order they were saved
+#[derive(Default)]
+pub struct Saved {
+    pub wallets: Vec<(usize, Multisig)>,
+    pub malformed: Vec<usize>, // Numbers of records that could not be read (the verify command lists their problems)
+}
+
+// Every saved multisig wallet; a malformed record is skipped and reported, so that it does not hide the others
+pub fn load(session: &Session) -> Result<Saved, &'static str> {
+    let mut saved = Saved::default();
+    for (i, value) in backend::current().load(MultisigRecord::KIND, session)?.into_iter().enumerate() {
+        match read(value) {
+            Ok(wallet) => saved.wallets.push((i + 1, wallet)),
+            Err(_) => saved.malformed.push(i + 1),
+        }
+    }
+    Ok(saved)
+}
+
+// Wallet of one stored record
+fn read(value: Value) -> Result<Multisig, &'static str> {
+    let record: MultisigRecord = serde_json::from_value(records::migrate(value)?).map_err(|_| "Record is malformed or has missing fields")?;
+    Multisig::from_record(&record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sortedmulti_wallet() {
+        let masters: Vec<ExtendedPrivKey> = (1u8..=3).map(|i| ExtendedPrivKey::new(&[i; 32]).unwrap()).collect();
+        let cosigners: Vec<Cosigner> = masters.iter().map(|master| Cosigner::local(master, MultisigScript::Wsh, 0).unwrap()).collect();
+        let wallet = Multisig::new("Treasury", 2, MultisigScript::Wsh, cosigners.clone()).unwrap();
+
+        // Key order does not change sortedmulti addresses
+        let reversed = Multisig::new("Treasury", 2, MultisigScript::Wsh, cosigners.iter().rev().cloned().collect()).unwrap();
+        let receive = wallet.addresses(false, 0, 3).unwrap();
+        assert_eq!(receive.len(), 3);
+        assert!(receive[0].address.starts_with("bc1q") && receive[0].address.len() == 62);
+        assert_eq!(receive[2].address, reversed.addresses(false, 2, 1).unwrap()[0].address);
+        assert_eq!(receive[1].paths[0], format!("{}/48h/0h/0h/2h/0/1", masters[0].fingerprint().unwrap()));
+
+        // Pasted keys must match what the local keys print, and records round-trip
+        let pasted = Cosigner::parse(&cosigners[1].key()).unwrap();
+        assert_eq!((pasted.fingerprint.as_str(), pasted.xpub.as_str(), pasted.local), (cosigners[1].fingerprint.as_str(), cosigners[1].xpub.as_str(), false));
+        let restored = Multisig::from_record(&wallet.to_record().unwrap()).unwrap();
+        assert_eq!(restored.descriptor(true).with_checksum(), wallet.descriptor(true).with_checksum());
+        assert!(restored.cosigners.iter().all(|cosigner| cosigner.local));
+
+        let config = wallet.coordinator_config();
+        assert!(config.contains("Policy: 2 of 3\nDerivation: m/48'/0'/0'/2'\nFormat: P2WSH\n"));
+        assert!(config.contains(&format!("{}: {}", cosigners[2].fingerprint.to_uppercase(), cosigners[2].xpub)));
+
+        // Nested segwit addresses and policy checks
+        let nested: Vec<Cosigner> = masters.iter().map(|master| Cosigner::local(master, MultisigScript::ShWsh, 0).unwrap()).collect();
+        assert!(Multisig::new("Nested", 2, MultisigScript::ShWsh, nested).unwrap().addresses(false, 0, 1).unwrap()[0].address.starts_with('3'));
+        assert!(Multisig::new("Treasury", 4, MultisigScript::Wsh, cosigners.clone()).is_err());
+        assert!(Multisig::new("Treasury", 1, MultisigScript::Wsh, vec![cosigners[0].clone(), cosigners[0].clone()]).is_err());
+        assert!(Cosigner::parse(&cosigners[0].xpub).is_err());
+
+        // A record with a broken cosigner key is refused on its own, so load can skip it and keep the others
+        let mut record = serde_json::to_value(wallet.to_record().unwrap()).unwrap();
+        assert!(read(record.clone()).is_ok());
+        record["cosigners"][1]["key"] = "[00000000/48h/0h/0h/2h]xpub-broken".into();
+        assert!(read(record).is_err());
+    }
+
+    #[test]
+    fn test_reference_vectors() {
+        // BIP383 vectors: P2WSH and P2SH-P2WSH multisig scripts, and ranged xpubs inside wsh()
+        let address = |descriptor: &str, index: u32| Descriptor::parse(descriptor).unwrap().derive(index).unwrap().address;
+        assert_eq!(
+            address("wsh(multi(2,03a0434d9e47f3c86235477c7b1ae6ae5d3442d49b1943c2b752a68e2a47e247c7,03774ae7f858a9411e5ef4246b70c65aac5649980be5c17891bbec17895da008cb,03d01115d548e7561b15c38f004d734633687cf4419620095bc5b0f47070afe85a))", 0),
+            "bc1qwu7hp9vckakyuw6htsy244qxtztrlyez4l7qlrpg68v6drgvj39qn4zazc"
+        );
+        assert_eq!(
+            address("sh(wsh(multi(1,03f28773c2d975288bc7d1d205c3748651b075fbc6610e58cddeeddf8f19405aa8,03499fdf9e895e719cfd64e67f07d38e3226aa7b63678949e6e49b241a60e823e4,02d7924d4f7d43ea965a465ae3095ff41131e5946f3c85f79e44adbcf8e27e080e)))", 0),
+            "3Hd7YQStg9gYpEt6hgK14ZHUABxSURzeuQ"
+        );
+        let ranged = "wsh(multi(1,xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB/1/0/*,xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH/0/0/*))";
+        assert_eq!(address(ranged, 0), "bc1qvjtfmrxu524qhdevl6yyyasjs7xmnzjlqlu60mrwepact60eyz9s9xjw0c");
+        assert_eq!(address(ranged, 2), "bc1qsflxzyj2f2evshspl9n5n745swcvs5k7p5t8qdww5unxpjwdvw5qx53ms4");
+
+        // BIP67 vector: sortedmulti sorts the keys of a 2-of-2 before building the script
+        assert_eq!(
+            address("sh(sortedmulti(2,02ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f8,02fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f))", 0),
+            "39bgKC7RFbpoCRbtD5KEdkYKtNyhpsNa3Z"
+        );
+
+        // Fixed BIP48 cosigners of three BIP39 test-vector mnemonics: their 2-of-3 addresses and setup file are
+        // pinned so that a change to key derivation, sorting or script building shows up here
+        let masters: Vec<ExtendedPrivKey> = [
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+            "letter advice cage absurd amount doctor acoustic avoid letter advice cage above",
+        ]
+        .iter()
+        .map(|phrase| ExtendedPrivKey::new(Seed::new(phrase, "").expose_bytes()).unwrap())
+        .collect();
+        let wallet = |script: MultisigScript| {
+            let cosigners = masters.iter().map(|master| Cosigner::local(master, script, 0).unwrap()).collect();
+            Multisig::new("Vault", 2, script, cosigners).unwrap()
+        };
+        let wsh = wallet(MultisigScript::Wsh);
+        let receive: Vec<String> = wsh.addresses(false, 0, 2).unwrap().into_iter().map(|derived| derived.address).collect();
+        assert_eq!(receive, ["bc1qm43n7nnev58aj3nrznz2xscgv98t7gxycq5pmp20a5vzfp5t0q2s7r6twa", "bc1qh0jxweder0zfwz363juas8vhav6p4d4hmk6yx7kphd3gvf769fzq2dp3an"]);
+        assert_eq!(wsh.addresses(true, 0, 1).unwrap()[0].address, "bc1qy0qa9lx04k0lk9lrv4542ndehul9spkcafdtcpdv38qx6ft5km2ssnce5d");
+        let nested = wallet(MultisigScript::ShWsh);
+        let receive: Vec<String> = nested.addresses(false, 0, 2).unwrap().into_iter().map(|derived| derived.address).collect();
+        assert_eq!(receive, ["354ZuUuwopSS5CsTjYzXwgQLxVeeN2eQFJ", "3E9vbWqsSPtgz1acMoVf1Rh3uoaHpdPxR7"]);
+
+        let config = "\
+# Coldcard Multisig setup file (exported by Bitcoin Wallet Generator)
+#
+Name: Vault
+Policy: 2 of 3
+Derivation: m/48'/0'/0'/2'
+Format: P2WSH
+
+73C5DA0A: xpub6DkFAXWQ2dHxq2vatrt9qyA3bXYU4ToWQwCHbf5XB2mSTexcHZCeKS1VZYcPoBd5X8yVcbXFHJR9R8UCVpt82VX1VhR28mCyxUFL4r6KFrf
+B8688DF1: xpub6FQya7zGhR92kacYsNnjreouvnHJMpXYsUXnW6NJJAJRCKsa26TzDy4LdnGhEurr3d6y1J8PJ7EEMKQp74XTqYvmGJNogYXSKDszYHtF8mX
+28645006: xpub6DnEBNkSJKBYQmsbhS1sP9cNdtU5c9PLFGCjTJmxicxc13WB8zNNGQazabQpyFAGW5bV9tMko4uBxDxjUKL6dSAcx1tEbgEHtgSqyRsekh6
+";
+        assert_eq!(wsh.coordinator_config(), config);
+    }
+}
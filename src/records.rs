@@ -0,0 +1,382 @@
+use crate::bip32::ExtendedPrivKey;
+use crate::descriptor::{Descriptor, KeyOrigin, ScriptType};
+use crate::electrum::SeedType;
+use crate::keystore;
+use crate::verify;
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+// Schema version written into every new record
+pub const SCHEMA_VERSION: u64 = 1;
+
+// Kinds of records, kept apart by every storage backend
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RecordKind {
+    Wallet,
+    ExtendedKey,
+    ChildKey,
+    Label,
+    Multisig,
+    Descriptor,
+}
+
+impl RecordKind {
+    // Every kind, in menu order
+    pub const ALL: [RecordKind; 6] = [RecordKind::Wallet, RecordKind::ExtendedKey, RecordKind::ChildKey, RecordKind::Label, RecordKind::Multisig, RecordKind::Descriptor];
+
+    // Name used for files, tables and messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            RecordKind::Wallet => "wallets",
+            RecordKind::ExtendedKey => "extended_keys",
+            RecordKind::ChildKey => "child_keys",
+            RecordKind::Label => "labels",
+            RecordKind::Multisig => "multisig_wallets",
+            RecordKind::Descriptor => "descriptors",
+        }
+    }
+}
+
+// A typed record of one kind
+pub trait Record: Serialize + DeserializeOwned {
+    const KIND: RecordKind;
+}
+
+// Whether a stored record predates the current schema
+pub fn is_outdated(value: &Value) -> bool {
+    value.get("schema_version").and_then(Value::as_u64) != Some(SCHEMA_VERSION)
+}
+
+// Saved wallet, restored or generated from a BIP39 mnemonic or an Electrum seed
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct WalletRecord {
+    pub schema_version: u64,
+    pub mnemonic: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed_type: Option<String>, // "electrum-standard", "electrum-segwit" or "imported-<address type>"; absent for BIP39 wallets
+    pub address: String,
+    pub public_key: String,
+    pub private_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derivation_path: Option<String>,
+    pub generated_at: String,
+}
+
+impl Record for WalletRecord {
+    const KIND: RecordKind = RecordKind::Wallet;
+}
+
+impl WalletRecord {
+    // Creates a wallet record stamped with the current time
+    pub fn new(mnemonic: &str, address: &str, public_key: &str, private_key: &str) -> Self {
+        WalletRecord {
+            schema_version: SCHEMA_VERSION,
+            mnemonic: mnemonic.to_string(),
+            seed_type: None,
+            address: address.to_string(),
+            public_key: public_key.to_string(),
+            private_key: private_key.to_string(),
+            derivation_path: None,
+            generated_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    // How the wallet's key was obtained, from its seed type
+    pub fn kind(&self) -> Result<WalletKind, &'static str> {
+        let Some(name) = self.seed_type.as_deref() else {
+            return Ok(WalletKind::Bip39);
+        };
+        if let Some(seed_type) = [SeedType::Standard, SeedType::Segwit].into_iter().find(|seed_type| name.strip_prefix("electrum-") == Some(seed_type.name())) {
+            return Ok(WalletKind::Electrum(seed_type));
+        }
+        name.strip_prefix("imported-").and_then(ScriptType::from_name).map(WalletKind::Imported).ok_or("Unknown seed type")
+    }
+}
+
+// Where the key of a saved wallet comes from
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WalletKind {
+    Bip39,                // Master key of a BIP39 mnemonic (no passphrase)
+    Electrum(SeedType),   // First receiving key of an Electrum seed
+    Imported(ScriptType), // Single key imported from another wallet, without a mnemonic
+}
+
+impl WalletKind {
+    // Script type of the saved address
+    pub fn script_type(&self) -> ScriptType {
+        match self {
+            WalletKind::Bip39 | WalletKind::Electrum(SeedType::Standard) => ScriptType::Pkh,
+            WalletKind::Electrum(SeedType::Segwit) => ScriptType::Wpkh,
+            WalletKind::Imported(script_type) => *script_type,
+        }
+    }
+
+    // Value of the record's seed type field
+    pub fn seed_type(&self) -> Option<String> {
+        match self {
+            WalletKind::Bip39 => None,
+            WalletKind::Electrum(seed_type) => Some(format!("electrum-{}", seed_type.name())),
+            WalletKind::Imported(script_type) => Some(format!("imported-{}", script_type.name())),
+        }
+    }
+}
+
+// Saved master extended private key
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ExtendedKeyRecord {
+    pub schema_version: u64,
+    pub private_key: String,
+    pub chain_code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>, // Key origin of an account key imported from another wallet, e.g. [73c5da0a/84h/0h/0h]; absent for master keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script_type: Option<String>, // Address type of an imported account key, e.g. p2wpkh; absent for master keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_fingerprint: Option<String>, // Parent key fingerprint of an imported account key (hex), so it serializes as imported; absent for master keys
+    pub generated_at: String,
+}
+
+impl Record for ExtendedKeyRecord {
+    const KIND: RecordKind = RecordKind::ExtendedKey;
+}
+
+impl ExtendedKeyRecord {
+    // Creates an extended key record stamped with the current time
+    pub fn new(key: &ExtendedPrivKey) -> Self {
+        ExtendedKeyRecord {
+            schema_version: SCHEMA_VERSION,
+            private_key: hex::encode(key.expose_private_key()),
+            chain_code: hex::encode(key.chain_code),
+            origin: None,
+            script_type: None,
+            parent_fingerprint: None,
+            generated_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    // Creates a record of an account key imported from another wallet, with the origin and address type it is used with
+    pub fn account(key: &ExtendedPrivKey, origin: &KeyOrigin, script_type: ScriptType) -> Self {
+        ExtendedKeyRecord {
+            origin: Some(origin.to_string()),
+            script_type: Some(script_type.name().to_string()),
+            parent_fingerprint: Some(hex::encode(key.parent_fingerprint)),
+            ..Self::new(key)
+        }
+    }
+
+    // Saved key; an imported account key gets back its depth and child number (from its key origin) and its
+    // parent fingerprint, so its xpub is the one of the wallet it came from
+    pub fn key(&self) -> Result<ExtendedPrivKey, &'static str> {
+        let mut key = verify::extended_key(&self.private_key, &self.chain_code)?;
+        if let Some((origin, _)) = self.account_origin()? {
+            let parent_fingerprint = self.parent_fingerprint.as_deref().and_then(|fingerprint| hex::decode(fingerprint).ok());
+            key.parent_fingerprint = parent_fingerprint.and_then(|bytes| bytes.try_into().ok()).ok_or("Account key has no valid parent fingerprint")?;
+            key.depth = u8::try_from(origin.path.len()).map_err(|_| "Key origin path is too long")?;
+            key.child_number = origin.path.last().copied().ok_or("Account key origin has no path")?;
+        }
+        Ok(key)
+    }
+
+    // Key origin and address type of an imported account key, or None for a master key
+    pub fn account_origin(&self) -> Result<Option<(KeyOrigin, ScriptType)>, &'static str> {
+        match (&self.origin, &self.script_type) {
+            (None, None) => Ok(None),
+            (Some(origin), Some(script_type)) => {
+                let script_type = ScriptType::from_name(script_type).ok_or("Account key has an unknown address type")?;
+                Ok(Some((KeyOrigin::parse(origin)?, script_type)))
+            }
+            _ => Err("Account key needs both a key origin and an address type"),
+        }
+    }
+}
+
+// Saved child key with its index
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChildKeyRecord {
+    pub schema_version: u64,
+    pub private_key: String,
+    pub chain_code: String,
+    pub index: u32,
+    pub derivation_path: String,
+    pub generated_at: String,
+}
+
+impl Record for ChildKeyRecord {
+    const KIND: RecordKind = RecordKind::ChildKey;
+}
+
+impl ChildKeyRecord {
+    // Creates a child key record stamped with the current time
+    pub fn new(key: &ExtendedPrivKey, index: u32) -> Self {
+        ChildKeyRecord {
+            schema_version: SCHEMA_VERSION,
+            private_key: hex::encode(key.expose_private_key()),
+            chain_code: hex::encode(key.chain_code),
+            index,
+            derivation_path: Self::path(index),
+            generated_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    // Derivation path written into child key records of an index
+    pub fn path(index: u32) -> String {
+        format!("m/44'/0'/0'/0/{}", index)
+    }
+}
+
+// Label and note attached to an address, public key, xpub or other BIP329 reference.
+// Labels are only ever appended: the latest record for a reference wins (see `labels::Labels`).
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct LabelRecord {
+    pub schema_version: u64,
+    #[serde(rename = "type")]
+    pub label_type: String, // BIP329 type: tx, addr, pubkey, input, output or xpub
+    #[serde(rename = "ref")]
+    pub reference: String,
+    pub label: String, // Empty to remove the label
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>, // Free text kept locally, not part of BIP329 exports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>, // BIP329 key origin, kept for round trips
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spendable: Option<bool>, // BIP329 output flag, kept for round trips
+    pub generated_at: String,
+}
+
+impl Record for LabelRecord {
+    const KIND: RecordKind = RecordKind::Label;
+}
+
+impl LabelRecord {
+    // Creates a label record stamped with the current time
+    pub fn new(label_type: &str, reference: &str, label: &str, note: Option<&str>) -> Self {
+        LabelRecord {
+            schema_version: SCHEMA_VERSION,
+            label_type: label_type.to_string(),
+            reference: reference.to_string(),
+            label: label.to_string(),
+            note: note.filter(|note| !note.is_empty()).map(str::to_string),
+            origin: None,
+            spendable: None,
+            generated_at: Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+// Saved multisig wallet: its policy and the account xpub of every cosigner, without private keys
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct MultisigRecord {
+    pub schema_version: u64,
+    pub name: String,
+    pub threshold: usize,
+    pub script_type: String, // "p2wsh" or "p2sh-p2wsh"
+    pub cosigners: Vec<CosignerRecord>,
+    pub address: String, // First receiving address
+    pub generated_at: String,
+}
+
+// Cosigner of a multisig wallet
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct CosignerRecord {
+    pub key: String, // Key origin and account xpub, e.g. [d34db33f/48h/0h/0h/2h]xpub...
+    #[serde(default)]
+    pub local: bool, // Whether its master key was generated here and saved as an extended key
+}
+
+impl Record for MultisigRecord {
+    const KIND: RecordKind = RecordKind::Multisig;
+}
+
+// Saved watch-only descriptor, imported from another wallet's xpubs or public keys
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct DescriptorRecord {
+    pub schema_version: u64,
+    pub label: String,
+    pub descriptor: String, // With its checksum
+    pub address: String,    // First receiving address
+    pub generated_at: String,
+}
+
+impl Record for DescriptorRecord {
+    const KIND: RecordKind = RecordKind::Descriptor;
+}
+
+impl DescriptorRecord {
+    // Creates a descriptor record stamped with the current time
+    pub fn new(label: &str, descriptor: &Descriptor) -> Result<Self, &'static str> {
+        Ok(DescriptorRecord {
+            schema_version: SCHEMA_VERSION,
+            label: label.to_string(),
+            descriptor: descriptor.with_checksum(),
+            address: descriptor.derive(0)?.address,
+            generated_at: Utc::now().to_rfc3339(),
+        })
+    }
+}
+
+// Upgrades a stored record to the current schema, one version at a time
+pub fn migrate(value: Value) -> Result<Value, &'static str> {
+    let Value::Object(mut fields) = value else {
+        return Err(keystore::CORRUPTED);
+    };
+    let version = match fields.get("schema_version") {
+        Some(version) => version.as_u64().ok_or(keystore::CORRUPTED)?,
+        None => 0,
+    };
+    if version > SCHEMA_VERSION {
+        return Err("Record was written by a newer version of this program");
+    }
+
+    // Version 0: untyped records with PascalCase keys such as "PrivateKey"
+    if version < 1 {
+        fields = fields.into_iter().map(|(key, value)| (snake_case(&key), value)).collect::<Map<_, _>>();
+    }
+
+    fields.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+    Ok(Value::Object(fields))
+}
+
+// Converts a PascalCase key to snake_case ("PrivateKey" -> "private_key")
+fn snake_case(key: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in key.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_migrate_legacy_records() {
+        let legacy = json!({
+            "Mnemonic": "abandon ability",
+            "SeedType": "electrum-segwit",
+            "Address": "bc1q3g5tmkmlvxryhh843v4dz026avatc0zzr6h3af",
+            "PublicKey": "02aa",
+            "PrivateKey": "L1aa",
+            "DerivationPath": "m/0'/0/0",
+            "GeneratedAt": "2024-01-01T00:00:00+00:00",
+        });
+        let wallet: WalletRecord = serde_json::from_value(migrate(legacy).unwrap()).unwrap();
+        assert_eq!(wallet.schema_version, SCHEMA_VERSION);
+        assert_eq!(wallet.seed_type.as_deref(), Some("electrum-segwit"));
+        assert_eq!(wallet.private_key, "L1aa");
+
+        let child: ChildKeyRecord = serde_json::from_value(
+            migrate(json!({"PrivateKey": "aa", "ChainCode": "bb", "Index": 7, "DerivationPath": "m/44'/0'/0'/0/7", "GeneratedAt": "now"})).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(child.index, 7);
+
+        assert!(migrate(json!({"schema_version": SCHEMA_VERSION + 1})).is_err());
+    }
+}
@@ -0,0 +1,547 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::bip32::{ExtendedPrivKey, ExtendedPubKey, HARDENED_OFFSET};
+use crate::bip39::{self, Seed};
+use crate::wallet::Wallet;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// Maximum number of unknown words the search accepts (2048^2 candidates)
+pub const MAX_UNKNOWN_WORDS: usize = 2;
+
+// Number of BIP44 receive addresses (m/44'/0'/0'/0/i) compared with a known address
+pub const ADDRESS_GAP: u32 = 5;
+
+// Maximum number of phrases kept when there is nothing to compare them with
+const MAX_RESULTS: usize = 1000;
+
+// Number of passphrase candidates processed between two checkpoints
+const PASSPHRASE_BATCH: u64 = 4096;
+
+// BIP purposes tried when matching an account-level xpub
+const ACCOUNT_PURPOSES: [u32; 4] = [44, 49, 84, 86];
+
+// What a recovered phrase is checked against
+pub enum RecoveryTarget {
+    Address(String),     // A legacy address from the master key or the first BIP44 receive addresses
+    Xpub(ExtendedPubKey), // A master (depth 0) or account-level (depth 3) xpub
+}
+
+impl RecoveryTarget {
+    // Parses user input into a target; an empty string means no target
+    pub fn parse(input: &str) -> Result<Option<Self>, &'static str> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(None);
+        }
+        if !input.starts_with("xpub") {
+            return Ok(Some(RecoveryTarget::Address(input.to_string())));
+        }
+
+        let xpub = ExtendedPubKey::from_base58(input)?;
+        if xpub.depth != 0 && xpub.depth != 3 {
+            return Err("Only master (depth 0) or account-level (depth 3) xpubs are supported");
+        }
+        if xpub.depth == 3 && xpub.child_number < HARDENED_OFFSET {
+            return Err("Account-level xpub must be derived with a hardened index");
+        }
+        Ok(Some(RecoveryTarget::Xpub(xpub)))
+    }
+
+    // Derives the wallet from a seed and checks whether it matches the target
+    pub fn matches_seed(&self, seed: &[u8]) -> bool {
+        let master = match ExtendedPrivKey::new(seed) {
+            Ok(master) => master,
+            Err(_) => return false,
+        };
+
+        match self {
+            RecoveryTarget::Address(address) => {
+                if Wallet::from_extended_key(&master).map(|wallet| wallet.get_address() == address).unwrap_or(false) {
+                    return true;
+                }
+                let receive = match master.derive_path(&[44 + HARDENED_OFFSET, HARDENED_OFFSET, HARDENED_OFFSET, 0]) {
+                    Ok(receive) => receive,
+                    Err(_) => return false,
+                };
+                (0..ADDRESS_GAP).any(|index| {
+                    receive
+                        .derive_child_key(index)
+                        .and_then(|key| Wallet::from_extended_key(&key))
+                        .map(|wallet| wallet.get_address() == address)
+                        .unwrap_or(false)
+                })
+            }
+            RecoveryTarget::Xpub(xpub) if xpub.depth == 0 => xpub.matches(&master),
+            RecoveryTarget::Xpub(xpub) => ACCOUNT_PURPOSES.iter().any(|purpose| {
+                master
+                    .derive_path(&[purpose + HARDENED_OFFSET, HARDENED_OFFSET, xpub.child_number])
+                    .map(|account| xpub.matches(&account))
+                    .unwrap_or(false)
+            }),
+        }
+    }
+}
+
+// Shared counters used to report the progress of a multi-threaded search
+pub struct Progress {
+    pub checked: AtomicU64, // Candidates processed so far
+    pub total: u64,         // Candidates in the whole search space
+    pub started: Instant,   // When the search started
+    resumed_from: u64,      // Candidates already processed before this run
+}
+
+impl Progress {
+    // Creates progress counters for a search space of the given size
+    pub fn new(total: u64) -> Self {
+        Self::resumed(total, 0)
+    }
+
+    // Creates progress counters for a search resumed after `checked` candidates
+    pub fn resumed(total: u64, checked: u64) -> Self {
+        Progress {
+            checked: AtomicU64::new(checked),
+            total,
+            started: Instant::now(),
+            resumed_from: checked,
+        }
+    }
+
+    // Returns the number of candidates processed per second during this run
+    pub fn rate(&self) -> f64 {
+        let elapsed = self.started.elapsed().as_secs_f64().max(0.001);
+        (self.checked.load(Ordering::Relaxed) - self.resumed_from) as f64 / elapsed
+    }
+
+    // Prints a single status line with percentage, throughput and ETA
+    pub fn print_status(&self) {
+        let checked = self.checked.load(Ordering::Relaxed);
+        let rate = self.rate();
+        let eta = if rate > 0.0 {
+            format!("{}s", (self.total.saturating_sub(checked) as f64 / rate).round() as u64)
+        } else {
+            "--".to_string()
+        };
+        print!(
+            "\r  ⏳ {}/{} candidates ({:.1}%) — {:.0}/s — ETA {}   ",
+            checked,
+            self.total,
+            checked as f64 * 100.0 / self.total.max(1) as f64,
+            rate,
+            eta
+        );
+        io::stdout().flush().unwrap();
+    }
+
+    // Prints the status every second until `done` is set
+    pub fn report_until(&self, done: &AtomicBool) {
+        while !done.load(Ordering::Relaxed) {
+            self.print_status();
+            thread::sleep(Duration::from_millis(1000));
+        }
+        self.print_status();
+        println!();
+    }
+}
+
+// Outcome of a mnemonic recovery search
+pub struct RecoveryResult {
+    pub matches: Vec<String>, // Phrases that pass the checksum (and match the target, if any)
+    pub valid_checksums: u64, // Number of candidates that passed the checksum
+}
+
+// Parses a phrase where unknown words are written '?'; words outside the wordlist are unknown too
+pub fn parse_template(wordlist: &[String], phrase: &str) -> Result<Vec<Option<usize>>, &'static str> {
+    let words = bip39::normalize_phrase(phrase);
+    if !bip39::VALID_WORD_COUNTS.contains(&words.len()) {
+        return Err("Mnemonic must contain 12, 15, 18, 21 or 24 words (use '?' for unknown words)");
+    }
+    Ok(words
+        .iter()
+        .map(|word| if word == "?" { None } else { bip39::word_index(wordlist, word) })
+        .collect())
+}
+
+// Enumerates every value of the unknown words and keeps the phrases that match
+pub fn recover_missing_words(
+    wordlist: &[String],
+    template: &[Option<usize>],
+    target: Option<&RecoveryTarget>,
+    passphrase: &str,
+    progress: &Progress,
+) -> RecoveryResult {
+    let unknown: Vec<usize> = (0..template.len()).filter(|&i| template[i].is_none()).collect();
+    let base: Vec<usize> = template.iter().map(|index| index.unwrap_or(0)).collect();
+
+    search(wordlist, template.len(), target, passphrase, progress, |candidate, indices| {
+        indices.copy_from_slice(&base);
+        let mut rest = candidate;
+        for &position in &unknown {
+            indices[position] = (rest % 2048) as usize;
+            rest /= 2048;
+        }
+        true
+    })
+}
+
+// Tries replacing each word of a complete phrase with every other word of the wordlist
+pub fn recover_wrong_word(
+    wordlist: &[String],
+    indices: &[usize],
+    target: Option<&RecoveryTarget>,
+    passphrase: &str,
+    progress: &Progress,
+) -> RecoveryResult {
+    search(wordlist, indices.len(), target, passphrase, progress, |candidate, candidate_indices| {
+        let position = (candidate / 2048) as usize;
+        let replacement = (candidate % 2048) as usize;
+        if replacement == indices[position] {
+            return false;
+        }
+        candidate_indices.copy_from_slice(indices);
+        candidate_indices[position] = replacement;
+        true
+    })
+}
+
+// Returns the size of the search space for a template with unknown words
+pub fn missing_words_space(template: &[Option<usize>]) -> u64 {
+    2048u64.pow(template.iter().filter(|index| index.is_none()).count() as u32)
+}
+
+// Returns the size of the search space for a single wrong word
+pub fn wrong_word_space(word_count: usize) -> u64 {
+    word_count as u64 * 2048
+}
+
+// Runs the candidate search on every CPU core
+fn search<F>(
+    wordlist: &[String],
+    word_count: usize,
+    target: Option<&RecoveryTarget>,
+    passphrase: &str,
+    progress: &Progress,
+    build_candidate: F,
+) -> RecoveryResult
+where
+    F: Fn(u64, &mut [usize]) -> bool + Sync,
+{
+    let threads = thread::available_parallelism().map(|count| count.get()).unwrap_or(1) as u64;
+
+    let found = AtomicBool::new(false);
+    let valid_checksums = AtomicU64::new(0);
+    let matches = Mutex::new(Vec::new());
+
+    thread::scope(|scope| {
+        for offset in 0..threads {
+            let build_candidate = &build_candidate;
+            let found = &found;
+            let valid_checksums = &valid_checksums;
+            let matches = &matches;
+            scope.spawn(move || {
+                let mut indices = vec![0; word_count];
+                let mut candidate = offset;
+                while candidate < progress.total {
+                    // Stop early once the target has been matched
+                    if target.is_some() && found.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    progress.checked.fetch_add(1, Ordering::Relaxed);
+
+                    if build_candidate(candidate, &mut indices) && bip39::checksum_is_valid(&indices) {
+                        valid_checksums.fetch_add(1, Ordering::Relaxed);
+                        let phrase = indices.iter().map(|&index| wordlist[index].as_str()).collect::<Vec<_>>().join(" ");
+
+                        let keep = match target {
+                            Some(target) => target.matches_seed(Seed::new(&phrase, passphrase).expose_bytes()),
+                            None => true,
+                        };
+                        if keep {
+                            let mut matches = matches.lock().unwrap();
+                            if matches.len() < MAX_RESULTS {
+                                matches.push(phrase);
+                            }
+                            found.store(true, Ordering::Relaxed);
+                        }
+                    }
+                    candidate += threads;
+                }
+            });
+        }
+    });
+
+    let mut matches = matches.into_inner().unwrap();
+    matches.sort();
+    RecoveryResult {
+        matches,
+        valid_checksums: valid_checksums.load(Ordering::Relaxed),
+    }
+}
+
+// Typo mutation rules applied to each base passphrase
+pub struct MutationRules {
+    pub case: bool,          // lower/UPPER/Capitalized and single-letter case flips
+    pub whitespace: bool,    // Leading/trailing spaces added or trimmed
+    pub typos: bool,         // Deleted, duplicated or swapped neighbouring characters
+    pub substitutions: bool, // Any printable ASCII character replaced or inserted
+}
+
+impl MutationRules {
+    // Parses a comma-separated list of rules (case, space, typo, subst)
+    pub fn parse(input: &str) -> Result<Self, &'static str> {
+        let mut rules = MutationRules { case: false, whitespace: false, typos: false, substitutions: false };
+        for rule in input.split(',').map(|rule| rule.trim().to_lowercase()).filter(|rule| !rule.is_empty()) {
+            match rule.as_str() {
+                "case" => rules.case = true,
+                "space" => rules.whitespace = true,
+                "typo" => rules.typos = true,
+                "subst" => rules.substitutions = true,
+                _ => return Err("Unknown mutation rule (expected case, space, typo or subst)"),
+            }
+        }
+        Ok(rules)
+    }
+
+    // Returns the rules in their canonical textual form
+    pub fn describe(&self) -> String {
+        let names = [(self.case, "case"), (self.whitespace, "space"), (self.typos, "typo"), (self.substitutions, "subst")];
+        names.iter().filter(|(enabled, _)| *enabled).map(|(_, name)| *name).collect::<Vec<_>>().join(",")
+    }
+}
+
+// Expands base passphrases with the mutation rules, in a stable order without duplicates
+pub fn expand_passphrases(bases: &[String], rules: &MutationRules) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut candidates = Vec::new();
+    let mut push = |candidate: String| {
+        if seen.insert(candidate.clone()) {
+            candidates.push(candidate);
+        }
+    };
+
+    for base in bases {
+        let chars: Vec<char> = base.chars().collect();
+
+        // Character-level edits of the base guess
+        let mut edited = vec![base.clone()];
+        if rules.typos {
+            for i in 0..chars.len() {
+                let mut deleted = chars.clone();
+                deleted.remove(i);
+                edited.push(deleted.into_iter().collect());
+
+                let mut duplicated = chars.clone();
+                duplicated.insert(i, chars[i]);
+                edited.push(duplicated.into_iter().collect());
+
+                if i + 1 < chars.len() {
+                    let mut swapped = chars.clone();
+                    swapped.swap(i, i + 1);
+                    edited.push(swapped.into_iter().collect());
+                }
+            }
+        }
+        if rules.substitutions {
+            for i in 0..=chars.len() {
+                for replacement in (0x20u8..0x7f).map(char::from) {
+                    if i < chars.len() {
+                        let mut substituted = chars.clone();
+                        substituted[i] = replacement;
+                        edited.push(substituted.into_iter().collect());
+                    }
+                    let mut inserted = chars.clone();
+                    inserted.insert(i, replacement);
+                    edited.push(inserted.into_iter().collect());
+                }
+            }
+        }
+
+        // Case changes on top of the edits; single-letter flips only on the base guess
+        let mut variants = Vec::new();
+        for candidate in edited {
+            if rules.case {
+                let lower = candidate.to_lowercase();
+                let upper = candidate.to_uppercase();
+                let mut capitalized = lower.clone();
+                if let Some(first) = lower.chars().next() {
+                    capitalized.replace_range(..first.len_utf8(), &first.to_uppercase().to_string());
+                }
+                variants.push(candidate);
+                variants.extend([lower, upper, capitalized]);
+            } else {
+                variants.push(candidate);
+            }
+        }
+        if rules.case {
+            for i in 0..chars.len() {
+                let mut flipped = chars.clone();
+                flipped[i] = if chars[i].is_uppercase() {
+                    chars[i].to_lowercase().next().unwrap_or(chars[i])
+                } else {
+                    chars[i].to_uppercase().next().unwrap_or(chars[i])
+                };
+                variants.push(flipped.into_iter().collect());
+            }
+        }
+
+        for variant in variants {
+            let trimmed = variant.trim().to_string();
+            push(variant.clone());
+            if rules.whitespace {
+                push(format!("{} ", variant));
+                push(format!(" {}", variant));
+                push(trimmed);
+            }
+        }
+    }
+
+    candidates
+}
+
+// Saved position of an interrupted passphrase search
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub job_id: String,  // Identifies the mnemonic, target and candidate list being searched
+    pub next_index: u64, // First candidate not yet checked
+}
+
+// Computes an identifier for a passphrase search without storing any secret in clear
+pub fn passphrase_job_id(mnemonic: &str, target: &str, candidates: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(Sha256::digest(mnemonic.as_bytes()));
+    hasher.update(target.trim().as_bytes());
+    for candidate in candidates {
+        hasher.update((candidate.len() as u64).to_be_bytes());
+        hasher.update(candidate.as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+// Runs passphrase candidates through PBKDF2 on every CPU core, from `progress.checked`
+// onwards, calling `on_checkpoint` with the next index after each completed batch
+pub fn recover_passphrase<F>(
+    mnemonic: &str,
+    candidates: &[String],
+    target: &RecoveryTarget,
+    progress: &Progress,
+    mut on_checkpoint: F,
+) -> Option<String>
+where
+    F: FnMut(u64),
+{
+    let threads = thread::available_parallelism().map(|count| count.get()).unwrap_or(1) as u64;
+    let total = candidates.len() as u64;
+    let found = Mutex::new(None);
+
+    let mut batch_start = progress.checked.load(Ordering::Relaxed);
+    while batch_start < total {
+        let batch_end = (batch_start + PASSPHRASE_BATCH).min(total);
+
+        thread::scope(|scope| {
+            for offset in 0..threads {
+                let found = &found;
+                scope.spawn(move || {
+                    let mut index = batch_start + offset;
+                    while index < batch_end {
+                        if found.lock().unwrap().is_some() {
+                            break;
+                        }
+                        let candidate = &candidates[index as usize];
+                        if target.matches_seed(Seed::new(mnemonic, candidate).expose_bytes()) {
+                            *found.lock().unwrap() = Some(candidate.clone());
+                        }
+                        progress.checked.fetch_add(1, Ordering::Relaxed);
+                        index += threads;
+                    }
+                });
+            }
+        });
+
+        if let Some(passphrase) = found.lock().unwrap().take() {
+            return Some(passphrase);
+        }
+        on_checkpoint(batch_end);
+        batch_start = batch_end;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bip39::Mnemonic;
+
+    const PHRASE: &str = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+
+    // Tests that a missing last word yields one candidate per checksum value
+    #[test]
+    fn test_missing_last_word_without_target() {
+        let wordlist = Mnemonic::wordlist();
+        let template = parse_template(&wordlist, &PHRASE.replace("yellow", "?")).unwrap();
+        let progress = Progress::new(missing_words_space(&template));
+
+        let result = recover_missing_words(&wordlist, &template, None, "", &progress);
+        assert_eq!(result.valid_checksums, 128);
+        assert!(result.matches.contains(&PHRASE.to_string()));
+    }
+
+    // Tests that a known address narrows the candidates down to the original phrase
+    #[test]
+    fn test_missing_word_with_address() {
+        let wordlist = Mnemonic::wordlist();
+        let wallet = Wallet::from_seed(Seed::new(PHRASE, "").expose_bytes()).unwrap();
+        let target = RecoveryTarget::parse(wallet.get_address()).unwrap().unwrap();
+        let template = parse_template(&wordlist, &PHRASE.replace("sausage", "?")).unwrap();
+        let progress = Progress::new(missing_words_space(&template));
+
+        let result = recover_missing_words(&wordlist, &template, Some(&target), "", &progress);
+        assert_eq!(result.matches, vec![PHRASE.to_string()]);
+    }
+
+    // Tests that a single wrong word is found among the checksum-valid replacements
+    #[test]
+    fn test_wrong_word_without_target() {
+        let wordlist = Mnemonic::wordlist();
+        let indices: Vec<usize> = parse_template(&wordlist, &PHRASE.replace("wave", "wage"))
+            .unwrap()
+            .into_iter()
+            .map(|index| index.unwrap())
+            .collect();
+        let progress = Progress::new(wrong_word_space(indices.len()));
+
+        let result = recover_wrong_word(&wordlist, &indices, None, "", &progress);
+        assert!(result.matches.contains(&PHRASE.to_string()));
+        assert_eq!(progress.checked.load(Ordering::Relaxed), progress.total);
+    }
+
+    // Tests that the mutation rules cover case, whitespace and typo variants
+    #[test]
+    fn test_expand_passphrases() {
+        let rules = MutationRules::parse("case,space,typo").unwrap();
+        let candidates = expand_passphrases(&["Secret".to_string()], &rules);
+
+        assert_eq!(candidates[0], "Secret");
+        for expected in ["Secret", "secret", "SECRET", " Secret", "secret ", "Secert", "Secet", "Seccret", "SECERT"] {
+            assert!(candidates.contains(&expected.to_string()), "missing {:?}", expected);
+        }
+        let unique: HashSet<&String> = candidates.iter().collect();
+        assert_eq!(unique.len(), candidates.len());
+    }
+
+    // Tests that a mistyped passphrase is found and checkpoints are reported
+    #[test]
+    fn test_recover_passphrase() {
+        let wallet = Wallet::from_seed(Seed::new(PHRASE, "hunter2 ").expose_bytes()).unwrap();
+        let target = RecoveryTarget::parse(wallet.get_address()).unwrap().unwrap();
+        let rules = MutationRules::parse("space,typo").unwrap();
+        let candidates = expand_passphrases(&["hunetr2".to_string()], &rules);
+        let progress = Progress::new(candidates.len() as u64);
+
+        let found = recover_passphrase(PHRASE, &candidates, &target, &progress, |_| {});
+        assert_eq!(found, Some("hunter2 ".to_string()));
+    }
+}
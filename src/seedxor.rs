@@ -0,0 +1,82 @@
+use crate::bip39::Mnemonic;
+use rand::Rng;
+
+// Largest number of parts offered when splitting (Coldcard allows 2 to 4)
+pub const MAX_PARTS: usize = 4;
+
+// Splits a mnemonic into `part_count` valid mnemonics of the same length whose entropies XOR to the original
+pub fn split_mnemonic(mnemonic: &Mnemonic, part_count: usize) -> Result<Vec<Mnemonic>, &'static str> {
+    if !(2..=MAX_PARTS).contains(&part_count) {
+        return Err("SeedXOR needs between 2 and 4 parts");
+    }
+
+    // Every part but the last is random; the last one cancels them out
+    let entropy = mnemonic.to_entropy();
+    let mut rng = rand::thread_rng();
+    let mut last = entropy.clone();
+    let mut parts = Vec::with_capacity(part_count);
+    for _ in 1..part_count {
+        let random: Vec<u8> = (0..entropy.len()).map(|_| rng.gen()).collect();
+        xor_into(&mut last, &random);
+        parts.push(Mnemonic::from_entropy(&random)?);
+    }
+    parts.push(Mnemonic::from_entropy(&last)?);
+    Ok(parts)
+}
+
+// Recombines SeedXOR parts into the original mnemonic
+pub fn combine_mnemonics(parts: &[Mnemonic]) -> Result<Mnemonic, &'static str> {
+    if parts.len() < 2 {
+        return Err("SeedXOR needs at least 2 parts");
+    }
+
+    let mut entropy = parts[0].to_entropy();
+    for part in &parts[1..] {
+        let part_entropy = part.to_entropy();
+        if part_entropy.len() != entropy.len() {
+            return Err("All SeedXOR parts must have the same number of words");
+        }
+        xor_into(&mut entropy, &part_entropy);
+    }
+    Mnemonic::from_entropy(&entropy)
+}
+
+// XORs `other` into `target` byte by byte
+fn xor_into(target: &mut [u8], other: &[u8]) {
+    for (byte, other_byte) in target.iter_mut().zip(other) {
+        *byte ^= other_byte;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coldcard_vector() {
+        // 24-word example from the Coldcard SeedXOR documentation
+        let parts: Vec<Mnemonic> = [
+            "romance wink lottery autumn shop bring dawn tongue range crater truth ability miss spice fitness easy legal release recall obey exchange recycle dragon room",
+            "lion misery divide hurry latin fluid camp advance illegal lab pyramid unaware eager fringe sick camera series noodle toy crowd jeans select depth lounge",
+            "vault nominee cradle silk own frown throw leg cactus recall talent worry gadget surface shy planet purpose coffee drip few seven term squeeze educate",
+        ]
+        .iter()
+        .map(|phrase| Mnemonic::from_phrase(phrase).unwrap())
+        .collect();
+
+        assert_eq!(
+            combine_mnemonics(&parts).unwrap().expose_phrase(),
+            "silent toe meat possible chair blossom wait occur this worth option bag nurse find fish scene bench asthma bike wage world quit primary indoor"
+        );
+    }
+
+    #[test]
+    fn test_split_and_combine() {
+        let mnemonic = Mnemonic::from_phrase("legal winner thank year wave sausage worth useful legal winner thank yellow").unwrap();
+        let parts = split_mnemonic(&mnemonic, 3).unwrap();
+        assert_eq!(parts.len(), 3);
+        assert!(parts.iter().all(|part| part.expose_phrase().split_whitespace().count() == 12));
+        assert_eq!(combine_mnemonics(&parts).unwrap().expose_phrase(), mnemonic.expose_phrase());
+        assert!(combine_mnemonics(&parts[..2]).unwrap().expose_phrase() != mnemonic.expose_phrase());
+    }
+}
@@ -0,0 +1,543 @@
+use hmac::{Hmac, Mac};
+use pbkdf2::pbkdf2;
+use rand::Rng;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+
+// SLIP-39 English wordlist, embedded so the binary runs from any directory
+const SLIP39_WORDLIST: &str = include_str!("slip39_english.txt");
+
+// Number of bits encoded by each SLIP-39 word
+const RADIX_BITS: usize = 10;
+
+// Words used by the share header (identifier, flags, group and member parameters)
+const HEADER_WORDS: usize = 4;
+
+// Words used by the RS1024 checksum
+const CHECKSUM_WORDS: usize = 3;
+
+// Shortest valid share (128-bit master secret)
+const MIN_MNEMONIC_WORDS: usize = 20;
+
+// Shortest master secret allowed, in bytes
+const MIN_SECRET_BYTES: usize = 16;
+
+// Maximum number of groups, or of members in a group
+const MAX_SHARE_COUNT: u8 = 16;
+
+// x-coordinates of the digest and secret points of the sharing polynomial
+const DIGEST_INDEX: u8 = 254;
+const SECRET_INDEX: u8 = 255;
+
+// Length of the digest protecting the shared secret
+const DIGEST_BYTES: usize = 4;
+
+// Base number of PBKDF2 iterations, spread over the four Feistel rounds
+const BASE_ITERATION_COUNT: u32 = 10000;
+const ROUND_COUNT: u8 = 4;
+
+// RS1024 generator polynomial coefficients
+const RS1024_GENERATOR: [u32; 10] = [
+    0xE0E040, 0x1C1C080, 0x3838100, 0x7070200, 0xE0E0009,
+    0x1C0C2412, 0x38086C24, 0x3090FC48, 0x21B1F890, 0x3F3F120,
+];
+
+// A single SLIP-39 share decoded from (or encoded to) a mnemonic
+#[derive(Clone, PartialEq)]
+pub struct Share {
+    pub identifier: u16,        // 15-bit random identifier common to all shares
+    pub extendable: bool,       // Whether the backup can later be extended with more shares
+    pub iteration_exponent: u8, // PBKDF2 iterations are 10000 << exponent
+    pub group_index: u8,        // Index of the group this share belongs to
+    pub group_threshold: u8,    // Number of groups needed to recover the secret
+    pub group_count: u8,        // Total number of groups
+    pub member_index: u8,       // Index of the share within its group
+    pub member_threshold: u8,   // Number of shares needed to recover the group secret
+    pub value: Vec<u8>,         // Share value
+}
+
+impl Share {
+    // Decodes a share from its mnemonic, checking the checksum and padding
+    pub fn from_mnemonic(wordlist: &[String], mnemonic: &str) -> Result<Self, &'static str> {
+        let words: Vec<String> = mnemonic.split_whitespace().map(|word| word.to_lowercase()).collect();
+        if words.len() < MIN_MNEMONIC_WORDS {
+            return Err("Share mnemonic must contain at least 20 words");
+        }
+
+        let mut indices = Vec::with_capacity(words.len());
+        for word in &words {
+            match wordlist.binary_search(word) {
+                Ok(index) => indices.push(index as u32),
+                Err(_) => return Err("Share contains a word that is not in the SLIP-39 wordlist"),
+            }
+        }
+
+        let extendable = (indices[1] >> 4) & 1 == 1;
+        if rs1024_polymod(customization_string(extendable), &indices) != 1 {
+            return Err("Invalid share checksum");
+        }
+
+        // Header: id (15 bits), ext (1), e (4), GI (4), Gt (4), g (4), I (4), t (4)
+        let header = indices[..HEADER_WORDS].iter().fold(0u64, |acc, &index| (acc << RADIX_BITS) | index as u64);
+        let identifier = (header >> 25) as u16;
+        let iteration_exponent = ((header >> 20) & 0xF) as u8;
+        let group_index = ((header >> 16) & 0xF) as u8;
+        let group_threshold = ((header >> 12) & 0xF) as u8 + 1;
+        let group_count = ((header >> 8) & 0xF) as u8 + 1;
+        let member_index = ((header >> 4) & 0xF) as u8;
+        let member_threshold = (header & 0xF) as u8 + 1;
+
+        if group_threshold > group_count {
+            return Err("Group threshold cannot be greater than the group count");
+        }
+
+        // Share value: left-padded with zero bits to a multiple of 10 bits
+        let value_words = &indices[HEADER_WORDS..indices.len() - CHECKSUM_WORDS];
+        let padding = (RADIX_BITS * value_words.len()) % 16;
+        if padding > 8 {
+            return Err("Invalid share length");
+        }
+        let bits: Vec<u8> = value_words
+            .iter()
+            .flat_map(|&index| (0..RADIX_BITS).rev().map(move |bit| ((index >> bit) & 1) as u8))
+            .collect();
+        if bits[..padding].iter().any(|&bit| bit != 0) {
+            return Err("Invalid share padding");
+        }
+        let value: Vec<u8> = bits[padding..]
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0u8, |acc, &bit| (acc << 1) | bit))
+            .collect();
+        if value.len() < MIN_SECRET_BYTES || !value.len().is_multiple_of(2) {
+            return Err("Invalid master secret length");
+        }
+
+        Ok(Share {
+            identifier,
+            extendable,
+            iteration_exponent,
+            group_index,
+            group_threshold,
+            group_count,
+            member_index,
+            member_threshold,
+            value,
+        })
+    }
+
+    // Encodes the share as a mnemonic with its RS1024 checksum
+    pub fn to_mnemonic(&self, wordlist: &[String]) -> String {
+        let header = ((self.identifier as u64) << 25)
+            | ((self.extendable as u64) << 24)
+            | ((self.iteration_exponent as u64) << 20)
+            | ((self.group_index as u64) << 16)
+            | (((self.group_threshold - 1) as u64) << 12)
+            | (((self.group_count - 1) as u64) << 8)
+            | ((self.member_index as u64) << 4)
+            | (self.member_threshold - 1) as u64;
+        let mut indices: Vec<u32> = (0..HEADER_WORDS)
+            .rev()
+            .map(|i| ((header >> (i * RADIX_BITS)) & 0x3FF) as u32)
+            .collect();
+
+        let value_words = (self.value.len() * 8).div_ceil(RADIX_BITS);
+        let padding = value_words * RADIX_BITS - self.value.len() * 8;
+        let mut bits = vec![0u8; padding];
+        for byte in &self.value {
+            bits.extend((0..8).rev().map(|bit| (byte >> bit) & 1));
+        }
+        indices.extend(bits.chunks(RADIX_BITS).map(|word| word.iter().fold(0u32, |acc, &bit| (acc << 1) | bit as u32)));
+
+        let mut padded = indices.clone();
+        padded.extend([0; CHECKSUM_WORDS]);
+        let checksum = rs1024_polymod(customization_string(self.extendable), &padded) ^ 1;
+        indices.extend((0..CHECKSUM_WORDS).rev().map(|i| (checksum >> (i * RADIX_BITS)) & 0x3FF));
+
+        indices.iter().map(|&index| wordlist[index as usize].as_str()).collect::<Vec<_>>().join(" ")
+    }
+}
+
+// Returns the SLIP-39 English wordlist
+pub fn wordlist() -> Vec<String> {
+    SLIP39_WORDLIST.lines().map(String::from).collect()
+}
+
+// Splits a master secret into groups of share mnemonics
+// `groups` lists the (member threshold, member count) of each group
+pub fn split_master_secret(
+    master_secret: &[u8],
+    passphrase: &str,
+    group_threshold: u8,
+    groups: &[(u8, u8)],
+    extendable: bool,
+    iteration_exponent: u8,
+) -> Result<Vec<Vec<String>>, &'static str> {
+    if master_secret.len() < MIN_SECRET_BYTES || !master_secret.len().is_multiple_of(2) {
+        return Err("Master secret must be at least 128 bits and a multiple of 16 bits");
+    }
+    if groups.is_empty() || groups.len() > MAX_SHARE_COUNT as usize {
+        return Err("Between 1 and 16 groups are required");
+    }
+    if group_threshold == 0 || group_threshold as usize > groups.len() {
+        return Err("Group threshold must be between 1 and the number of groups");
+    }
+    if iteration_exponent > 15 {
+        return Err("Iteration exponent must be between 0 and 15");
+    }
+    for &(threshold, count) in groups {
+        if threshold == 0 || threshold > count || count > MAX_SHARE_COUNT {
+            return Err("Each group needs 1 <= threshold <= count <= 16");
+        }
+        if threshold == 1 && count > 1 {
+            return Err("Creating multiple member shares with member threshold 1 is not allowed; use 1-of-1 instead");
+        }
+    }
+    check_passphrase(passphrase)?;
+
+    let wordlist = wordlist();
+    let identifier: u16 = rand::thread_rng().gen_range(0..0x8000);
+    let encrypted = feistel(master_secret, passphrase, iteration_exponent, identifier, extendable, false);
+
+    let group_secrets = split_secret(group_threshold, groups.len() as u8, &encrypted);
+    let mut mnemonics = Vec::with_capacity(groups.len());
+    for (&(member_threshold, member_count), (group_index, group_secret)) in groups.iter().zip(group_secrets) {
+        let member_shares = split_secret(member_threshold, member_count, &group_secret);
+        mnemonics.push(
+            member_shares
+                .into_iter()
+                .map(|(member_index, value)| {
+                    Share {
+                        identifier,
+                        extendable,
+                        iteration_exponent,
+                        group_index,
+                        group_threshold,
+                        group_count: groups.len() as u8,
+                        member_index,
+                        member_threshold,
+                        value,
+                    }
+                    .to_mnemonic(&wordlist)
+                })
+                .collect(),
+        );
+    }
+    Ok(mnemonics)
+}
+
+// Recovers the master secret from enough share mnemonics
+pub fn combine_mnemonics(mnemonics: &[String], passphrase: &str) -> Result<Vec<u8>, &'static str> {
+    if mnemonics.is_empty() {
+        return Err("No shares provided");
+    }
+    check_passphrase(passphrase)?;
+
+    let wordlist = wordlist();
+    let mut shares: Vec<Share> = Vec::with_capacity(mnemonics.len());
+    for mnemonic in mnemonics {
+        let share = Share::from_mnemonic(&wordlist, mnemonic)?;
+        if !shares.contains(&share) {
+            shares.push(share);
+        }
+    }
+
+    let first = &shares[0];
+    for share in &shares {
+        if share.identifier != first.identifier
+            || share.extendable != first.extendable
+            || share.iteration_exponent != first.iteration_exponent
+        {
+            return Err("All shares must belong to the same backup (identifier and iteration exponent differ)");
+        }
+        if share.group_threshold != first.group_threshold || share.group_count != first.group_count {
+            return Err("All shares must have the same group threshold and group count");
+        }
+        if share.value.len() != first.value.len() {
+            return Err("All shares must have the same length");
+        }
+    }
+
+    // Collect the member shares of each group
+    let mut groups: BTreeMap<u8, Vec<&Share>> = BTreeMap::new();
+    for share in &shares {
+        let group = groups.entry(share.group_index).or_default();
+        if group.iter().any(|other| other.member_threshold != share.member_threshold) {
+            return Err("All shares of a group must have the same member threshold");
+        }
+        if group.iter().any(|other| other.member_index == share.member_index) {
+            return Err("Duplicate member index in a group");
+        }
+        group.push(share);
+    }
+
+    // Recover the secret of every complete group
+    let mut group_secrets = Vec::new();
+    for (&group_index, members) in &groups {
+        let threshold = members[0].member_threshold;
+        if members.len() < threshold as usize {
+            continue;
+        }
+        let points: Vec<(u8, Vec<u8>)> = members
+            .iter()
+            .take(threshold as usize)
+            .map(|share| (share.member_index, share.value.clone()))
+            .collect();
+        group_secrets.push((group_index, recover_secret(threshold, &points)?));
+        if group_secrets.len() == first.group_threshold as usize {
+            break;
+        }
+    }
+    if group_secrets.len() < first.group_threshold as usize {
+        return Err("Insufficient number of complete groups to recover the master secret");
+    }
+
+    let encrypted = recover_secret(first.group_threshold, &group_secrets)?;
+    Ok(feistel(&encrypted, passphrase, first.iteration_exponent, first.identifier, first.extendable, true))
+}
+
+// Passphrases are limited to printable ASCII characters
+fn check_passphrase(passphrase: &str) -> Result<(), &'static str> {
+    if passphrase.bytes().all(|byte| (32..=126).contains(&byte)) {
+        Ok(())
+    } else {
+        Err("Passphrase must only contain printable ASCII characters")
+    }
+}
+
+// Customization string mixed into the RS1024 checksum
+fn customization_string(extendable: bool) -> &'static [u8] {
+    if extendable { b"shamir_extendable" } else { b"shamir" }
+}
+
+// Computes the RS1024 polynomial over the customization string and the words
+fn rs1024_polymod(customization: &[u8], values: &[u32]) -> u32 {
+    let mut checksum: u32 = 1;
+    for value in customization.iter().map(|&byte| byte as u32).chain(values.iter().copied()) {
+        let top = checksum >> 20;
+        checksum = ((checksum & 0xFFFFF) << 10) ^ value;
+        for (i, generator) in RS1024_GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+// Encrypts or decrypts the master secret with a four-round Feistel network
+fn feistel(data: &[u8], passphrase: &str, iteration_exponent: u8, identifier: u16, extendable: bool, decrypt: bool) -> Vec<u8> {
+    let half = data.len() / 2;
+    let mut left = data[..half].to_vec();
+    let mut right = data[half..].to_vec();
+
+    let mut salt_prefix = Vec::new();
+    if !extendable {
+        salt_prefix.extend_from_slice(b"shamir");
+        salt_prefix.extend_from_slice(&identifier.to_be_bytes());
+    }
+    let iterations = (BASE_ITERATION_COUNT << iteration_exponent) / ROUND_COUNT as u32;
+
+    for step in 0..ROUND_COUNT {
+        let round = if decrypt { ROUND_COUNT - 1 - step } else { step };
+
+        let mut password = vec![round];
+        password.extend_from_slice(passphrase.as_bytes());
+        let mut salt = salt_prefix.clone();
+        salt.extend_from_slice(&right);
+        let mut mask = vec![0u8; half];
+        pbkdf2::<Hmac<Sha256>>(&password, &salt, iterations, &mut mask);
+
+        let mixed: Vec<u8> = left.iter().zip(&mask).map(|(a, b)| a ^ b).collect();
+        left = right;
+        right = mixed;
+    }
+
+    let mut result = right;
+    result.extend_from_slice(&left);
+    result
+}
+
+// Builds the exp/log tables of GF(256) with the Rijndael polynomial
+fn gf256_tables() -> ([u8; 255], [u8; 256]) {
+    let mut exp = [0u8; 255];
+    let mut log = [0u8; 256];
+    let mut poly: u16 = 1;
+    for (i, entry) in exp.iter_mut().enumerate() {
+        *entry = poly as u8;
+        log[poly as usize] = i as u8;
+        poly = (poly << 1) ^ poly;
+        if poly & 0x100 != 0 {
+            poly ^= 0x11B;
+        }
+    }
+    (exp, log)
+}
+
+// Evaluates at `x` the polynomial passing through the given points, byte by byte
+fn interpolate(points: &[(u8, Vec<u8>)], x: u8) -> Result<Vec<u8>, &'static str> {
+    for (i, (xi, _)) in points.iter().enumerate() {
+        if points[..i].iter().any(|(other, _)| other == xi) {
+            return Err("Share indices must be unique");
+        }
+    }
+    if let Some((_, value)) = points.iter().find(|(xi, _)| *xi == x) {
+        return Ok(value.clone());
+    }
+
+    let (exp, log) = gf256_tables();
+    let log_product: u32 = points.iter().map(|(xi, _)| log[(xi ^ x) as usize] as u32).sum();
+
+    let mut result = vec![0u8; points[0].1.len()];
+    for (xi, value) in points {
+        let log_denominator: u32 = points.iter().map(|(xj, _)| log[(xi ^ xj) as usize] as u32).sum();
+        // Offset by a multiple of 255 so the subtraction cannot underflow
+        let log_basis = (log_product + 255 * points.len() as u32 * 2 - log[(xi ^ x) as usize] as u32 - log_denominator) % 255;
+        for (out, &byte) in result.iter_mut().zip(value) {
+            if byte != 0 {
+                *out ^= exp[((log[byte as usize] as u32 + log_basis) % 255) as usize];
+            }
+        }
+    }
+    Ok(result)
+}
+
+// Digest binding the random part of the digest share to the secret
+fn create_digest(random_part: &[u8], secret: &[u8]) -> Vec<u8> {
+    let mut hmac = Hmac::<Sha256>::new_from_slice(random_part).expect("HMAC accepts any key length");
+    hmac.update(secret);
+    hmac.finalize().into_bytes()[..DIGEST_BYTES].to_vec()
+}
+
+// Splits a secret into `count` shares, any `threshold` of which recover it
+fn split_secret(threshold: u8, count: u8, secret: &[u8]) -> Vec<(u8, Vec<u8>)> {
+    if threshold == 1 {
+        return (0..count).map(|index| (index, secret.to_vec())).collect();
+    }
+
+    let mut rng = rand::thread_rng();
+    let random_count = threshold - 2;
+    let mut shares: Vec<(u8, Vec<u8>)> = (0..random_count)
+        .map(|index| (index, (0..secret.len()).map(|_| rng.gen()).collect()))
+        .collect();
+
+    let random_part: Vec<u8> = (0..secret.len() - DIGEST_BYTES).map(|_| rng.gen()).collect();
+    let mut digest_share = create_digest(&random_part, secret);
+    digest_share.extend_from_slice(&random_part);
+
+    let mut base_points = shares.clone();
+    base_points.push((DIGEST_INDEX, digest_share));
+    base_points.push((SECRET_INDEX, secret.to_vec()));
+
+    for index in random_count..count {
+        shares.push((index, interpolate(&base_points, index).expect("Base points have unique indices")));
+    }
+    shares
+}
+
+// Recovers a secret from `threshold` shares, verifying its digest
+fn recover_secret(threshold: u8, shares: &[(u8, Vec<u8>)]) -> Result<Vec<u8>, &'static str> {
+    if threshold == 1 {
+        return Ok(shares[0].1.clone());
+    }
+
+    let secret = interpolate(shares, SECRET_INDEX)?;
+    let digest_share = interpolate(shares, DIGEST_INDEX)?;
+    if create_digest(&digest_share[DIGEST_BYTES..], &secret) != digest_share[..DIGEST_BYTES] {
+        return Err("Invalid digest of the shared secret");
+    }
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(mnemonics: &[&str]) -> Vec<String> {
+        mnemonics.iter().map(|mnemonic| mnemonic.to_string()).collect()
+    }
+
+    // SLIP-39 test vector 1: valid mnemonic without sharing (128 bits)
+    #[test]
+    fn test_vector_single_share() {
+        let mnemonics = strings(&["duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision keyboard"]);
+        let secret = combine_mnemonics(&mnemonics, "TREZOR").expect("Valid share");
+        assert_eq!(hex::encode(secret), "bb54aac4b89dc868ba37d9cc21b2cece");
+    }
+
+    // SLIP-39 test vectors 2 and 3: invalid checksum and invalid padding
+    #[test]
+    fn test_vector_invalid_checksum_and_padding() {
+        let checksum = strings(&["duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision kidney"]);
+        let padding = strings(&["duckling enlarge academic academic email result length solution fridge kidney coal piece deal husband erode duke ajar music cargo fitness"]);
+        assert!(combine_mnemonics(&checksum, "TREZOR").is_err());
+        assert!(combine_mnemonics(&padding, "TREZOR").is_err());
+    }
+
+    // SLIP-39 test vectors 4 and 5: basic 2-of-3 sharing with enough and too few shares
+    #[test]
+    fn test_vector_basic_sharing() {
+        let mnemonics = strings(&[
+            "shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist rescue view short owner flip making coding armed",
+            "shadow pistol academic acid actress prayer class unknown daughter sweater depict flip twice unkind craft early superior advocate guest smoking",
+        ]);
+        let secret = combine_mnemonics(&mnemonics, "TREZOR").expect("Valid shares");
+        assert_eq!(hex::encode(secret), "b43ceb7e57a0ea8766221624d01b0864");
+        assert!(combine_mnemonics(&mnemonics[..1], "TREZOR").is_err());
+    }
+
+    // SLIP-39 test vector 6: shares from different backups are rejected
+    #[test]
+    fn test_vector_different_identifiers() {
+        let mnemonics = strings(&[
+            "adequate smoking academic acid debut wine petition glen cluster slow rhyme slow simple epidemic rumor junk tracks treat olympic tolerate",
+            "adequate stay academic agency agency formal party ting frequent learn upstairs remember smear leaf damage anatomy ladle market hush corner",
+        ]);
+        assert!(combine_mnemonics(&mnemonics, "TREZOR").is_err());
+    }
+
+    // SLIP-39 test vector 13: shares giving an invalid digest are rejected
+    #[test]
+    fn test_vector_invalid_digest() {
+        let mnemonics = strings(&[
+            "guilt walnut academic acid deliver remove equip listen vampire tactics nylon rhythm failure husband fatigue alive blind enemy teaspoon rebound",
+            "guilt walnut academic agency brave hamster hobo declare herd taste alpha slim criminal mild arcade formal romp branch pink ambition",
+        ]);
+        assert!(combine_mnemonics(&mnemonics, "TREZOR").is_err());
+    }
+
+    // SLIP-39 test vector 19: group sharing with threshold groups and members (128 bits)
+    #[test]
+    fn test_vector_group_sharing() {
+        let mnemonics = strings(&[
+            "eraser senior beard romp adorn nuclear spill corner cradle style ancient family general leader ambition exchange unusual garlic promise voice",
+            "eraser senior acrobat romp bishop medical gesture pumps secret alive ultimate quarter priest subject class dictate spew material endless market",
+        ]);
+        let secret = combine_mnemonics(&mnemonics, "TREZOR").expect("Valid shares");
+        assert_eq!(hex::encode(secret), "7c3397a292a5941682d7a4ae2d898d11");
+    }
+
+    // SLIP-39 test vector 20: valid mnemonic without sharing (256 bits)
+    #[test]
+    fn test_vector_single_share_256() {
+        let mnemonics = strings(&["theory painting academic academic armed sweater year military elder discuss acne wildlife boring employer fused large satoshi bundle carbon diagnose anatomy hamster leaves tracks paces beyond phantom capital marvel lips brave detect luck"]);
+        let secret = combine_mnemonics(&mnemonics, "TREZOR").expect("Valid share");
+        assert_eq!(hex::encode(secret), "989baf9dcaad5b10ca33dfd8cc75e42477025dce88ae83e75a230086a0e00e92");
+    }
+
+    // Tests that a 2-of-3 groups backup round-trips, extendable or not
+    #[test]
+    fn test_split_and_combine() {
+        let secret = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        for extendable in [false, true] {
+            let groups = split_master_secret(&secret, "TREZOR", 2, &[(1, 1), (2, 3), (3, 5)], extendable, 0)
+                .expect("Failed to split secret");
+            assert_eq!(groups.iter().map(Vec::len).collect::<Vec<_>>(), vec![1, 3, 5]);
+
+            let mut selected = groups[0].clone();
+            selected.extend(groups[2][1..4].iter().cloned());
+            assert_eq!(combine_mnemonics(&selected, "TREZOR").unwrap(), secret);
+            assert_ne!(combine_mnemonics(&selected, "").unwrap(), secret);
+            assert!(combine_mnemonics(&groups[2][..3], "TREZOR").is_err());
+        }
+    }
+}
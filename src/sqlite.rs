@@ -0,0 +1,374 @@
+use crate::backend::{self, Backend, RecordPage, RecordQuery};
+use crate::keystore::{self, EncryptedRecord, KeystoreHeader, KeystoreKey, Session};
+use crate::records::{self, RecordKind, SCHEMA_VERSION};
+use crate::storage;
+use chrono::Utc;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Transaction, TransactionBehavior};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// How long to wait for another process (e.g. a second instance) holding the database lock
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Returned for any failure of the database itself
+const DATABASE_ERROR: &str = "Database error";
+
+// Tables and indexes. Records stay encrypted with the keystore key; only the public address,
+// the creation time and the schema version are kept in clear so they can be indexed.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS keystore (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        header TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS records (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        kind TEXT NOT NULL,
+        address TEXT,
+        generated_at TEXT NOT NULL,
+        schema_version INTEGER NOT NULL,
+        nonce TEXT NOT NULL,
+        ciphertext TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS records_address ON records (address);
+    CREATE INDEX IF NOT EXISTS records_kind_generated_at ON records (kind, generated_at);
+    CREATE TABLE IF NOT EXISTS qr_codes (
+        address TEXT PRIMARY KEY,
+        svg TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+";
+
+// Stores every record kind and the QR codes in one embedded SQLite database
+pub struct SqliteBackend {
+    connection: Mutex<Connection>,
+    path: PathBuf,
+}
+
+impl SqliteBackend {
+    // Opens (or creates) the database and its schema
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, &'static str> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            storage::create_private_dir(parent).map_err(|_| "Failed to create database directory")?;
+        }
+        // Create the file first so it is private; SQLite gives its journal the same permissions
+        storage::private_file_options()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|_| "Failed to open database")?;
+        let connection = Connection::open(&path).map_err(|_| "Failed to open database")?;
+        connection.busy_timeout(BUSY_TIMEOUT).map_err(|_| DATABASE_ERROR)?;
+        connection.execute_batch(SCHEMA).map_err(|_| "Failed to create database schema")?;
+        Ok(SqliteBackend {
+            connection: Mutex::new(connection),
+            path,
+        })
+    }
+}
+
+impl Backend for SqliteBackend {
+    fn describe(&self) -> String {
+        format!("SQLite database {}", self.path.display())
+    }
+
+    fn location(&self, kind: RecordKind) -> String {
+        format!("{} (table records, kind {})", self.path.display(), kind.name())
+    }
+
+    fn has_password(&self) -> bool {
+        let connection = self.connection.lock().unwrap();
+        matches!(read_header(&connection), Ok(Some(_)))
+    }
+
+    fn unlock(&self, password: &str) -> Result<Session, &'static str> {
+        let connection = self.connection.lock().unwrap();
+        Session::open(password, read_header(&connection)?.as_slice())
+    }
+
+    fn load(&self, kind: RecordKind, session: &Session) -> Result<Vec<Value>, &'static str> {
+        let connection = self.connection.lock().unwrap();
+        let Some(header) = read_header(&connection)? else {
+            return Ok(Vec::new());
+        };
+        let key = session.key(&header)?;
+        let rows = select_rows(&connection, "SELECT 0, id, nonce, ciphertext FROM records WHERE kind = ?1 ORDER BY id", params![kind.name()])?;
+        rows.into_iter().map(|(_, id, sealed)| keystore::unseal(key, &row_slot(kind.name(), id), &sealed)).collect()
+    }
+
+    fn count(&self, kind: RecordKind, _session: &Session) -> Result<usize, &'static str> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row("SELECT COUNT(*) FROM records WHERE kind = ?1", [kind.name()], |row| row.get(0))
+            .map_err(|_| DATABASE_ERROR)
+    }
+
+    // Uses the address index; only the matching rows are decrypted
+    fn find_by_address(&self, kind: RecordKind, session: &Session, address: &str) -> Result<Vec<Value>, &'static str> {
+        let connection = self.connection.lock().unwrap();
+        let Some(header) = read_header(&connection)? else {
+            return Ok(Vec::new());
+        };
+        let key = session.key(&header)?;
+        let sql = "SELECT 0, id, nonce, ciphertext FROM records WHERE kind = ?1 AND address = ?2 ORDER BY id";
+        let rows = select_rows(&connection, sql, params![kind.name(), address])?;
+        rows.into_iter().map(|(_, id, sealed)| keystore::unseal(key, &row_slot(kind.name(), id), &sealed)).collect()
+    }
+
+    // Numbers rows on the (kind, generated_at) index and filters them on their clear address and creation time;
+    // only the rows of the window are decrypted
+    fn query(&self, kind: RecordKind, session: &Session, query: &RecordQuery) -> Result<RecordPage<Value>, &'static str> {
+        let connection = self.connection.lock().unwrap();
+        let Some(header) = read_header(&connection)? else {
+            return Ok(RecordPage { records: Vec::new(), total: 0 });
+        };
+        let key = session.key(&header)?;
+        let mut values = vec![SqlValue::from(kind.name().to_string())];
+        let mut filter = String::from("1");
+        for term in &query.terms {
+            let pattern = escape_like(&term.text);
+            let addresses = serde_json::to_string(&term.addresses).map_err(|_| DATABASE_ERROR)?;
+            let n = values.len();
+            filter.push_str(&format!(
+                " AND (w.address LIKE ?{} ESCAPE '\\' OR w.generated_at LIKE ?{} ESCAPE '\\' OR w.address IN (SELECT value FROM json_each(?{})))",
+                n + 1,
+                n + 2,
+                n + 3
+            ));
+            values.extend([SqlValue::from(format!("%{}%", pattern)), SqlValue::from(format!("{}%", pattern)), SqlValue::from(addresses)]);
+        }
+        let numbered = "(SELECT id, address, generated_at, ROW_NUMBER() OVER (ORDER BY generated_at, id) AS number FROM records WHERE kind = ?1) w";
+        let total = connection
+            .query_row(&format!("SELECT COUNT(*) FROM {} WHERE {}", numbered, filter), params_from_iter(&values), |row| row.get(0))
+            .map_err(|_| DATABASE_ERROR)?;
+        let sql = format!(
+            "SELECT w.number, r.id, r.nonce, r.ciphertext FROM {} JOIN records r ON r.id = w.id WHERE {} ORDER BY w.number LIMIT ?{} OFFSET ?{}",
+            numbered,
+            filter,
+            values.len() + 1,
+            values.len() + 2
+        );
+        values.extend([SqlValue::from(query.limit.map_or(-1, |limit| limit as i64)), SqlValue::from(query.offset as i64)]);
+        let rows = select_rows(&connection, &sql, params_from_iter(&values))?;
+        let records = rows
+            .into_iter()
+            .map(|(number, id, sealed)| Ok((number, keystore::unseal(key, &row_slot(kind.name(), id), &sealed)?)))
+            .collect::<Result<_, &'static str>>()?;
+        Ok(RecordPage { records, total })
+    }
+
+    // Each batch is one IMMEDIATE transaction, so concurrent writers queue instead of failing mid-way
+    fn append(&self, kind: RecordKind, session: &Session, records: &[Value]) -> Result<(), &'static str> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(|_| DATABASE_ERROR)?;
+        let key = open_or_create_key(&transaction, session)?;
+        for record in records {
+            records::migrate(record.clone())?;
+            // The row is inserted first, so the ciphertext can be bound to its id
+            transaction
+                .execute(
+                    "INSERT INTO records (kind, address, generated_at, schema_version, nonce, ciphertext) VALUES (?1, ?2, ?3, ?4, '', '')",
+                    params![
+                        kind.name(),
+                        backend::address_of(record),
+                        record.get("generated_at").and_then(Value::as_str).unwrap_or_default(),
+                        record.get("schema_version").and_then(Value::as_u64).unwrap_or(0),
+                    ],
+                )
+                .map_err(|_| DATABASE_ERROR)?;
+            let id = transaction.last_insert_rowid();
+            let sealed = keystore::seal(key, &row_slot(kind.name(), id), record)?;
+            transaction
+                .execute("UPDATE records SET nonce = ?1, ciphertext = ?2 WHERE id = ?3", params![sealed.nonce, sealed.ciphertext, id])
+                .map_err(|_| DATABASE_ERROR)?;
+        }
+        transaction.commit().map_err(|_| DATABASE_ERROR)
+    }
+
+    fn upgrade(&self, kind: RecordKind, session: &Session) -> Result<usize, &'static str> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(|_| DATABASE_ERROR)?;
+        let Some(header) = read_header(&transaction)? else {
+            return Ok(0);
+        };
+        let key = session.key(&header)?;
+        let outdated = {
+            let mut statement = transaction
+                .prepare("SELECT id, nonce, ciphertext FROM records WHERE kind = ?1 AND schema_version < ?2")
+                .map_err(|_| DATABASE_ERROR)?;
+            let rows = statement
+                .query_map(params![kind.name(), SCHEMA_VERSION], |row| {
+                    Ok((row.get::<_, i64>(0)?, EncryptedRecord { nonce: row.get(1)?, ciphertext: row.get(2)? }))
+                })
+                .map_err(|_| DATABASE_ERROR)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(|_| DATABASE_ERROR)?
+        };
+        for (id, sealed) in &outdated {
+            let slot = row_slot(kind.name(), *id);
+            let record = records::migrate(keystore::unseal(key, &slot, sealed)?)?;
+            let resealed = keystore::seal(key, &slot, &record)?;
+            transaction
+                .execute(
+                    "UPDATE records SET address = ?1, generated_at = ?2, schema_version = ?3, nonce = ?4, ciphertext = ?5 WHERE id = ?6",
+                    params![
+                        backend::address_of(&record),
+                        record.get("generated_at").and_then(Value::as_str).unwrap_or_default(),
+                        SCHEMA_VERSION,
+                        resealed.nonce,
+                        resealed.ciphertext,
+                        id,
+                    ],
+                )
+                .map_err(|_| DATABASE_ERROR)?;
+        }
+        transaction.commit().map_err(|_| DATABASE_ERROR)?;
+        Ok(outdated.len())
+    }
+
+    fn save_qr_code(&self, address: &str, svg: &str) -> Result<String, &'static str> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "INSERT OR REPLACE INTO qr_codes (address, svg, created_at) VALUES (?1, ?2, ?3)",
+                params![address, svg, Utc::now().to_rfc3339()],
+            )
+            .map_err(|_| DATABASE_ERROR)?;
+        Ok(format!("{} (table qr_codes, address {})", self.path.display(), address))
+    }
+
+    fn load_qr_code(&self, address: &str) -> Result<Option<String>, &'static str> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row("SELECT svg FROM qr_codes WHERE address = ?1", [address], |row| row.get(0))
+            .optional()
+            .map_err(|_| DATABASE_ERROR)
+    }
+}
+
+// Reads the keystore header, if one was created
+fn read_header(connection: &Connection) -> Result<Option<KeystoreHeader>, &'static str> {
+    let header: Option<String> = connection
+        .query_row("SELECT header FROM keystore WHERE id = 1", [], |row| row.get(0))
+        .optional()
+        .map_err(|_| DATABASE_ERROR)?;
+    header.map(|header| serde_json::from_str(&header).map_err(|_| keystore::CORRUPTED)).transpose()
+}
+
+// Runs a query returning (number, id, nonce, ciphertext) rows
+fn select_rows(connection: &Connection, sql: &str, params: impl rusqlite::Params) -> Result<Vec<(usize, i64, EncryptedRecord)>, &'static str> {
+    let mut statement = connection.prepare(sql).map_err(|_| DATABASE_ERROR)?;
+    let rows = statement
+        .query_map(params, |row| Ok((row.get(0)?, row.get(1)?, EncryptedRecord { nonce: row.get(2)?, ciphertext: row.get(3)? })))
+        .map_err(|_| DATABASE_ERROR)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|_| DATABASE_ERROR)
+}
+
+// Escapes the wildcards of a LIKE pattern (used with ESCAPE '\')
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+// Slot of a record row, which its ciphertext is bound to
+fn row_slot(kind: &str, id: i64) -> String {
+    format!("{}/{}", kind, id)
+}
+
+// Key of the keystore header, creating the header with the session's key on the first save
+fn open_or_create_key<'a>(transaction: &Transaction, session: &'a Session) -> Result<&'a KeystoreKey, &'static str> {
+    if let Some(header) = read_header(transaction)? {
+        return session.key(&header);
+    }
+    let (header, key) = session.new_header()?;
+    let header = serde_json::to_string(&header).map_err(|_| "Failed to serialize keystore")?;
+    transaction
+        .execute("INSERT INTO keystore (id, header) VALUES (1, ?1)", [header])
+        .map_err(|_| DATABASE_ERROR)?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::records::WalletRecord;
+    use serde_json::json;
+    use std::fs;
+
+    // Opens a database in a fresh temporary directory
+    fn temp_database(name: &str) -> Box<dyn Backend> {
+        let dir = std::env::temp_dir().join(format!("hd_wallet_sqlite_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Box::new(SqliteBackend::open(dir.join("hd_wallet.sqlite3")).unwrap())
+    }
+
+    #[test]
+    fn test_round_trip_and_password() {
+        let backend = temp_database("round_trip");
+        assert!(!backend.has_password());
+        let session = backend.unlock("secret").unwrap();
+        backend.append_records(&session, &[WalletRecord::new("m", "a", "p", "k")]).unwrap();
+        backend.append_records(&session, &[WalletRecord::new("m2", "a2", "p2", "k2")]).unwrap();
+
+        let wallets: Vec<WalletRecord> = backend.load_records(&session).unwrap();
+        assert_eq!(wallets.iter().map(|wallet| wallet.address.as_str()).collect::<Vec<_>>(), ["a", "a2"]);
+        assert!(backend.load(RecordKind::ChildKey, &session).unwrap().is_empty());
+        assert!(backend.has_password());
+        assert_eq!(backend.unlock("wrong").err(), Some("Wrong keystore password"));
+        // A session opened before the keystore existed has its own salt
+        let other = temp_database("round_trip_other").unlock("secret").unwrap();
+        assert!(backend.append(RecordKind::Wallet, &other, &[]).is_err());
+        assert!(backend.save_qr_code("a", "<svg/>").is_ok());
+        assert_eq!(backend.load_qr_code("a"), Ok(Some("<svg/>".to_string())));
+
+        // Ciphertexts swapped between rows no longer authenticate
+        let path = std::env::temp_dir().join(format!("hd_wallet_sqlite_round_trip_{}", std::process::id())).join("hd_wallet.sqlite3");
+        Connection::open(path)
+            .unwrap()
+            .execute_batch("UPDATE records SET ciphertext = (SELECT ciphertext FROM records r WHERE r.id != records.id), nonce = (SELECT nonce FROM records r WHERE r.id != records.id)")
+            .unwrap();
+        assert_eq!(backend.load(RecordKind::Wallet, &session), Err("Keystore record failed authentication"));
+    }
+
+    #[test]
+    fn test_legacy_records_are_upgraded() {
+        let backend = temp_database("upgrade");
+        let session = backend.unlock("secret").unwrap();
+        let legacy = json!({"Mnemonic": "m", "Address": "a", "PublicKey": "p", "PrivateKey": "k", "GeneratedAt": "t"});
+        backend.append(RecordKind::Wallet, &session, &[legacy]).unwrap();
+
+        assert_eq!(backend.upgrade(RecordKind::Wallet, &session), Ok(1));
+        assert_eq!(backend.upgrade(RecordKind::Wallet, &session), Ok(0));
+        assert!(!records::is_outdated(&backend.load(RecordKind::Wallet, &session).unwrap()[0]));
+    }
+
+    // Separate connections to one file, like the CLI and another instance, must not lose records
+    #[test]
+    fn test_concurrent_connections_keep_every_record() {
+        let dir = std::env::temp_dir().join(format!("hd_wallet_sqlite_concurrent_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("hd_wallet.sqlite3");
+        let backend: Box<dyn Backend> = Box::new(SqliteBackend::open(&path).unwrap());
+        let session = backend.unlock("secret").unwrap();
+        backend.append_records::<WalletRecord>(&session, &[]).unwrap();
+
+        std::thread::scope(|scope| {
+            for i in 0..4 {
+                let (path, session) = (&path, &session);
+                scope.spawn(move || {
+                    let backend: Box<dyn Backend> = Box::new(SqliteBackend::open(path).unwrap());
+                    let address = format!("address-{}", i);
+                    backend.append_records(session, &[WalletRecord::new("m", &address, "p", "k")]).unwrap();
+                });
+            }
+        });
+
+        assert_eq!(backend.load(RecordKind::Wallet, &session).unwrap().len(), 4);
+    }
+}
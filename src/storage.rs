@@ -0,0 +1,184 @@
+use fs2::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Distinguishes temporary files written at the same time by one process
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Runs `f` while holding an exclusive advisory lock on a data file.
+// The lock lives in a `<file>.lock` sibling so it survives the atomic renames of the data file,
+// and it is honored by every thread and process (CLI, web server, or another instance).
+// Not reentrant: `f` must not lock the same file again.
+pub fn with_lock<T>(path: impl AsRef<Path>, f: impl FnOnce() -> T) -> io::Result<T> {
+    let lock = private_file_options().create(true).truncate(false).read(true).write(true).open(lock_path(path.as_ref()))?;
+    lock.lock_exclusive()?;
+    let result = f();
+    lock.unlock()?;
+    Ok(result)
+}
+
+// Replaces a file atomically: readers see either the old or the new contents, never a partial write
+pub fn write_atomic(path: impl AsRef<Path>, contents: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    let temp = temp_path(path);
+    let result = write_and_rename(&temp, path, contents);
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+// Creates a directory (and any missing parents) that only its owner can access (0700 on Unix)
+pub fn create_private_dir(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    if path.is_dir() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    builder.mode(0o700);
+    match builder.create(path) {
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists && path.is_dir() => Ok(()),
+        result => result,
+    }
+}
+
+// Open options for data files: new files are readable and writable by their owner only (0600 on Unix)
+pub fn private_file_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    #[cfg(unix)]
+    options.mode(0o600);
+    options
+}
+
+// Files and directories under `dir` that other users can read
+#[cfg(unix)]
+pub fn world_readable(dir: impl AsRef<Path>) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending = vec![dir.as_ref().to_path_buf()];
+    while let Some(path) = pending.pop() {
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if metadata.mode() & 0o004 != 0 {
+            found.push(path.clone());
+        }
+        if metadata.is_dir() {
+            if let Ok(entries) = fs::read_dir(&path) {
+                pending.extend(entries.flatten().map(|entry| entry.path()));
+            }
+        }
+    }
+    found.sort();
+    found
+}
+
+#[cfg(not(unix))]
+pub fn world_readable(_dir: impl AsRef<Path>) -> Vec<PathBuf> {
+    Vec::new()
+}
+
+// Writes and syncs the temporary file, renames it over the target and syncs the directory entry
+fn write_and_rename(temp: &Path, path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = private_file_options().write(true).create_new(true).open(temp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(temp, path)?;
+    sync_dir(&parent_dir(path))
+}
+
+// Lock file guarding a data file
+fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    path.with_file_name(name)
+}
+
+// Unique temporary file next to a data file, so the rename stays on the same filesystem
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{}.{}.{}.tmp", name, std::process::id(), counter))
+}
+
+// Directory containing a file ("." for bare file names)
+fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    // Many threads doing read-modify-write on one file must not lose any update
+    #[test]
+    fn test_concurrent_updates_are_not_lost() {
+        let dir = std::env::temp_dir().join(format!("hd_wallet_storage_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("counter.json");
+        write_atomic(&path, b"0").unwrap();
+
+        thread::scope(|scope| {
+            for _ in 0..16 {
+                scope.spawn(|| {
+                    for _ in 0..25 {
+                        with_lock(&path, || {
+                            let count: u32 = fs::read_to_string(&path).unwrap().parse().unwrap();
+                            write_atomic(&path, (count + 1).to_string().as_bytes()).unwrap();
+                        })
+                        .unwrap();
+                    }
+                });
+            }
+        });
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "400");
+        let leftovers = fs::read_dir(&dir).unwrap().filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().ends_with(".tmp")).count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_new_data_is_private() {
+        let dir = std::env::temp_dir().join(format!("hd_wallet_private_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let data_dir = dir.join("data");
+        create_private_dir(data_dir.join("wallets")).unwrap();
+        create_private_dir(data_dir.join("wallets")).unwrap();
+        let path = data_dir.join("wallets/wallets.json");
+        with_lock(&path, || write_atomic(&path, b"[]").unwrap()).unwrap();
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().mode() & 0o777;
+        assert_eq!(mode(&data_dir.join("wallets")), 0o700);
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(mode(&lock_path(&path)), 0o600);
+        assert!(world_readable(data_dir.join("wallets")).is_empty());
+
+        fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o644)).unwrap();
+        assert_eq!(world_readable(data_dir.join("wallets")), std::slice::from_ref(&path));
+        write_atomic(&path, b"[]").unwrap();
+        assert_eq!(mode(&path), 0o600);
+    }
+}
@@ -0,0 +1,263 @@
+use crate::backend;
+use crate::bip32::{format_path, ExtendedPrivKey};
+use crate::bip39::{Mnemonic, Seed};
+use crate::descriptor::Descriptor;
+use crate::electrum;
+use crate::keystore::Session;
+use crate::multisig::Multisig;
+use crate::records::{self, ChildKeyRecord, DescriptorRecord, ExtendedKeyRecord, MultisigRecord, Record, RecordKind, WalletKind, WalletRecord};
+use crate::wallet::Wallet;
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+// Stored record whose fields disagree with the keys they are derived from
+pub struct Finding {
+    pub kind: RecordKind,
+    pub number: usize,      // 1-based position among the records of its kind, as in the wallet browser
+    pub reference: String,  // Address, xpub or derivation path identifying the record (never a secret)
+    pub problems: Vec<&'static str>,
+}
+
+// Outcome of checking every stored wallet and key
+pub struct Report {
+    pub checked: usize,
+    pub findings: Vec<Finding>,
+}
+
+// A record that can be checked against the keys it is derived from
+trait Verify: Record {
+    // Public identifier of the record
+    fn reference(&self) -> String;
+
+    // Every field that disagrees with what it should be derived from
+    fn problems(&self) -> Vec<&'static str>;
+}
+
+impl Verify for WalletRecord {
+    fn reference(&self) -> String {
+        self.address.clone()
+    }
+
+    fn problems(&self) -> Vec<&'static str> {
+        let kind = match self.kind() {
+            Ok(kind) => kind,
+            Err(err) => return vec![err],
+        };
+        let mut problems = Vec::new();
+
+        // Public key and address recomputed from the stored private key
+        match decode_key(&self.private_key).and_then(|key| Wallet::from_private_key(&key).ok()) {
+            None => problems.push("Private key is not a valid secp256k1 key"),
+            Some(wallet) => {
+                if !wallet.get_public_key().eq_ignore_ascii_case(&self.public_key) {
+                    problems.push("Public key does not match the private key");
+                }
+                let address = hex::decode(wallet.get_public_key()).ok().and_then(|public_key| kind.script_type().address(&public_key).ok());
+                if address.as_deref() != Some(self.address.as_str()) {
+                    problems.push("Address does not match the private key");
+                }
+            }
+        }
+
+        // Private key re-derived from the mnemonic, the same way it was saved (imported keys have none)
+        if let WalletKind::Imported(_) = kind {
+            return problems;
+        }
+        match rederive(self) {
+            Err(err) => problems.push(err),
+            Ok((master, path)) => {
+                if matches!(kind, WalletKind::Electrum(_)) && self.derivation_path.as_deref() != Some(format_path(&path).as_str()) {
+                    problems.push("Derivation path is not the first Electrum receiving address");
+                }
+                if !derives_private_key(&master, &path, &self.private_key) {
+                    problems.push("Mnemonic does not derive the private key (or the wallet was restored with a passphrase)");
+                }
+            }
+        }
+        problems
+    }
+}
+
+impl Verify for ExtendedKeyRecord {
+    fn reference(&self) -> String {
+        self.key()
+            .ok()
+            .and_then(|key| key.xpub().ok())
+            .unwrap_or_else(|| format!("saved {}", self.generated_at))
+    }
+
+    fn problems(&self) -> Vec<&'static str> {
+        // Imported account keys also carry their key origin, address type and parent fingerprint
+        self.key().err().into_iter().collect()
+    }
+}
+
+impl Verify for ChildKeyRecord {
+    fn reference(&self) -> String {
+        self.derivation_path.clone()
+    }
+
+    fn problems(&self) -> Vec<&'static str> {
+        let mut problems: Vec<&'static str> = extended_key(&self.private_key, &self.chain_code).err().into_iter().collect();
+        if self.derivation_path != ChildKeyRecord::path(self.index) {
+            problems.push("Derivation path does not match the child index");
+        }
+        problems
+    }
+}
+
+impl Verify for MultisigRecord {
+    fn reference(&self) -> String {
+        self.address.clone()
+    }
+
+    fn problems(&self) -> Vec<&'static str> {
+        match Multisig::from_record(self).and_then(|multisig| multisig.addresses(false, 0, 1)) {
+            Err(err) => vec![err],
+            Ok(derived) if derived[0].address != self.address => vec!["Address does not match the cosigner keys"],
+            Ok(_) => Vec::new(),
+        }
+    }
+}
+
+impl Verify for DescriptorRecord {
+    fn reference(&self) -> String {
+        self.address.clone()
+    }
+
+    fn problems(&self) -> Vec<&'static str> {
+        match Descriptor::parse(&self.descriptor).and_then(|descriptor| descriptor.derive(0)) {
+            Err(err) => vec![err],
+            Ok(derived) if derived.address != self.address => vec!["Address does not match the descriptor"],
+            Ok(_) => Vec::new(),
+        }
+    }
+}
+
+// Re-derives and checks every stored wallet, extended key, child key, multisig wallet and watch-only descriptor
+pub fn run(session: &Session) -> Result<Report, &'static str> {
+    let mut report = Report { checked: 0, findings: Vec::new() };
+    check_all::<WalletRecord>(&mut report, backend::current().load(RecordKind::Wallet, session)?);
+    check_all::<ExtendedKeyRecord>(&mut report, backend::current().load(RecordKind::ExtendedKey, session)?);
+    check_all::<ChildKeyRecord>(&mut report, backend::current().load(RecordKind::ChildKey, session)?);
+    check_all::<MultisigRecord>(&mut report, backend::current().load(RecordKind::Multisig, session)?);
+    check_all::<DescriptorRecord>(&mut report, backend::current().load(RecordKind::Descriptor, session)?);
+    Ok(report)
+}
+
+// Checks stored records one by one, so that a malformed record does not hide the others
+fn check_all<T: Verify>(report: &mut Report, values: Vec<Value>) {
+    for (i, value) in values.into_iter().enumerate() {
+        report.checked += 1;
+        let (reference, problems) = match records::migrate(value).ok().and_then(|value| serde_json::from_value::<T>(value).ok()) {
+            Some(record) => (record.reference(), record.problems()),
+            None => (String::from("?"), vec!["Record is malformed or has missing fields"]),
+        };
+        if !problems.is_empty() {
+            report.findings.push(Finding { kind: T::KIND, number: i + 1, reference, problems });
+        }
+    }
+}
+
+// Master key and key path of a saved wallet, re-derived from its mnemonic the same way it was saved
+// (BIP39 wallets use the master key itself, Electrum wallets their first receiving address)
+pub fn rederive(wallet: &WalletRecord) -> Result<(ExtendedPrivKey, Vec<u32>), &'static str> {
+    match wallet.kind()? {
+        WalletKind::Bip39 => {
+            Mnemonic::from_phrase(&wallet.mnemonic).map_err(|_| "Mnemonic is not a valid BIP39 phrase")?;
+            let seed = Seed::new(&wallet.mnemonic, "");
+            Ok((ExtendedPrivKey::new(seed.expose_bytes())?, Vec::new()))
+        }
+        WalletKind::Electrum(seed_type) => {
+            if electrum::seed_type(&wallet.mnemonic) != Some(seed_type) {
+                return Err("Mnemonic is not an Electrum seed of the stored type");
+            }
+            let seed = electrum::to_seed(&wallet.mnemonic, "");
+            Ok((ExtendedPrivKey::new(seed.expose_bytes())?, electrum::derivation_path(seed_type, false, 0)))
+        }
+        WalletKind::Imported(_) => Err("Imported keys have no mnemonic to re-derive them from"),
+    }
+}
+
+// Whether a key path of a master key leads to a stored hex-encoded private key
+pub fn derives_private_key(master: &ExtendedPrivKey, path: &[u32], private_key: &str) -> bool {
+    master.derive_path(path).is_ok_and(|key| Zeroizing::new(hex::encode(key.expose_private_key())).eq_ignore_ascii_case(private_key))
+}
+
+// Decodes a hex-encoded 32-byte private key
+fn decode_key(encoded: &str) -> Option<Zeroizing<Vec<u8>>> {
+    Some(Zeroizing::new(hex::decode(encoded).ok()?)).filter(|key| key.len() == 32)
+}
+
+// Rebuilds an extended key from its stored hex-encoded private key and chain code
+pub fn extended_key(private_key: &str, chain_code: &str) -> Result<ExtendedPrivKey, &'static str> {
+    let private_key = decode_key(private_key).ok_or("Private key is not 32 hex-encoded bytes")?;
+    let chain_code = hex::decode(chain_code).ok().and_then(|bytes| <[u8; 32]>::try_from(bytes).ok()).ok_or("Chain code is not 32 hex-encoded bytes")?;
+    let private_key: [u8; 32] = private_key.as_slice().try_into().map_err(|_| "Private key is not 32 hex-encoded bytes")?;
+    ExtendedPrivKey::from_parts(private_key, chain_code).map_err(|_| "Private key is not a valid secp256k1 key")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::electrum::SeedType;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn wallet_record() -> WalletRecord {
+        let seed = Seed::new(MNEMONIC, "");
+        let wallet = Wallet::from_seed(seed.expose_bytes()).unwrap();
+        WalletRecord::new(MNEMONIC, wallet.get_address(), wallet.get_public_key(), wallet.expose_private_key())
+    }
+
+    #[test]
+    fn test_wallet_fields_are_rederived() {
+        let wallet = wallet_record();
+        assert!(wallet.problems().is_empty());
+
+        let edited = WalletRecord { address: String::from("1BoatSLRHtKNngkdXEeobR76b53LETtpyT"), ..wallet.clone() };
+        assert_eq!(edited.problems(), ["Address does not match the private key"]);
+
+        let other = WalletRecord { mnemonic: MNEMONIC.replace("about", "wrong"), ..wallet.clone() };
+        assert_eq!(other.problems(), ["Mnemonic is not a valid BIP39 phrase"]);
+
+        let corrupted = WalletRecord { private_key: wallet.private_key.replace(&wallet.private_key[..2], "00"), ..wallet.clone() };
+        assert!(corrupted.problems().contains(&"Public key does not match the private key"));
+        assert!(corrupted.problems().contains(&"Mnemonic does not derive the private key (or the wallet was restored with a passphrase)"));
+
+        // Electrum wallets are saved with their first receiving address, as in the restore flow
+        let phrase = electrum::generate(SeedType::Segwit);
+        let path = electrum::derivation_path(SeedType::Segwit, false, 0);
+        let master = ExtendedPrivKey::new(electrum::to_seed(&phrase, "").expose_bytes()).unwrap();
+        let key = Wallet::from_extended_key(&master.derive_path(&path).unwrap()).unwrap();
+        let electrum_wallet = WalletRecord {
+            seed_type: Some(String::from("electrum-segwit")),
+            derivation_path: Some(format_path(&path)),
+            ..WalletRecord::new(&phrase, &electrum::addresses(SeedType::Segwit, &master, false, 1).unwrap()[0], key.get_public_key(), key.expose_private_key())
+        };
+        assert!(electrum_wallet.problems().is_empty());
+        let mislabelled = WalletRecord { seed_type: Some(String::from("electrum-standard")), ..electrum_wallet };
+        assert_eq!(mislabelled.problems(), ["Address does not match the private key", "Mnemonic is not an Electrum seed of the stored type"]);
+    }
+
+    #[test]
+    fn test_malformed_and_child_records_are_reported() {
+        let child = ChildKeyRecord {
+            private_key: "ff".repeat(32),
+            derivation_path: ChildKeyRecord::path(3),
+            ..ChildKeyRecord::new(&ExtendedPrivKey::new(&[1u8; 32]).unwrap(), 2)
+        };
+        let values = vec![
+            serde_json::to_value(ChildKeyRecord::new(&ExtendedPrivKey::new(&[1u8; 32]).unwrap(), 0)).unwrap(),
+            serde_json::json!({ "index": 1 }),
+            serde_json::to_value(child).unwrap(),
+        ];
+
+        let mut report = Report { checked: 0, findings: Vec::new() };
+        check_all::<ChildKeyRecord>(&mut report, values);
+        assert_eq!(report.checked, 3);
+        assert_eq!(report.findings.iter().map(|finding| finding.number).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(report.findings[0].problems, ["Record is malformed or has missing fields"]);
+        assert_eq!(report.findings[1].problems, ["Private key is not a valid secp256k1 key", "Derivation path does not match the child index"]);
+    }
+}
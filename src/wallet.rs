@@ -2,10 +2,12 @@ use secp256k1::{Secp256k1, SecretKey, PublicKey};
 use bitcoin_hashes::{sha256d, Hash, hash160};
 use bitcoin::util::base58;
 use crate::bip32::ExtendedPrivKey;
+use std::fmt;
+use zeroize::Zeroizing;
 
 // Wallet structure to store private key, public key, and address
 pub struct Wallet {
-    private_key: String, // Hex-encoded private key
+    private_key: Zeroizing<String>, // Hex-encoded private key, wiped from memory on drop
     public_key: String,  // Hex-encoded public key
     address: String,     // Base58-encoded Bitcoin address
 }
@@ -13,13 +15,23 @@ pub struct Wallet {
 impl Wallet {
     // Creates a wallet from a seed
     pub fn from_seed(seed: &[u8]) -> Result<Self, &'static str> {
-        let secp = Secp256k1::new(); // Create a new secp256k1 context
-
         // Generate the master extended private key from the seed
         let master_key = ExtendedPrivKey::new(seed)?;
 
-        // Derive the secret key from the master private key
-        let secret_key = SecretKey::from_slice(&master_key.private_key).map_err(|_| "Invalid private key")?;
+        Self::from_extended_key(&master_key)
+    }
+
+    // Creates a wallet from an extended private key (e.g. a derived child key)
+    pub fn from_extended_key(key: &ExtendedPrivKey) -> Result<Self, &'static str> {
+        Self::from_private_key(key.expose_private_key())
+    }
+
+    // Creates a wallet from a raw 32-byte private key (e.g. one read back from a saved record)
+    pub fn from_private_key(private_key: &[u8]) -> Result<Self, &'static str> {
+        let secp = Secp256k1::new(); // Create a new secp256k1 context
+
+        // Derive the secret key from the private key
+        let secret_key = SecretKey::from_slice(private_key).map_err(|_| "Invalid private key")?;
 
         // Derive the public key from the secret key
         let public_key = PublicKey::from_secret_key(&secp, &secret_key);
@@ -28,8 +40,8 @@ impl Wallet {
         let address = Self::generate_address(&public_key);
 
         Ok(Wallet {
-            private_key: hex::encode(&master_key.private_key), // Encode private key as hex
-            public_key: hex::encode(&public_key.serialize()),  // Encode public key as hex
+            private_key: Zeroizing::new(hex::encode(private_key)), // Encode private key as hex
+            public_key: hex::encode(public_key.serialize()),  // Encode public key as hex
             address,                                           // Store the generated address
         })
     }
@@ -58,8 +70,8 @@ impl Wallet {
         &self.address
     }
 
-    // Returns the wallet's private key (hex-encoded)
-    pub fn get_private_key(&self) -> &str {
+    // Exposes the wallet's private key (hex-encoded); callers must not log or persist it by accident
+    pub fn expose_private_key(&self) -> &str {
         &self.private_key
     }
 
@@ -69,6 +81,17 @@ impl Wallet {
     }
 }
 
+// Shows the address but never the private key
+impl fmt::Debug for Wallet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Wallet")
+            .field("address", &self.address)
+            .field("public_key", &self.public_key)
+            .field("private_key", &"[REDACTED]")
+            .finish()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -88,8 +111,17 @@ mod tests {
         let wallet = Wallet::from_seed(&seed).expect("Wallet generation failed");
 
         // Ensure private key, public key, and address are generated
-        assert!(!wallet.get_private_key().is_empty());
+        assert!(!wallet.expose_private_key().is_empty());
         assert!(!wallet.get_public_key().is_empty());
         assert!(!wallet.get_address().is_empty());
     }
+
+    // Tests that debug output never contains the private key
+    #[test]
+    fn test_debug_is_redacted() {
+        let wallet = Wallet::from_seed(&[0u8; 64]).expect("Wallet generation failed");
+        let debug = format!("{:?}", wallet);
+        assert!(debug.contains(wallet.get_address()));
+        assert!(!debug.contains(wallet.expose_private_key()));
+    }
 }
\ No newline at end of file
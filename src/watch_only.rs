@@ -0,0 +1,297 @@
+use crate::backend;
+use crate::descriptor::ScriptType;
+use crate::electrum;
+use crate::keystore::Session;
+use crate::labels::{self, Labels};
+use crate::records::{ChildKeyRecord, ExtendedKeyRecord, WalletKind, WalletRecord};
+use crate::verify;
+use chrono::Utc;
+use public::Public;
+use serde::Serialize;
+
+// Public values that may appear in a watch-only export.
+// The field is private to this module and every constructor takes public material only
+// (public keys, xpubs, paths, labels), so no mnemonic or private key can reach an export.
+mod public {
+    use crate::bip32::{format_path, ExtendedPrivKey};
+    use crate::descriptor::ScriptType;
+    use crate::records::LabelRecord;
+    use serde::Serialize;
+
+    #[derive(Serialize, Clone, PartialEq, Debug)]
+    #[serde(transparent)]
+    pub struct Public(String);
+
+    impl Public {
+        // Hex-encoded compressed public key
+        pub fn public_key(public_key: &[u8; 33]) -> Self {
+            Public(hex::encode(public_key))
+        }
+
+        // Address of a public key for a script type
+        pub fn address(script_type: ScriptType, public_key: &[u8; 33]) -> Result<Self, &'static str> {
+            script_type.address(public_key).map(Public)
+        }
+
+        // Xpub of a key, at its own depth (only its public half is serialized)
+        pub fn xpub(key: &ExtendedPrivKey) -> Result<Self, &'static str> {
+            key.xpub().map(Public)
+        }
+
+        // Derivation path, e.g. m/0'/0/0
+        pub fn path(path: &[u32]) -> Self {
+            Public(format_path(path))
+        }
+
+        // Derivation path as written into a saved record, e.g. m/44'/0'/0'/0/5
+        pub fn recorded_path(path: &str) -> Self {
+            Public(path.to_string())
+        }
+
+        // Single-key descriptor, e.g. wpkh([d34db33f/0'/0/0]02...), with the key origin when the path is known
+        pub fn descriptor(script_type: ScriptType, origin: Option<(&str, &[u32])>, key: &Public) -> Self {
+            let origin = origin
+                .map(|(fingerprint, path)| format!("[{}{}]", fingerprint, format_path(path).trim_start_matches('m')))
+                .unwrap_or_default();
+            Public(script_type.descriptor(&format!("{}{}", origin, key.0)))
+        }
+
+        // User-chosen label text (BIP329 labels are public by design; local notes are left out)
+        pub fn label(label: &LabelRecord) -> Self {
+            Public(label.label.clone())
+        }
+
+        // The value as text
+        pub fn as_str(&self) -> &str {
+            &self.0
+        }
+    }
+}
+
+// Format version of the JSON export
+const FORMAT_VERSION: u64 = 1;
+
+// Columns of the CSV export, in order
+const CSV_HEADER: &str = "kind,address,public_key,xpub,descriptor,path,label";
+
+// One watch-only key: everything needed to watch its funds, nothing that can spend them
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct WatchOnlyEntry {
+    pub kind: &'static str, // "wallet", "extended_key" or "child_key"
+    pub address: Public,
+    pub public_key: Public,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xpub: Option<Public>, // Master xpub of the wallet when it can be re-derived, or the xpub of an imported account key
+    pub descriptor: Public,
+    pub path: Public, // Path of the address key below the master key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<Public>,
+}
+
+// Whole JSON export
+#[derive(Serialize)]
+struct WatchOnlyExport<'a> {
+    format: &'static str,
+    version: u64,
+    exported_at: String,
+    entries: &'a [WatchOnlyEntry],
+}
+
+// Builds watch-only entries for every saved wallet, extended key and child key, returning them with
+// the number of records skipped because their keys are invalid (see the verify command)
+pub fn collect(session: &Session) -> Result<(Vec<WatchOnlyEntry>, usize), &'static str> {
+    let labels = labels::load(session)?;
+    let wallets = backend::current().load_records::<WalletRecord>(session)?;
+    let extended_keys = backend::current().load_records::<ExtendedKeyRecord>(session)?;
+    let child_keys = backend::current().load_records::<ChildKeyRecord>(session)?;
+    let total = wallets.len() + extended_keys.len() + child_keys.len();
+
+    let entries: Vec<WatchOnlyEntry> = wallets
+        .iter()
+        .map(|wallet| wallet_entry(wallet, &labels))
+        .chain(extended_keys.iter().map(|key| extended_key_entry(key, &labels)))
+        .chain(child_keys.iter().map(child_key_entry))
+        .filter_map(Result::ok)
+        .collect();
+    let skipped = total - entries.len();
+    Ok((entries, skipped))
+}
+
+// Writes entries as a JSON document
+pub fn to_json(entries: &[WatchOnlyEntry]) -> String {
+    let export = WatchOnlyExport { format: "hd_wallet-watch-only", version: FORMAT_VERSION, exported_at: Utc::now().to_rfc3339(), entries };
+    serde_json::to_string_pretty(&export).expect("watch-only exports always serialize") + "\n"
+}
+
+// Writes entries as CSV, one key per row
+pub fn to_csv(entries: &[WatchOnlyEntry]) -> String {
+    let mut csv = format!("{}\n", CSV_HEADER);
+    for entry in entries {
+        let optional = |value: &Option<Public>| value.as_ref().map(Public::as_str).unwrap_or_default().to_string();
+        let row = [
+            entry.kind.to_string(),
+            entry.address.as_str().to_string(),
+            entry.public_key.as_str().to_string(),
+            optional(&entry.xpub),
+            entry.descriptor.as_str().to_string(),
+            entry.path.as_str().to_string(),
+            optional(&entry.label),
+        ];
+        csv.push_str(&row.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+// Entry of a saved wallet; the master xpub and key origin are only added when the mnemonic reproduces the key
+fn wallet_entry(wallet: &WalletRecord, labels: &Labels) -> Result<WatchOnlyEntry, &'static str> {
+    let kind = wallet.kind()?;
+    let public_key = public_key(&wallet.private_key)?;
+    let master = verify::rederive(wallet).ok().filter(|(master, path)| verify::derives_private_key(master, path, &wallet.private_key));
+
+    let script_type = kind.script_type();
+    let address = Public::address(script_type, &public_key)?;
+    let path = match kind {
+        WalletKind::Electrum(seed_type) => electrum::derivation_path(seed_type, false, 0),
+        _ => Vec::new(),
+    };
+    let key = Public::public_key(&public_key);
+    let (xpub, descriptor) = match &master {
+        Some((master, _)) if !path.is_empty() => (Some(Public::xpub(master)?), Public::descriptor(script_type, Some((&master.fingerprint()?, &path)), &key)),
+        Some((master, _)) => (Some(Public::xpub(master)?), Public::descriptor(script_type, None, &key)),
+        None => (None, Public::descriptor(script_type, None, &key)),
+    };
+    Ok(WatchOnlyEntry {
+        kind: "wallet",
+        label: labels.address(address.as_str()).map(Public::label),
+        address,
+        public_key: key,
+        xpub,
+        descriptor,
+        path: Public::path(&path),
+    })
+}
+
+// Entry of a saved extended key: a master key, or an account key imported with its key origin
+fn extended_key_entry(record: &ExtendedKeyRecord, labels: &Labels) -> Result<WatchOnlyEntry, &'static str> {
+    let key = record.key()?;
+    let xpub = Public::xpub(&key)?;
+    let label = labels.get("xpub", xpub.as_str()).map(Public::label);
+    let Some((origin, script_type)) = record.account_origin()? else {
+        let public_key = key.public_key()?;
+        return Ok(WatchOnlyEntry {
+            kind: "extended_key",
+            address: Public::address(ScriptType::Pkh, &public_key)?,
+            public_key: Public::public_key(&public_key),
+            descriptor: Public::descriptor(ScriptType::Pkh, None, &xpub),
+            path: Public::path(&[]),
+            label,
+            xpub: Some(xpub),
+        });
+    };
+
+    // Imported account key: its first receiving address, at the path below the master key it came from
+    let public_key = key.derive_path(&[0, 0])?.public_key()?;
+    let path = [&origin.path[..], &[0, 0]].concat();
+    let key = Public::public_key(&public_key);
+    Ok(WatchOnlyEntry {
+        kind: "extended_key",
+        address: Public::address(script_type, &public_key)?,
+        descriptor: Public::descriptor(script_type, Some((&origin.fingerprint, &path)), &key),
+        public_key: key,
+        path: Public::path(&path),
+        label,
+        xpub: Some(xpub),
+    })
+}
+
+// Entry of a saved child key, at the path written into its record
+fn child_key_entry(record: &ChildKeyRecord) -> Result<WatchOnlyEntry, &'static str> {
+    let key = verify::extended_key(&record.private_key, &record.chain_code)?;
+    let public_key = key.public_key()?;
+    let key = Public::public_key(&public_key);
+    Ok(WatchOnlyEntry {
+        kind: "child_key",
+        address: Public::address(ScriptType::Pkh, &public_key)?,
+        descriptor: Public::descriptor(ScriptType::Pkh, None, &key),
+        public_key: key,
+        xpub: None,
+        path: Public::recorded_path(&record.derivation_path),
+        label: None,
+    })
+}
+
+// Compressed public key of a stored hex-encoded private key
+fn public_key(private_key: &str) -> Result<[u8; 33], &'static str> {
+    verify::extended_key(private_key, &hex::encode([0u8; 32]))?.public_key()
+}
+
+// Quotes a CSV field when needed, and defuses text that spreadsheets would run as a formula
+fn csv_field(field: &str) -> String {
+    let field = if field.starts_with(['=', '+', '-', '@']) { format!("'{}", field) } else { field.to_string() };
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bip32::{ExtendedPrivKey, XPUB_VERSION};
+    use crate::bip39::Seed;
+    use crate::descriptor::{KeyOrigin, DEFAULT_ACCOUNT};
+    use crate::labels::new_label;
+    use crate::wallet::Wallet;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn test_export_has_no_secrets() {
+        let seed = Seed::new(MNEMONIC, "");
+        let wallet = Wallet::from_seed(seed.expose_bytes()).unwrap();
+        let record = WalletRecord::new(MNEMONIC, wallet.get_address(), wallet.get_public_key(), wallet.expose_private_key());
+        let labels = Labels::from_records(vec![new_label("addr", wallet.get_address(), "=Payroll, March", Some("secret note")).unwrap()]);
+
+        let entry = wallet_entry(&record, &labels).unwrap();
+        assert_eq!(entry.address.as_str(), wallet.get_address());
+        assert_eq!(entry.descriptor.as_str(), format!("pkh({})", wallet.get_public_key()));
+        assert_eq!(entry.xpub.as_ref().unwrap().as_str(), ExtendedPrivKey::new(seed.expose_bytes()).unwrap().xpub().unwrap());
+        assert_eq!(entry.path.as_str(), "m");
+
+        let json = to_json(std::slice::from_ref(&entry));
+        let csv = to_csv(&[entry]);
+        for export in [&json, &csv] {
+            assert!(!export.contains(wallet.expose_private_key()));
+            assert!(!export.contains("abandon"));
+            assert!(!export.contains("secret note"));
+        }
+        assert!(csv.lines().nth(1).unwrap().ends_with(",\"'=Payroll, March\""));
+
+        // A wallet restored with a passphrase keeps its address but gets no master xpub
+        let seed = Seed::new(MNEMONIC, "TREZOR");
+        let restored = Wallet::from_seed(seed.expose_bytes()).unwrap();
+        let record = WalletRecord::new(MNEMONIC, restored.get_address(), restored.get_public_key(), restored.expose_private_key());
+        let entry = wallet_entry(&record, &Labels::from_records(Vec::new())).unwrap();
+        assert_eq!(entry.address.as_str(), restored.get_address());
+        assert!(entry.xpub.is_none());
+
+        // An imported account key is watched at its first receiving address, below its key origin
+        let master = ExtendedPrivKey::new(Seed::new(MNEMONIC, "").expose_bytes()).unwrap();
+        let origin = KeyOrigin { fingerprint: master.fingerprint().unwrap(), path: DEFAULT_ACCOUNT.to_vec() };
+        let account = ExtendedKeyRecord::account(&master.derive_path(&DEFAULT_ACCOUNT).unwrap(), &origin, ScriptType::Wpkh);
+        let entry = extended_key_entry(&account, &Labels::from_records(Vec::new())).unwrap();
+        assert_eq!(entry.address.as_str(), "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu");
+        assert_eq!(entry.path.as_str(), "m/84'/0'/0'/0/0");
+        assert!(entry.descriptor.as_str().starts_with("wpkh([73c5da0a/84'/0'/0'/0/0]"));
+        // The account's own xpub, as the wallet it came from shows it, not a depth 0 key
+        assert_eq!(entry.xpub.as_ref().unwrap().as_str(), master.xpub_at(&DEFAULT_ACCOUNT, XPUB_VERSION).unwrap());
+        let stored: ExtendedKeyRecord = serde_json::from_value(serde_json::to_value(&account).unwrap()).unwrap();
+        assert_eq!(extended_key_entry(&stored, &Labels::from_records(Vec::new())).unwrap().xpub.unwrap().as_str(), entry.xpub.as_ref().unwrap().as_str());
+
+        // A child key is listed at the path saved with it
+        let child = ChildKeyRecord { derivation_path: "m/0/7".to_string(), ..ChildKeyRecord::new(&master, 7) };
+        assert_eq!(child_key_entry(&child).unwrap().path.as_str(), "m/0/7");
+    }
+}
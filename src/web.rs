@@ -1,19 +1,34 @@
 use axum::{
-    extract::Form,
-    response::{Html, IntoResponse},
+    extract::{Form, Path as UrlPath, Query},
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
     routing::{get, post},
     Router,
 };
 use serde::Deserialize;
 use std::net::SocketAddr;
 use crate::wallet::Wallet;
-use crate::bip39::{Mnemonic, Seed};
-use crate::bip32::ExtendedPrivKey;
-use serde_json::{json, Value};
-use chrono::Utc;
+use crate::bip39::{self, Mnemonic, Seed, WordMatch};
+use crate::bip32::{format_path, ExtendedPrivKey};
+use crate::bip85;
+use crate::descriptor::{self, Derived, Descriptor};
+use crate::multisig::{self, Cosigner, Multisig};
+use crate::bsms::{self, Encryption};
+use crate::electrum::{self, PhraseType, SeedType};
+use crate::audit::{self, Operation};
+use crate::backend;
+use crate::browse;
+use crate::interop::{self, Format};
+use crate::labels;
+use crate::verify;
+use crate::watch_only;
+use crate::keystore::{self, Session};
+use crate::records::{ChildKeyRecord, ExtendedKeyRecord, Record, RecordKind, WalletRecord};
+use base64::Engine;
 use qrcode::QrCode;
-use std::fs;
-use std::env;
+use std::sync::Arc;
+use zeroize::Zeroizing;
 
 // Starts the web server with defined routes
 pub async fn start_server() {
@@ -24,11 +39,33 @@ pub async fn start_server() {
         .route("/extended_priv_key", get(extended_priv_key_form).post(generate_extended_priv_key)) // Extended private key generation
         .route("/derive_child_key", get(derive_child_key_form).post(derive_child_key)) // Child key derivation
         .route("/qr_code", get(qr_code_form).post(generate_qr_code_web)) // QR code generation
+        .route("/restore_wallet", get(restore_wallet_form).post(restore_wallet)) // Wallet restore from mnemonic
+        .route("/bip85", get(bip85_form).post(derive_bip85)) // BIP85 child entropy derivation
+        .route("/unlock", get(unlock_form).post(unlock_keystore)) // Keystore unlock
+        .route("/wallets", get(list_wallets)) // Browse saved wallets
+        .route("/wallets/:id", get(show_wallet).post(reveal_wallet)) // Saved wallet details
+        .route("/wallets/:id/export", post(export_wallet)) // Saved wallet export for other wallet software
+        .route("/wallet_files", get(wallet_files_page).post(import_wallet_file)) // Import of other wallets' files
+        .route("/descriptor", get(descriptor_form).post(derive_descriptor)) // Addresses of a pasted descriptor
+        .route("/multisig", get(multisig_page).post(create_multisig)) // Multisig wallets
+        .route("/multisig/:id", get(show_multisig)) // Saved multisig wallet details and addresses
+        .route("/multisig/:id/config", get(export_multisig_config)) // Coldcard / Sparrow coordinator config
+        .route("/bsms", get(bsms_page)) // BIP129 multisig setup
+        .route("/bsms/session", post(start_bsms_session)) // BSMS coordinator: session tokens
+        .route("/bsms/coordinate", post(coordinate_bsms)) // BSMS coordinator: key records to descriptor record
+        .route("/bsms/sign", post(sign_key_record)) // BSMS signer: key record
+        .route("/bsms/check", post(check_descriptor_record)) // BSMS signer: descriptor record check
+        .route("/labels", get(labels_page).post(save_label)) // Labels of addresses, public keys and xpubs
+        .route("/labels/import", post(import_labels)) // BIP329 label import
+        .route("/labels/export", get(export_labels)) // BIP329 label export
+        .route("/verify", get(verify_page)) // Saved record verification
+        .route("/watch_only", get(export_watch_only)) // Watch-only export (JSON or CSV)
         .route("/save_all_wallets", post(save_all_wallets)) // Save all wallets
         .route("/save_all_qr_codes", post(save_all_qr_codes)) // Save all QR codes
         .route("/save_extended_priv_keys", post(save_extended_priv_keys)) // Save extended private keys
         .route("/save_child_keys", post(save_child_keys)) // Save child keys
-        .fallback(handle_404); // 404 handler
+        .fallback(handle_404) // 404 handler
+        .layer(middleware::from_fn(check_origin)); // Refuses requests from other sites
 
     // Define the server address
     let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
@@ -41,6 +78,38 @@ pub async fn start_server() {
         .unwrap();
 }
 
+// Origins the web interface is served from (the server only listens on the loopback interface)
+const ALLOWED_ORIGINS: [&str; 2] = ["http://127.0.0.1:3000", "http://localhost:3000"];
+
+// Refuses requests that do not come from the web interface itself, before any handler runs
+async fn check_origin<B>(request: Request<B>, next: Next<B>) -> Response {
+    match refusal(request.method(), request.headers()) {
+        Some(reason) => (StatusCode::FORBIDDEN, reason).into_response(),
+        None => next.run(request).await,
+    }
+}
+
+// Why a request is refused, if it is. Another Host means a page that rebound its own domain name to this
+// server (DNS rebinding); every POST must carry our Origin, so forms of other sites cannot save, unlock or
+// reveal anything; and pages with saved records are not served to requests the browser marks as coming from
+// another site, or whose Origin is another one.
+fn refusal(method: &Method, headers: &HeaderMap) -> Option<&'static str> {
+    let value = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let host = value("host").unwrap_or_default();
+    if !ALLOWED_ORIGINS.iter().any(|origin| origin.strip_prefix("http://") == Some(host)) {
+        return Some("Unknown host. Open the web interface at http://127.0.0.1:3000 or http://localhost:3000");
+    }
+    let origin = value("origin");
+    if *method == Method::POST && !origin.is_some_and(|origin| ALLOWED_ORIGINS.contains(&origin)) {
+        return Some("Forms can only be sent from the web interface itself");
+    }
+    let cross_site = matches!(value("sec-fetch-site"), Some("cross-site" | "same-site"));
+    if cross_site || origin.is_some_and(|origin| !ALLOWED_ORIGINS.contains(&origin)) {
+        return Some("Requests from other sites are refused");
+    }
+    None
+}
+
 // HTML template for consistent styling across pages
 fn html_template(title: &str, content: &str) -> String {
     format!(
@@ -162,6 +231,16 @@ fn html_template(title: &str, content: &str) -> String {
                 <a href="/extended_priv_key">Extended Private Key</a>
                 <a href="/derive_child_key">Derive Child Key</a>
                 <a href="/qr_code">QR Code</a>
+                <a href="/restore_wallet">Restore Wallet</a>
+                <a href="/bip85">BIP85</a>
+                <a href="/wallets">Saved Wallets</a>
+                <a href="/labels">Labels</a>
+                <a href="/wallet_files">Wallet Files</a>
+                <a href="/descriptor">Descriptor</a>
+                <a href="/multisig">Multisig</a>
+                <a href="/bsms">BSMS</a>
+                <a href="/verify">Verify</a>
+                <a href="/unlock">Unlock</a>
             </div>
         </div>
         {}
@@ -239,24 +318,28 @@ struct WalletRequest {
 
 // Wallet generation handler
 async fn generate_wallets(Form(input): Form<WalletRequest>) -> impl IntoResponse {
-    let count = input.count.min(100).max(1); // Limit the number of wallets to 100
+    let count = input.count.clamp(1, 100); // Limit the number of wallets to 100
 
     let mut wallets = Vec::new();
+    let mut accounts = Vec::new();
     for _ in 0..count {
         let mnemonic = Mnemonic::generate(128); // Generate a 12-word mnemonic
-        let seed = Seed::new(&mnemonic.to_string(), ""); // Derive seed from mnemonic
-        let wallet = Wallet::from_seed(seed.as_bytes()).unwrap(); // Create wallet from seed
-        let wallet_data = json!({
-            "Mnemonic": mnemonic.to_string(),
-            "Address": wallet.get_address(),
-            "PublicKey": wallet.get_public_key(),
-            "PrivateKey": wallet.get_private_key(),
-            "GeneratedAt": Utc::now().to_rfc3339(),
-        });
-
-        wallets.push(wallet_data);
+        let seed = Seed::new(mnemonic.expose_phrase(), ""); // Derive seed from mnemonic
+        let wallet = Wallet::from_seed(seed.expose_bytes()).unwrap(); // Create wallet from seed
+        // Master-key and BIP84 account descriptors
+        match descriptor::default_account(seed.expose_bytes(), 3) {
+            Ok(account) => accounts.push(account),
+            Err(err) => return error_page(err, "/generate_wallets"),
+        }
+        wallets.push(WalletRecord::new(
+            mnemonic.expose_phrase(),
+            wallet.get_address(),
+            wallet.get_public_key(),
+            wallet.expose_private_key(),
+        ));
     }
 
+    audit(Operation::GenerateWallets, &wallets.iter().map(|wallet| wallet.address.as_str()).collect::<Vec<_>>()).await;
     let wallets_json = serde_json::to_string(&wallets).unwrap();
 
     Html(html_template(
@@ -269,6 +352,7 @@ async fn generate_wallets(Form(input): Form<WalletRequest>) -> impl IntoResponse
                     <div class="scrollable-wallets">
                         {}
                     </div>
+                    <input type="text" id="wallet-label" placeholder="Label for the saved wallets (optional)" class="mt-6 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">
                     <div class="flex space-x-4 mt-6">
                         <button onclick="saveAllWallets()" class="btn">Save All Wallets</button>
                         <button onclick="saveAllQrCodes()" class="btn">Save All QR Codes</button>
@@ -285,6 +369,7 @@ async fn generate_wallets(Form(input): Form<WalletRequest>) -> impl IntoResponse
                         }},
                         body: new URLSearchParams({{
                             wallets: JSON.stringify({}),
+                            label: document.getElementById('wallet-label').value,
                         }}),
                     }})
                     .then(response => response.text())
@@ -316,20 +401,29 @@ async fn generate_wallets(Form(input): Form<WalletRequest>) -> impl IntoResponse
                 }}
             </script>
             "#,
-            wallets.iter().map(|wallet| {
+            wallets.iter().zip(&accounts).map(|(wallet, account)| {
                 format!(
                     r#"
                     <div class='mb-4 p-4 bg-gray-700 rounded-lg'>
                         <p class='text-sm text-gray-300'>Mnemonic: <span class='font-medium text-white'>{}</span></p>
-                        <p class='text-sm text-gray-300'>Address: <span class='font-medium text-white'>{}</span></p>
+                        <p class='text-sm text-gray-300'>Address (master key, P2PKH; the address that is saved): <span class='font-medium text-white'>{}</span></p>
                         <p class='text-sm text-gray-300'>Public Key: <span class='font-medium text-white'>{}</span></p>
                         <p class='text-sm text-gray-300'>Private Key: <span class='font-medium text-white'>{}</span></p>
+                        <p class='text-sm text-gray-300 break-all'>Descriptor: <span class='font-medium text-white'>{}</span></p>
+                        <p class='text-sm text-gray-300 mt-2'>BIP84 account m/84'/0'/0' of the same mnemonic (a different address, for wallet software):</p>
+                        <p class='text-sm text-gray-300 break-all'>Receive Descriptor: <span class='font-medium text-white'>{}</span></p>
+                        <p class='text-sm text-gray-300 break-all'>Change Descriptor: <span class='font-medium text-white'>{}</span></p>
+                        <p class='text-sm text-gray-300'>Receiving Addresses: <span class='font-medium text-white'>{}</span></p>
                     </div>
                     "#,
-                    wallet["Mnemonic"],
-                    wallet["Address"],
-                    wallet["PublicKey"],
-                    wallet["PrivateKey"]
+                    wallet.mnemonic,
+                    wallet.address,
+                    wallet.public_key,
+                    wallet.private_key,
+                    account.master,
+                    account.receive,
+                    account.change,
+                    account.addresses.join(", ")
                 )
             }).collect::<Vec<_>>().join(""),
             wallets_json,
@@ -342,53 +436,74 @@ async fn generate_wallets(Form(input): Form<WalletRequest>) -> impl IntoResponse
 #[derive(Deserialize)]
 struct SaveAllWalletsRequest {
     wallets: String,
+    #[serde(default)]
+    label: String, // Optional label attached to every saved address
 }
 
 // Handler to save all wallets to a file
 async fn save_all_wallets(Form(input): Form<SaveAllWalletsRequest>) -> impl IntoResponse {
-    let current_dir = env::current_dir().unwrap();
-    let file_path = current_dir.join("data/wallets/wallets.json");
-
-    let wallets_data: Vec<Value> = match serde_json::from_str(&input.wallets) {
+    let wallets_data: Vec<WalletRecord> = match serde_json::from_str(&input.wallets) {
         Ok(data) => data,
         Err(_) => return "Failed to parse wallets data.".to_string(),
     };
-
-    let mut existing_wallets: Vec<Value> = match fs::read_to_string(&file_path) {
-        Ok(file) => serde_json::from_str(&file).unwrap_or_else(|_| Vec::new()),
-        Err(_) => Vec::new(),
+    let session = match keystore::session() {
+        Some(session) => session,
+        None => return KEYSTORE_LOCKED.to_string(),
+    };
+    let wallet_labels = if input.label.trim().is_empty() {
+        Vec::new()
+    } else {
+        match wallets_data.iter().map(|wallet| labels::new_label("addr", &wallet.address, &input.label, None)).collect::<Result<Vec<_>, _>>() {
+            Ok(wallet_labels) => wallet_labels,
+            Err(err) => return format!("Failed to save wallets: {}", err),
+        }
     };
-    existing_wallets.extend(wallets_data);
 
-    match fs::write(&file_path, serde_json::to_string_pretty(&existing_wallets).unwrap()) {
-        Ok(_) => format!("All wallets saved successfully at: {}", file_path.display()),
-        Err(_) => "Failed to save wallets.".to_string(),
+    let addresses: Vec<String> = wallets_data.iter().map(|wallet| wallet.address.clone()).collect();
+    let saved = match save_records(session.clone(), wallets_data).await {
+        Ok(()) => save_records(session, wallet_labels).await,
+        Err(err) => Err(err),
+    };
+    match saved {
+        Ok(_) => {
+            audit(Operation::SaveWallets, &addresses.iter().map(String::as_str).collect::<Vec<_>>()).await;
+            format!("All wallets saved successfully at: {}", backend::current().location(WalletRecord::KIND))
+        }
+        Err(err) => format!("Failed to save wallets: {}", err),
     }
 }
 
+// Appends records on a blocking thread, so waiting for the storage lock and encrypting does not stall the server
+async fn save_records<T: Record + Send + 'static>(session: Arc<Session>, records: Vec<T>) -> Result<(), &'static str> {
+    tokio::task::spawn_blocking(move || backend::current().append_records(&session, &records))
+        .await
+        .unwrap_or(Err("Save task failed"))
+}
+
 // Form data structure for saving all QR codes
 #[derive(Deserialize)]
 struct SaveAllQrCodesRequest {
     wallets: String,
 }
 
-// Handler to save all QR codes to files
+// Handler to save all QR codes with the storage backend
 async fn save_all_qr_codes(Form(input): Form<SaveAllQrCodesRequest>) -> impl IntoResponse {
-    let wallets_data: Vec<Value> = match serde_json::from_str(&input.wallets) {
+    let wallets_data: Vec<WalletRecord> = match serde_json::from_str(&input.wallets) {
         Ok(data) => data,
         Err(_) => return "Failed to parse wallets data.".to_string(),
     };
 
+    let mut locations = Vec::new();
     for wallet in wallets_data.iter() {
-        let address = wallet["Address"].as_str().unwrap();
-        let file_name = format!("data/qr_codes/{}.svg", address);
-        let file_path = env::current_dir().unwrap().join(&file_name);
-        match generate_qr_code(address, &file_path.to_string_lossy()) {
-            Ok(_) => (),
+        let address = &wallet.address;
+        match generate_qr_code(address) {
+            Ok(location) => locations.push(location),
             Err(_) => return format!("Failed to generate QR code for wallet {}", address).to_string(),
         }
     }
-    format!("All QR codes saved successfully in: data/qr_codes/")
+    let subjects: Vec<String> = wallets_data.iter().map(|wallet| audit::address_subject(&wallet.address)).collect();
+    audit(Operation::SaveQrCode, &subjects.iter().map(String::as_str).collect::<Vec<_>>()).await;
+    format!("All QR codes saved successfully: {}", locations.join(", "))
 }
 
 // Extended private key generation form handler
@@ -423,11 +538,8 @@ async fn generate_extended_priv_key(Form(input): Form<ExtendedPrivKeyRequest>) -
     match hex::decode(&input.seed) {
         Ok(seed) => match ExtendedPrivKey::new(&seed) {
             Ok(key) => {
-                let ext_key_json = json!({
-                    "PrivateKey": hex::encode(key.private_key),
-                    "ChainCode": hex::encode(key.chain_code),
-                    "GeneratedAt": Utc::now().to_rfc3339(),
-                });
+                audit(Operation::GenerateMasterKey, &[&key.fingerprint().unwrap_or_default()]).await;
+                let ext_key_record = ExtendedKeyRecord::new(&key);
 
                 Html(html_template(
                     "Extended Private Key",
@@ -467,9 +579,9 @@ async fn generate_extended_priv_key(Form(input): Form<ExtendedPrivKeyRequest>) -
                             }}
                         </script>
                         "#,
-                        hex::encode(key.private_key),
+                        hex::encode(key.expose_private_key()),
                         hex::encode(key.chain_code),
-                        serde_json::to_string(&ext_key_json).unwrap()
+                        serde_json::to_string(&ext_key_record).unwrap()
                     ),
                 ))
             }
@@ -512,23 +624,22 @@ struct SaveExtendedPrivKeysRequest {
 
 // Handler to save extended private keys to a file
 async fn save_extended_priv_keys(Form(input): Form<SaveExtendedPrivKeysRequest>) -> impl IntoResponse {
-    let current_dir = env::current_dir().unwrap();
-    let file_path = current_dir.join("data/extended_keys/extended_keys.json");
-
-    let ext_keys_data: Vec<Value> = match serde_json::from_str(&input.ext_keys) {
+    let ext_keys_data: Vec<ExtendedKeyRecord> = match serde_json::from_str(&input.ext_keys) {
         Ok(data) => data,
         Err(_) => return "Failed to parse extended private keys data.".to_string(),
     };
-
-    let mut existing_ext_keys: Vec<Value> = match fs::read_to_string(&file_path) {
-        Ok(file) => serde_json::from_str(&file).unwrap_or_else(|_| Vec::new()),
-        Err(_) => Vec::new(),
+    let session = match keystore::session() {
+        Some(session) => session,
+        None => return KEYSTORE_LOCKED.to_string(),
     };
-    existing_ext_keys.extend(ext_keys_data);
 
-    match fs::write(&file_path, serde_json::to_string_pretty(&existing_ext_keys).unwrap()) {
-        Ok(_) => format!("Extended private key saved successfully at: {}", file_path.display()),
-        Err(_) => "Failed to save extended private key.".to_string(),
+    let fingerprints: Vec<String> = ext_keys_data.iter().map(|key| key_fingerprint(&key.private_key, &key.chain_code)).collect();
+    match save_records(session, ext_keys_data).await {
+        Ok(_) => {
+            audit(Operation::SaveExtendedKey, &fingerprints.iter().map(String::as_str).collect::<Vec<_>>()).await;
+            format!("Extended private key saved successfully at: {}", backend::current().location(ExtendedKeyRecord::KIND))
+        }
+        Err(err) => format!("Failed to save extended private key: {}", err),
     }
 }
 
@@ -615,20 +726,15 @@ async fn derive_child_key(Form(input): Form<ChildKeyRequest>) -> impl IntoRespon
         }
     };
 
-    let parent_ext_key = ExtendedPrivKey {
-        private_key: parent_private_key,
-        chain_code,
+    let parent_ext_key = match ExtendedPrivKey::from_parts(parent_private_key, chain_code) {
+        Ok(key) => key,
+        Err(err) => return error_page(err, "/derive_child_key"),
     };
 
     match parent_ext_key.derive_child_key(input.index) {
         Ok(child_key) => {
-            let child_key_json = json!({
-                "PrivateKey": hex::encode(child_key.private_key),
-                "ChainCode": hex::encode(child_key.chain_code),
-                "Index": input.index,
-                "DerivationPath": format!("m/44'/0'/0'/0/{}", input.index),
-                "GeneratedAt": Utc::now().to_rfc3339(),
-            });
+            audit(Operation::DeriveChildKey, &[&parent_ext_key.fingerprint().unwrap_or_default(), &child_key.fingerprint().unwrap_or_default()]).await;
+            let child_key_record = ChildKeyRecord::new(&child_key, input.index);
 
             Html(html_template(
                 "Derived Child Key",
@@ -671,10 +777,10 @@ async fn derive_child_key(Form(input): Form<ChildKeyRequest>) -> impl IntoRespon
                     </script>
                     "#,
                     input.index,
-                    hex::encode(child_key.private_key),
+                    hex::encode(child_key.expose_private_key()),
                     hex::encode(child_key.chain_code),
                     input.index,
-                    serde_json::to_string(&child_key_json).unwrap()
+                    serde_json::to_string(&child_key_record).unwrap()
                 ),
             ))
         }
@@ -704,23 +810,22 @@ struct SaveChildKeysRequest {
 
 // Handler to save child keys to a file
 async fn save_child_keys(Form(input): Form<SaveChildKeysRequest>) -> impl IntoResponse {
-    let current_dir = env::current_dir().unwrap();
-    let file_path = current_dir.join("data/child_keys/child_keys.json");
-
-    let child_keys_data: Vec<Value> = match serde_json::from_str(&input.child_keys) {
+    let child_keys_data: Vec<ChildKeyRecord> = match serde_json::from_str(&input.child_keys) {
         Ok(data) => data,
         Err(_) => return "Failed to parse child keys data.".to_string(),
     };
-
-    let mut existing_child_keys: Vec<Value> = match fs::read_to_string(&file_path) {
-        Ok(file) => serde_json::from_str(&file).unwrap_or_else(|_| Vec::new()),
-        Err(_) => Vec::new(),
+    let session = match keystore::session() {
+        Some(session) => session,
+        None => return KEYSTORE_LOCKED.to_string(),
     };
-    existing_child_keys.extend(child_keys_data);
 
-    match fs::write(&file_path, serde_json::to_string_pretty(&existing_child_keys).unwrap()) {
-        Ok(_) => format!("Child key saved successfully at: {}", file_path.display()),
-        Err(_) => "Failed to save child key.".to_string(),
+    let fingerprints: Vec<String> = child_keys_data.iter().map(|key| key_fingerprint(&key.private_key, &key.chain_code)).collect();
+    match save_records(session, child_keys_data).await {
+        Ok(_) => {
+            audit(Operation::SaveChildKey, &fingerprints.iter().map(String::as_str).collect::<Vec<_>>()).await;
+            format!("Child key saved successfully at: {}", backend::current().location(ChildKeyRecord::KIND))
+        }
+        Err(err) => format!("Failed to save child key: {}", err),
     }
 }
 
@@ -753,9 +858,10 @@ struct QRCodeRequest {
 
 // Handler to generate a QR code
 async fn generate_qr_code_web(Form(input): Form<QRCodeRequest>) -> impl IntoResponse {
-    let file_name = format!("data/qr_codes/{}.svg", input.address);
-    match generate_qr_code(&input.address, &file_name) {
-        Ok(_) => Html(html_template(
+    match generate_qr_code(&input.address) {
+        Ok(location) => {
+            audit(Operation::SaveQrCode, &[&audit::address_subject(&input.address)]).await;
+            Html(html_template(
             "QR Code",
             &format!(
                 r#"
@@ -767,9 +873,10 @@ async fn generate_qr_code_web(Form(input): Form<QRCodeRequest>) -> impl IntoResp
                     </div>
                 </div>
                 "#,
-                file_name
+                escape_html(&location)
             ),
-        )),
+        ))
+        }
         Err(err) => Html(html_template(
             "Error",
             &format!(
@@ -782,19 +889,1806 @@ async fn generate_qr_code_web(Form(input): Form<QRCodeRequest>) -> impl IntoResp
                     </div>
                 </div>
                 "#,
-                err
+                escape_html(&err)
             ),
         )),
     }
 }
 
-// Helper function to generate a QR code and save it to a file
-fn generate_qr_code(data: &str, file_name: &str) -> Result<(), String> {
-    let code = QrCode::new(data).map_err(|e| e.to_string())?;
-    let image = code
-        .render::<qrcode::render::svg::Color>()
-        .min_dimensions(200, 200)
-        .build();
-    fs::write(file_name, image).map_err(|e| e.to_string())?;
-    Ok(())
-}
\ No newline at end of file
+// Wallet restore form handler
+async fn restore_wallet_form() -> impl IntoResponse {
+    Html(html_template("Restore Wallet", &restore_form_content("", "", "")))
+}
+
+// Renders the restore form, prefilled with a phrase and an optional notice above it
+fn restore_form_content(mnemonic: &str, passphrase: &str, notice: &str) -> String {
+    let wordlist_json = serde_json::to_string(&Mnemonic::wordlist()).unwrap();
+
+    format!(
+        r#"
+        <div class="flex items-center justify-center min-h-screen pt-20">
+            <div class="neumorphic p-8 max-w-2xl w-full fade-in">
+                <h1 class="text-3xl font-bold mb-6 slide-up">Restore Wallet</h1>
+                {}
+                <form method="post" action="/restore_wallet" class="space-y-4">
+                    <div>
+                        <label for="mnemonic" class="block text-sm font-medium text-gray-300">Mnemonic phrase (words may be shortened to their first 4 letters):</label>
+                        <textarea id="mnemonic" name="mnemonic" rows="3" required autocomplete="off" spellcheck="false" class="mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md shadow-sm focus:outline-none focus:ring-purple-500 focus:border-purple-500 text-white">{}</textarea>
+                        <div id="completions" class="flex flex-wrap gap-2 mt-2"></div>
+                    </div>
+                    <div>
+                        <label for="passphrase" class="block text-sm font-medium text-gray-300">Passphrase (optional):</label>
+                        <input type="password" id="passphrase" name="passphrase" value="{}" class="mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md shadow-sm focus:outline-none focus:ring-purple-500 focus:border-purple-500 text-white">
+                    </div>
+                    <button type="submit" class="btn w-full">Restore</button>
+                </form>
+            </div>
+        </div>
+        <script>
+            const WORDLIST = {};
+            const textarea = document.getElementById('mnemonic');
+            const completions = document.getElementById('completions');
+
+            // Shows the wordlist entries matching the word being typed
+            function showCompletions() {{
+                const words = textarea.value.split(/\s+/);
+                const current = words[words.length - 1].toLowerCase();
+                completions.innerHTML = '';
+                if (current.length === 0) return;
+                WORDLIST.filter(word => word.startsWith(current)).slice(0, 8).forEach(word => {{
+                    const chip = document.createElement('button');
+                    chip.type = 'button';
+                    chip.className = 'px-2 py-1 bg-purple-700 rounded text-sm';
+                    chip.textContent = word;
+                    chip.onclick = () => {{
+                        words[words.length - 1] = word;
+                        textarea.value = words.join(' ') + ' ';
+                        completions.innerHTML = '';
+                        textarea.focus();
+                    }};
+                    completions.appendChild(chip);
+                }});
+            }}
+
+            // Replaces the word at a position (used by the typo suggestions)
+            function replaceWord(position, word) {{
+                const words = textarea.value.trim().split(/\s+/);
+                words[position] = word;
+                textarea.value = words.join(' ');
+            }}
+
+            textarea.addEventListener('input', showCompletions);
+        </script>
+        "#,
+        notice,
+        escape_html(mnemonic),
+        escape_html(passphrase),
+        wordlist_json
+    )
+}
+
+// Form data structure for wallet restore
+#[derive(Deserialize)]
+struct RestoreWalletRequest {
+    mnemonic: String,
+    #[serde(default)]
+    passphrase: String,
+}
+
+// Handler to restore a wallet, completing prefixes and suggesting typo fixes
+async fn restore_wallet(Form(input): Form<RestoreWalletRequest>) -> impl IntoResponse {
+    // Electrum seeds are not BIP39 mnemonics and are restored differently
+    if let PhraseType::Electrum(seed_type) = electrum::detect_phrase_type(&input.mnemonic) {
+        return restore_electrum_wallet(&input.mnemonic, &input.passphrase, seed_type).await;
+    }
+
+    let wordlist = Mnemonic::wordlist();
+    let typed_words = bip39::normalize_phrase(&input.mnemonic);
+
+    if !bip39::VALID_WORD_COUNTS.contains(&typed_words.len()) {
+        return error_page("Invalid mnemonic. It must contain 12, 15, 18, 21 or 24 words.", "/restore_wallet");
+    }
+
+    // Resolve each word, collecting the ones that need the user's attention
+    let mut words = Vec::with_capacity(typed_words.len());
+    let mut problems = Vec::new();
+    for (position, typed) in typed_words.iter().enumerate() {
+        match bip39::match_word(&wordlist, typed) {
+            WordMatch::Exact(word) | WordMatch::Completed(word) => words.push(word),
+            WordMatch::Suggestions(suggestions) => {
+                let buttons = suggestions
+                    .iter()
+                    .take(9)
+                    .map(|word| {
+                        format!(
+                            r#"<button type="button" onclick="replaceWord({}, '{}')" class="px-2 py-1 bg-purple-700 rounded text-sm">{}</button>"#,
+                            position, word, word
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                problems.push(format!(
+                    r#"<li class="mb-2">Word #{} <span class="font-medium text-white">'{}'</span> is not in the wordlist. {}</li>"#,
+                    position + 1,
+                    escape_html(typed),
+                    if buttons.is_empty() { "No close match found.".to_string() } else { format!("Did you mean: {}", buttons) }
+                ));
+                words.push(typed.clone());
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        let notice = format!(
+            r#"<div class="mb-4 p-4 bg-gray-700 rounded-lg text-sm text-gray-300"><ul>{}</ul></div>"#,
+            problems.join("")
+        );
+        return Html(html_template("Restore Wallet", &restore_form_content(&words.join(" "), &input.passphrase, &notice)));
+    }
+
+    // Validate the checksum, offering adjacent swaps that would fix it
+    let mnemonic = match Mnemonic::from_phrase(&words.join(" ")) {
+        Ok(mnemonic) => mnemonic,
+        Err(err) => {
+            let candidates = bip39::find_adjacent_swaps(&wordlist, &words);
+            if candidates.is_empty() {
+                return error_page(err, "/restore_wallet");
+            }
+
+            let options = candidates
+                .iter()
+                .map(|candidate| {
+                    format!(
+                        r#"
+                        <form method="post" action="/restore_wallet" class="mb-2 p-4 bg-gray-700 rounded-lg flex items-center justify-between gap-4">
+                            <span class="text-sm font-medium text-white">{}</span>
+                            <input type="hidden" name="mnemonic" value="{}">
+                            <input type="hidden" name="passphrase" value="{}">
+                            <button type="submit" class="btn">Use</button>
+                        </form>
+                        "#,
+                        candidate,
+                        candidate,
+                        escape_html(&input.passphrase)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("");
+            let notice = format!(
+                r#"<div class="mb-4"><p class="text-sm text-gray-300 mb-2">{}. Swapping two adjacent words gives a valid phrase:</p>{}</div>"#,
+                err, options
+            );
+            return Html(html_template("Restore Wallet", &restore_form_content(&words.join(" "), &input.passphrase, &notice)));
+        }
+    };
+
+    let seed = Seed::new(mnemonic.expose_phrase(), &input.passphrase);
+    let wallet = match Wallet::from_seed(seed.expose_bytes()) {
+        Ok(wallet) => wallet,
+        Err(err) => return error_page(err, "/restore_wallet"),
+    };
+    audit(Operation::RestoreWallet, &[wallet.get_address()]).await;
+
+    let wallet_record = WalletRecord::new(
+        mnemonic.expose_phrase(),
+        wallet.get_address(),
+        wallet.get_public_key(),
+        wallet.expose_private_key(),
+    );
+
+    Html(html_template(
+        "Restored Wallet",
+        &format!(
+            r#"
+            <div class="flex items-center justify-center h-screen">
+                <div class="neumorphic p-8 max-w-3xl w-full fade-in">
+                    <h1 class="text-3xl font-bold mb-6 slide-up">Wallet Restored</h1>
+                    <div class="mb-4 p-4 bg-gray-700 rounded-lg">
+                        <p class="text-sm text-gray-300">Mnemonic: <span class="font-medium text-white">{}</span></p>
+                        <p class="text-sm text-gray-300">Address: <span class="font-medium text-white">{}</span></p>
+                        <p class="text-sm text-gray-300">Public Key: <span class="font-medium text-white">{}</span></p>
+                        <p class="text-sm text-gray-300">Private Key: <span class="font-medium text-white">{}</span></p>
+                    </div>
+                    <div class="flex space-x-4 mt-6">
+                        <button onclick="saveWallet()" class="btn">Save Wallet</button>
+                        <a href="/" class="btn bg-gray-500 hover:bg-gray-600">Back to Home</a>
+                    </div>
+                </div>
+            </div>
+            <script>
+                function saveWallet() {{
+                    fetch('/save_all_wallets', {{
+                        method: 'POST',
+                        headers: {{
+                            'Content-Type': 'application/x-www-form-urlencoded',
+                        }},
+                        body: new URLSearchParams({{
+                            wallets: JSON.stringify([{}]),
+                        }}),
+                    }})
+                    .then(response => response.text())
+                    .then(message => {{
+                        alert(message);
+                    }})
+                    .catch(error => {{
+                        alert('Failed to save wallet: ' + error);
+                    }});
+                }}
+            </script>
+            "#,
+            mnemonic.expose_phrase(),
+            wallet.get_address(),
+            wallet.get_public_key(),
+            wallet.expose_private_key(),
+            serde_json::to_string(&wallet_record).unwrap()
+        ),
+    ))
+}
+
+// Handler to display the BIP85 derivation form
+async fn bip85_form() -> impl IntoResponse {
+    Html(html_template(
+        "BIP85 Derivation",
+        r#"
+        <div class="flex items-center justify-center min-h-screen pt-20">
+            <div class="neumorphic p-8 max-w-2xl w-full fade-in">
+                <h1 class="text-3xl font-bold mb-6 slide-up">BIP85 Child Derivation</h1>
+                <form method="post" action="/bip85" class="space-y-4">
+                    <div>
+                        <label for="master" class="block text-sm font-medium text-gray-300">Master xprv, seed (hex) or mnemonic:</label>
+                        <textarea id="master" name="master" rows="3" required autocomplete="off" spellcheck="false" class="mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md shadow-sm focus:outline-none focus:ring-purple-500 focus:border-purple-500 text-white"></textarea>
+                    </div>
+                    <div>
+                        <label for="passphrase" class="block text-sm font-medium text-gray-300">Mnemonic passphrase (optional):</label>
+                        <input type="password" id="passphrase" name="passphrase" class="mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md shadow-sm focus:outline-none focus:ring-purple-500 focus:border-purple-500 text-white">
+                    </div>
+                    <div>
+                        <label for="application" class="block text-sm font-medium text-gray-300">Application:</label>
+                        <select id="application" name="application" class="mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md shadow-sm focus:outline-none focus:ring-purple-500 focus:border-purple-500 text-white">
+                            <option value="39">BIP39 mnemonic (39')</option>
+                            <option value="2">WIF private key (2')</option>
+                            <option value="32">Extended private key (32')</option>
+                            <option value="128169">Hex entropy (128169')</option>
+                            <option value="707764">Base64 password (707764')</option>
+                            <option value="707785">Base85 password (707785')</option>
+                        </select>
+                    </div>
+                    <div>
+                        <label for="length" class="block text-sm font-medium text-gray-300">Words, bytes or password length (ignored for WIF and xprv):</label>
+                        <input type="number" id="length" name="length" value="24" min="10" max="86" class="mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md shadow-sm focus:outline-none focus:ring-purple-500 focus:border-purple-500 text-white">
+                    </div>
+                    <div>
+                        <label for="index" class="block text-sm font-medium text-gray-300">Child index:</label>
+                        <input type="number" id="index" name="index" value="0" min="0" class="mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md shadow-sm focus:outline-none focus:ring-purple-500 focus:border-purple-500 text-white">
+                    </div>
+                    <button type="submit" class="btn w-full">Derive</button>
+                </form>
+            </div>
+        </div>
+        "#,
+    ))
+}
+
+// Form data structure for BIP85 derivation
+#[derive(Deserialize)]
+struct Bip85Request {
+    master: String,
+    #[serde(default)]
+    passphrase: String,
+    application: u32,
+    length: usize,
+    index: u32,
+}
+
+// Handler to derive a BIP85 child mnemonic, key or password
+async fn derive_bip85(Form(input): Form<Bip85Request>) -> impl IntoResponse {
+    let master = match bip85::master_key_from_input(&input.master, &input.passphrase) {
+        Ok(master) => master,
+        Err(err) => return error_page(err, "/bip85"),
+    };
+    let application = match bip85::Application::from_number(input.application, input.length) {
+        Ok(application) => application,
+        Err(err) => return error_page(err, "/bip85"),
+    };
+    let output = match application.derive(&master, input.index) {
+        Ok(output) => output,
+        Err(err) => return error_page(err, "/bip85"),
+    };
+    audit(Operation::DeriveBip85, &[&master.fingerprint().unwrap_or_default(), &application.path_string(input.index)]).await;
+
+    Html(html_template(
+        "BIP85 Derivation",
+        &format!(
+            r#"
+            <div class="flex items-center justify-center h-screen">
+                <div class="neumorphic p-8 max-w-3xl w-full fade-in">
+                    <h1 class="text-3xl font-bold mb-6 slide-up">BIP85 Child Derived</h1>
+                    <div class="mb-4 p-4 bg-gray-700 rounded-lg">
+                        <p class="text-sm text-gray-300">Path: <span class="font-medium text-white">{}</span></p>
+                        <p class="text-sm text-gray-300 break-all">Result: <span class="font-medium text-white">{}</span></p>
+                    </div>
+                    <div class="flex space-x-4 mt-6">
+                        <a href="/bip85" class="btn">Derive Another</a>
+                        <a href="/" class="btn bg-gray-500 hover:bg-gray-600">Back to Home</a>
+                    </div>
+                </div>
+            </div>
+            "#,
+            application.path_string(input.index),
+            escape_html(&output)
+        ),
+    ))
+}
+
+// Message returned by the save handlers while the keystore is locked
+const KEYSTORE_LOCKED: &str = "The keystore is locked. Unlock it from the Unlock page (/unlock) before saving.";
+
+// Keystore unlock form handler
+async fn unlock_form() -> impl IntoResponse {
+    // A confirmation field is only needed when the password creates the first keystore
+    let has_password = tokio::task::spawn_blocking(|| backend::current().has_password()).await.unwrap_or(true);
+    let confirmation = if has_password {
+        ""
+    } else {
+        r#"
+                    <div>
+                        <label for="confirmation" class="block text-sm font-medium text-gray-300">Confirm the new password:</label>
+                        <input type="password" id="confirmation" name="confirmation" required class="mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md shadow-sm focus:outline-none focus:ring-purple-500 focus:border-purple-500 text-white">
+                    </div>"#
+    };
+    let status = if keystore::session().is_some() { "Unlocked" } else { "Locked" };
+
+    Html(html_template(
+        "Unlock Keystore",
+        &format!(
+            r#"
+        <div class="flex items-center justify-center h-screen">
+            <div class="neumorphic p-8 max-w-md w-full fade-in">
+                <h1 class="text-3xl font-bold mb-6 slide-up">Unlock Keystore</h1>
+                <p class="text-sm text-gray-300 mb-4">Status: <span class="font-medium text-white">{}</span>. Saved wallets and keys are encrypted with this password.</p>
+                <form method="post" action="/unlock" class="space-y-4">
+                    <div>
+                        <label for="password" class="block text-sm font-medium text-gray-300">Keystore password:</label>
+                        <input type="password" id="password" name="password" required autofocus class="mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md shadow-sm focus:outline-none focus:ring-purple-500 focus:border-purple-500 text-white">
+                    </div>{}
+                    <button type="submit" class="btn w-full">Unlock</button>
+                </form>
+            </div>
+        </div>
+        "#,
+            status, confirmation
+        ),
+    ))
+}
+
+// Form data structure for unlocking the keystore
+#[derive(Deserialize)]
+struct UnlockRequest {
+    password: String,
+    confirmation: Option<String>,
+}
+
+// Handler to unlock the keystore for the rest of the session
+async fn unlock_keystore(Form(input): Form<UnlockRequest>) -> impl IntoResponse {
+    let password = Zeroizing::new(input.password);
+    let confirmation = input.confirmation.map(Zeroizing::new);
+
+    // Key derivation and file reads run on a blocking thread (Argon2id takes seconds)
+    let counts = tokio::task::spawn_blocking(move || {
+        let backend = backend::current();
+        if !backend.has_password() && confirmation.as_deref() != Some(&password) {
+            return Err("Passwords do not match");
+        }
+        keystore::unlock(&password)?;
+        let session = keystore::session().ok_or("Keystore is locked")?;
+
+        // How many records of each kind are stored
+        let counts = RecordKind::ALL
+            .iter()
+            .map(|&kind| {
+                let count = match backend.count(kind, &session) {
+                    Ok(count) => count.to_string(),
+                    Err(err) => err.to_string(),
+                };
+                (backend.location(kind), count)
+            })
+            .collect::<Vec<_>>();
+        Ok(counts)
+    })
+    .await
+    .unwrap_or(Err("Storage task failed"));
+    let counts = match counts {
+        Ok(counts) => counts,
+        Err(err) => return error_page(err, "/unlock"),
+    };
+    let rows = counts
+        .iter()
+        .map(|(location, count)| {
+            format!(
+                "<p class='text-sm text-gray-300'>{}: <span class='font-medium text-white'>{}</span></p>",
+                escape_html(location),
+                escape_html(count)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("");
+
+    Html(html_template(
+        "Keystore Unlocked",
+        &format!(
+            r#"
+            <div class="flex items-center justify-center h-screen">
+                <div class="neumorphic p-8 max-w-md w-full fade-in">
+                    <h1 class="text-3xl font-bold mb-6 slide-up">Keystore Unlocked</h1>
+                    <div class="mb-4 p-4 bg-gray-700 rounded-lg">{}</div>
+                    <a href="/" class="btn w-full mt-6">Back to Home</a>
+                </div>
+            </div>
+            "#,
+            rows
+        ),
+    ))
+}
+
+// Message shown by the wallet pages while the keystore is locked
+const WALLETS_LOCKED: &str = "The keystore is locked. Unlock it from the Unlock page (/unlock) to browse saved wallets.";
+
+// Query string of the saved wallets list
+#[derive(Deserialize)]
+struct WalletsQuery {
+    #[serde(default)]
+    q: String,
+    page: Option<usize>,
+}
+
+// Loads one saved wallet by its number, with its label
+async fn load_wallet(id: usize) -> Result<Option<browse::Entry>, &'static str> {
+    with_session(WALLETS_LOCKED, move |session| browse::wallet(backend::current(), session, id)).await
+}
+
+// Handler listing saved wallets, filtered by address, label or date and split into pages
+async fn list_wallets(Query(query): Query<WalletsQuery>) -> impl IntoResponse {
+    let text = query.q.clone();
+    let number = query.page.unwrap_or(1);
+    let page = match with_session(WALLETS_LOCKED, move |session| browse::search(backend::current(), session, &text, number)).await {
+        Ok(page) => page,
+        Err(err) => return error_page(err, "/unlock"),
+    };
+
+    let rows = page
+        .entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "<tr><td class='p-2'><a href='/wallets/{}' class='text-purple-400'>#{}</a></td><td class='p-2 font-mono'>{}</td><td class='p-2'>{}</td><td class='p-2 text-gray-300'>{}</td></tr>",
+                entry.id,
+                entry.id,
+                escape_html(&entry.wallet.address),
+                escape_html(entry.label.as_ref().map(|label| label.label.as_str()).unwrap_or_default()),
+                escape_html(&entry.wallet.generated_at)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("");
+    // Previous/next buttons are small GET forms so the browser encodes the search text
+    let page_link = |number: usize, label: &str| {
+        format!(
+            r#"<form method="get" action="/wallets"><input type="hidden" name="q" value="{}"><input type="hidden" name="page" value="{}"><button type="submit" class="btn">{}</button></form>"#,
+            escape_html(&query.q),
+            number,
+            label
+        )
+    };
+    let previous = if page.number > 1 { page_link(page.number - 1, "Previous") } else { String::new() };
+    let next = if page.number < page.pages { page_link(page.number + 1, "Next") } else { String::new() };
+
+    Html(html_template(
+        "Saved Wallets",
+        &format!(
+            r#"
+            <div class="flex items-center justify-center min-h-screen pt-20">
+                <div class="neumorphic p-8 max-w-4xl w-full fade-in">
+                    <h1 class="text-3xl font-bold mb-6 slide-up">Saved Wallets</h1>
+                    <form method="get" action="/wallets" class="flex space-x-4 mb-6">
+                        <input type="text" name="q" value="{}" placeholder="Address, label or date (e.g. 2024-05-17)" class="block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md shadow-sm focus:outline-none focus:ring-purple-500 focus:border-purple-500 text-white">
+                        <button type="submit" class="btn">Search</button>
+                    </form>
+                    <p class="text-sm text-gray-300 mb-4">Page {} of {}, {} matching wallet(s).</p>
+                    <div class="scrollable-wallets">
+                        <table class="w-full text-sm text-left">
+                            <thead><tr><th class="p-2">#</th><th class="p-2">Address</th><th class="p-2">Label</th><th class="p-2">Saved at</th></tr></thead>
+                            <tbody>{}</tbody>
+                        </table>
+                    </div>
+                    <div class="flex space-x-4 mt-6">{}{}</div>
+                    <div class="flex space-x-4 mt-6">
+                        <a href="/watch_only?format=json" class="btn">Export watch-only JSON</a>
+                        <a href="/watch_only?format=csv" class="btn">Export watch-only CSV</a>
+                    </div>
+                </div>
+            </div>
+            "#,
+            escape_html(&query.q),
+            page.number,
+            page.pages,
+            page.total,
+            rows,
+            previous,
+            next
+        ),
+    ))
+}
+
+// Handler showing one saved wallet with its secrets hidden
+async fn show_wallet(UrlPath(id): UrlPath<usize>) -> impl IntoResponse {
+    wallet_page(id, false).await
+}
+
+// Handler showing one saved wallet with its secrets revealed (POST, so it is never cached or prefetched)
+async fn reveal_wallet(UrlPath(id): UrlPath<usize>) -> impl IntoResponse {
+    wallet_page(id, true).await
+}
+
+// Renders the detail page of a saved wallet, with its saved QR code (or a fresh, unsaved one)
+async fn wallet_page(id: usize, reveal: bool) -> Html<String> {
+    let (wallet, label) = match load_wallet(id).await {
+        Ok(Some(entry)) => (entry.wallet, entry.label),
+        Ok(None) => return error_page("No saved wallet with this number", "/wallets"),
+        Err(err) => return error_page(err, "/unlock"),
+    };
+    if reveal {
+        audit(Operation::RevealWallet, &[&wallet.address]).await;
+    }
+
+    let (svg, qr_note) = match backend::current().load_qr_code(&wallet.address) {
+        Ok(Some(svg)) => (Some(svg), "Saved QR code"),
+        _ => (
+            QrCode::new(&wallet.address).ok().map(|code| code.render::<qrcode::render::svg::Color>().min_dimensions(200, 200).build()),
+            "QR code (not saved)",
+        ),
+    };
+    // The SVG is shown as an image so stored markup can never run as part of the page
+    let qr_code = svg
+        .map(|svg| {
+            format!(
+                "<p class='text-sm text-gray-300 mb-2'>{}</p><img src='data:image/svg+xml;base64,{}' alt='QR code' class='bg-white p-2 rounded-lg'>",
+                qr_note,
+                base64::engine::general_purpose::STANDARD.encode(svg)
+            )
+        })
+        .unwrap_or_default();
+
+    let field = |label: &str, value: &str| {
+        format!("<p class='text-sm text-gray-300'>{}: <span class='font-medium text-white break-all'>{}</span></p>", label, escape_html(value))
+    };
+    let label = label.as_ref();
+    let mut fields = vec![field("Address", &wallet.address)];
+    if let Some(label) = label {
+        fields.push(field("Label", &label.label));
+        if let Some(note) = &label.note {
+            fields.push(field("Note", note));
+        }
+    }
+    fields.push(field("Public Key", &wallet.public_key));
+    if let Some(path) = &wallet.derivation_path {
+        fields.push(field("Derivation Path", path));
+    }
+    if let Some(seed_type) = &wallet.seed_type {
+        fields.push(field("Seed Type", seed_type));
+    }
+    fields.push(field("Saved At", &wallet.generated_at));
+    let (mnemonic, private_key) = if reveal { (wallet.mnemonic.as_str(), wallet.private_key.as_str()) } else { (browse::HIDDEN, browse::HIDDEN) };
+    fields.push(field("Mnemonic", mnemonic));
+    fields.push(field("Private Key", private_key));
+    let reveal_button = if reveal {
+        format!("<a href='/wallets/{}' class='btn'>Hide Secrets</a>", id)
+    } else {
+        format!("<form method='post' action='/wallets/{}'><button type='submit' class='btn'>Reveal Secrets</button></form>", id)
+    };
+
+    Html(html_template(
+        "Saved Wallet",
+        &format!(
+            r#"
+            <div class="flex items-center justify-center min-h-screen pt-20">
+                <div class="neumorphic p-8 max-w-3xl w-full fade-in">
+                    <h1 class="text-3xl font-bold mb-6 slide-up">Wallet #{}</h1>
+                    <div class="mb-4 p-4 bg-gray-700 rounded-lg">{}</div>
+                    <div class="mb-4">{}</div>
+                    <form method="post" action="/wallets/{}/export" class="flex space-x-4 mb-4">
+                        <select name="format" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">{}</select>
+                        <button type="submit" class="btn">Export Wallet File</button>
+                    </form>
+                    <form method="post" action="/labels" class="flex space-x-4 mb-4">
+                        <input type="hidden" name="label_type" value="addr">
+                        <input type="hidden" name="reference" value="{}">
+                        <input type="text" name="label" value="{}" placeholder="Label" class="block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">
+                        <input type="text" name="note" value="{}" placeholder="Note" class="block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">
+                        <button type="submit" class="btn">Save Label</button>
+                    </form>
+                    <div class="flex space-x-4 mt-6">
+                        {}
+                        <a href="/wallets" class="btn bg-gray-500 hover:bg-gray-600">Back to List</a>
+                    </div>
+                </div>
+            </div>
+            "#,
+            id,
+            fields.join(""),
+            qr_code,
+            id,
+            format_options(true),
+            escape_html(&wallet.address),
+            escape_html(label.map(|label| label.label.as_str()).unwrap_or_default()),
+            escape_html(label.and_then(|label| label.note.as_deref()).unwrap_or_default()),
+            reveal_button
+        ),
+    ))
+}
+
+// Form data structure for exporting a saved wallet to other wallet software
+#[derive(Deserialize)]
+struct ExportWalletRequest {
+    format: String,
+}
+
+// Handler to download a saved wallet in another wallet's format
+async fn export_wallet(UrlPath(id): UrlPath<usize>, Form(input): Form<ExportWalletRequest>) -> Response {
+    let back = format!("/wallets/{}", id);
+    let Some(format) = Format::from_name(&input.format).filter(Format::can_export) else {
+        return error_page("Unknown export format", &back).into_response();
+    };
+    let (wallet, label) = match load_wallet(id).await {
+        Ok(Some(entry)) => (entry.wallet, entry.label),
+        Ok(None) => return error_page("No saved wallet with this number", "/wallets").into_response(),
+        Err(err) => return error_page(err, "/unlock").into_response(),
+    };
+    match interop::export(format, &wallet, label.as_ref().map(|label| label.label.as_str())) {
+        Ok(contents) => {
+            audit(Operation::ExportWallet, &[format.name(), &wallet.address]).await;
+            (
+                [
+                    (header::CONTENT_TYPE, "application/json".to_string()),
+                    (header::CONTENT_DISPOSITION, format!("attachment; filename=\"wallet-{}-{}.json\"", id, format.name())),
+                ],
+                contents,
+            )
+                .into_response()
+        }
+        Err(err) => error_page(err, &back).into_response(),
+    }
+}
+
+// Message shown by the wallet files page while the keystore is locked
+const WALLET_FILES_LOCKED: &str = "The keystore is locked. Unlock it from the Unlock page (/unlock) to import wallet files.";
+
+// Form data structure for importing another wallet's file
+#[derive(Deserialize)]
+struct ImportWalletFileRequest {
+    format: String,
+    contents: String,
+}
+
+// Wallet files page handler
+async fn wallet_files_page() -> impl IntoResponse {
+    render_wallet_files_page("")
+}
+
+// Handler to import pasted wallet files of other wallet software into storage
+async fn import_wallet_file(Form(input): Form<ImportWalletFileRequest>) -> impl IntoResponse {
+    let Some(format) = Format::from_name(&input.format) else {
+        return error_page("Unknown import format", "/wallet_files");
+    };
+    let contents = Zeroizing::new(input.contents);
+    let result = with_session(WALLET_FILES_LOCKED, move |session| {
+        let imported = interop::import(format, &contents)?;
+        let subjects = imported.audit_subjects();
+        Ok((interop::save(imported, session)?, subjects))
+    })
+    .await;
+    match result {
+        Ok((summary, subjects)) => {
+            let subjects: Vec<&str> = [format.name()].into_iter().chain(subjects.iter().map(String::as_str)).collect();
+            audit(Operation::ImportWallets, &subjects).await;
+            render_wallet_files_page(&format!(
+                "Imported {} wallet(s), {} extended key(s), {} watch-only descriptor(s) and {} label(s); {} already saved.",
+                summary.wallets, summary.extended_keys, summary.descriptors, summary.labels, summary.duplicates
+            ))
+        }
+        Err(err) => error_page(err, "/wallet_files"),
+    }
+}
+
+// Options of a format select, limited to formats that can be exported when `export` is set
+fn format_options(export: bool) -> String {
+    Format::ALL
+        .into_iter()
+        .filter(|format| !export || format.can_export())
+        .map(|format| {
+            let secrets = if export && format.has_secrets() { " (holds private keys)" } else { "" };
+            format!("<option value='{}'>{}{}</option>", format.name(), format.describe(), secrets)
+        })
+        .collect()
+}
+
+// Renders the import form for other wallets' files
+fn render_wallet_files_page(notice: &str) -> Html<String> {
+    let notice = if notice.is_empty() { String::new() } else { format!("<p class='text-sm text-green-400 mb-4'>{}</p>", escape_html(notice)) };
+    Html(html_template(
+        "Wallet Files",
+        &format!(
+            r#"
+            <div class="flex items-center justify-center min-h-screen pt-20">
+                <div class="neumorphic p-8 max-w-4xl w-full fade-in">
+                    <h1 class="text-3xl font-bold mb-6 slide-up">Wallet Files</h1>
+                    {}
+                    <p class="text-sm text-gray-300 mb-4">Import keys, xpubs and labels from Electrum, Sparrow, Specter, Coldcard or Bitcoin Core. To export a saved wallet, open it from <a href="/wallets" class="text-purple-400">Saved Wallets</a>.</p>
+                    <form method="post" action="/wallet_files" class="space-y-4">
+                        <select name="format" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">{}</select>
+                        <label for="contents" class="block text-sm font-medium text-gray-300">Paste the file contents:</label>
+                        <textarea id="contents" name="contents" rows="10" required class="mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white font-mono"></textarea>
+                        <button type="submit" class="btn">Import</button>
+                    </form>
+                </div>
+            </div>
+            "#,
+            notice,
+            format_options(false)
+        ),
+    ))
+}
+
+// Form data structure for deriving addresses of a descriptor
+#[derive(Deserialize)]
+struct DescriptorRequest {
+    descriptor: String,
+    #[serde(default)]
+    start: u32,
+    #[serde(default = "default_address_count")]
+    count: u32,
+}
+
+// Number of addresses derived when the form does not say
+fn default_address_count() -> u32 {
+    10
+}
+
+// Descriptor form handler
+async fn descriptor_form() -> impl IntoResponse {
+    render_descriptor_page("", 0, default_address_count(), "")
+}
+
+// Handler deriving addresses, scripts and key paths of a pasted descriptor
+async fn derive_descriptor(Form(input): Form<DescriptorRequest>) -> impl IntoResponse {
+    let text = input.descriptor.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let descriptor = Descriptor::parse(&text)?;
+        let derived = descriptor.derive_range(input.start, input.count.max(1))?;
+        Ok::<_, &'static str>((descriptor, derived))
+    })
+    .await
+    .unwrap_or(Err("Derivation task failed"));
+    let (descriptor, derived) = match result {
+        Ok(result) => result,
+        Err(err) => return error_page(err, "/descriptor"),
+    };
+
+    let results = format!(
+        r#"<p class="text-sm text-gray-300 mb-4 break-all">Valid {} descriptor: <code class="bg-gray-700 p-1 rounded">{}</code></p>{}"#,
+        descriptor.address_type(),
+        escape_html(&descriptor.with_checksum()),
+        derived_table(&derived)
+    );
+    render_descriptor_page(&input.descriptor, input.start, input.count, &results)
+}
+
+// Table of derived addresses, each with a small form posting to the QR code page
+fn derived_table(derived: &[Derived]) -> String {
+    let rows = derived
+        .iter()
+        .map(|address| {
+            format!(
+                r#"<tr><td class="p-2">{}</td><td class="p-2 font-mono break-all">{}</td><td class="p-2 font-mono break-all">{}</td><td class="p-2 font-mono">{}</td><td class="p-2"><form method="post" action="/qr_code"><input type="hidden" name="address" value="{}"><button type="submit" class="btn">QR</button></form></td></tr>"#,
+                address.index,
+                escape_html(&address.address),
+                escape_html(&address.script_pubkey),
+                address.paths.iter().map(|path| escape_html(path)).collect::<Vec<_>>().join("<br>"),
+                escape_html(&address.address)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("");
+    format!(
+        r#"
+        <div class="scrollable-wallets">
+            <table class="w-full text-sm text-left">
+                <thead><tr><th class="p-2">Index</th><th class="p-2">Address</th><th class="p-2">Script</th><th class="p-2">Path(s)</th><th class="p-2"></th></tr></thead>
+                <tbody>{}</tbody>
+            </table>
+        </div>
+        "#,
+        rows
+    )
+}
+
+// Message shown by the multisig pages while the keystore is locked
+const MULTISIG_LOCKED: &str = "The keystore is locked. Unlock it from the Unlock page (/unlock) to manage multisig wallets.";
+
+// Form data structure for creating a multisig wallet
+#[derive(Deserialize)]
+struct MultisigRequest {
+    name: String,
+    script: String,
+    #[serde(default)]
+    account: u32,
+    #[serde(default)]
+    local: usize, // Number of keys generated here
+    #[serde(default)]
+    keys: String, // Pasted cosigner keys, one per line
+    threshold: usize,
+}
+
+// Query of a saved multisig wallet's page
+#[derive(Deserialize)]
+struct MultisigQuery {
+    #[serde(default)]
+    change: bool,
+    #[serde(default)]
+    start: u32,
+}
+
+// Multisig page handler: saved wallets and the creation form
+async fn multisig_page() -> impl IntoResponse {
+    let saved = match with_session(MULTISIG_LOCKED, multisig::load).await {
+        Ok(saved) => saved,
+        Err(err) => return error_page(err, "/unlock"),
+    };
+    let malformed = match saved.malformed.is_empty() {
+        true => String::new(),
+        false => format!(
+            r#"<p class="text-sm text-yellow-400 mb-4">Multisig wallet record(s) #{} could not be read and are skipped. The <a href="/verify" class="text-purple-400">Verify</a> page shows why.</p>"#,
+            saved.malformed.iter().map(ToString::to_string).collect::<Vec<_>>().join(", #")
+        ),
+    };
+    let rows = saved
+        .wallets
+        .iter()
+        .map(|(number, wallet)| {
+            format!(
+                r#"<tr><td class="p-2"><a href="/multisig/{}" class="text-purple-400">#{}</a></td><td class="p-2">{}</td><td class="p-2">{}</td><td class="p-2">{}</td></tr>"#,
+                number,
+                number,
+                escape_html(&wallet.name),
+                wallet.policy(),
+                wallet.script.name()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("");
+    let scripts = multisig::SCRIPTS
+        .iter()
+        .map(|script| format!(r#"<option value="{0}">{0}</option>"#, script.name()))
+        .collect::<Vec<_>>()
+        .join("");
+
+    Html(html_template(
+        "Multisig Wallets",
+        &format!(
+            r#"
+            <div class="flex items-center justify-center min-h-screen pt-20">
+                <div class="neumorphic p-8 max-w-4xl w-full fade-in">
+                    <h1 class="text-3xl font-bold mb-6 slide-up">Multisig Wallets</h1>
+                    {}
+                    <table class="w-full text-sm text-left mb-6">
+                        <thead><tr><th class="p-2">#</th><th class="p-2">Name</th><th class="p-2">Policy</th><th class="p-2">Script</th></tr></thead>
+                        <tbody>{}</tbody>
+                    </table>
+                    <h2 class="text-xl font-bold mb-4">Create a multisig wallet</h2>
+                    <p class="text-sm text-gray-300 mb-4">Cosigners use BIP48 account keys (m/48'/0'/account'/2' for P2WSH, /1' for P2SH-P2WSH) in a sortedmulti script. Keys generated here are saved as extended keys, and their mnemonics are shown once.</p>
+                    <form method="post" action="/multisig" class="space-y-4">
+                        <input type="text" name="name" maxlength="20" required placeholder="Wallet name" class="block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">
+                        <div class="flex space-x-4">
+                            <select name="script" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">{}</select>
+                            <label class="text-sm text-gray-300">Account <input type="number" name="account" min="0" value="0" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white"></label>
+                            <label class="text-sm text-gray-300">Keys generated here <input type="number" name="local" min="0" max="{}" value="1" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white"></label>
+                            <label class="text-sm text-gray-300">Signatures required <input type="number" name="threshold" min="1" max="{}" value="2" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white"></label>
+                        </div>
+                        <label for="keys" class="block text-sm font-medium text-gray-300">Other cosigners' keys, one per line ([fingerprint/48h/0h/0h/2h]xpub...):</label>
+                        <textarea id="keys" name="keys" rows="4" class="block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white font-mono"></textarea>
+                        <button type="submit" class="btn">Create and Save</button>
+                    </form>
+                </div>
+            </div>
+            "#,
+            malformed,
+            rows,
+            scripts,
+            multisig::MAX_COSIGNERS,
+            multisig::MAX_COSIGNERS
+        ),
+    ))
+}
+
+// Handler creating and saving a multisig wallet, showing the mnemonics of the keys generated here once
+async fn create_multisig(Form(input): Form<MultisigRequest>) -> impl IntoResponse {
+    let result = with_session(MULTISIG_LOCKED, move |session| {
+        let script = multisig::script_from_name(&input.script).ok_or("Unknown multisig script type")?;
+        if input.local > multisig::MAX_COSIGNERS {
+            return Err("A multisig wallet needs 2 to 15 cosigners");
+        }
+        let generated = (0..input.local).map(|_| multisig::generate_cosigner(script, input.account)).collect::<Result<Vec<_>, _>>()?;
+        let pasted = input.keys.lines().filter(|line| !line.trim().is_empty()).map(Cosigner::parse).collect::<Result<Vec<_>, _>>()?;
+        let cosigners = generated.iter().map(|(_, _, cosigner)| cosigner.clone()).chain(pasted).collect();
+        let wallet = Multisig::new(&input.name, input.threshold, script, cosigners)?;
+        let masters: Vec<&ExtendedPrivKey> = generated.iter().map(|(_, master, _)| master).collect();
+        multisig::save(&wallet, &masters, session)?;
+        let number = multisig::load(session)?.wallets.last().map(|(number, _)| *number).ok_or("Failed to read the saved multisig wallet")?;
+        let mnemonics: Vec<(String, Zeroizing<String>)> = generated
+            .iter()
+            .map(|(mnemonic, _, cosigner)| (cosigner.fingerprint.clone(), Zeroizing::new(mnemonic.expose_phrase().to_string())))
+            .collect();
+        Ok((number, wallet, mnemonics))
+    })
+    .await;
+    let (id, wallet, mnemonics) = match result {
+        Ok(created) => created,
+        Err(err) => return error_page(err, "/multisig"),
+    };
+    let subjects: Vec<&str> = [wallet.name.as_str()].into_iter().chain(wallet.cosigners.iter().map(|cosigner| cosigner.fingerprint.as_str())).collect();
+    audit(Operation::CreateMultisig, &subjects).await;
+
+    let mnemonics = mnemonics
+        .iter()
+        .map(|(fingerprint, phrase)| format!("<p class='text-sm text-gray-300'>Key {}: <span class='font-medium text-white'>{}</span></p>", fingerprint, escape_html(phrase)))
+        .collect::<Vec<_>>()
+        .join("");
+    let backup = if mnemonics.is_empty() {
+        String::new()
+    } else {
+        format!("<div class='mb-4 p-4 bg-gray-700 rounded-lg'><p class='text-sm text-yellow-400 mb-2'>Write down the mnemonics of the keys generated here. They are not shown again.</p>{}</div>", mnemonics)
+    };
+    render_multisig(id, &wallet, &backup, false, 0)
+}
+
+// Handler showing a saved multisig wallet with its descriptors and ten addresses
+async fn show_multisig(UrlPath(id): UrlPath<usize>, Query(query): Query<MultisigQuery>) -> impl IntoResponse {
+    match load_multisig(id).await {
+        Ok(wallet) => render_multisig(id, &wallet, "", query.change, query.start),
+        Err(err) => error_page(err, "/multisig"),
+    }
+}
+
+// Handler downloading the Coldcard multisig setup file of a saved wallet, which Sparrow imports too
+async fn export_multisig_config(UrlPath(id): UrlPath<usize>) -> Response {
+    let wallet = match load_multisig(id).await {
+        Ok(wallet) => wallet,
+        Err(err) => return error_page(err, "/multisig").into_response(),
+    };
+    audit(Operation::ExportWallet, &["coldcard-multisig", &wallet.name]).await;
+    (
+        [
+            (header::CONTENT_TYPE, "text/plain".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"multisig-{}-coldcard.txt\"", id)),
+        ],
+        wallet.coordinator_config(),
+    )
+        .into_response()
+}
+
+// Saved multisig wallet by its 1-based number
+async fn load_multisig(id: usize) -> Result<Multisig, &'static str> {
+    let saved = with_session(MULTISIG_LOCKED, multisig::load).await?;
+    match saved.wallets.into_iter().find(|(number, _)| *number == id) {
+        Some((_, wallet)) => Ok(wallet),
+        None => Err("No saved multisig wallet with this number"),
+    }
+}
+
+// Renders a multisig wallet: cosigners, descriptors and ten receiving or change addresses from `start`
+fn render_multisig(id: usize, wallet: &Multisig, notice: &str, change: bool, start: u32) -> Html<String> {
+    let derived = match wallet.addresses(change, start, 10) {
+        Ok(derived) => derived,
+        Err(err) => return error_page(err, "/multisig"),
+    };
+    let cosigners = wallet
+        .cosigners
+        .iter()
+        .map(|cosigner| format!("<p class='text-sm font-mono break-all text-white'>{}{}</p>", escape_html(&cosigner.key()), if cosigner.local { " (generated here)" } else { "" }))
+        .collect::<Vec<_>>()
+        .join("");
+    Html(html_template(
+        "Multisig Wallet",
+        &format!(
+            r#"
+            <div class="flex items-center justify-center min-h-screen pt-20">
+                <div class="neumorphic p-8 max-w-4xl w-full fade-in">
+                    <h1 class="text-3xl font-bold mb-6 slide-up">{} ({} {})</h1>
+                    {}
+                    <p class="text-sm text-gray-300 mb-2">Cosigners:</p>
+                    <div class="mb-4">{}</div>
+                    <p class="text-sm text-gray-300 break-all mb-2">Receive descriptor: <code class="bg-gray-700 p-1 rounded">{}</code></p>
+                    <p class="text-sm text-gray-300 break-all mb-4">Change descriptor: <code class="bg-gray-700 p-1 rounded">{}</code></p>
+                    <form method="get" action="/multisig/{}" class="flex space-x-4 mb-4">
+                        <select name="change" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white"><option value="false"{}>Receiving</option><option value="true"{}>Change</option></select>
+                        <label class="text-sm text-gray-300">From index <input type="number" name="start" min="0" value="{}" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white"></label>
+                        <button type="submit" class="btn">Show</button>
+                    </form>
+                    {}
+                    <div class="flex space-x-4 mt-6">
+                        <a href="/multisig/{}/config" class="btn">Download Coldcard / Sparrow config</a>
+                        <a href="/multisig" class="btn">All multisig wallets</a>
+                    </div>
+                </div>
+            </div>
+            "#,
+            escape_html(&wallet.name),
+            wallet.policy(),
+            wallet.script.name(),
+            notice,
+            cosigners,
+            escape_html(&wallet.descriptor(false).with_checksum()),
+            escape_html(&wallet.descriptor(true).with_checksum()),
+            id,
+            if change { "" } else { " selected" },
+            if change { " selected" } else { "" },
+            start,
+            derived_table(&derived),
+            id
+        ),
+    ))
+}
+
+// Message shown by the BSMS page while the keystore is locked
+const BSMS_LOCKED: &str = "The keystore is locked. Unlock it from the Unlock page (/unlock) to set up multisig wallets over BSMS.";
+
+// Form data structure for starting a BSMS session
+#[derive(Deserialize)]
+struct BsmsSessionRequest {
+    encryption: String,
+    signers: usize,
+}
+
+// Form data structure for combining key records into a descriptor record
+#[derive(Deserialize)]
+struct BsmsCoordinateRequest {
+    name: String,
+    script: String,
+    threshold: usize,
+    tokens: String,  // Session tokens, one per line
+    records: String, // Key records, separated by empty lines
+    #[serde(default)]
+    save: bool,
+}
+
+// Form data structure for signing a key record with a saved extended key
+#[derive(Deserialize)]
+struct BsmsSignRequest {
+    key: usize, // 1-based number of the saved extended key
+    script: String,
+    #[serde(default)]
+    account: u32,
+    token: String,
+    #[serde(default)]
+    description: String,
+}
+
+// Form data structure for checking a descriptor record against a saved extended key
+#[derive(Deserialize)]
+struct BsmsCheckRequest {
+    key: usize, // 1-based number of the saved extended key
+    token: String,
+    record: String,
+    name: String,
+    #[serde(default)]
+    save: bool,
+}
+
+// BSMS page handler: the coordinator and signer forms
+async fn bsms_page() -> impl IntoResponse {
+    render_bsms_page("").await
+}
+
+// Handler starting a BSMS session, showing the tokens to hand to the signers
+async fn start_bsms_session(Form(input): Form<BsmsSessionRequest>) -> impl IntoResponse {
+    let Some(encryption) = Encryption::from_name(&input.encryption) else {
+        return error_page("Unknown encryption mode", "/bsms");
+    };
+    if !(2..=multisig::MAX_COSIGNERS).contains(&input.signers) {
+        return error_page("A multisig wallet needs 2 to 15 cosigners", "/bsms");
+    }
+    let tokens = encryption
+        .new_tokens(input.signers)
+        .iter()
+        .map(|token| format!("<p class='text-sm font-mono text-white'>{}</p>", token))
+        .collect::<Vec<_>>()
+        .join("");
+    render_bsms_page(&format!(
+        "<div class='mb-4 p-4 bg-gray-700 rounded-lg'><p class='text-sm text-yellow-400 mb-2'>Session tokens ({}); hand one to each signer and keep them to combine the key records.</p>{}</div>",
+        encryption.name(),
+        tokens
+    ))
+    .await
+}
+
+// Handler checking the signers' key records and showing the descriptor record for each token
+async fn coordinate_bsms(Form(input): Form<BsmsCoordinateRequest>) -> impl IntoResponse {
+    let result = with_session(BSMS_LOCKED, move |session| {
+        let script = multisig::script_from_name(&input.script).ok_or("Unknown multisig script type")?;
+        let tokens: Vec<String> = input.tokens.split_whitespace().map(str::to_string).collect();
+        let setup = bsms::coordinate(&input.name, input.threshold, script, &tokens, &bsms::split_records(&input.records))?;
+        if input.save {
+            multisig::save(&setup.multisig, &[], session)?;
+        }
+        Ok((setup, input.save))
+    })
+    .await;
+    let (setup, saved) = match result {
+        Ok(result) => result,
+        Err(err) => return error_page(err, "/bsms"),
+    };
+    let wallet = &setup.multisig;
+    if saved {
+        let subjects: Vec<&str> = [wallet.name.as_str()].into_iter().chain(wallet.cosigners.iter().map(|cosigner| cosigner.fingerprint.as_str())).collect();
+        audit(Operation::CreateMultisig, &subjects).await;
+    }
+
+    let signers = wallet
+        .cosigners
+        .iter()
+        .zip(&setup.descriptions)
+        .map(|(cosigner, description)| format!("<p class='text-sm text-gray-300'>Signer {}: {}</p>", cosigner.fingerprint, escape_html(description)))
+        .collect::<Vec<_>>()
+        .join("");
+    let records = setup
+        .records
+        .iter()
+        .map(|(token, record)| format!("<p class='text-sm text-gray-300 mt-2'>Descriptor record for token {}:</p><pre class='text-sm font-mono break-all whitespace-pre-wrap bg-gray-800 p-2 rounded'>{}</pre>", token, escape_html(record)))
+        .collect::<Vec<_>>()
+        .join("");
+    render_bsms_page(&format!(
+        "<div class='mb-4 p-4 bg-gray-700 rounded-lg'><p class='text-sm text-green-400 mb-2'>{} {} wallet \"{}\"{}.</p>{}<p class='text-sm text-gray-300 break-all'>Receive descriptor: <code>{}</code></p>{}</div>",
+        wallet.policy(),
+        wallet.script.name(),
+        escape_html(&wallet.name),
+        if saved { " saved" } else { " (not saved)" },
+        signers,
+        escape_html(&wallet.descriptor(false).with_checksum()),
+        records
+    ))
+    .await
+}
+
+// Handler signing the BIP48 account key of a saved extended key for a session
+async fn sign_key_record(Form(input): Form<BsmsSignRequest>) -> impl IntoResponse {
+    let result = with_session(BSMS_LOCKED, move |session| {
+        let script = multisig::script_from_name(&input.script).ok_or("Unknown multisig script type")?;
+        let master = signer_key(session, input.key)?;
+        let record = bsms::KeyRecord::sign(&master, script, input.account, &input.token, &input.description)?;
+        Ok((master.fingerprint()?, bsms::encrypt(&input.token, &record)?))
+    })
+    .await;
+    let (fingerprint, record) = match result {
+        Ok(result) => result,
+        Err(err) => return error_page(err, "/bsms"),
+    };
+    audit(Operation::SignKeyRecord, &[&fingerprint]).await;
+    render_bsms_page(&format!(
+        "<div class='mb-4 p-4 bg-gray-700 rounded-lg'><p class='text-sm text-green-400 mb-2'>Key record of {} for the coordinator:</p><pre class='text-sm font-mono break-all whitespace-pre-wrap bg-gray-800 p-2 rounded'>{}</pre></div>",
+        fingerprint,
+        escape_html(&record)
+    ))
+    .await
+}
+
+// Handler checking that a descriptor record includes a saved key and derives the address it states
+async fn check_descriptor_record(Form(input): Form<BsmsCheckRequest>) -> impl IntoResponse {
+    let result = with_session(BSMS_LOCKED, move |session| {
+        let master = signer_key(session, input.key)?;
+        let wallet = bsms::check_descriptor_record(&input.record, input.token.trim(), &master, &input.name)?;
+        if input.save {
+            multisig::save(&wallet, &[], session)?;
+        }
+        Ok((wallet, input.save))
+    })
+    .await;
+    let (wallet, saved) = match result {
+        Ok(result) => result,
+        Err(err) => return error_page(err, "/bsms"),
+    };
+    if saved {
+        let subjects: Vec<&str> = [wallet.name.as_str()].into_iter().chain(wallet.cosigners.iter().map(|cosigner| cosigner.fingerprint.as_str())).collect();
+        audit(Operation::CreateMultisig, &subjects).await;
+    }
+    let address = match wallet.addresses(false, 0, 1) {
+        Ok(derived) => derived[0].address.clone(),
+        Err(err) => return error_page(err, "/bsms"),
+    };
+    render_bsms_page(&format!(
+        "<div class='mb-4 p-4 bg-gray-700 rounded-lg'><p class='text-sm text-green-400 mb-2'>Descriptor record checked: {} {} wallet including this key{}.</p><p class='text-sm text-gray-300 break-all'>Receive descriptor: <code>{}</code></p><p class='text-sm text-gray-300'>First address (compare it with the coordinator and the other signers): <span class='font-mono text-white'>{}</span></p></div>",
+        wallet.policy(),
+        wallet.script.name(),
+        if saved { ", saved" } else { "" },
+        escape_html(&wallet.descriptor(false).with_checksum()),
+        address
+    ))
+    .await
+}
+
+// Saved extended key by its 1-based number
+fn signer_key(session: &Session, number: usize) -> Result<ExtendedPrivKey, &'static str> {
+    let mut keys = bsms::signer_keys(session)?;
+    match number.checked_sub(1).filter(|&index| index < keys.len()) {
+        Some(index) => Ok(keys.swap_remove(index)),
+        None => Err("No saved extended key with this number"),
+    }
+}
+
+// Renders the BSMS page: a result notice, then the coordinator and signer forms
+async fn render_bsms_page(notice: &str) -> Html<String> {
+    let keys = match with_session(BSMS_LOCKED, bsms::signer_keys).await {
+        Ok(keys) => keys,
+        Err(err) => return error_page(err, "/unlock"),
+    };
+    let keys = keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| format!(r#"<option value="{}">#{} {}</option>"#, i + 1, i + 1, key.fingerprint().unwrap_or_default()))
+        .collect::<Vec<_>>()
+        .join("");
+    let scripts = multisig::SCRIPTS
+        .iter()
+        .map(|script| format!(r#"<option value="{0}">{0}</option>"#, script.name()))
+        .collect::<Vec<_>>()
+        .join("");
+    let encryptions = Encryption::ALL
+        .iter()
+        .map(|encryption| format!(r#"<option value="{}">{}</option>"#, encryption.name(), encryption.describe()))
+        .collect::<Vec<_>>()
+        .join("");
+    let save = r#"<select name="save" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white"><option value="false">Don't save</option><option value="true">Save the wallet</option></select>"#;
+
+    Html(html_template(
+        "BSMS Multisig Setup",
+        &format!(
+            r#"
+            <div class="flex items-center justify-center min-h-screen pt-20">
+                <div class="neumorphic p-8 max-w-4xl w-full fade-in">
+                    <h1 class="text-3xl font-bold mb-6 slide-up">BSMS Multisig Setup</h1>
+                    <p class="text-sm text-gray-300 mb-4">BIP129 sets up a sortedmulti wallet without trusting the coordinator: signers send signed key records, and check that the descriptor record includes their key and derives the stated first address.</p>
+                    {}
+                    <h2 class="text-xl font-bold mb-4">Coordinator: start a session</h2>
+                    <form method="post" action="/bsms/session" class="flex space-x-4 mb-6">
+                        <select name="encryption" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">{}</select>
+                        <label class="text-sm text-gray-300">Signers <input type="number" name="signers" min="2" max="{}" value="3" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white"></label>
+                        <button type="submit" class="btn">Create Tokens</button>
+                    </form>
+                    <h2 class="text-xl font-bold mb-4">Coordinator: combine key records</h2>
+                    <form method="post" action="/bsms/coordinate" class="space-y-4 mb-6">
+                        <input type="text" name="name" maxlength="20" required placeholder="Wallet name" class="block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">
+                        <div class="flex space-x-4">
+                            <select name="script" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">{}</select>
+                            <label class="text-sm text-gray-300">Signatures required <input type="number" name="threshold" min="1" max="{}" value="2" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white"></label>
+                            {}
+                        </div>
+                        <textarea name="tokens" rows="2" required placeholder="Session tokens, one per line (00 without encryption)" class="block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white font-mono"></textarea>
+                        <textarea name="records" rows="8" required placeholder="Key records, separated by empty lines" class="block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white font-mono"></textarea>
+                        <button type="submit" class="btn">Combine</button>
+                    </form>
+                    <h2 class="text-xl font-bold mb-4">Signer: make a key record</h2>
+                    <form method="post" action="/bsms/sign" class="space-y-4 mb-6">
+                        <div class="flex space-x-4">
+                            <select name="key" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">{}</select>
+                            <select name="script" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">{}</select>
+                            <label class="text-sm text-gray-300">Account <input type="number" name="account" min="0" value="0" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white"></label>
+                        </div>
+                        <input type="text" name="token" required placeholder="Session token (00 without encryption)" class="block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white font-mono">
+                        <input type="text" name="description" maxlength="80" placeholder="Description" class="block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">
+                        <button type="submit" class="btn">Sign Key Record</button>
+                    </form>
+                    <h2 class="text-xl font-bold mb-4">Signer: check a descriptor record</h2>
+                    <form method="post" action="/bsms/check" class="space-y-4">
+                        <div class="flex space-x-4">
+                            <select name="key" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">{}</select>
+                            <input type="text" name="name" maxlength="20" required placeholder="Wallet name" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">
+                            {}
+                        </div>
+                        <input type="text" name="token" required placeholder="Session token (00 without encryption)" class="block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white font-mono">
+                        <textarea name="record" rows="5" required placeholder="Descriptor record" class="block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white font-mono"></textarea>
+                        <button type="submit" class="btn">Check</button>
+                    </form>
+                </div>
+            </div>
+            "#,
+            notice,
+            encryptions,
+            multisig::MAX_COSIGNERS,
+            scripts,
+            multisig::MAX_COSIGNERS,
+            save,
+            keys,
+            scripts,
+            keys,
+            save
+        ),
+    ))
+}
+
+// Renders the descriptor form, followed by the derived addresses when there are any
+fn render_descriptor_page(descriptor: &str, start: u32, count: u32, results: &str) -> Html<String> {
+    Html(html_template(
+        "Descriptor Addresses",
+        &format!(
+            r#"
+            <div class="flex items-center justify-center min-h-screen pt-20">
+                <div class="neumorphic p-8 max-w-4xl w-full fade-in">
+                    <h1 class="text-3xl font-bold mb-6 slide-up">Descriptor Addresses</h1>
+                    <p class="text-sm text-gray-300 mb-4">Paste a descriptor exported from a hardware wallet, Sparrow or Bitcoin Core, e.g. <code class="bg-gray-700 p-1 rounded">wpkh([d34db33f/84h/0h/0h]xpub.../0/*)#checksum</code>. The checksum is optional, but checked when present.</p>
+                    <form method="post" action="/descriptor" class="space-y-4 mb-6">
+                        <textarea name="descriptor" rows="4" required class="block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white font-mono">{}</textarea>
+                        <div class="flex space-x-4">
+                            <label class="text-sm text-gray-300">First index <input type="number" name="start" min="0" value="{}" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white"></label>
+                            <label class="text-sm text-gray-300">Addresses <input type="number" name="count" min="1" max="{}" value="{}" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white"></label>
+                        </div>
+                        <button type="submit" class="btn">Derive</button>
+                    </form>
+                    {}
+                </div>
+            </div>
+            "#,
+            escape_html(descriptor),
+            start,
+            descriptor::MAX_RANGE,
+            count,
+            results
+        ),
+    ))
+}
+
+// Message shown by the label pages while the keystore is locked
+const LABELS_LOCKED: &str = "The keystore is locked. Unlock it from the Unlock page (/unlock) to manage labels.";
+
+// Form data structure for adding or changing a label
+#[derive(Deserialize)]
+struct LabelRequest {
+    label_type: String,
+    reference: String,
+    label: String,
+    #[serde(default)]
+    note: String,
+}
+
+// Form data structure for importing BIP329 labels
+#[derive(Deserialize)]
+struct ImportLabelsRequest {
+    labels: String,
+}
+
+// Labels page handler
+async fn labels_page() -> impl IntoResponse {
+    render_labels_page("").await
+}
+
+// Handler to add, change or remove (empty label and note) the label of a reference
+async fn save_label(Form(input): Form<LabelRequest>) -> impl IntoResponse {
+    let result = with_session(LABELS_LOCKED, move |session| {
+        let label = labels::new_label(&input.label_type, &input.reference, &input.label, Some(&input.note))?;
+        backend::current().append_records(session, &[label])
+    })
+    .await;
+    match result {
+        Ok(()) => render_labels_page("Label saved.").await,
+        Err(err) => error_page(err, "/labels"),
+    }
+}
+
+// Handler to import pasted BIP329 JSON Lines
+async fn import_labels(Form(input): Form<ImportLabelsRequest>) -> impl IntoResponse {
+    match with_session(LABELS_LOCKED, move |session| labels::import_bip329(session, &input.labels)).await {
+        Ok(count) => render_labels_page(&format!("{} label(s) imported.", count)).await,
+        Err(err) => error_page(err, "/labels"),
+    }
+}
+
+// Handler to download all labels as a BIP329 JSON Lines file
+async fn export_labels() -> Response {
+    match with_session(LABELS_LOCKED, labels::load).await {
+        Ok(labels) => {
+            audit(Operation::ExportLabels, &["labels.jsonl"]).await;
+            (
+            [
+                (header::CONTENT_TYPE, "application/jsonl"),
+                (header::CONTENT_DISPOSITION, "attachment; filename=\"labels.jsonl\""),
+            ],
+            labels.to_bip329(),
+        )
+            .into_response()
+        }
+        Err(err) => error_page(err, "/labels").into_response(),
+    }
+}
+
+// Renders the current labels with the forms to add, import and export them
+async fn render_labels_page(notice: &str) -> Html<String> {
+    let labels = match with_session(LABELS_LOCKED, labels::load).await {
+        Ok(labels) => labels,
+        Err(err) => return error_page(err, "/unlock"),
+    };
+    let rows = labels
+        .iter()
+        .map(|label| {
+            format!(
+                "<tr><td class='p-2'>{}</td><td class='p-2 font-mono break-all'>{}</td><td class='p-2'>{}</td><td class='p-2 text-gray-300'>{}</td></tr>",
+                escape_html(&label.label_type),
+                escape_html(&label.reference),
+                escape_html(&label.label),
+                escape_html(label.note.as_deref().unwrap_or_default())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("");
+    let types = labels::BIP329_TYPES.iter().map(|label_type| format!("<option value='{0}'>{0}</option>", label_type)).collect::<Vec<_>>().join("");
+    let notice = if notice.is_empty() { String::new() } else { format!("<p class='text-sm text-green-400 mb-4'>{}</p>", escape_html(notice)) };
+
+    Html(html_template(
+        "Labels",
+        &format!(
+            r#"
+            <div class="flex items-center justify-center min-h-screen pt-20">
+                <div class="neumorphic p-8 max-w-4xl w-full fade-in">
+                    <h1 class="text-3xl font-bold mb-6 slide-up">Labels</h1>
+                    {}
+                    <div class="scrollable-wallets mb-6">
+                        <table class="w-full text-sm text-left">
+                            <thead><tr><th class="p-2">Type</th><th class="p-2">Reference</th><th class="p-2">Label</th><th class="p-2">Note</th></tr></thead>
+                            <tbody>{}</tbody>
+                        </table>
+                    </div>
+                    <form method="post" action="/labels" class="space-y-4 mb-6">
+                        <div class="flex space-x-4">
+                            <select name="label_type" class="px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">{}</select>
+                            <input type="text" name="reference" required placeholder="Address, public key, xpub or other reference" class="block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">
+                        </div>
+                        <div class="flex space-x-4">
+                            <input type="text" name="label" placeholder="Label (empty removes it)" class="block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">
+                            <input type="text" name="note" placeholder="Note (kept locally)" class="block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white">
+                            <button type="submit" class="btn">Save</button>
+                        </div>
+                    </form>
+                    <form method="post" action="/labels/import" class="space-y-4">
+                        <label for="labels" class="block text-sm font-medium text-gray-300">Import BIP329 labels (paste JSON Lines, e.g. a Sparrow export):</label>
+                        <textarea id="labels" name="labels" rows="4" required class="mt-1 block w-full px-3 py-2 bg-gray-700 border border-gray-600 rounded-md text-white font-mono"></textarea>
+                        <div class="flex space-x-4">
+                            <button type="submit" class="btn">Import</button>
+                            <a href="/labels/export" class="btn">Export BIP329</a>
+                        </div>
+                    </form>
+                </div>
+            </div>
+            "#,
+            notice,
+            rows,
+            types
+        ),
+    ))
+}
+
+// Query string of the watch-only export
+#[derive(Deserialize)]
+struct WatchOnlyQuery {
+    #[serde(default)]
+    format: String,
+    #[serde(default)]
+    confirmed: bool, // Download even though records with invalid keys are left out
+}
+
+// Handler to download every saved wallet and key without its mnemonic and private key
+// (when records with invalid keys would be left out, a page first says how many)
+async fn export_watch_only(Query(query): Query<WatchOnlyQuery>) -> Response {
+    let (entries, skipped) = match with_session(WALLETS_LOCKED, watch_only::collect).await {
+        Ok(collected) => collected,
+        Err(err) => return error_page(err, "/unlock").into_response(),
+    };
+    let (content_type, file_name, contents) = match query.format.as_str() {
+        "csv" => ("text/csv", "watch_only.csv", watch_only::to_csv(&entries)),
+        "" | "json" => ("application/json", "watch_only.json", watch_only::to_json(&entries)),
+        _ => return error_page("Unknown export format (expected json or csv)", "/wallets").into_response(),
+    };
+    if skipped > 0 && !query.confirmed {
+        return Html(html_template(
+            "Watch-only Export",
+            &format!(
+                r#"
+            <div class="flex items-center justify-center h-screen">
+                <div class="neumorphic p-8 max-w-md w-full fade-in">
+                    <h1 class="text-3xl font-bold mb-6 slide-up">Watch-only Export</h1>
+                    <p class="text-sm text-yellow-400 mb-4">{} record(s) with invalid keys will be left out of the export. The <a href="/verify" class="text-purple-400">Verify</a> page lists them.</p>
+                    <p class="text-sm text-gray-300 mb-4">{} entries will be exported.</p>
+                    <div class="flex space-x-4">
+                        <a href="/watch_only?format={}&amp;confirmed=true" class="btn">Download {}</a>
+                        <a href="/wallets" class="btn">Back to Saved Wallets</a>
+                    </div>
+                </div>
+            </div>
+            "#,
+                skipped,
+                entries.len(),
+                if content_type == "text/csv" { "csv" } else { "json" },
+                file_name
+            ),
+        ))
+        .into_response();
+    }
+    audit(Operation::ExportWatchOnly, &[file_name]).await;
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+        ],
+        contents,
+    )
+        .into_response()
+}
+
+// Message shown by the verification page while the keystore is locked
+const VERIFY_LOCKED: &str = "The keystore is locked. Unlock it from the Unlock page (/unlock) to verify saved records.";
+
+// Handler re-deriving every saved wallet and key and listing the records whose fields disagree
+async fn verify_page() -> impl IntoResponse {
+    let report = match with_session(VERIFY_LOCKED, verify::run).await {
+        Ok(report) => report,
+        Err(err) => return error_page(err, "/unlock"),
+    };
+    let rows = report
+        .findings
+        .iter()
+        .map(|finding| {
+            // Wallet numbers link to the saved wallet browser
+            let number = match finding.kind {
+                RecordKind::Wallet => format!("<a href='/wallets/{0}' class='text-purple-400'>#{0}</a>", finding.number),
+                _ => format!("#{}", finding.number),
+            };
+            format!(
+                "<tr><td class='p-2'>{}</td><td class='p-2'>{}</td><td class='p-2 font-mono break-all'>{}</td><td class='p-2 text-red-400'>{}</td></tr>",
+                finding.kind.name(),
+                number,
+                escape_html(&finding.reference),
+                finding.problems.iter().map(|problem| escape_html(problem)).collect::<Vec<_>>().join("<br>")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("");
+    let summary = if report.findings.is_empty() {
+        format!("<p class='text-green-400 mb-4'>All {} saved record(s) match the keys they were derived from.</p>", report.checked)
+    } else {
+        format!(
+            "<p class='text-red-400 mb-4'>{} of {} saved record(s) do not match. Restore them from your backup before relying on them.</p>",
+            report.findings.len(),
+            report.checked
+        )
+    };
+    let verification = tokio::task::spawn_blocking(audit::verify_log).await.unwrap_or(Err("Audit check failed"));
+    let audit_status = match verification {
+        Ok(audit::Verification::Intact { entries, head }) => format!(
+            "<p class='text-green-400 mb-2'>Audit log intact: {} entries, latest hash <span class='font-mono break-all'>{}</span>.</p><p class='text-yellow-400 text-sm mb-4'>{}</p>",
+            entries,
+            head,
+            audit::HEAD_NOTE
+        ),
+        Ok(audit::Verification::Broken { line, problem }) => format!("<p class='text-red-400 mb-4'>Audit log broken at line {}: {}.</p>", line, problem),
+        Err(err) => format!("<p class='text-red-400 mb-4'>{}</p>", escape_html(err)),
+    };
+    let table = if report.findings.is_empty() {
+        String::new()
+    } else {
+        format!(
+            r#"<div class="scrollable-wallets"><table class="w-full text-sm text-left"><thead><tr><th class="p-2">Kind</th><th class="p-2">#</th><th class="p-2">Record</th><th class="p-2">Problems</th></tr></thead><tbody>{}</tbody></table></div>"#,
+            rows
+        )
+    };
+
+    Html(html_template(
+        "Verify Saved Records",
+        &format!(
+            r#"
+            <div class="flex items-center justify-center min-h-screen pt-20">
+                <div class="neumorphic p-8 max-w-4xl w-full fade-in">
+                    <h1 class="text-3xl font-bold mb-6 slide-up">Verify Saved Records</h1>
+                    <p class="text-sm text-gray-300 mb-4">Every saved wallet is re-derived from its mnemonic, and public keys and addresses are recomputed from the stored private keys.</p>
+                    {}
+                    {}
+                    {}
+                </div>
+            </div>
+            "#,
+            summary,
+            audit_status,
+            table
+        ),
+    ))
+}
+
+// Records a key operation started from the web interface on a blocking thread (the log is locked and synced to disk);
+// a failure is logged but does not fail the request
+async fn audit(operation: Operation, subjects: &[&str]) {
+    let subjects: Vec<String> = subjects.iter().map(|subject| subject.to_string()).collect();
+    let result = tokio::task::spawn_blocking(move || audit::record(audit::FrontEnd::Web, operation, &subjects.iter().map(String::as_str).collect::<Vec<_>>()))
+        .await
+        .unwrap_or(Err("Audit task failed"));
+    if let Err(err) = result {
+        eprintln!("⚠️ {}", err);
+    }
+}
+
+// Fingerprint of a submitted key for the audit log ("invalid" if the key cannot be decoded)
+fn key_fingerprint(private_key: &str, chain_code: &str) -> String {
+    let key = match (hex::decode(private_key), hex::decode(chain_code)) {
+        (Ok(private_key), Ok(chain_code)) => match (<[u8; 32]>::try_from(private_key), <[u8; 32]>::try_from(chain_code)) {
+            (Ok(private_key), Ok(chain_code)) => ExtendedPrivKey::from_parts(private_key, chain_code).ok(),
+            _ => None,
+        },
+        _ => None,
+    };
+    key.and_then(|key| key.fingerprint().ok()).unwrap_or_else(|| String::from("invalid"))
+}
+
+// Runs a storage operation with the session keys on a blocking thread (file locks and decryption can take a while)
+async fn with_session<T: Send + 'static>(
+    locked: &'static str,
+    operation: impl FnOnce(&Session) -> Result<T, &'static str> + Send + 'static,
+) -> Result<T, &'static str> {
+    let session = keystore::session().ok_or(locked)?;
+    tokio::task::spawn_blocking(move || operation(&session))
+        .await
+        .unwrap_or(Err("Storage task failed"))
+}
+
+// Renders the first addresses of a restored Electrum wallet
+async fn restore_electrum_wallet(phrase: &str, passphrase: &str, seed_type: SeedType) -> Html<String> {
+    let seed = electrum::to_seed(phrase, passphrase);
+    let master = match ExtendedPrivKey::new(seed.expose_bytes()) {
+        Ok(master) => master,
+        Err(err) => return error_page(err, "/restore_wallet"),
+    };
+    let receiving = match electrum::addresses(seed_type, &master, false, 5) {
+        Ok(receiving) => receiving,
+        Err(err) => return error_page(err, "/restore_wallet"),
+    };
+    let path = electrum::derivation_path(seed_type, false, 0);
+    let key = match master.derive_path(&path).and_then(|key| Wallet::from_extended_key(&key)) {
+        Ok(key) => key,
+        Err(err) => return error_page(err, "/restore_wallet"),
+    };
+    audit(Operation::RestoreWallet, &[&receiving[0]]).await;
+
+    let wallet_record = WalletRecord {
+        seed_type: Some(format!("electrum-{}", seed_type.name())),
+        derivation_path: Some(format_path(&path)),
+        ..WalletRecord::new(&electrum::normalize(phrase), &receiving[0], key.get_public_key(), key.expose_private_key())
+    };
+    let address_rows = receiving
+        .iter()
+        .enumerate()
+        .map(|(index, address)| {
+            format!(
+                r#"<p class="text-sm text-gray-300">{}: <span class="font-medium text-white">{}</span></p>"#,
+                format_path(&electrum::derivation_path(seed_type, false, index as u32)),
+                address
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("");
+
+    Html(html_template(
+        "Restored Wallet",
+        &format!(
+            r#"
+            <div class="flex items-center justify-center h-screen">
+                <div class="neumorphic p-8 max-w-3xl w-full fade-in">
+                    <h1 class="text-3xl font-bold mb-6 slide-up">Electrum Wallet Restored</h1>
+                    <div class="mb-4 p-4 bg-gray-700 rounded-lg">
+                        <p class="text-sm text-gray-300">Seed: <span class="font-medium text-white">{}</span></p>
+                        <p class="text-sm text-gray-300">Seed type: <span class="font-medium text-white">Electrum {}</span></p>
+                    </div>
+                    <div class="mb-4 p-4 bg-gray-700 rounded-lg">{}</div>
+                    <div class="flex space-x-4 mt-6">
+                        <button onclick="saveWallet()" class="btn">Save Wallet</button>
+                        <a href="/" class="btn bg-gray-500 hover:bg-gray-600">Back to Home</a>
+                    </div>
+                </div>
+            </div>
+            <script>
+                function saveWallet() {{
+                    fetch('/save_all_wallets', {{
+                        method: 'POST',
+                        headers: {{
+                            'Content-Type': 'application/x-www-form-urlencoded',
+                        }},
+                        body: new URLSearchParams({{
+                            wallets: JSON.stringify([{}]),
+                        }}),
+                    }})
+                    .then(response => response.text())
+                    .then(message => {{
+                        alert(message);
+                    }})
+                    .catch(error => {{
+                        alert('Failed to save wallet: ' + error);
+                    }});
+                }}
+            </script>
+            "#,
+            escape_html(&electrum::normalize(phrase)),
+            seed_type.name(),
+            address_rows,
+            serde_json::to_string(&wallet_record).unwrap()
+        ),
+    ))
+}
+
+// Renders an error page with a link back to the form that failed
+fn error_page(message: &str, retry_href: &str) -> Html<String> {
+    Html(html_template(
+        "Error",
+        &format!(
+            r#"
+            <div class="flex items-center justify-center h-screen">
+                <div class="neumorphic p-8 max-w-md w-full fade-in error-container">
+                    <h1 class="text-3xl font-bold mb-6 text-red-600 slide-up">Error</h1>
+                    <p class="text-sm text-gray-300 slide-up">{}</p>
+                    <a href="{}" class="btn w-full mt-6 slide-up">Try Again</a>
+                </div>
+            </div>
+            "#,
+            escape_html(message),
+            retry_href
+        ),
+    ))
+}
+
+// Escapes user-provided text before embedding it in HTML
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+// Helper function to generate a QR code and save it with the storage backend, returning where it was stored
+fn generate_qr_code(data: &str) -> Result<String, String> {
+    let code = QrCode::new(data).map_err(|e| e.to_string())?;
+    let image = code
+        .render::<qrcode::render::svg::Color>()
+        .min_dimensions(200, 200)
+        .build();
+    backend::current().save_qr_code(data, &image).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs.iter().map(|(name, value)| (header::HeaderName::from_static(name), value.parse().unwrap())).collect()
+    }
+
+    #[test]
+    fn test_requests_from_other_sites_are_refused() {
+        let local = [("host", "localhost:3000"), ("origin", "http://localhost:3000")];
+        assert_eq!(refusal(&Method::POST, &headers(&local)), None);
+        assert_eq!(refusal(&Method::GET, &headers(&[("host", "127.0.0.1:3000"), ("sec-fetch-site", "same-origin")])), None);
+        assert_eq!(refusal(&Method::GET, &headers(&[("host", "127.0.0.1:3000"), ("sec-fetch-site", "none")])), None);
+
+        // DNS rebinding: the browser sends the attacker's host name
+        assert!(refusal(&Method::GET, &headers(&[("host", "evil.example:3000")])).is_some());
+        assert!(refusal(&Method::GET, &headers(&[])).is_some());
+        // Cross-site forms, and POSTs without an Origin
+        assert!(refusal(&Method::POST, &headers(&[("host", "localhost:3000"), ("origin", "http://evil.example")])).is_some());
+        assert!(refusal(&Method::POST, &headers(&[("host", "localhost:3000"), ("origin", "null")])).is_some());
+        assert!(refusal(&Method::POST, &headers(&[("host", "localhost:3000")])).is_some());
+        // Cross-site reads, including other servers on localhost
+        assert!(refusal(&Method::GET, &headers(&[("host", "localhost:3000"), ("sec-fetch-site", "cross-site")])).is_some());
+        assert!(refusal(&Method::GET, &headers(&[("host", "localhost:3000"), ("sec-fetch-site", "same-site")])).is_some());
+        assert!(refusal(&Method::GET, &headers(&[("host", "localhost:3000"), ("origin", "http://localhost:8080")])).is_some());
+    }
+}
@@ -0,0 +1,42 @@
+// Hardened mode changes process-wide state (resource limits, dumpability, memory locking), so it is
+// checked on a separate run of the program instead of inside the unit test binary
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+
+// Starts the CLI with some arguments and returns it with the hardening line of its menu banner
+fn start(name: &str, args: &[&str]) -> (Child, String) {
+    let data_dir = std::env::temp_dir().join(format!("hd_wallet_hardening_{}_{}", name, std::process::id()));
+    let mut child = Command::new(env!("CARGO_BIN_EXE_hd_wallet"))
+        .args(args)
+        .arg("--data-dir")
+        .arg(&data_dir)
+        .env_remove("HD_WALLET_HARDENED")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    let banner = stdout.lines().map(Result::unwrap).find(|line| line.contains("Hardened mode")).unwrap();
+    (child, banner)
+}
+
+#[test]
+fn test_hardened_mode_is_opt_in() {
+    let (mut child, banner) = start("off", &[]);
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert!(banner.contains("Hardened mode: off"), "{}", banner);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_hardened_mode_disables_core_dumps() {
+    let (mut child, banner) = start("on", &["--hardened"]);
+    let limits = std::fs::read_to_string(format!("/proc/{}/limits", child.id())).unwrap();
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(banner.contains("no core dumps on, not dumpable on"), "{}", banner);
+    let core = limits.lines().find(|line| line.starts_with("Max core file size")).unwrap();
+    assert_eq!(core.split_whitespace().skip(4).take(2).collect::<Vec<_>>(), ["0", "0"]);
+}